@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xpose_fuzz::desktop::state::MAX_DESKTOPS;
+use xpose_fuzz::desktop::DesktopState;
+use xpose_fuzz::desktop_bar::{BarPosition, DesktopBar};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok((state, _version)) = DesktopState::from_json(text) else {
+        return;
+    };
+
+    assert!(state.desktops >= 1 && state.desktops <= MAX_DESKTOPS);
+    assert!(state.current < state.desktops);
+    assert!(state.windows.values().all(|&d| d < state.desktops));
+    assert!(state.stacking.keys().all(|&d| d < state.desktops));
+
+    let bar = DesktopBar::new(state.desktops, state.current, (0, 0, 1920, 1080), 240, BarPosition::Top);
+    assert_eq!(bar.preview_layouts.len(), state.desktops as usize);
+});
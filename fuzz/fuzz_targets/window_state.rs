@@ -0,0 +1,34 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xpose_fuzz::state::WindowState;
+use xpose_fuzz::window_finder::WindowInfo;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(mut state) = serde_json::from_str::<WindowState>(text) {
+        // Windows named after the saved keys, grouped by the saved groups
+        let mut windows: Vec<WindowInfo> = state
+            .orders
+            .iter()
+            .flat_map(|(group, saved)| saved.window_order.iter().map(move |key| (group.clone(), key)))
+            .enumerate()
+            .map(|(i, (group, key))| WindowInfo {
+                client_window: i as u32,
+                frame_window: i as u32,
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+                wm_class: Some(group),
+                wm_name: Some(key.clone()),
+                is_mapped: true,
+                urgent: false,
+            })
+            .collect();
+        state.restore_order(&mut windows, |info| info.wm_class.clone().unwrap_or_default());
+        let _ = serde_json::to_string(&state);
+    }
+});
@@ -0,0 +1,33 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xpose_fuzz::config::Config;
+use xpose_fuzz::desktop::state::MAX_DESKTOPS;
+use xpose_fuzz::desktop_bar::DesktopBar;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    // The same text through both formats
+    for config in [Config::parse(text), Config::parse_toml(text)] {
+        // Whatever was parsed must be safe to feed into layout and timing math
+        assert!(config.animation_speed.is_finite() && config.animation_speed > 0.0);
+        let _ = config.entrance_duration();
+        let _ = config.exit_duration();
+
+        for screen_width in [640u16, 1920, 7680] {
+            for desktops in [1, 4, MAX_DESKTOPS] {
+                let bar = DesktopBar::new(
+                    desktops,
+                    0,
+                    (0, 0, screen_width, 1080),
+                    config.desktop_bar_height,
+                    config.bar_position,
+                );
+                let (x, y, width, height) = bar.rect;
+                let _ = bar.hit_test(x + (width / 2) as i16, y + (height / 2) as i16);
+            }
+        }
+    }
+});
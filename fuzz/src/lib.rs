@@ -0,0 +1,59 @@
+//! Shim that compiles the parser-facing modules of the xpose binary so the
+//! fuzz targets can call them directly.
+
+#![allow(dead_code, unused_imports)]
+
+#[path = "../../src/alpha.rs"]
+pub mod alpha;
+#[path = "../../src/animation.rs"]
+pub mod animation;
+#[path = "../../src/capture.rs"]
+pub mod capture;
+#[path = "../../src/clients.rs"]
+pub mod clients;
+#[path = "../../src/compositor.rs"]
+pub mod compositor;
+#[path = "../../src/config.rs"]
+pub mod config;
+#[path = "../../src/connection.rs"]
+pub mod connection;
+#[path = "../../src/desktop/mod.rs"]
+pub mod desktop;
+#[path = "../../src/desktop_bar.rs"]
+pub mod desktop_bar;
+#[path = "../../src/desktop_grid.rs"]
+pub mod desktop_grid;
+#[path = "../../src/diagnostics.rs"]
+pub mod diagnostics;
+#[path = "../../src/error.rs"]
+pub mod error;
+#[path = "../../src/ewmh.rs"]
+pub mod ewmh;
+#[path = "../../src/fullscreen.rs"]
+pub mod fullscreen;
+#[path = "../../src/input.rs"]
+pub mod input;
+#[path = "../../src/keymap.rs"]
+pub mod keymap;
+#[path = "../../src/layout.rs"]
+pub mod layout;
+#[path = "../../src/monitor.rs"]
+pub mod monitor;
+#[path = "../../src/paths.rs"]
+pub mod paths;
+#[path = "../../src/placement.rs"]
+pub mod placement;
+#[path = "../../src/resources.rs"]
+pub mod resources;
+#[path = "../../src/scaled.rs"]
+pub mod scaled;
+#[path = "../../src/schema.rs"]
+pub mod schema;
+#[path = "../../src/state.rs"]
+pub mod state;
+#[path = "../../src/theme.rs"]
+pub mod theme;
+#[path = "../../src/toast.rs"]
+pub mod toast;
+#[path = "../../src/window_finder.rs"]
+pub mod window_finder;
@@ -0,0 +1,73 @@
+//! Alpha masks kept for the session.
+//!
+//! Fading windows and toasts composite through a solid-fill mask of the
+//! wanted opacity. Creating one per draw means a create and a free for every
+//! skipped window on every animation frame, so opacities are rounded to one
+//! of `STEPS` levels and each level's mask is created once, on first use,
+//! and kept until the connection closes.
+
+use std::collections::HashMap;
+
+use x11rb::protocol::render::{Color, Picture};
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+/// Opacity levels above fully transparent; finer than a fade can show.
+const STEPS: u16 = 64;
+
+/// Mask pictures, by opacity level.
+#[derive(Debug, Default)]
+pub struct AlphaMasks {
+    masks: HashMap<u16, Picture>,
+}
+
+/// The level an opacity (0.0 to 1.0) rounds to, 0 for fully transparent.
+fn level(opacity: f64) -> u16 {
+    (opacity.clamp(0.0, 1.0) * f64::from(STEPS)).round() as u16
+}
+
+/// The 16-bit alpha of a level.
+fn level_alpha(level: u16) -> u16 {
+    (u32::from(level) * 65535 / u32::from(STEPS)) as u16
+}
+
+impl XConnection {
+    /// A solid-fill mask of `opacity`, for compositing through. It belongs
+    /// to the connection; don't free it.
+    pub fn alpha_mask(&self, opacity: f64) -> Result<Picture> {
+        let level = level(opacity);
+        if let Some(&mask) = self.alpha_masks.borrow().masks.get(&level) {
+            return Ok(mask);
+        }
+        let alpha = level_alpha(level);
+        let mask = self
+            .create_solid_fill(Color {
+                red: alpha,
+                green: alpha,
+                blue: alpha,
+                alpha,
+            })?
+            .into_raw();
+        self.alpha_masks.borrow_mut().masks.insert(level, mask);
+        Ok(mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alpha_levels() {
+        assert_eq!(level(0.0), 0);
+        assert_eq!(level(-0.5), 0);
+        assert_eq!(level(1.0), STEPS);
+        assert_eq!(level(2.0), STEPS);
+        assert_eq!(level(0.5), STEPS / 2);
+        // Close opacities share a mask
+        assert_eq!(level(0.501), level(0.499));
+        assert_eq!(level_alpha(STEPS), 65535);
+        assert_eq!(level_alpha(0), 0);
+    }
+}
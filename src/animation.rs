@@ -3,15 +3,58 @@ use std::time::{Duration, Instant};
 use crate::layout::ThumbnailLayout;
 use crate::window_finder::WindowInfo;
 
+/// Timing curve of an animation: maps elapsed time (0.0 to 1.0) to how far
+/// along the way things are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Easing {
+    /// Fast start, smooth deceleration.
+    #[default]
+    EaseOutCubic,
+    /// Gentle start and stop.
+    EaseInOut,
+    /// Overshoots the target a little and settles back.
+    Spring,
+    Linear,
+}
+
+impl Easing {
+    /// Parse a config value: ease-out-cubic, ease-in-out, spring or linear.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "ease-out-cubic" => Some(Self::EaseOutCubic),
+            "ease-in-out" => Some(Self::EaseInOut),
+            "spring" => Some(Self::Spring),
+            "linear" => Some(Self::Linear),
+            _ => None,
+        }
+    }
+
+    /// Eased progress for linear progress `t`. Starts at 0.0 and ends at
+    /// exactly 1.0; only `Spring` goes past 1.0 on the way.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut if t < 0.5 => 4.0 * t.powi(3),
+            Self::EaseInOut => 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0,
+            // Damped oscillation, about 10% overshoot; the tail is cut off
+            Self::Spring if t >= 1.0 => 1.0,
+            Self::Spring => 1.0 - (-7.0 * t).exp() * (3.0 * std::f64::consts::PI * t).cos(),
+            Self::Linear => t,
+        }
+    }
+}
+
 /// Animation configuration.
 pub struct AnimationConfig {
     pub duration: Duration,
     pub fps: u32,
+    pub easing: Easing,
 }
 
 impl AnimationConfig {
-    pub fn new(duration: Duration) -> Self {
-        Self { duration, fps: 60 }
+    pub fn new(duration: Duration, easing: Easing) -> Self {
+        Self { duration, fps: 60, easing }
     }
 }
 
@@ -20,6 +63,7 @@ impl Default for AnimationConfig {
         Self {
             duration: Duration::from_millis(350),
             fps: 60,
+            easing: Easing::default(),
         }
     }
 }
@@ -98,18 +142,14 @@ pub fn calculate_exit_layouts(
     (start_layouts, end_layouts)
 }
 
-/// Ease-out cubic function for smooth deceleration.
-fn ease_out_cubic(t: f64) -> f64 {
-    1.0 - (1.0 - t).powi(3)
-}
-
 /// Interpolate between start and end layouts.
 pub fn interpolate_layouts(
     start: &[AnimatedLayout],
     end: &[ThumbnailLayout],
     progress: f64,
+    easing: Easing,
 ) -> Vec<AnimatedLayout> {
-    let t = ease_out_cubic(progress.clamp(0.0, 1.0));
+    let t = easing.apply(progress);
 
     start
         .iter()
@@ -118,8 +158,8 @@ pub fn interpolate_layouts(
             AnimatedLayout {
                 x: lerp(s.x as f64, e.x as f64, t) as i16,
                 y: lerp(s.y as f64, e.y as f64, t) as i16,
-                width: lerp(s.width as f64, e.width as f64, t) as u16,
-                height: lerp(s.height as f64, e.height as f64, t) as u16,
+                width: lerp(s.width as f64, e.width as f64, t).max(1.0) as u16,
+                height: lerp(s.height as f64, e.height as f64, t).max(1.0) as u16,
                 window_index: s.window_index,
             }
         })
@@ -131,13 +171,74 @@ fn lerp(a: f64, b: f64, t: f64) -> f64 {
     a + (b - a) * t
 }
 
+/// Timing of animation frames.
+///
+/// Progress follows the clock, so a frame that takes longer than its budget
+/// to draw isn't waited after: the next one starts at once, further along,
+/// and the frames in between are skipped rather than the animation
+/// stretching out on a slow server. Call `sync` before `frame_wait`, so the
+/// time taken is the server's drawing and not just sending the requests.
+#[derive(Debug)]
+pub struct FramePacer {
+    budget: Duration,
+    frame_start: Instant,
+    skipped: u32,
+}
+
+impl FramePacer {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            frame_start: Instant::now(),
+            skipped: 0,
+        }
+    }
+
+    /// How long to wait before the next frame, now that this one is drawn.
+    pub fn frame_wait(&mut self) -> Duration {
+        let now = Instant::now();
+        let (wait, skipped) = pace(self.budget, now - self.frame_start);
+        if skipped > 0 {
+            log::debug!("Frame took {:?}, skipping {} frames", now - self.frame_start, skipped);
+        }
+        self.skipped += skipped;
+        self.frame_start = now + wait;
+        wait
+    }
+
+    /// Frames skipped so far.
+    pub fn skipped(&self) -> u32 {
+        self.skipped
+    }
+}
+
+/// The wait after a frame that took `took` to draw, and how many frames'
+/// time it ran over `budget` by.
+fn pace(budget: Duration, took: Duration) -> (Duration, u32) {
+    match budget.checked_sub(took) {
+        Some(wait) => (wait, 0),
+        None => (Duration::ZERO, (took.as_nanos() / budget.as_nanos().max(1)) as u32),
+    }
+}
+
+/// One rise and fall of an urgent window's border.
+pub const URGENCY_PULSE_PERIOD: Duration = Duration::from_millis(1200);
+
+/// How strongly an urgent window's border shows `elapsed` into the pulsing,
+/// from 0.0 (the plain border) to 1.0, rising and falling smoothly.
+pub fn urgency_pulse(elapsed: Duration) -> f64 {
+    let phase = elapsed.as_secs_f64() / URGENCY_PULSE_PERIOD.as_secs_f64();
+    0.5 - 0.5 * (phase * std::f64::consts::TAU).cos()
+}
+
 /// Animation state manager.
 pub struct Animator {
     start_layouts: Vec<AnimatedLayout>,
     end_layouts: Vec<ThumbnailLayout>,
     start_time: Instant,
     duration: Duration,
-    frame_duration: Duration,
+    easing: Easing,
+    pacer: FramePacer,
 }
 
 impl Animator {
@@ -151,7 +252,8 @@ impl Animator {
             end_layouts,
             start_time: Instant::now(),
             duration: config.duration,
-            frame_duration: Duration::from_secs_f64(1.0 / config.fps as f64),
+            easing: config.easing,
+            pacer: FramePacer::new(Duration::from_secs_f64(1.0 / config.fps as f64)),
         }
     }
 
@@ -168,12 +270,62 @@ impl Animator {
 
     /// Get current interpolated layouts.
     pub fn current_layouts(&self) -> Vec<AnimatedLayout> {
-        interpolate_layouts(&self.start_layouts, &self.end_layouts, self.progress())
+        interpolate_layouts(&self.start_layouts, &self.end_layouts, self.progress(), self.easing)
+    }
+
+    /// How long to wait before the next frame (see `FramePacer`).
+    pub fn frame_wait(&mut self) -> Duration {
+        self.pacer.frame_wait()
     }
 
-    /// Get the frame duration for timing.
-    pub fn frame_duration(&self) -> Duration {
-        self.frame_duration
+    /// Frames skipped because drawing fell behind.
+    pub fn skipped_frames(&self) -> u32 {
+        self.pacer.skipped()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in [Easing::EaseOutCubic, Easing::EaseInOut, Easing::Spring, Easing::Linear] {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+        }
+        assert!(Easing::Spring.apply(0.4) > 1.0);
+        assert!(Easing::EaseInOut.apply(0.25) < 0.25);
+        assert_eq!(Easing::parse("Spring"), Some(Easing::Spring));
+        assert_eq!(Easing::parse("bounce"), None);
     }
 
+    #[test]
+    fn test_layouts_morph_size() {
+        // A window shrinks into its slot as it moves there, and grows back out
+        let window = [AnimatedLayout { x: 0, y: 0, width: 1000, height: 800, window_index: 0 }];
+        let slot = [ThumbnailLayout { x: 100, y: 100, width: 200, height: 160, window_index: 0 }];
+        let halfway = &interpolate_layouts(&window, &slot, 0.5, Easing::Linear)[0];
+        assert_eq!((halfway.x, halfway.y, halfway.width, halfway.height), (50, 50, 600, 480));
+        let landed = &interpolate_layouts(&window, &slot, 1.0, Easing::EaseOutCubic)[0];
+        assert_eq!((landed.width, landed.height), (200, 160));
+    }
+
+    #[test]
+    fn test_urgency_pulse() {
+        assert_eq!(urgency_pulse(Duration::ZERO), 0.0);
+        assert!((urgency_pulse(URGENCY_PULSE_PERIOD / 2) - 1.0).abs() < 1e-9);
+        assert!(urgency_pulse(URGENCY_PULSE_PERIOD).abs() < 1e-9);
+        assert!((urgency_pulse(URGENCY_PULSE_PERIOD / 4) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_frame_pacing() {
+        let ms = Duration::from_millis;
+        assert_eq!(pace(ms(16), ms(6)), (ms(10), 0));
+        assert_eq!(pace(ms(16), ms(16)), (ms(0), 0));
+        // Fell behind: on at once, a frame's time and more lost
+        assert_eq!(pace(ms(16), ms(20)), (ms(0), 1));
+        assert_eq!(pace(ms(16), ms(50)), (ms(0), 3));
+    }
 }
@@ -0,0 +1,74 @@
+//! Telling screen readers which window is highlighted.
+//!
+//! As the highlight moves, the overview describes the window ("Firefox:
+//! Inbox — 3 of 8, desktop 2") in `_XPOSE_HIGHLIGHTED` on the root, and
+//! deletes it on the way out. The services pass it on like a desktop change
+//! (see service.rs): the socket as a `highlighted` event, D-Bus as the
+//! `Highlighted(s)` signal, and with `Announce true` also as a desktop
+//! notification, which screen readers such as Orca speak. Going through the
+//! root lets them announce an overview started by a key binding too.
+
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::window_finder::WindowInfo;
+
+impl XConnection {
+    /// Publish what is highlighted, or clear it with None.
+    pub fn announce(&self, description: Option<&str>) -> Result<()> {
+        match description {
+            Some(text) => self.conn.change_property8(
+                PropMode::REPLACE,
+                self.root,
+                self.atoms._XPOSE_HIGHLIGHTED,
+                self.atoms.UTF8_STRING,
+                text.as_bytes(),
+            )?,
+            None => self.conn.delete_property(self.root, self.atoms._XPOSE_HIGHLIGHTED)?,
+        };
+        self.flush()
+    }
+
+    /// The description last published, if the overview is up with
+    /// something highlighted.
+    pub fn highlighted_description(&self) -> Result<Option<String>> {
+        let reply = self
+            .conn
+            .get_property(false, self.root, self.atoms._XPOSE_HIGHLIGHTED, self.atoms.UTF8_STRING, 0, 1024)?
+            .reply()?;
+        Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()).filter(|text| !text.is_empty()))
+    }
+}
+
+/// How a highlighted window is read out: class and title, its place among
+/// the thumbnails (1-based) if known, and the desktop's name.
+pub fn describe(info: &WindowInfo, place: Option<(usize, usize)>, desktop: &str) -> String {
+    // WM_CLASS is "instance Class"; the class reads better
+    let class = info.wm_class.as_deref().and_then(|class| class.split(' ').next_back());
+    let mut text = match (class, info.wm_name.as_deref()) {
+        (Some(class), Some(title)) if class != title => format!("{}: {}", class, title),
+        (_, Some(title)) => title.to_string(),
+        (Some(class), None) => class.to_string(),
+        (None, None) => "Untitled window".to_string(),
+    };
+    if let Some((position, count)) = place {
+        text.push_str(&format!(" — {} of {}", position, count));
+    }
+    text.push_str(&format!(", desktop {}", desktop));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let info = WindowInfo::for_test(1, Some("Navigator firefox"), Some("Inbox"));
+        assert_eq!(describe(&info, Some((3, 8)), "2"), "firefox: Inbox — 3 of 8, desktop 2");
+        let untitled = WindowInfo { wm_name: None, ..info };
+        assert_eq!(describe(&untitled, None, "Web"), "firefox, desktop Web");
+    }
+}
@@ -1,123 +1,168 @@
+use std::cell::RefCell;
+
 use x11rb::connection::Connection;
 use x11rb::protocol::composite;
-use x11rb::protocol::damage::{self, Damage, ReportLevel};
-use x11rb::protocol::render::{self, Picture};
+use x11rb::protocol::damage::Damage;
+use x11rb::protocol::render;
 use x11rb::protocol::xproto::*;
 
 use crate::connection::XConnection;
+use crate::diagnostics::Subsystem;
 use crate::error::Result;
+use crate::resources::{OwnedDamage, OwnedPicture, OwnedPixmap, OwnedRedirection};
+use crate::scaled::Scaled;
 use crate::window_finder::WindowInfo;
 
+/// A window's contents, kept up to date through its damage. Dropping it
+/// frees everything and puts the window back the way it was.
 #[derive(Debug)]
 pub struct CapturedWindow {
     pub info: WindowInfo,
-    pub pixmap: Pixmap,
-    pub picture: Picture,
-    pub damage: Damage,
+    pub pixmap: OwnedPixmap,
+    pub picture: OwnedPicture,
+    /// None once the window is destroyed, which takes it along
+    pub damage: Option<OwnedDamage>,
+    /// The contents at thumbnail size, once drawn there (see scaled.rs)
+    pub scaled: RefCell<Option<Scaled>>,
+    /// Set if xpose redirected the window rather than a compositor
+    redirection: Option<OwnedRedirection>,
+}
+
+impl CapturedWindow {
+    /// Whether `damage` tracks this window.
+    pub fn has_damage(&self, damage: Damage) -> bool {
+        self.damage.as_ref().is_some_and(|own| own.id() == damage)
+    }
+
+    /// Let go of what went with the destroyed window (its damage tracking
+    /// and redirection) without freeing it again; only the contents are
+    /// left.
+    pub fn window_destroyed(&mut self) {
+        if let Some(damage) = self.damage.take() {
+            damage.into_raw();
+        }
+        if let Some(redirection) = self.redirection.take() {
+            redirection.into_raw();
+        }
+    }
 }
 
 impl XConnection {
     /// Capture window contents to a pixmap using XComposite.
     /// Returns immediately - no retries. Use retry_capture for failed windows.
     pub fn capture_window(&self, info: &WindowInfo) -> Result<CapturedWindow> {
-        // Redirect window to off-screen storage
-        composite::redirect_window(&self.conn, info.frame_window, composite::Redirect::AUTOMATIC)?;
-
-        // Try to get pixmap with window contents (single attempt)
-        let pixmap = self.generate_id()?;
-        composite::name_window_pixmap(&self.conn, info.frame_window, pixmap)?;
-
-        // Get actual pixmap geometry (may differ from frame geometry)
-        let pixmap_geom = self.conn.get_geometry(pixmap)?.reply()?;
-
-        // Create XRender picture from pixmap
-        let picture = self.generate_id()?;
-        render::create_picture(
-            &self.conn,
-            picture,
-            pixmap,
-            self.pict_format_rgb,
-            &render::CreatePictureAux::new(),
-        )?;
-
-        // Create damage tracking for real-time updates
-        // ReportLevel::NON_EMPTY gives us simple "something changed" notifications
-        let damage_id = self.generate_id()?;
-        damage::create(&self.conn, damage_id, info.frame_window, ReportLevel::NON_EMPTY)?;
-
-        self.conn.flush()?;
-
-        // Update info with actual pixmap dimensions
-        let mut captured_info = info.clone();
-        captured_info.width = pixmap_geom.width;
-        captured_info.height = pixmap_geom.height;
-
-        log::debug!(
-            "Captured window {:?} -> pixmap 0x{:x} ({}x{}), picture 0x{:x}, damage 0x{:x}",
-            info.wm_name,
-            pixmap,
-            pixmap_geom.width,
-            pixmap_geom.height,
-            picture,
-            damage_id
-        );
-
-        Ok(CapturedWindow {
-            info: captured_info,
-            pixmap,
-            picture,
-            damage: damage_id,
-        })
+        self.capture_windows(std::slice::from_ref(info)).remove(0)
     }
 
-    /// Release captured resources.
-    pub fn release_capture(&self, capture: &CapturedWindow) -> Result<()> {
-        // Destroy damage tracking
-        damage::destroy(&self.conn, capture.damage)?;
-
-        // Free picture
-        render::free_picture(&self.conn, capture.picture)?;
-
-        // Free pixmap
-        self.conn.free_pixmap(capture.pixmap)?;
+    /// Capture several windows, with one result per window in order.
+    /// Under a compositor its redirection is used (see compositor.rs).
+    ///
+    /// Requests for all of them go out before any reply is waited for, so
+    /// capturing many windows costs about one round trip rather than one
+    /// each.
+    pub fn capture_windows(&self, infos: &[WindowInfo]) -> Vec<Result<CapturedWindow>> {
+        // Redirect each window to off-screen storage, name the pixmap with
+        // its contents and ask for the pixmap's size (which may differ from
+        // the frame's)
+        let requested: Vec<_> = infos
+            .iter()
+            .map(|info| -> Result<_> {
+                let redirection =
+                    if self.has_compositor() { None } else { Some(self.redirect_window(info.frame_window)?) };
+                let pixmap = self.name_window_pixmap(info.frame_window)?;
+                let geometry = self.conn.get_geometry(pixmap.id())?;
+                Ok((redirection, pixmap, geometry))
+            })
+            .collect();
+
+        let captures = infos
+            .iter()
+            .zip(requested)
+            .map(|(info, requested)| {
+                let (redirection, pixmap, geometry) = requested?;
+                let pixmap_geom = geometry.reply()?;
+
+                // Create XRender picture from pixmap
+                let picture = self.create_owned_picture(pixmap.id())?;
+
+                // Create damage tracking for real-time updates
+                // ReportLevel::NON_EMPTY gives us simple "something changed" notifications
+                let damage = self.create_owned_damage(info.frame_window)?;
+
+                // Update info with actual pixmap dimensions
+                let mut captured_info = info.clone();
+                captured_info.width = pixmap_geom.width;
+                captured_info.height = pixmap_geom.height;
+
+                log::debug!(
+                    "Captured window {:?} -> pixmap 0x{:x} ({}x{}), picture 0x{:x}, damage 0x{:x}",
+                    info.wm_name,
+                    pixmap.id(),
+                    pixmap_geom.width,
+                    pixmap_geom.height,
+                    picture.id(),
+                    damage.id()
+                );
+
+                Ok(CapturedWindow {
+                    info: captured_info,
+                    pixmap,
+                    picture,
+                    damage: Some(damage),
+                    scaled: RefCell::new(None),
+                    redirection,
+                })
+            })
+            .collect();
+        if let Err(e) = self.conn.flush() {
+            log::warn!("Cannot flush captures: {}", e);
+        }
+        captures
+    }
 
-        // Unredirect window
-        composite::unredirect_window(
-            &self.conn,
-            capture.info.frame_window,
-            composite::Redirect::AUTOMATIC,
-        )?;
+    /// Name the composite pixmap holding a redirected window's contents.
+    fn name_window_pixmap(&self, window: Window) -> Result<OwnedPixmap> {
+        let pixmap = self.generate_id()?;
+        composite::name_window_pixmap(&self.conn, window, pixmap)?;
+        Ok(OwnedPixmap::new(&self.conn, pixmap))
+    }
 
-        Ok(())
+    /// Swap new contents into a capture, freeing the ones they replace
+    /// along with their copy at thumbnail size.
+    pub fn replace_capture_contents(&self, capture: &mut CapturedWindow, pixmap: OwnedPixmap, picture: OwnedPicture) {
+        capture.scaled.take();
+        capture.picture = picture;
+        capture.pixmap = pixmap;
     }
 
     /// Re-capture window pixmap after damage (window content changed).
-    /// This creates a new pixmap/picture from the current window contents.
+    /// This creates a new pixmap/picture from the current window contents;
+    /// the old ones are only freed once the new ones exist, so a failure
+    /// leaves the capture showing its previous contents.
     pub fn refresh_capture(&self, capture: &mut CapturedWindow) -> Result<()> {
-        // Free old picture and pixmap
-        render::free_picture(&self.conn, capture.picture)?;
-        self.conn.free_pixmap(capture.pixmap)?;
-
         // Get new pixmap with updated window contents
         let pixmap = self.generate_id()?;
-        composite::name_window_pixmap(&self.conn, capture.info.frame_window, pixmap)?;
+        let cookie = composite::name_window_pixmap(&self.conn, capture.info.frame_window, pixmap)?;
+        self.check_request(Subsystem::Capture, "refresh_capture", cookie)?;
+        let pixmap = OwnedPixmap::new(&self.conn, pixmap);
 
         // Create new picture
         let picture = self.generate_id()?;
-        render::create_picture(
+        let cookie = render::create_picture(
             &self.conn,
             picture,
-            pixmap,
+            pixmap.id(),
             self.pict_format_rgb,
             &render::CreatePictureAux::new(),
         )?;
+        self.check_request(Subsystem::Capture, "refresh_capture", cookie)?;
+        let picture = OwnedPicture::new(&self.conn, picture);
+
+        self.replace_capture_contents(capture, pixmap, picture);
 
         // Ensure commands are sent to server
         self.conn.flush()?;
 
-        capture.pixmap = pixmap;
-        capture.picture = picture;
-
         Ok(())
     }
 
@@ -125,50 +170,31 @@ impl XConnection {
     /// Uses a solid black picture. Can be upgraded later via try_upgrade_placeholder.
     pub fn create_placeholder_capture(&self, info: &WindowInfo) -> Result<CapturedWindow> {
         // Redirect window (needed for later retry)
-        let _ = composite::redirect_window(&self.conn, info.frame_window, composite::Redirect::AUTOMATIC);
+        let redirection = if self.has_compositor() { None } else { self.redirect_window(info.frame_window).ok() };
 
         // Create a small pixmap filled with black as placeholder
-        let pixmap = self.generate_id()?;
-        self.conn.create_pixmap(
-            self.root_depth,
-            pixmap,
-            self.root,
-            info.width.max(1),
-            info.height.max(1),
-        )?;
+        let (width, height) = (info.width.max(1), info.height.max(1));
+        let pixmap = self.create_owned_pixmap(self.root, width, height)?;
 
         // Fill with black
-        let gc = self.generate_id()?;
-        self.conn.create_gc(
-            gc,
-            pixmap,
-            &x11rb::protocol::xproto::CreateGCAux::new().foreground(0x222222),
-        )?;
+        let gc = self.create_owned_gc(pixmap.id(), &CreateGCAux::new().foreground(0x222222))?;
         self.conn.poly_fill_rectangle(
-            pixmap,
-            gc,
-            &[x11rb::protocol::xproto::Rectangle {
+            pixmap.id(),
+            gc.id(),
+            &[Rectangle {
                 x: 0,
                 y: 0,
-                width: info.width.max(1),
-                height: info.height.max(1),
+                width,
+                height,
             }],
         )?;
-        self.conn.free_gc(gc)?;
+        drop(gc);
 
         // Create picture from placeholder pixmap
-        let picture = self.generate_id()?;
-        render::create_picture(
-            &self.conn,
-            picture,
-            pixmap,
-            self.pict_format_rgb,
-            &render::CreatePictureAux::new(),
-        )?;
+        let picture = self.create_owned_picture(pixmap.id())?;
 
         // Create damage tracking (even for placeholder)
-        let damage_id = self.generate_id()?;
-        damage::create(&self.conn, damage_id, info.frame_window, ReportLevel::NON_EMPTY)?;
+        let damage = self.create_owned_damage(info.frame_window)?;
 
         self.conn.flush()?;
 
@@ -183,7 +209,9 @@ impl XConnection {
             info: info.clone(),
             pixmap,
             picture,
-            damage: damage_id,
+            damage: Some(damage),
+            scaled: RefCell::new(None),
+            redirection,
         })
     }
 
@@ -191,63 +219,26 @@ impl XConnection {
     /// Returns true if successful, false if window still not ready.
     pub fn try_upgrade_placeholder(&self, capture: &mut CapturedWindow) -> bool {
         // Try to get actual window pixmap
-        let new_pixmap = match self.generate_id() {
-            Ok(id) => id,
-            Err(_) => return false,
+        let Ok(pixmap) = self.name_window_pixmap(capture.info.frame_window) else {
+            return false;
         };
 
-        if composite::name_window_pixmap(&self.conn, capture.info.frame_window, new_pixmap).is_err() {
+        // Check if pixmap is valid
+        let Some(geom) = self.conn.get_geometry(pixmap.id()).ok().and_then(|cookie| cookie.reply().ok()) else {
             return false;
-        }
+        };
 
-        // Check if pixmap is valid
-        match self.conn.get_geometry(new_pixmap) {
-            Ok(cookie) => match cookie.reply() {
-                Ok(geom) => {
-                    // Success! Create new picture and replace placeholder
-                    let new_picture = match self.generate_id() {
-                        Ok(id) => id,
-                        Err(_) => {
-                            let _ = self.conn.free_pixmap(new_pixmap);
-                            return false;
-                        }
-                    };
-
-                    if render::create_picture(
-                        &self.conn,
-                        new_picture,
-                        new_pixmap,
-                        self.pict_format_rgb,
-                        &render::CreatePictureAux::new(),
-                    ).is_err() {
-                        let _ = self.conn.free_pixmap(new_pixmap);
-                        return false;
-                    }
-
-                    // Free old placeholder resources
-                    let _ = render::free_picture(&self.conn, capture.picture);
-                    let _ = self.conn.free_pixmap(capture.pixmap);
-
-                    // Update capture with real content
-                    capture.pixmap = new_pixmap;
-                    capture.picture = new_picture;
-                    capture.info.width = geom.width;
-                    capture.info.height = geom.height;
-
-                    let _ = self.conn.flush();
-
-                    log::info!("Upgraded placeholder to real capture: {:?}", capture.info.wm_name);
-                    true
-                }
-                Err(_) => {
-                    let _ = self.conn.free_pixmap(new_pixmap);
-                    false
-                }
-            },
-            Err(_) => {
-                let _ = self.conn.free_pixmap(new_pixmap);
-                false
-            }
-        }
+        // Success! Create new picture and replace placeholder
+        let Ok(picture) = self.create_owned_picture(pixmap.id()) else {
+            return false;
+        };
+        self.replace_capture_contents(capture, pixmap, picture);
+        capture.info.width = geom.width;
+        capture.info.height = geom.height;
+
+        let _ = self.conn.flush();
+
+        log::info!("Upgraded placeholder to real capture: {:?}", capture.info.wm_name);
+        true
     }
 }
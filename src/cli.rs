@@ -0,0 +1,446 @@
+//! Command-line argument parsing.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+/// What `--screenshot` captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotTarget {
+    /// The focused window (topmost window if nothing is focused).
+    Window,
+    /// A fully rendered overview frame, desktop bar included.
+    Overview,
+}
+
+/// Desktop control commands, run without opening the overview.
+#[derive(Debug, PartialEq)]
+pub enum DesktopCommand {
+    /// Switch to a desktop (0-indexed).
+    Switch(u32),
+    Next,
+    Prev,
+    /// Move a window (client or frame id) to a desktop (0-indexed).
+    MoveWindow { window: u32, desktop: u32 },
+    /// Set or clear a desktop's name.
+    Rename { desktop: u32, name: String },
+    /// Minimize every window on the current desktop, or bring them back.
+    ShowDesktop,
+    /// Switch to a window's desktop, restore it if minimized, and raise and
+    /// focus it (client or frame id).
+    Activate(u32),
+}
+
+/// Which application `--class` or `--current-app` shows the windows of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppFilter {
+    /// A WM_CLASS instance or class name.
+    Class(String),
+    /// The focused window's application.
+    Current,
+}
+
+/// How the interactive overview is shown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OverviewOptions {
+    /// Drive the overview with a scripted XTEST demo.
+    pub demo: bool,
+    /// Leave out the desktop bar.
+    pub no_bar: bool,
+    /// Lay every window out on this monitor (0-indexed) alone.
+    pub monitor: Option<usize>,
+    /// Show one application's windows alone.
+    pub app: Option<AppFilter>,
+}
+
+/// Top-level command selected on the command line.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Interactive overview (default), possibly a scripted demo.
+    Overview(OverviewOptions),
+    /// Export a PNG without entering interactive mode.
+    Screenshot {
+        target: ScreenshotTarget,
+        path: PathBuf,
+    },
+    /// Change desktops from a key binding or script.
+    Desktop(DesktopCommand),
+    /// Report problems in a config file (the one in use if no path is given).
+    CheckConfig(Option<PathBuf>),
+    /// Record focus history in the background.
+    TrackFocus,
+    /// Print the managed windows, possibly as JSON.
+    ListWindows { json: bool },
+    /// Print the windows to pick from, one line each, for rofi or dmenu.
+    PrintWindows,
+    /// Take JSON commands on the control socket, possibly as a systemd unit.
+    Daemon { systemd: bool },
+    /// Serve the D-Bus control interface.
+    Dbus,
+    /// Hold the clipboard with text copied in the overview.
+    ServeClipboard(String),
+}
+
+/// Expose-like window switcher for X11/TWM.
+///
+/// Without a command, shows every window on the current desktop as a live
+/// thumbnail; click one to raise and focus it.
+#[derive(Parser, Debug)]
+#[command(name = "xpose", version)]
+pub struct Cli {
+    /// Read this config file instead of ~/.config/xpose/config.toml or
+    /// ~/.xposerc (TOML if it ends in .toml)
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// X display to connect to instead of $DISPLAY
+    #[arg(long, value_name = "DISPLAY", global = true)]
+    pub display: Option<String>,
+
+    /// Log level for xpose.log: off, error, warn, info, debug or trace
+    /// (overrides RUST_LOG)
+    #[arg(long, value_name = "LEVEL", global = true)]
+    pub log_level: Option<log::LevelFilter>,
+
+    /// Show the overview without the desktop bar (like `ShowDesktopBar false`)
+    #[arg(long)]
+    no_bar: bool,
+
+    /// Lay all windows out on monitor N alone (monitors count from 1)
+    #[arg(long, value_name = "N", value_parser = parse_monitor)]
+    monitor: Option<usize>,
+
+    /// Show only the windows of one application, by WM_CLASS instance or
+    /// class name (e.g. Firefox)
+    #[arg(long, value_name = "WM_CLASS")]
+    class: Option<String>,
+
+    /// Show only the windows of the focused window's application
+    #[arg(long, conflicts_with = "class")]
+    current_app: bool,
+
+    /// Write the focused window (or a rendered overview frame) to a PNG
+    /// file and exit; the target defaults to overview
+    #[arg(long, num_args = 1..=2, value_names = ["window|overview", "PATH"])]
+    screenshot: Option<Vec<String>>,
+
+    /// Switch to desktop N without opening the overview (same as `switch N`)
+    #[arg(long, value_name = "N", value_parser = parse_desktop, conflicts_with = "screenshot")]
+    switch: Option<u32>,
+
+    /// Print the windows to pick from, current desktop first, one per line
+    /// starting with the window id (for rofi or dmenu), and exit
+    #[arg(long, conflicts_with_all = ["screenshot", "switch"])]
+    print_windows: bool,
+
+    /// Activate a window as picking it in the overview does, without
+    /// opening it: the id alone, or a whole line from --print-windows
+    #[arg(long, value_name = "ID", value_parser = parse_picked_window, conflicts_with_all = ["screenshot", "switch", "print_windows"])]
+    activate: Option<u32>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Drive the overview with synthetic input (needs XTEST)
+    Demo,
+    /// Switch to desktop N (desktops count from 1) with a short slide,
+    /// without opening the overview
+    Switch {
+        #[arg(value_name = "N", value_parser = parse_desktop)]
+        desktop: u32,
+    },
+    /// Switch to the next desktop, wrapping around
+    Next,
+    /// Switch to the previous desktop, wrapping around
+    Prev,
+    /// Move a window (id in decimal or 0x hex, client or frame) to desktop N
+    MoveWindow {
+        #[arg(value_parser = parse_window)]
+        window: u32,
+        #[arg(value_name = "N", value_parser = parse_desktop)]
+        desktop: u32,
+    },
+    /// Name desktop N; without NAME, clear its name
+    RenameDesktop {
+        #[arg(value_name = "N", value_parser = parse_desktop)]
+        desktop: u32,
+        /// The rest of the line, so it needs no quoting
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        name: Vec<String>,
+    },
+    /// Minimize every window on the current desktop; run again to bring
+    /// them back
+    ShowDesktop,
+    /// Check a config file (by default the one xpose would read) and report
+    /// unknown keys and invalid or out-of-range values; exits non-zero if
+    /// there are any
+    CheckConfig {
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
+    /// Keep running and record when each window has the focus, so windows
+    /// can be ordered by recent use
+    TrackFocus,
+    /// Print each managed window's id, desktop, class and title
+    ListWindows {
+        /// Print a JSON array with geometry and mapped state too
+        #[arg(long)]
+        json: bool,
+    },
+    /// Keep running and take JSON commands (show, toggle, switch,
+    /// move-window, query-state) on $XDG_RUNTIME_DIR/xpose.sock
+    Daemon {
+        /// Run as a systemd user unit: take the socket systemd passes, if
+        /// any, and report readiness
+        #[arg(long)]
+        systemd: bool,
+    },
+    /// Keep running and serve org.xpose.Control on the session bus, for
+    /// panels and scripts (needs a build with the dbus feature)
+    Dbus,
+    /// Own the clipboard with TEXT until another client takes it (started
+    /// by the overview's copy keys)
+    #[command(hide = true)]
+    ServeClipboard { text: String },
+}
+
+impl Cli {
+    /// The command to run. Fails only for bad `--screenshot` arguments.
+    pub fn into_command(self) -> Result<Command, String> {
+        if let Some(mut args) = self.screenshot {
+            if let [only] = args.as_slice() {
+                if only == "window" || only == "overview" {
+                    return Err("--screenshot requires an output path".to_string());
+                }
+            }
+            let path = PathBuf::from(args.pop().unwrap_or_default());
+            let target = match args.pop().as_deref() {
+                None | Some("overview") => ScreenshotTarget::Overview,
+                Some("window") => ScreenshotTarget::Window,
+                Some(other) => return Err(format!("invalid screenshot target '{}'", other)),
+            };
+            return Ok(Command::Screenshot { target, path });
+        }
+        if let Some(desktop) = self.switch {
+            return Ok(Command::Desktop(DesktopCommand::Switch(desktop)));
+        }
+        if self.print_windows {
+            return Ok(Command::PrintWindows);
+        }
+        if let Some(window) = self.activate {
+            return Ok(Command::Desktop(DesktopCommand::Activate(window)));
+        }
+
+        let app = match self.class {
+            Some(class) => Some(AppFilter::Class(class)),
+            None => self.current_app.then_some(AppFilter::Current),
+        };
+        let overview = OverviewOptions {
+            demo: false,
+            no_bar: self.no_bar,
+            monitor: self.monitor,
+            app,
+        };
+        Ok(match self.command {
+            None => Command::Overview(overview),
+            Some(Commands::Demo) => Command::Overview(OverviewOptions { demo: true, ..overview }),
+            Some(Commands::Switch { desktop }) => Command::Desktop(DesktopCommand::Switch(desktop)),
+            Some(Commands::Next) => Command::Desktop(DesktopCommand::Next),
+            Some(Commands::Prev) => Command::Desktop(DesktopCommand::Prev),
+            Some(Commands::MoveWindow { window, desktop }) => {
+                Command::Desktop(DesktopCommand::MoveWindow { window, desktop })
+            }
+            Some(Commands::RenameDesktop { desktop, name }) => Command::Desktop(DesktopCommand::Rename {
+                desktop,
+                name: name.join(" "),
+            }),
+            Some(Commands::ShowDesktop) => Command::Desktop(DesktopCommand::ShowDesktop),
+            Some(Commands::CheckConfig { path }) => Command::CheckConfig(path),
+            Some(Commands::TrackFocus) => Command::TrackFocus,
+            Some(Commands::ListWindows { json }) => Command::ListWindows { json },
+            Some(Commands::Daemon { systemd }) => Command::Daemon { systemd },
+            Some(Commands::Dbus) => Command::Dbus,
+            Some(Commands::ServeClipboard { text }) => Command::ServeClipboard(text),
+        })
+    }
+}
+
+/// Parse a 1-based desktop number into a 0-indexed desktop.
+fn parse_desktop(arg: &str) -> Result<u32, String> {
+    match arg.parse::<u32>() {
+        Ok(n) if n >= 1 => Ok(n - 1),
+        _ => Err(format!("invalid desktop number '{}'", arg)),
+    }
+}
+
+/// Parse a 1-based monitor number into a 0-indexed monitor.
+fn parse_monitor(arg: &str) -> Result<usize, String> {
+    match arg.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(n - 1),
+        _ => Err(format!("invalid monitor number '{}'", arg)),
+    }
+}
+
+/// Parse a window id, decimal or 0x-prefixed hex (as xwininfo prints it).
+fn parse_window(arg: &str) -> Result<u32, String> {
+    let parsed = match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => arg.parse::<u32>(),
+    };
+    parsed.map_err(|_| format!("invalid window id '{}'", arg))
+}
+
+/// Parse the window id at the start of a line picked from
+/// `--print-windows`.
+fn parse_picked_window(arg: &str) -> Result<u32, String> {
+    parse_window(arg.split_whitespace().next().unwrap_or(arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let cli = Cli::try_parse_from(std::iter::once("xpose").chain(args.iter().copied()))
+            .map_err(|e| e.to_string())?;
+        cli.into_command()
+    }
+
+    #[test]
+    fn test_no_args_is_overview() {
+        assert_eq!(parse(&[]), Ok(Command::Overview(OverviewOptions::default())));
+    }
+
+    #[test]
+    fn test_overview_options() {
+        assert_eq!(
+            parse(&["demo"]),
+            Ok(Command::Overview(OverviewOptions { demo: true, ..Default::default() }))
+        );
+        assert_eq!(
+            parse(&["--no-bar", "--monitor", "2"]),
+            Ok(Command::Overview(OverviewOptions {
+                demo: false,
+                no_bar: true,
+                monitor: Some(1),
+                app: None,
+            }))
+        );
+        assert!(parse(&["--monitor", "0"]).is_err());
+        assert_eq!(
+            parse(&["--class", "Firefox"]),
+            Ok(Command::Overview(OverviewOptions {
+                app: Some(AppFilter::Class("Firefox".to_string())),
+                ..Default::default()
+            }))
+        );
+        assert_eq!(
+            parse(&["--current-app"]),
+            Ok(Command::Overview(OverviewOptions { app: Some(AppFilter::Current), ..Default::default() }))
+        );
+        assert!(parse(&["--class", "XTerm", "--current-app"]).is_err());
+    }
+
+    #[test]
+    fn test_global_options() {
+        let cli = Cli::try_parse_from(["xpose", "next", "--display", ":1", "--log-level", "debug"]).unwrap();
+        assert_eq!(cli.display.as_deref(), Some(":1"));
+        assert_eq!(cli.log_level, Some(log::LevelFilter::Debug));
+        assert!(Cli::try_parse_from(["xpose", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn test_desktop_commands() {
+        assert_eq!(parse(&["switch", "3"]), Ok(Command::Desktop(DesktopCommand::Switch(2))));
+        assert_eq!(parse(&["--switch", "1"]), Ok(Command::Desktop(DesktopCommand::Switch(0))));
+        assert_eq!(parse(&["next"]), Ok(Command::Desktop(DesktopCommand::Next)));
+        assert_eq!(parse(&["show-desktop"]), Ok(Command::Desktop(DesktopCommand::ShowDesktop)));
+        assert_eq!(
+            parse(&["move-window", "0x1a00007", "1"]),
+            Ok(Command::Desktop(DesktopCommand::MoveWindow {
+                window: 0x1a00007,
+                desktop: 0,
+            }))
+        );
+        assert!(parse(&["switch"]).is_err());
+        assert!(parse(&["move-window", "abc", "1"]).is_err());
+        assert!(parse(&["move-window", "42"]).is_err());
+        assert_eq!(parse(&["--activate", "0x1a00007"]), Ok(Command::Desktop(DesktopCommand::Activate(0x1a00007))));
+        // A line as picked from --print-windows
+        assert_eq!(
+            parse(&["--activate", "0x01a00007  [2]  XTerm  vim"]),
+            Ok(Command::Desktop(DesktopCommand::Activate(0x1a00007)))
+        );
+        assert!(parse(&["--activate", "vim"]).is_err());
+        assert!(parse(&["--activate", "1", "--print-windows"]).is_err());
+    }
+
+    #[test]
+    fn test_rename_desktop() {
+        assert_eq!(
+            parse(&["rename-desktop", "2", "Web", "stuff"]),
+            Ok(Command::Desktop(DesktopCommand::Rename {
+                desktop: 1,
+                name: "Web stuff".to_string(),
+            }))
+        );
+        assert_eq!(
+            parse(&["rename-desktop", "1"]),
+            Ok(Command::Desktop(DesktopCommand::Rename {
+                desktop: 0,
+                name: String::new(),
+            }))
+        );
+        assert!(parse(&["rename-desktop", "0", "x"]).is_err());
+        assert!(parse(&["rename-desktop"]).is_err());
+    }
+
+    #[test]
+    fn test_check_config() {
+        assert_eq!(parse(&["check-config"]), Ok(Command::CheckConfig(None)));
+        assert_eq!(
+            parse(&["check-config", "xposerc"]),
+            Ok(Command::CheckConfig(Some(PathBuf::from("xposerc"))))
+        );
+        assert_eq!(parse(&["track-focus"]), Ok(Command::TrackFocus));
+        assert_eq!(parse(&["list-windows", "--json"]), Ok(Command::ListWindows { json: true }));
+        assert_eq!(parse(&["--print-windows"]), Ok(Command::PrintWindows));
+        // As the overview starts it, so a title may begin with a dash
+        assert_eq!(
+            parse(&["serve-clipboard", "--", "-bash"]),
+            Ok(Command::ServeClipboard("-bash".to_string()))
+        );
+        assert_eq!(parse(&["daemon"]), Ok(Command::Daemon { systemd: false }));
+        assert_eq!(parse(&["daemon", "--systemd"]), Ok(Command::Daemon { systemd: true }));
+        assert_eq!(parse(&["dbus"]), Ok(Command::Dbus));
+    }
+
+    #[test]
+    fn test_screenshot_target() {
+        assert_eq!(
+            parse(&["--screenshot", "window", "out.png"]),
+            Ok(Command::Screenshot {
+                target: ScreenshotTarget::Window,
+                path: PathBuf::from("out.png"),
+            })
+        );
+        assert_eq!(
+            parse(&["--screenshot", "out.png"]),
+            Ok(Command::Screenshot {
+                target: ScreenshotTarget::Overview,
+                path: PathBuf::from("out.png"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_screenshot_missing_path() {
+        assert!(parse(&["--screenshot"]).is_err());
+        assert!(parse(&["--screenshot", "window"]).is_err());
+        assert!(parse(&["--screenshot", "desktop", "out.png"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,51 @@
+//! Which client window each frame holds, remembered.
+//!
+//! Finding a frame's client means walking the tree below it for the window
+//! with WM_STATE, a round trip per level. Discovery does that for every frame
+//! (see window_finder.rs), and desktop moves and logging would do it again
+//! for the same frames, so what it finds is kept for the connection's
+//! lifetime. The overview forgets a frame when it sees it destroyed or a
+//! window reparented into or out of it.
+
+use std::collections::HashMap;
+
+use x11rb::protocol::xproto::Window;
+
+/// Frame window -> client window.
+#[derive(Debug, Default)]
+pub struct ClientCache {
+    clients: HashMap<Window, Window>,
+}
+
+impl ClientCache {
+    pub fn get(&self, frame: Window) -> Option<Window> {
+        self.clients.get(&frame).copied()
+    }
+
+    pub fn insert(&mut self, frame: Window, client: Window) {
+        self.clients.insert(frame, client);
+    }
+
+    /// Drop whatever is known about `window`, as a frame or as a client.
+    pub fn forget(&mut self, window: Window) {
+        self.clients.retain(|&frame, &mut client| frame != window && client != window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forget_client() {
+        let mut cache = ClientCache::default();
+        cache.insert(10, 11);
+        cache.insert(20, 21);
+        cache.insert(30, 30);
+        cache.forget(11);
+        cache.forget(30);
+        assert_eq!(cache.get(10), None);
+        assert_eq!(cache.get(20), Some(21));
+        assert_eq!(cache.get(30), None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Copying a window's title (`y`) or id (`Y`) from the overview.
+//!
+//! X keeps no clipboard contents: whoever owns the CLIPBOARD selection hands
+//! the text over to each client that pastes. The overview closes moments
+//! later, so it leaves that to a child process, `xpose serve-clipboard TEXT`,
+//! which owns CLIPBOARD and PRIMARY with an unmapped window, answers paste
+//! requests, and exits once other clients have taken both over. Titles are
+//! short, so the text always goes in one property (no INCR transfers).
+//!
+//! ICCCM has selections owned from a real server time, not CurrentTime, so
+//! clients can tell which owner came last; the child gets one from the
+//! PropertyNotify for an empty append to its own window.
+
+use std::process::{Command, Stdio};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::Event;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::connection::XConnection;
+use crate::error::{Result, XposeError};
+
+/// Put `text` on the clipboard, through a `serve-clipboard` child. A child
+/// still serving earlier text gives the selections up to the new one.
+pub fn copy(text: &str) -> Result<()> {
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["serve-clipboard", "--", text])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()?;
+    // Reap it when it's done, in case this process outlives it (daemon mode)
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Own CLIPBOARD and PRIMARY with `text` until other clients take both.
+pub fn serve(text: &str) -> Result<()> {
+    let xconn = XConnection::new()?;
+    let window = xconn.generate_id()?;
+    xconn.conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        xconn.root,
+        -1,
+        -1,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new().override_redirect(1).event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+    let acquired = xconn.server_time(window)?;
+    let mut owned = Vec::new();
+    for selection in [xconn.atoms.CLIPBOARD, AtomEnum::PRIMARY.into()] {
+        xconn.conn.set_selection_owner(window, selection, acquired)?;
+        if xconn.conn.get_selection_owner(selection)?.reply()?.owner == window {
+            owned.push(selection);
+        }
+    }
+    if owned.is_empty() {
+        return Err(XposeError::Other("Cannot own the clipboard".to_string()));
+    }
+    log::info!("Serving {} bytes on the clipboard", text.len());
+
+    while !owned.is_empty() {
+        match xconn.conn.wait_for_event()? {
+            Event::SelectionRequest(request) => xconn.answer_selection_request(&request, text, acquired)?,
+            Event::SelectionClear(clear) => owned.retain(|&selection| selection != clear.selection),
+            _ => {}
+        }
+    }
+    log::info!("Clipboard taken over, exiting");
+    Ok(())
+}
+
+impl XConnection {
+    /// The server's time now, read from the PropertyNotify for an empty
+    /// append to `window`, which must select PROPERTY_CHANGE.
+    fn server_time(&self, window: Window) -> Result<Timestamp> {
+        self.conn
+            .change_property8(PropMode::APPEND, window, AtomEnum::WM_NAME, AtomEnum::STRING, &[])?;
+        self.conn.flush()?;
+        loop {
+            if let Event::PropertyNotify(notify) = self.conn.wait_for_event()? {
+                if notify.window == window {
+                    return Ok(notify.time);
+                }
+            }
+        }
+    }
+
+    /// Store `text` as the requested target on the requestor's window, or
+    /// refuse a target we can't give, and tell the requestor. Requests from
+    /// before the selections were `acquired` are refused too.
+    fn answer_selection_request(&self, request: &SelectionRequestEvent, text: &str, acquired: Timestamp) -> Result<()> {
+        let atoms = &self.atoms;
+        // Clients older than ICCCM 2 ask with no property; the target names it
+        let property = if request.property == x11rb::NONE { request.target } else { request.property };
+        let stale = request.time != x11rb::CURRENT_TIME && request.time < acquired;
+        let stored = if stale {
+            false
+        } else if request.target == atoms.TARGETS {
+            let targets = [atoms.TARGETS, atoms.TIMESTAMP, atoms.UTF8_STRING, atoms.TEXT, AtomEnum::STRING.into()];
+            self.conn
+                .change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::ATOM, &targets)?;
+            true
+        } else if request.target == atoms.TIMESTAMP {
+            self.conn
+                .change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::INTEGER, &[acquired])?;
+            true
+        } else if request.target == atoms.UTF8_STRING || request.target == atoms.TEXT {
+            self.conn.change_property8(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                atoms.UTF8_STRING,
+                text.as_bytes(),
+            )?;
+            true
+        } else if request.target == Atom::from(AtomEnum::STRING) {
+            self.conn.change_property8(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::STRING,
+                &latin1(text),
+            )?;
+            true
+        } else {
+            false
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored { property } else { x11rb::NONE },
+        };
+        self.conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+/// `text` as ISO Latin-1, which STRING is; what it can't hold becomes '?'.
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin1() {
+        assert_eq!(latin1("café — vim"), b"caf\xe9 ? vim");
+    }
+}
@@ -0,0 +1,75 @@
+//! Getting along with a running composite manager (picom and the like).
+//!
+//! A compositor owns the `_NET_WM_CM_Sn` selection and already keeps every
+//! top-level window redirected, so captures name its pixmaps as they are
+//! instead of redirecting each window again and undoing it afterwards (see
+//! capture.rs). The overview asks it not to shadow or dim it, and not to
+//! stop compositing while it covers the screen, which would take those
+//! pixmaps away.
+
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+/// `_NET_WM_BYPASS_COMPOSITOR` value asking to stay composited.
+const KEEP_COMPOSITING: u32 = 2;
+
+impl XConnection {
+    /// Whether a composite manager is running on our screen. Asked once per
+    /// connection.
+    pub fn has_compositor(&self) -> bool {
+        *self.compositor.get_or_init(|| match self.find_compositor() {
+            Ok(found) => {
+                if found {
+                    log::info!("Compositor running, using its redirection");
+                }
+                found
+            }
+            Err(e) => {
+                log::warn!("Cannot check for a compositor: {}", e);
+                false
+            }
+        })
+    }
+
+    fn find_compositor(&self) -> Result<bool> {
+        let name = format!("_NET_WM_CM_S{}", self.screen_num);
+        let selection = self.conn.intern_atom(true, name.as_bytes())?.reply()?.atom;
+        if selection == x11rb::NONE {
+            return Ok(false);
+        }
+        Ok(self.conn.get_selection_owner(selection)?.reply()?.owner != x11rb::NONE)
+    }
+
+    /// Hints for the compositor on the overview window: fully opaque, no
+    /// shadow, and keep compositing.
+    pub fn set_compositor_hints(&self, window: Window) -> Result<()> {
+        if !self.has_compositor() {
+            return Ok(());
+        }
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.atoms._NET_WM_WINDOW_OPACITY,
+            AtomEnum::CARDINAL,
+            &[u32::MAX],
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.atoms._COMPTON_SHADOW,
+            AtomEnum::CARDINAL,
+            &[0],
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.atoms._NET_WM_BYPASS_COMPOSITOR,
+            AtomEnum::CARDINAL,
+            &[KEEP_COMPOSITING],
+        )?;
+        Ok(())
+    }
+}
@@ -1,15 +1,183 @@
+use std::collections::HashMap;
 use std::fs;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::time::Duration;
 
-/// Application configuration loaded from ~/.xposerc
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::animation::Easing;
+use crate::desktop::backend::BackendKind;
+use crate::desktop::state::MAX_DESKTOPS;
+use crate::desktop_bar::BarPosition;
+use crate::fullscreen::FullscreenActivation;
+use crate::input::ButtonMap;
+use crate::layout::{GridOrder, MonitorConfig, MonitorLayout};
+use crate::theme::ThemeKind;
+use crate::window_finder::WindowFilter;
+
+// Bounds for user-supplied values; anything outside is clamped so later
+// layout and animation math can't overflow.
+const MAX_ANIMATION_MS: u64 = 10_000;
+const MIN_ANIMATION_SPEED: f64 = 0.1;
+const MAX_ANIMATION_SPEED: f64 = 10.0;
+const MIN_DESKTOP_BAR_HEIGHT: u16 = 60;
+const MAX_DESKTOP_BAR_HEIGHT: u16 = 600;
+const MAX_MONITOR_MARGIN: u16 = 1000;
+const MAX_HIT_TOLERANCE: u16 = 32;
+
+/// The easing names, for messages about invalid ones.
+const EASINGS: &str = "ease-out-cubic, ease-in-out, spring or linear";
+
+/// Keys config.toml understands, by section.
+const TOML_KEYS: &[(&str, &[&str])] = &[
+    (
+        "layout",
+        &[
+            "desktop_bar_height",
+            "bar_position",
+            "show_desktop_bar",
+            "show_icons",
+            "icon_theme",
+            "theme",
+            "order",
+            "exclude_classes",
+            "exclude_titles",
+            "include_only_classes",
+            "include_only_titles",
+        ],
+    ),
+    (
+        "animation",
+        &[
+            "entrance_ms",
+            "exit_ms",
+            "speed",
+            "switch_ms",
+            "peek_ms",
+            "tooltip_ms",
+            "present",
+            "entrance_easing",
+            "exit_easing",
+            "snap_easing",
+            "revert_easing",
+            "grid_easing",
+        ],
+    ),
+    ("desktops", &["backend", "remove_empty", "restore_geometry", "wallpapers", "identity_titles"]),
+    (
+        "keybindings",
+        &[
+            "click_through",
+            "button_select",
+            "button_drag",
+            "button_close",
+            "button_dismiss",
+            "fullscreen_activation",
+            "hit_tolerance",
+        ],
+    ),
+    ("accessibility", &["announce"]),
+    ("theme", &["name"]),
+];
+
+/// Keys of a `[monitor.NAME]` section.
+const MONITOR_KEYS: &[&str] = &["show_desktop_bar", "layout", "margin"];
+
+/// Config file named with `--config`, used instead of the usual lookup.
+static PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Application configuration, loaded from ~/.config/xpose/config.toml or,
+/// failing that, the legacy ~/.xposerc
 pub struct Config {
     pub entrance_ms: u64,
     pub exit_ms: u64,
     pub animation_speed: f64,
+    /// Timing curves of the entrance and exit, a dropped window snapping to
+    /// a desktop or reverting to the grid, and grid and desktop bar re-layouts
+    pub entrance_easing: Easing,
+    pub exit_easing: Easing,
+    pub snap_easing: Easing,
+    pub revert_easing: Easing,
+    pub grid_easing: Easing,
     /// WM_CLASS values to exclude from the exposé view
     pub exclude_classes: Vec<String>,
+    /// Window titles (WM_NAME) to exclude, compiled once at load
+    pub exclude_titles: Vec<Regex>,
+    /// When either include list is set, only matching windows are shown
+    pub include_classes: Vec<String>,
+    pub include_titles: Vec<Regex>,
     /// Height of the virtual desktop bar in pixels
     pub desktop_bar_height: u16,
+    /// Screen edge the desktop bar sits on
+    pub bar_position: BarPosition,
+    /// Show the desktop bar; without it the overview is a plain window picker
+    pub show_desktop_bar: bool,
+    /// Put each window's application icon on its thumbnail (see icons.rs)
+    pub show_icons: bool,
+    /// Icon theme searched before hicolor, e.g. "Adwaita"
+    pub icon_theme: Option<String>,
+    /// Built-in look: default or high contrast (see theme.rs)
+    pub theme: ThemeKind,
+    /// Fill grids by where windows are on screen, or most recently focused
+    /// first
+    pub grid_order: GridOrder,
+    /// Replay a dismissing background click to whatever is underneath
+    pub click_through: bool,
+    /// Pointer buttons that select, drag, close and dismiss
+    pub buttons: ButtonMap,
+    /// Whether the overview opens while a fullscreen window has the focus
+    pub fullscreen_activation: FullscreenActivation,
+    /// How far off a thumbnail or desktop bar button (in pixels) a click
+    /// still counts as on it
+    pub hit_tolerance: u16,
+    /// Desktop (0-indexed) -> wallpaper image (PNG or JPEG)
+    pub wallpapers: HashMap<u32, PathBuf>,
+    /// Whose desktops the overview shows: xpose's own, or a tiling window
+    /// manager's workspaces (see desktop/backend.rs)
+    pub desktop_backend: BackendKind,
+    /// Remove a desktop once its last window leaves it
+    pub remove_empty_desktops: bool,
+    /// Put windows moved to another desktop back where they last were on it
+    pub restore_geometry: bool,
+    /// WM_CLASS -> title patterns that tell its windows apart when their
+    /// desktop is remembered for after the application restarts
+    pub identity_titles: HashMap<String, Vec<Regex>>,
+    /// Slide transition for `xpose switch`/`next`/`prev` (0 disables it)
+    pub switch_ms: u64,
+    /// How long the pointer rests on a thumbnail before the window is shown
+    /// full-size (0 disables it)
+    pub peek_ms: u64,
+    /// How long the pointer rests on a thumbnail before a tooltip gives the
+    /// window's details (0 disables it)
+    pub tooltip_ms: u64,
+    /// Show frames with the Present extension, in step with the display
+    pub present: bool,
+    /// Overrides for single monitors, by RandR output name (e.g. "DP-1")
+    pub monitors: HashMap<String, MonitorConfig>,
+    /// Have `xpose dbus` announce the highlighted window as a desktop
+    /// notification, for screen readers (see announce.rs)
+    pub announce: bool,
+}
+
+/// Something wrong with a config file: an unknown key, or a value that is
+/// invalid or out of range (and was ignored or clamped).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Line number (from 1), when it is known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn new(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
 }
 
 impl Default for Config {
@@ -18,29 +186,366 @@ impl Default for Config {
             entrance_ms: 350,
             exit_ms: 350,
             animation_speed: 1.0,
+            entrance_easing: Easing::default(),
+            exit_easing: Easing::default(),
+            snap_easing: Easing::default(),
+            revert_easing: Easing::default(),
+            grid_easing: Easing::default(),
             exclude_classes: Vec::new(),
+            exclude_titles: Vec::new(),
+            include_classes: Vec::new(),
+            include_titles: Vec::new(),
             desktop_bar_height: 240,
+            bar_position: BarPosition::Top,
+            show_desktop_bar: true,
+            show_icons: false,
+            icon_theme: None,
+            theme: ThemeKind::Default,
+            grid_order: GridOrder::Position,
+            click_through: false,
+            buttons: ButtonMap::default(),
+            fullscreen_activation: FullscreenActivation::Allow,
+            hit_tolerance: 6,
+            wallpapers: HashMap::new(),
+            desktop_backend: BackendKind::Xpose,
+            remove_empty_desktops: false,
+            restore_geometry: false,
+            identity_titles: HashMap::new(),
+            switch_ms: 150,
+            peek_ms: 0,
+            tooltip_ms: 800,
+            present: false,
+            monitors: HashMap::new(),
+            announce: false,
         }
     }
 }
 
 impl Config {
-    /// Load configuration from ~/.xposerc
-    /// Falls back to defaults if file doesn't exist or has parse errors.
+    /// Load configuration from ~/.config/xpose/config.toml, or from
+    /// ~/.xposerc when there is no TOML file.
+    /// Falls back to defaults if neither exists or has parse errors.
     pub fn load() -> Self {
-        let mut config = Self::default();
-
-        let path = match dirs::home_dir() {
-            Some(home) => home.join(".xposerc"),
-            None => return config,
+        let Some(path) = Self::path() else {
+            return Self::default();
         };
+        match fs::read_to_string(&path) {
+            Ok(contents) if path.extension().is_some_and(|ext| ext == "toml") => {
+                log::debug!("Config: reading {}", path.display());
+                Self::parse_toml(&contents)
+            }
+            Ok(contents) => Self::parse(&contents),
+            Err(e) => {
+                if PATH_OVERRIDE.get().is_some() {
+                    log::warn!("Config: cannot read {}: {}", path.display(), e);
+                }
+                Self::default()
+            }
+        }
+    }
 
+    /// Read the configuration again after the file changed. None while it
+    /// can't be read or isn't valid TOML, as half way through an edit, so
+    /// the configuration in use stays rather than falling back to defaults.
+    pub fn reload() -> Option<Self> {
+        let path = Self::path()?;
         let contents = match fs::read_to_string(&path) {
-            Ok(s) => s,
-            Err(_) => return config,
+            Ok(contents) => contents,
+            Err(e) => {
+                log::warn!("Config: cannot read {}, keeping the current config: {}", path.display(), e);
+                return None;
+            }
         };
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            Self::parse_toml_complete(&contents)
+        } else {
+            Some(Self::parse(&contents))
+        }
+    }
 
-        for line in contents.lines() {
+    /// Parse TOML config text, or None if it isn't valid TOML (or has
+    /// mistyped values).
+    fn parse_toml_complete(contents: &str) -> Option<Self> {
+        if let Err(e) = toml::from_str::<TomlConfig>(contents) {
+            log::warn!("Config: invalid config.toml, keeping the current config: {}", e);
+            return None;
+        }
+        Some(Self::parse_toml(contents))
+    }
+
+    /// Read configuration from `path` from now on (`--config`).
+    pub fn set_path(path: PathBuf) {
+        let _ = PATH_OVERRIDE.set(path);
+    }
+
+    /// The file `load` reads: the `--config` file if one was given, else
+    /// config.toml if it exists, else ~/.xposerc (whether or not that exists).
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = PATH_OVERRIDE.get() {
+            return Some(path.clone());
+        }
+        let toml = dirs::config_dir().map(|dir| dir.join("xpose").join("config.toml"));
+        match toml {
+            Some(path) if path.is_file() => Some(path),
+            _ => dirs::home_dir().map(|home| home.join(".xposerc")),
+        }
+    }
+
+    /// Parse TOML config text. Unknown keys and out-of-range values are
+    /// ignored; a file that isn't valid TOML (or has mistyped values) gives
+    /// the defaults.
+    pub fn parse_toml(contents: &str) -> Self {
+        Self::parse_toml_checked(contents).0
+    }
+
+    /// Parse TOML config text, also returning everything that was wrong
+    /// with it.
+    pub fn parse_toml_checked(contents: &str) -> (Self, Vec<ConfigIssue>) {
+        let mut config = Self::default();
+        let mut issues = Vec::new();
+        let file: TomlConfig = match toml::from_str(contents) {
+            Ok(file) => file,
+            Err(e) => {
+                log::warn!("Config: invalid config.toml, using defaults: {}", e);
+                let line = e.span().map(|span| line_of_offset(contents, span.start));
+                issues.push(ConfigIssue::new(line, e.message().trim_end().replace('\n', "; ")));
+                return (config, issues);
+            }
+        };
+
+        let lines = toml_key_lines(contents);
+        let line = |section: &str, key: &str| lines.get(&(section.to_string(), key.to_string())).copied();
+        if let Ok(table) = contents.parse::<toml::Table>() {
+            for (section, value) in &table {
+                if section == "monitor" {
+                    for (name, monitor) in value.as_table().into_iter().flatten() {
+                        let section = format!("monitor.{}", name);
+                        for key in monitor.as_table().map(|t| t.keys()).into_iter().flatten() {
+                            if !MONITOR_KEYS.contains(&key.as_str()) {
+                                issues.push(ConfigIssue::new(line(&section, key), format!("unknown key '{}' in [{}]", key, section)));
+                            }
+                        }
+                    }
+                    continue;
+                }
+                let Some(&(_, known)) = TOML_KEYS.iter().find(|(name, _)| name == section) else {
+                    let header = lines.get(&(section.clone(), String::new())).copied();
+                    issues.push(ConfigIssue::new(header.or(line("", section)), format!("unknown section [{}]", section)));
+                    continue;
+                };
+                for key in value.as_table().map(|t| t.keys()).into_iter().flatten() {
+                    if !known.contains(&key.as_str()) {
+                        issues.push(ConfigIssue::new(line(section, key), format!("unknown key '{}' in [{}]", key, section)));
+                    }
+                }
+            }
+        }
+
+        let TomlConfig { layout, animation, desktops, keybindings, accessibility, theme, monitor } = file;
+        if let Some(value) = layout.desktop_bar_height {
+            if let Some(height) = checked(desktop_bar_height(value), value, line("layout", "desktop_bar_height"), "desktop_bar_height", &mut issues) {
+                config.desktop_bar_height = height;
+            }
+        }
+        if let Some(ref value) = layout.bar_position {
+            match BarPosition::parse(value) {
+                Some(position) => config.bar_position = position,
+                None => issues.push(ConfigIssue::new(
+                    line("layout", "bar_position"),
+                    format!("bar_position must be top, bottom, left or right, not '{}'", value),
+                )),
+            }
+        }
+        if let Some(show) = layout.show_desktop_bar {
+            config.show_desktop_bar = show;
+        }
+        if let Some(show) = layout.show_icons {
+            config.show_icons = show;
+        }
+        if layout.icon_theme.is_some() {
+            config.icon_theme = layout.icon_theme;
+        }
+        // [theme] name wins over layout.theme, which older files still use
+        for (section, key, value) in [("layout", "theme", &layout.theme), ("theme", "name", &theme.name)] {
+            if let Some(value) = value {
+                match ThemeKind::parse(value) {
+                    Some(theme) => config.theme = theme,
+                    None => issues.push(ConfigIssue::new(
+                        line(section, key),
+                        format!("theme must be default or high-contrast, not '{}'", value),
+                    )),
+                }
+            }
+        }
+        if let Some(ref value) = layout.order {
+            match GridOrder::parse(value) {
+                Some(order) => config.grid_order = order,
+                None => issues.push(ConfigIssue::new(
+                    line("layout", "order"),
+                    format!("order must be position or recent, not '{}'", value),
+                )),
+            }
+        }
+        config.exclude_classes = layout.exclude_classes;
+        config.exclude_titles = title_patterns(&layout.exclude_titles, line("layout", "exclude_titles"), &mut issues);
+        config.include_classes = layout.include_only_classes;
+        config.include_titles = title_patterns(&layout.include_only_titles, line("layout", "include_only_titles"), &mut issues);
+
+        for (key, value, field) in [
+            ("entrance_ms", animation.entrance_ms, &mut config.entrance_ms),
+            ("exit_ms", animation.exit_ms, &mut config.exit_ms),
+            ("switch_ms", animation.switch_ms, &mut config.switch_ms),
+            ("peek_ms", animation.peek_ms, &mut config.peek_ms),
+            ("tooltip_ms", animation.tooltip_ms, &mut config.tooltip_ms),
+        ] {
+            if let Some(ms) = value {
+                *field = checked(Some(ms.min(MAX_ANIMATION_MS)), ms, line("animation", key), key, &mut issues).unwrap_or(*field);
+            }
+        }
+        for (key, value, field) in [
+            ("entrance_easing", &animation.entrance_easing, &mut config.entrance_easing),
+            ("exit_easing", &animation.exit_easing, &mut config.exit_easing),
+            ("snap_easing", &animation.snap_easing, &mut config.snap_easing),
+            ("revert_easing", &animation.revert_easing, &mut config.revert_easing),
+            ("grid_easing", &animation.grid_easing, &mut config.grid_easing),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+            match Easing::parse(value) {
+                Some(easing) => *field = easing,
+                None => issues.push(ConfigIssue::new(
+                    line("animation", key),
+                    format!("{} must be {}, not '{}'", key, EASINGS, value),
+                )),
+            }
+        }
+        if let Some(enabled) = animation.present {
+            config.present = enabled;
+        }
+        if let Some(value) = animation.speed {
+            if let Some(speed) = checked(animation_speed(value), value, line("animation", "speed"), "speed", &mut issues) {
+                config.animation_speed = speed;
+            }
+        }
+
+        if let Some(ref value) = desktops.backend {
+            match BackendKind::parse(value) {
+                Some(kind) => config.desktop_backend = kind,
+                None => issues.push(ConfigIssue::new(
+                    line("desktops", "backend"),
+                    format!("backend must be xpose, i3 or bspwm, not '{}'", value),
+                )),
+            }
+        }
+        if let Some(enabled) = desktops.remove_empty {
+            config.remove_empty_desktops = enabled;
+        }
+        if let Some(enabled) = desktops.restore_geometry {
+            config.restore_geometry = enabled;
+        }
+        for (class, patterns) in &desktops.identity_titles {
+            let patterns = title_patterns(patterns, line("desktops", "identity_titles"), &mut issues);
+            config.identity_titles.insert(class.clone(), patterns);
+        }
+        for (desktop, path) in &desktops.wallpapers {
+            match desktop.parse::<u32>() {
+                Ok(desktop) if (1..=MAX_DESKTOPS).contains(&desktop) && !path.is_empty() => {
+                    config.wallpapers.insert(desktop - 1, expand_home(path));
+                }
+                _ => {
+                    log::debug!("Config: invalid wallpaper for desktop '{}'", desktop);
+                    issues.push(ConfigIssue::new(
+                        line("desktops", "wallpapers"),
+                        format!("invalid wallpaper for desktop '{}' (desktops are 1 to {})", desktop, MAX_DESKTOPS),
+                    ));
+                }
+            }
+        }
+
+        if let Some(enabled) = keybindings.click_through {
+            config.click_through = enabled;
+        }
+        if let Some(value) = keybindings.hit_tolerance {
+            let line = line("keybindings", "hit_tolerance");
+            if let Some(tolerance) = checked(Some(value.min(MAX_HIT_TOLERANCE)), value, line, "hit_tolerance", &mut issues) {
+                config.hit_tolerance = tolerance;
+            }
+        }
+        if let Some(ref value) = keybindings.fullscreen_activation {
+            match FullscreenActivation::parse(value) {
+                Some(activation) => config.fullscreen_activation = activation,
+                None => issues.push(ConfigIssue::new(
+                    line("keybindings", "fullscreen_activation"),
+                    format!("fullscreen_activation must be allow, refuse or twice, not '{}'", value),
+                )),
+            }
+        }
+        for (key, value) in [
+            ("button_select", keybindings.button_select),
+            ("button_drag", keybindings.button_drag),
+            ("button_close", keybindings.button_close),
+            ("button_dismiss", keybindings.button_dismiss),
+        ] {
+            let Some(button) = value else {
+                continue;
+            };
+            if button == 0 {
+                issues.push(ConfigIssue::new(line("keybindings", key), format!("{} 0 is out of range", key)));
+                continue;
+            }
+            match key {
+                "button_select" => config.buttons.select = button,
+                "button_drag" => config.buttons.drag = button,
+                "button_close" => config.buttons.close = Some(button),
+                _ => config.buttons.dismiss = Some(button),
+            }
+        }
+        check_buttons(&config.buttons, line("keybindings", "button_close"), &mut issues);
+
+        if let Some(announce) = accessibility.announce {
+            config.announce = announce;
+        }
+
+        for (name, section) in monitor {
+            let header = format!("monitor.{}", name);
+            let settings = config.monitors.entry(name).or_default();
+            if let Some(show) = section.show_desktop_bar {
+                settings.show_desktop_bar = show;
+            }
+            if let Some(ref value) = section.layout {
+                match MonitorLayout::parse(value) {
+                    Some(layout) => settings.layout = layout,
+                    None => issues.push(ConfigIssue::new(
+                        line(&header, "layout"),
+                        format!("layout must be grid or primary, not '{}'", value),
+                    )),
+                }
+            }
+            if let Some(margin) = section.margin {
+                settings.margin = checked(Some(margin.min(MAX_MONITOR_MARGIN)), margin, line(&header, "margin"), "margin", &mut issues);
+            }
+        }
+
+        issues.sort_by_key(|issue| issue.line);
+        (config, issues)
+    }
+
+    /// Parse "Key Value" config text.
+    /// Unknown keys and unparsable or out-of-range values are ignored.
+    pub fn parse(contents: &str) -> Self {
+        Self::parse_checked(contents).0
+    }
+
+    /// Parse "Key Value" config text, also returning everything that was
+    /// wrong with it.
+    pub fn parse_checked(contents: &str) -> (Self, Vec<ConfigIssue>) {
+        let mut config = Self::default();
+        let mut issues = Vec::new();
+        let mut close_line = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let number = Some(index + 1);
             let line = line.trim();
 
             // Skip empty lines and comments
@@ -56,49 +561,303 @@ impl Config {
             };
             let value = match parts.next() {
                 Some(v) => v,
-                None => continue,
+                None => {
+                    issues.push(ConfigIssue::new(number, format!("{} has no value", key)));
+                    continue;
+                }
+            };
+            let mut invalid = |expected: &str| {
+                issues.push(ConfigIssue::new(number, format!("{} must be {}, not '{}'", key, expected, value)));
             };
 
             match key {
-                "EntranceMs" => {
-                    if let Ok(ms) = value.parse::<u64>() {
-                        config.entrance_ms = ms;
-                        log::debug!("Config: EntranceMs = {}", ms);
+                "EntranceMs" | "ExitMs" | "SwitchMs" | "PeekMs" | "TooltipMs" => {
+                    let Ok(ms) = value.parse::<u64>() else {
+                        invalid("a number of milliseconds");
+                        continue;
+                    };
+                    if let Some(ms) = checked(Some(ms.min(MAX_ANIMATION_MS)), ms, number, key, &mut issues) {
+                        let field = match key {
+                            "EntranceMs" => &mut config.entrance_ms,
+                            "ExitMs" => &mut config.exit_ms,
+                            "SwitchMs" => &mut config.switch_ms,
+                            "PeekMs" => &mut config.peek_ms,
+                            _ => &mut config.tooltip_ms,
+                        };
+                        *field = ms;
+                        log::debug!("Config: {} = {}", key, ms);
                     }
                 }
-                "ExitMs" => {
-                    if let Ok(ms) = value.parse::<u64>() {
-                        config.exit_ms = ms;
-                        log::debug!("Config: ExitMs = {}", ms);
-                    }
+                "EntranceEasing" | "ExitEasing" | "SnapEasing" | "RevertEasing" | "GridEasing" => {
+                    let Some(easing) = Easing::parse(value) else {
+                        invalid(EASINGS);
+                        continue;
+                    };
+                    let field = match key {
+                        "EntranceEasing" => &mut config.entrance_easing,
+                        "ExitEasing" => &mut config.exit_easing,
+                        "SnapEasing" => &mut config.snap_easing,
+                        "RevertEasing" => &mut config.revert_easing,
+                        _ => &mut config.grid_easing,
+                    };
+                    *field = easing;
+                    log::debug!("Config: {} = {:?}", key, easing);
                 }
                 "AnimationSpeed" => {
-                    if let Ok(speed) = value.parse::<f64>() {
-                        if speed > 0.0 {
-                            config.animation_speed = speed;
-                            log::debug!("Config: AnimationSpeed = {}", speed);
-                        }
+                    let Ok(speed) = value.parse::<f64>() else {
+                        invalid("a number");
+                        continue;
+                    };
+                    if let Some(speed) = checked(animation_speed(speed), speed, number, key, &mut issues) {
+                        config.animation_speed = speed;
+                        log::debug!("Config: AnimationSpeed = {}", speed);
                     }
                 }
                 "ExcludeClass" => {
                     config.exclude_classes.push(value.to_string());
                     log::debug!("Config: ExcludeClass = {}", value);
                 }
+                "ExcludeTitle" => {
+                    // The pattern is the rest of the line, spaces included
+                    let pattern = line[key.len()..].trim();
+                    if let Some(re) = title_pattern(pattern, number, &mut issues) {
+                        log::debug!("Config: ExcludeTitle = {}", pattern);
+                        config.exclude_titles.push(re);
+                    }
+                }
+                "IncludeOnlyClass" => {
+                    config.include_classes.push(value.to_string());
+                    log::debug!("Config: IncludeOnlyClass = {}", value);
+                }
+                "IncludeOnlyTitle" => {
+                    let pattern = line[key.len()..].trim();
+                    if let Some(re) = title_pattern(pattern, number, &mut issues) {
+                        log::debug!("Config: IncludeOnlyTitle = {}", pattern);
+                        config.include_titles.push(re);
+                    }
+                }
                 "DesktopBarHeight" => {
-                    if let Ok(height) = value.parse::<u16>() {
-                        if height > 0 {
-                            config.desktop_bar_height = height;
-                            log::debug!("Config: DesktopBarHeight = {}", height);
+                    let Ok(height) = value.parse::<u16>() else {
+                        invalid("a number of pixels");
+                        continue;
+                    };
+                    if let Some(height) = checked(desktop_bar_height(height), height, number, key, &mut issues) {
+                        config.desktop_bar_height = height;
+                        log::debug!("Config: DesktopBarHeight = {}", height);
+                    }
+                }
+                "ShowDesktopBar" => match parse_bool(value) {
+                    Some(show) => {
+                        config.show_desktop_bar = show;
+                        log::debug!("Config: ShowDesktopBar = {}", show);
+                    }
+                    None => invalid("true or false"),
+                },
+                "ShowIcons" => match parse_bool(value) {
+                    Some(show) => {
+                        config.show_icons = show;
+                        log::debug!("Config: ShowIcons = {}", show);
+                    }
+                    None => invalid("true or false"),
+                },
+                "Announce" => match parse_bool(value) {
+                    Some(announce) => {
+                        config.announce = announce;
+                        log::debug!("Config: Announce = {}", announce);
+                    }
+                    None => invalid("true or false"),
+                },
+                "IconTheme" => {
+                    config.icon_theme = Some(value.to_string());
+                    log::debug!("Config: IconTheme = {}", value);
+                }
+                "Theme" => match ThemeKind::parse(value) {
+                    Some(theme) => {
+                        config.theme = theme;
+                        log::debug!("Config: Theme = {:?}", theme);
+                    }
+                    None => invalid("default or high-contrast"),
+                },
+                "Order" => match GridOrder::parse(value) {
+                    Some(order) => {
+                        config.grid_order = order;
+                        log::debug!("Config: Order = {:?}", order);
+                    }
+                    None => invalid("position or recent"),
+                },
+                "BarPosition" => match BarPosition::parse(value) {
+                    Some(position) => {
+                        config.bar_position = position;
+                        log::debug!("Config: BarPosition = {:?}", position);
+                    }
+                    None => invalid("top, bottom, left or right"),
+                },
+                "ButtonSelect" | "ButtonDrag" | "ButtonClose" | "ButtonDismiss" => {
+                    let button = match value.parse::<u8>() {
+                        Ok(button) if button > 0 => button,
+                        _ => {
+                            invalid("a button number (1 left, 2 middle, 3 right)");
+                            continue;
+                        }
+                    };
+                    match key {
+                        "ButtonSelect" => config.buttons.select = button,
+                        "ButtonDrag" => config.buttons.drag = button,
+                        "ButtonClose" => {
+                            config.buttons.close = Some(button);
+                            close_line = number;
+                        }
+                        _ => config.buttons.dismiss = Some(button),
+                    }
+                    log::debug!("Config: {} = {}", key, button);
+                }
+                "FullscreenActivation" => match FullscreenActivation::parse(value) {
+                    Some(activation) => {
+                        config.fullscreen_activation = activation;
+                        log::debug!("Config: FullscreenActivation = {:?}", activation);
+                    }
+                    None => invalid("allow, refuse or twice"),
+                },
+                "HitTolerance" => {
+                    let Ok(pixels) = value.parse::<u16>() else {
+                        invalid("a number of pixels");
+                        continue;
+                    };
+                    if let Some(pixels) = checked(Some(pixels.min(MAX_HIT_TOLERANCE)), pixels, number, key, &mut issues) {
+                        config.hit_tolerance = pixels;
+                        log::debug!("Config: HitTolerance = {}", pixels);
+                    }
+                }
+                "Present" => match parse_bool(value) {
+                    Some(enabled) => {
+                        config.present = enabled;
+                        log::debug!("Config: Present = {}", enabled);
+                    }
+                    None => invalid("true or false"),
+                },
+                "ClickThrough" => match parse_bool(value) {
+                    Some(enabled) => {
+                        config.click_through = enabled;
+                        log::debug!("Config: ClickThrough = {}", enabled);
+                    }
+                    None => invalid("true or false"),
+                },
+                "DesktopBackend" => match BackendKind::parse(value) {
+                    Some(kind) => {
+                        config.desktop_backend = kind;
+                        log::debug!("Config: DesktopBackend = {:?}", kind);
+                    }
+                    None => invalid("xpose, i3 or bspwm"),
+                },
+                "RemoveEmptyDesktops" => match parse_bool(value) {
+                    Some(enabled) => {
+                        config.remove_empty_desktops = enabled;
+                        log::debug!("Config: RemoveEmptyDesktops = {}", enabled);
+                    }
+                    None => invalid("true or false"),
+                },
+                "IdentityTitle" => {
+                    // "IdentityTitle CLASS PATTERN", the pattern being the rest of the line
+                    let rest = line[key.len()..].trim_start();
+                    let Some((class, pattern)) = rest.split_once(char::is_whitespace) else {
+                        issues.push(ConfigIssue::new(number, "IdentityTitle needs a class and a title pattern"));
+                        continue;
+                    };
+                    if let Some(re) = title_pattern(pattern.trim(), number, &mut issues) {
+                        log::debug!("Config: IdentityTitle {} = {}", class, pattern.trim());
+                        config.identity_titles.entry(class.to_string()).or_default().push(re);
+                    }
+                }
+                "RestoreGeometry" => match parse_bool(value) {
+                    Some(enabled) => {
+                        config.restore_geometry = enabled;
+                        log::debug!("Config: RestoreGeometry = {}", enabled);
+                    }
+                    None => invalid("true or false"),
+                },
+                "Monitor" => {
+                    // "Monitor NAME KEY VALUE"
+                    let mut rest = line[key.len()..].split_whitespace().skip(1);
+                    let (Some(setting), Some(setting_value)) = (rest.next(), rest.next()) else {
+                        issues.push(ConfigIssue::new(number, "Monitor needs a monitor name, a key and a value"));
+                        continue;
+                    };
+                    let settings = config.monitors.entry(value.to_string()).or_default();
+                    let mut invalid = |expected: &str| {
+                        issues.push(ConfigIssue::new(
+                            number,
+                            format!("Monitor {} must be {}, not '{}'", setting, expected, setting_value),
+                        ));
+                    };
+                    match setting {
+                        "ShowDesktopBar" => match parse_bool(setting_value) {
+                            Some(show) => settings.show_desktop_bar = show,
+                            None => invalid("true or false"),
+                        },
+                        "Layout" => match MonitorLayout::parse(setting_value) {
+                            Some(layout) => settings.layout = layout,
+                            None => invalid("grid or primary"),
+                        },
+                        "Margin" => match setting_value.parse::<u16>() {
+                            Ok(margin) => {
+                                settings.margin =
+                                    checked(Some(margin.min(MAX_MONITOR_MARGIN)), margin, number, "Margin", &mut issues);
+                            }
+                            Err(_) => invalid("a number of pixels"),
+                        },
+                        _ => issues.push(ConfigIssue::new(number, format!("unknown monitor key '{}'", setting))),
+                    }
+                    log::debug!("Config: Monitor {} {} = {}", value, setting, setting_value);
+                }
+                "Wallpaper" => {
+                    // "Wallpaper N PATH" - the path is the rest of the line
+                    let path = line[key.len()..].trim_start()[value.len()..].trim();
+                    match value.parse::<u32>() {
+                        Ok(desktop) if (1..=MAX_DESKTOPS).contains(&desktop) && !path.is_empty() => {
+                            let path = expand_home(path);
+                            log::debug!("Config: Wallpaper {} = {}", desktop, path.display());
+                            config.wallpapers.insert(desktop - 1, path);
+                        }
+                        _ => {
+                            log::debug!("Config: invalid Wallpaper line '{}'", line);
+                            issues.push(ConfigIssue::new(
+                                number,
+                                format!("Wallpaper needs a desktop from 1 to {} and an image path", MAX_DESKTOPS),
+                            ));
                         }
                     }
                 }
                 _ => {
                     log::debug!("Config: unknown key '{}'", key);
+                    issues.push(ConfigIssue::new(number, format!("unknown key '{}'", key)));
                 }
             }
         }
+        check_buttons(&config.buttons, close_line, &mut issues);
 
-        config
+        (config, issues)
+    }
+
+    /// Everything wrong with the config file at `path`, for
+    /// `xpose check-config`.
+    pub fn check_file(path: &Path) -> std::io::Result<Vec<ConfigIssue>> {
+        let contents = fs::read_to_string(path)?;
+        let (_, issues) = if path.extension().is_some_and(|ext| ext == "toml") {
+            Self::parse_toml_checked(&contents)
+        } else {
+            Self::parse_checked(&contents)
+        };
+        Ok(issues)
+    }
+
+    /// The windows to keep out of the grid.
+    pub fn window_filter(&self) -> WindowFilter<'_> {
+        WindowFilter {
+            exclude_classes: &self.exclude_classes,
+            exclude_titles: &self.exclude_titles,
+            include_classes: &self.include_classes,
+            include_titles: &self.include_titles,
+        }
     }
 
     pub fn entrance_duration(&self) -> Duration {
@@ -109,3 +868,340 @@ impl Config {
         Duration::from_millis(self.exit_ms)
     }
 }
+
+/// config.toml, section by section. Every key is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TomlConfig {
+    layout: LayoutSection,
+    animation: AnimationSection,
+    desktops: DesktopsSection,
+    keybindings: KeybindingsSection,
+    accessibility: AccessibilitySection,
+    theme: ThemeSection,
+    /// `[monitor.NAME]` sections, by monitor name
+    monitor: HashMap<String, MonitorSection>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LayoutSection {
+    desktop_bar_height: Option<u16>,
+    bar_position: Option<String>,
+    show_desktop_bar: Option<bool>,
+    show_icons: Option<bool>,
+    icon_theme: Option<String>,
+    /// Kept for older files; `[theme] name` is the place for it now
+    theme: Option<String>,
+    order: Option<String>,
+    exclude_classes: Vec<String>,
+    exclude_titles: Vec<String>,
+    include_only_classes: Vec<String>,
+    include_only_titles: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct AnimationSection {
+    entrance_ms: Option<u64>,
+    exit_ms: Option<u64>,
+    speed: Option<f64>,
+    switch_ms: Option<u64>,
+    peek_ms: Option<u64>,
+    tooltip_ms: Option<u64>,
+    present: Option<bool>,
+    entrance_easing: Option<String>,
+    exit_easing: Option<String>,
+    snap_easing: Option<String>,
+    revert_easing: Option<String>,
+    grid_easing: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DesktopsSection {
+    backend: Option<String>,
+    remove_empty: Option<bool>,
+    restore_geometry: Option<bool>,
+    /// WM_CLASS -> title patterns
+    identity_titles: HashMap<String, Vec<String>>,
+    /// Desktop number (from 1, as a string key) -> image path
+    wallpapers: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct MonitorSection {
+    show_desktop_bar: Option<bool>,
+    layout: Option<String>,
+    margin: Option<u16>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeybindingsSection {
+    click_through: Option<bool>,
+    button_select: Option<u8>,
+    button_drag: Option<u8>,
+    button_close: Option<u8>,
+    button_dismiss: Option<u8>,
+    fullscreen_activation: Option<String>,
+    hit_tolerance: Option<u16>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct AccessibilitySection {
+    announce: Option<bool>,
+}
+
+/// The built-in look, by name (see theme.rs).
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ThemeSection {
+    name: Option<String>,
+}
+
+/// Report a close button that is also used to select or drag, where it
+/// would take over clicks on thumbnails.
+fn check_buttons(buttons: &ButtonMap, line: Option<usize>, issues: &mut Vec<ConfigIssue>) {
+    if let Some(close) = buttons.close.filter(|&close| close == buttons.select || close == buttons.drag) {
+        issues.push(ConfigIssue::new(
+            line,
+            format!("button {} closes windows, so it cannot also select or drag them", close),
+        ));
+    }
+}
+
+/// Compile a window title pattern; invalid ones are logged, reported and
+/// dropped.
+fn title_pattern(pattern: &str, line: Option<usize>, issues: &mut Vec<ConfigIssue>) -> Option<Regex> {
+    match Regex::new(pattern) {
+        Ok(re) => Some(re),
+        Err(e) => {
+            log::warn!("Config: invalid title pattern '{}': {}", pattern, e);
+            // The regex error spans several lines; its last one says what is wrong
+            let reason = e.to_string();
+            let reason = reason.lines().last().unwrap_or_default().trim();
+            let reason = reason.strip_prefix("error: ").unwrap_or(reason);
+            issues.push(ConfigIssue::new(line, format!("invalid title pattern '{}': {}", pattern, reason)));
+            None
+        }
+    }
+}
+
+fn title_patterns(patterns: &[String], line: Option<usize>, issues: &mut Vec<ConfigIssue>) -> Vec<Regex> {
+    patterns.iter().filter_map(|p| title_pattern(p, line, issues)).collect()
+}
+
+/// Pass on a value made usable by one of the helpers below, reporting it if
+/// it had to be changed or could not be used at all.
+fn checked<T: PartialEq + fmt::Display>(
+    usable: Option<T>,
+    value: T,
+    line: Option<usize>,
+    key: &str,
+    issues: &mut Vec<ConfigIssue>,
+) -> Option<T> {
+    match usable {
+        Some(ref usable) if *usable != value => {
+            issues.push(ConfigIssue::new(line, format!("{} {} is out of range, using {}", key, value, usable)));
+        }
+        None => issues.push(ConfigIssue::new(line, format!("{} {} is out of range", key, value))),
+        Some(_) => {}
+    }
+    usable
+}
+
+/// Line number (from 1) of a byte offset into `text`.
+fn line_of_offset(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Where each key is set in config.toml text, by (section, key); section
+/// headers are listed under an empty key. A plain scan, which is all that is
+/// needed to point at lines in the simple files xpose reads.
+fn toml_key_lines(text: &str) -> HashMap<(String, String), usize> {
+    let mut lines = HashMap::new();
+    let mut section = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|rest| rest.split(']').next()) {
+            section = header.trim().replace('"', "");
+            lines.entry((section.clone(), String::new())).or_insert(index + 1);
+        } else if let Some((key, _)) = line.split_once('=') {
+            let key = key.trim().trim_matches('"').to_string();
+            if !key.is_empty() && !key.starts_with('#') {
+                lines.entry((section.clone(), key)).or_insert(index + 1);
+            }
+        }
+    }
+    lines
+}
+
+/// A usable animation speed multiplier, clamped to sane bounds.
+fn animation_speed(speed: f64) -> Option<f64> {
+    (speed.is_finite() && speed > 0.0).then(|| speed.clamp(MIN_ANIMATION_SPEED, MAX_ANIMATION_SPEED))
+}
+
+/// A usable desktop bar height, clamped to sane bounds.
+fn desktop_bar_height(height: u16) -> Option<u16> {
+    (height > 0).then(|| height.clamp(MIN_DESKTOP_BAR_HEIGHT, MAX_DESKTOP_BAR_HEIGHT))
+}
+
+/// Expand a leading `~/` to the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Parse a boolean config value (true/false, yes/no, on/off, 1/0).
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml_sections() {
+        let config = Config::parse_toml(
+            r#"
+[layout]
+desktop_bar_height = 20
+bar_position = "left"
+show_icons = true
+icon_theme = "Papirus"
+order = "recent"
+exclude_classes = ["xpad", "xclock"]
+exclude_titles = ["^Picture-in-Picture$", "("]
+include_only_classes = ["XTerm"]
+
+[animation]
+entrance_ms = 200
+speed = 2.0
+peek_ms = 500
+tooltip_ms = 0
+present = true
+snap_easing = "spring"
+
+[desktops]
+backend = "i3"
+remove_empty = true
+restore_geometry = true
+wallpapers = { 2 = "/tmp/web.png", 0 = "/tmp/none.png" }
+identity_titles = { XTerm = ["^mutt", "vim$"] }
+
+[keybindings]
+click_through = true
+fullscreen_activation = "twice"
+hit_tolerance = 100
+
+[accessibility]
+announce = true
+
+[theme]
+name = "high-contrast"
+"#,
+        );
+        assert_eq!(config.desktop_bar_height, MIN_DESKTOP_BAR_HEIGHT);
+        assert_eq!(config.bar_position, BarPosition::Left);
+        assert!(config.show_icons);
+        assert_eq!(config.icon_theme.as_deref(), Some("Papirus"));
+        assert_eq!(config.theme, ThemeKind::HighContrast);
+        assert_eq!(config.grid_order, GridOrder::Recent);
+        assert_eq!(config.exclude_classes, ["xpad", "xclock"]);
+        assert_eq!(config.exclude_titles.len(), 1);
+        assert_eq!(config.include_classes, ["XTerm"]);
+        assert_eq!(config.entrance_ms, 200);
+        assert_eq!(config.exit_ms, Config::default().exit_ms);
+        assert_eq!(config.peek_ms, 500);
+        assert_eq!(config.tooltip_ms, 0);
+        assert!(config.present);
+        assert_eq!(config.animation_speed, 2.0);
+        assert_eq!(config.snap_easing, Easing::Spring);
+        assert_eq!(config.grid_easing, Easing::EaseOutCubic);
+        assert_eq!(config.desktop_backend, BackendKind::I3);
+        assert!(config.remove_empty_desktops);
+        assert!(config.restore_geometry);
+        assert_eq!(config.wallpapers.len(), 1);
+        assert_eq!(config.wallpapers[&1], PathBuf::from("/tmp/web.png"));
+        assert_eq!(config.identity_titles["XTerm"].len(), 2);
+        assert!(config.click_through);
+        assert_eq!(config.fullscreen_activation, FullscreenActivation::Twice);
+        assert_eq!(config.hit_tolerance, MAX_HIT_TOLERANCE);
+        assert!(config.announce);
+    }
+
+    #[test]
+    fn test_theme_section_over_layout_theme() {
+        let (config, issues) = Config::parse_toml_checked("[layout]\ntheme = \"high-contrast\"\n");
+        assert_eq!(config.theme, ThemeKind::HighContrast);
+        assert!(issues.is_empty());
+        let (config, issues) =
+            Config::parse_toml_checked("[layout]\ntheme = \"high-contrast\"\n\n[theme]\nname = \"default\"\n");
+        assert_eq!(config.theme, ThemeKind::Default);
+        assert!(issues.is_empty());
+        let (config, issues) = Config::parse_toml_checked("[theme]\nname = \"neon\"\n");
+        assert_eq!(config.theme, ThemeKind::Default);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_parse_toml_invalid_gives_defaults() {
+        let (config, issues) = Config::parse_toml_checked("[animation]\nentrance_ms = \"fast\"\n");
+        assert_eq!(config.entrance_ms, Config::default().entrance_ms);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(2));
+        // A reload keeps the config in use instead
+        assert!(Config::parse_toml_complete("[animation]\nentrance_ms = \"fast\"\n").is_none());
+        assert_eq!(Config::parse_toml_complete("[animation]\nentrance_ms = 90\n").unwrap().entrance_ms, 90);
+    }
+
+    #[test]
+    fn test_monitor_sections() {
+        let (config, issues) = Config::parse_toml_checked(
+            "[monitor.HDMI-1]\nshow_desktop_bar = false\nlayout = \"primary\"\n\n[monitor.\"DP-1\"]\nmargin = 20\ngap = 4\n",
+        );
+        assert_eq!(config.monitors["HDMI-1"].layout, MonitorLayout::Primary);
+        assert!(!config.monitors["HDMI-1"].show_desktop_bar);
+        assert_eq!(config.monitors["DP-1"].margin, Some(20));
+        assert_eq!(issues, [ConfigIssue::new(Some(7), "unknown key 'gap' in [monitor.DP-1]")]);
+
+        let (config, issues) = Config::parse_checked("Monitor DP-1 Margin 0\nMonitor DP-1 Layout tiled\n");
+        assert_eq!(config.monitors["DP-1"].margin, Some(0));
+        assert_eq!(issues.len(), 1);
+    }
+
+    #[test]
+    fn test_buttons() {
+        let (config, issues) = Config::parse_checked("ButtonSelect 3\nButtonClose 2\nButtonDismiss 0\n");
+        assert_eq!(config.buttons, ButtonMap { select: 3, close: Some(2), ..Default::default() });
+        assert_eq!(issues.len(), 1);
+
+        let (config, issues) = Config::parse_toml_checked("[keybindings]\nbutton_close = 1\n");
+        assert_eq!(config.buttons.close, Some(1));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_check_reports_lines() {
+        let (_, issues) = Config::parse_checked("# comment\nExitMs 99999\nColour red\nExcludeTitle (\nExitMs 100\n");
+        let lines: Vec<_> = issues.iter().map(|issue| issue.line).collect();
+        assert_eq!(lines, [Some(2), Some(3), Some(4)]);
+        assert!(issues[0].message.contains("using 10000"));
+
+        let (_, issues) = Config::parse_toml_checked("[layout]\nbar_position = \"up\"\n\n[animation]\nspeeed = 2.0\n");
+        let lines: Vec<_> = issues.iter().map(|issue| issue.line).collect();
+        assert_eq!(lines, [Some(2), Some(5)]);
+    }
+}
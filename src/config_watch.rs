@@ -0,0 +1,105 @@
+//! Config file watching with inotify.
+//!
+//! The directory holding the config file is watched rather than the file
+//! itself: editors usually save by writing a new file and renaming it over
+//! the old one, which would silently end a watch on the file.
+
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::error::{Result, XposeError};
+
+/// Room for a burst of events; each is 16 bytes plus the padded file name.
+const EVENT_BUFFER_SIZE: usize = 4096;
+/// Size of `struct inotify_event` without the trailing name.
+const EVENT_HEADER_SIZE: usize = 16;
+
+/// Reports when a config file has been written, replaced or removed.
+pub struct ConfigWatcher {
+    fd: OwnedFd,
+    file_name: Vec<u8>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`. Its directory must exist; the file need not.
+    pub fn new(path: &Path) -> Result<Self> {
+        let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+            return Err(XposeError::Other(format!("Cannot watch {}", path.display())));
+        };
+        let dir = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|_| XposeError::Other(format!("Cannot watch {}", path.display())))?;
+
+        let raw = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if raw < 0 {
+            return Err(XposeError::Other(format!("inotify_init1: {}", io::Error::last_os_error())));
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE;
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), dir.as_ptr(), mask) } < 0 {
+            return Err(XposeError::Other(format!(
+                "Cannot watch {}: {}",
+                path.display(),
+                io::Error::last_os_error()
+            )));
+        }
+
+        Ok(Self {
+            fd,
+            file_name: file_name.as_bytes().to_vec(),
+        })
+    }
+
+    /// Whether the file changed since the last call. Never blocks.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        let mut buffer = [0u8; EVENT_BUFFER_SIZE];
+        loop {
+            let len = unsafe { libc::read(self.fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+            if len <= 0 {
+                // EAGAIN: nothing (more) queued
+                return changed;
+            }
+            changed |= events_name(&buffer[..len as usize], &self.file_name);
+        }
+    }
+}
+
+/// Whether any event in a buffer of raw inotify events is about `file_name`.
+fn events_name(mut buffer: &[u8], file_name: &[u8]) -> bool {
+    let mut found = false;
+    while buffer.len() >= EVENT_HEADER_SIZE {
+        let name_len = u32::from_ne_bytes([buffer[12], buffer[13], buffer[14], buffer[15]]) as usize;
+        let end = (EVENT_HEADER_SIZE + name_len).min(buffer.len());
+        // The name is NUL-padded to keep the next event aligned
+        let name = &buffer[EVENT_HEADER_SIZE..end];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        found |= name == file_name;
+        buffer = &buffer[end..];
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &[u8]) -> Vec<u8> {
+        let padded = (name.len() + 1).next_multiple_of(4);
+        let mut bytes = vec![0u8; EVENT_HEADER_SIZE + padded];
+        bytes[12..16].copy_from_slice(&(padded as u32).to_ne_bytes());
+        bytes[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + name.len()].copy_from_slice(name);
+        bytes
+    }
+
+    #[test]
+    fn test_events_name() {
+        let mut buffer = event(b".config.toml.swp");
+        assert!(!events_name(&buffer, b"config.toml"));
+        buffer.extend(event(b"config.toml"));
+        assert!(events_name(&buffer, b"config.toml"));
+    }
+}
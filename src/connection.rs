@@ -1,13 +1,24 @@
+use std::cell::{OnceCell, RefCell};
+use std::rc::Rc;
+
 use x11rb::atom_manager;
-use x11rb::connection::Connection;
+use x11rb::connection::{Connection, RequestConnection};
 use x11rb::wrapper::ConnectionExt as WrapperConnectionExt;
 use x11rb::protocol::composite;
 use x11rb::protocol::damage;
+use x11rb::protocol::randr;
 use x11rb::protocol::render::{self, Pictformat};
+use x11rb::protocol::shm;
+use x11rb::protocol::xinerama;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 
+use crate::alpha::AlphaMasks;
+use crate::clients::ClientCache;
+use crate::diagnostics::ErrorStats;
 use crate::error::{Result, XposeError};
+use crate::monitor::Monitor;
+use crate::toast::ToastQueue;
 
 atom_manager! {
     pub Atoms: AtomsCookie {
@@ -16,6 +27,8 @@ atom_manager! {
         WM_NAME,
         WM_PROTOCOLS,
         WM_DELETE_WINDOW,
+        WM_TAKE_FOCUS,
+        WM_CHANGE_STATE,
         WM_TRANSIENT_FOR,
         UTF8_STRING,
         // EWMH window type atoms
@@ -38,17 +51,55 @@ atom_manager! {
         _NET_WM_STATE,
         _NET_WM_STATE_SKIP_TASKBAR,
         _NET_WM_STATE_SKIP_PAGER,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+        _NET_WM_STATE_DEMANDS_ATTENTION,
+        // EWMH allowed actions (what the WM lets the user do to a window)
+        _NET_WM_ALLOWED_ACTIONS,
+        _NET_WM_ACTION_CLOSE,
+        _NET_WM_ACTION_MINIMIZE,
+        _NET_WM_ACTION_MAXIMIZE_HORZ,
+        _NET_WM_ACTION_MAXIMIZE_VERT,
+        // Selections, for copying from the overview
+        CLIPBOARD,
+        TARGETS,
+        TEXT,
+        TIMESTAMP,
         // Root window background pixmap atoms
         _XROOTPMAP_ID,
         ESETROOT_PMAP_ID,
+        // EWMH desktop atoms (mirrored for pagers and wmctrl)
+        _NET_NUMBER_OF_DESKTOPS,
+        _NET_CURRENT_DESKTOP,
+        _NET_DESKTOP_NAMES,
+        _NET_WM_DESKTOP,
+        _NET_SHOWING_DESKTOP,
+        // EWMH window manager atoms (window discovery)
+        _NET_SUPPORTING_WM_CHECK,
+        _NET_SUPPORTED,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_ICON,
+        _NET_WM_PID,
+        // Compositor hints on the overview
+        _NET_WM_WINDOW_OPACITY,
+        _NET_WM_BYPASS_COMPOSITOR,
+        _COMPTON_SHADOW,
         // xpose virtual desktop atoms
         _XPOSE_NUM_DESKTOPS,
         _XPOSE_CURRENT_DESKTOP,
+        _XPOSE_DISMISS,
+        _XPOSE_RELOAD_CONFIG,
+        _XPOSE_WINDOW_DESKTOP,
+        _XPOSE_HIGHLIGHTED,
     }
 }
 
 pub struct XConnection {
-    pub conn: RustConnection,
+    /// Shared with the resources that free themselves on it (see
+    /// resources.rs)
+    pub conn: Rc<RustConnection>,
     #[allow(dead_code)]
     pub screen_num: usize,
     pub atoms: Atoms,
@@ -58,6 +109,28 @@ pub struct XConnection {
     pub root_depth: u8,
     pub root_visual: Visualid,
     pub pict_format_rgb: Pictformat,
+    /// Whether MIT-SHM is usable for image transfers (see image.rs)
+    pub has_shm: bool,
+    /// Whether RandR is available for screen change notifications (see monitor.rs)
+    pub has_randr: bool,
+    /// Whether RandR is 1.5 or newer, which can list monitors
+    pub has_randr_monitors: bool,
+    /// Whether Xinerama is available (monitor fallback for older servers)
+    pub has_xinerama: bool,
+    /// Monitor areas of the screen, at least one
+    pub monitors: Vec<Monitor>,
+    /// Check hot-path requests synchronously (debug mode, see diagnostics.rs)
+    pub checked_requests: bool,
+    /// Protocol errors seen so far, per subsystem
+    pub errors: ErrorStats,
+    /// Non-fatal errors waiting to be shown (see toast.rs)
+    pub toasts: RefCell<ToastQueue>,
+    /// Solid-fill masks for fading, kept for the session (see alpha.rs)
+    pub alpha_masks: RefCell<AlphaMasks>,
+    /// Frame -> client windows found so far (see clients.rs)
+    pub clients: RefCell<ClientCache>,
+    /// Whether a composite manager runs, once asked (see compositor.rs)
+    pub compositor: OnceCell<bool>,
 }
 
 impl XConnection {
@@ -110,17 +183,90 @@ impl XConnection {
             damage_version.minor_version
         );
 
-        Ok(Self {
+        // MIT-SHM is optional - image grabs fall back to plain GetImage without it
+        let has_shm = match conn.extension_information(shm::X11_EXTENSION_NAME)? {
+            Some(_) => match shm::query_version(&conn)?.reply() {
+                Ok(version) => {
+                    log::info!(
+                        "MIT-SHM extension version {}.{}",
+                        version.major_version,
+                        version.minor_version
+                    );
+                    true
+                }
+                Err(e) => {
+                    log::warn!("MIT-SHM version query failed: {}", e);
+                    false
+                }
+            },
+            None => {
+                log::info!("MIT-SHM extension not available");
+                false
+            }
+        };
+
+        // RandR is optional - without it the overview keeps its startup geometry
+        let (has_randr, has_randr_monitors) = match conn.extension_information(randr::X11_EXTENSION_NAME)? {
+            Some(_) => match randr::query_version(&conn, 1, 5)?.reply() {
+                Ok(version) => {
+                    log::info!(
+                        "RandR extension version {}.{}",
+                        version.major_version,
+                        version.minor_version
+                    );
+                    (true, (version.major_version, version.minor_version) >= (1, 5))
+                }
+                Err(e) => {
+                    log::warn!("RandR version query failed: {}", e);
+                    (false, false)
+                }
+            },
+            None => {
+                log::info!("RandR extension not available");
+                (false, false)
+            }
+        };
+
+        // Xinerama only matters when RandR can't list monitors
+        let has_xinerama = !has_randr_monitors
+            && match conn.extension_information(xinerama::X11_EXTENSION_NAME)? {
+                Some(_) => match xinerama::query_version(&conn, 1, 1)?.reply() {
+                    Ok(version) => {
+                        log::info!("Xinerama extension version {}.{}", version.major, version.minor);
+                        true
+                    }
+                    Err(e) => {
+                        log::warn!("Xinerama version query failed: {}", e);
+                        false
+                    }
+                },
+                None => false,
+            };
+
+        let mut xconn = Self {
             root: screen.root,
             screen_width: screen.width_in_pixels,
             screen_height: screen.height_in_pixels,
             root_depth: screen.root_depth,
             root_visual: screen.root_visual,
-            conn,
+            conn: Rc::new(conn),
             screen_num,
             atoms,
             pict_format_rgb,
-        })
+            has_shm,
+            has_randr,
+            has_randr_monitors,
+            has_xinerama,
+            monitors: Vec::new(),
+            checked_requests: log::log_enabled!(log::Level::Debug),
+            errors: ErrorStats::default(),
+            toasts: RefCell::new(ToastQueue::default()),
+            alpha_masks: RefCell::new(AlphaMasks::default()),
+            clients: RefCell::new(ClientCache::default()),
+            compositor: OnceCell::new(),
+        };
+        xconn.monitors = xconn.query_monitors(xconn.screen_width, xconn.screen_height)?;
+        Ok(xconn)
     }
 
     pub fn flush(&self) -> Result<()> {
@@ -139,61 +285,49 @@ impl XConnection {
         Ok(self.conn.generate_id()?)
     }
 
-    /// Get the number of virtual desktops.
-    pub fn get_num_desktops(&self) -> Result<Option<u32>> {
+    /// Read a single CARDINAL from a root window property.
+    fn get_root_cardinal(&self, atom: Atom) -> Result<Option<u32>> {
         let reply = self
             .conn
-            .get_property(
-                false,
-                self.root,
-                self.atoms._XPOSE_NUM_DESKTOPS,
-                AtomEnum::CARDINAL,
-                0,
-                1,
-            )?
+            .get_property(false, self.root, atom, AtomEnum::CARDINAL, 0, 1)?
             .reply()?;
 
         Ok(reply.value32().and_then(|mut v| v.next()))
     }
 
+    /// Get the number of virtual desktops.
+    /// Prefers our own property, falling back to the EWMH one.
+    pub fn get_num_desktops(&self) -> Result<Option<u32>> {
+        match self.get_root_cardinal(self.atoms._XPOSE_NUM_DESKTOPS)? {
+            Some(count) => Ok(Some(count)),
+            None => self.get_root_cardinal(self.atoms._NET_NUMBER_OF_DESKTOPS),
+        }
+    }
+
     /// Get the current active desktop.
+    /// Prefers our own property, falling back to the EWMH one.
     pub fn get_current_desktop(&self) -> Result<Option<u32>> {
-        let reply = self
-            .conn
-            .get_property(
-                false,
-                self.root,
-                self.atoms._XPOSE_CURRENT_DESKTOP,
-                AtomEnum::CARDINAL,
-                0,
-                1,
-            )?
-            .reply()?;
-
-        Ok(reply.value32().and_then(|mut v| v.next()))
+        match self.get_root_cardinal(self.atoms._XPOSE_CURRENT_DESKTOP)? {
+            Some(desktop) => Ok(Some(desktop)),
+            None => self.get_root_cardinal(self.atoms._NET_CURRENT_DESKTOP),
+        }
     }
 
-    /// Set the number of virtual desktops.
+    /// Set the number of virtual desktops (xpose and EWMH properties).
     pub fn set_num_desktops(&self, count: u32) -> Result<()> {
-        self.conn.change_property32(
-            PropMode::REPLACE,
-            self.root,
-            self.atoms._XPOSE_NUM_DESKTOPS,
-            AtomEnum::CARDINAL,
-            &[count],
-        )?;
+        for atom in [self.atoms._XPOSE_NUM_DESKTOPS, self.atoms._NET_NUMBER_OF_DESKTOPS] {
+            self.conn
+                .change_property32(PropMode::REPLACE, self.root, atom, AtomEnum::CARDINAL, &[count])?;
+        }
         Ok(())
     }
 
-    /// Set the current active desktop.
+    /// Set the current active desktop (xpose and EWMH properties).
     pub fn set_current_desktop(&self, desktop: u32) -> Result<()> {
-        self.conn.change_property32(
-            PropMode::REPLACE,
-            self.root,
-            self.atoms._XPOSE_CURRENT_DESKTOP,
-            AtomEnum::CARDINAL,
-            &[desktop],
-        )?;
+        for atom in [self.atoms._XPOSE_CURRENT_DESKTOP, self.atoms._NET_CURRENT_DESKTOP] {
+            self.conn
+                .change_property32(PropMode::REPLACE, self.root, atom, AtomEnum::CARDINAL, &[desktop])?;
+        }
         Ok(())
     }
 
@@ -0,0 +1,155 @@
+//! Desktop control from the command line.
+//!
+//! `xpose switch`, `next`, `prev`, `move-window`, `rename-desktop` and
+//! `show-desktop` drive the same desktop state as the overview, without
+//! opening it, so window manager key bindings can switch desktops directly. Switches slide the old
+//! desktop away (see `transition`) unless `SwitchMs` is 0.
+//!
+//! `xpose --activate` picks a window the way the overview does (switching to
+//! its desktop and restoring it if minimized), for launchers such as rofi
+//! that list windows with `--print-windows`.
+
+use std::time::Duration;
+
+use crate::cli::DesktopCommand;
+use crate::config::Config;
+use crate::connection::XConnection;
+use crate::desktop::{self, DesktopState};
+use crate::error::{Result, XposeError};
+use crate::transition::SlideDirection;
+use crate::wallpaper;
+use crate::window_finder::WindowInfo;
+
+/// Run a desktop command against the running X session.
+pub fn run(command: DesktopCommand) -> Result<()> {
+    let config = Config::load();
+    let xconn = XConnection::new()?;
+    let _lock = DesktopState::lock()?;
+    let mut state = DesktopState::load()?;
+    state.sync_from_x(&xconn)?;
+    let previous = state.current;
+    if let DesktopCommand::Switch(target) | DesktopCommand::MoveWindow { desktop: target, .. } =
+        command
+    {
+        check_desktop(&state, target)?;
+    }
+
+    // The old desktop slides away opposite to the direction of travel
+    let slide = match command {
+        DesktopCommand::Switch(target) if target == previous => None,
+        DesktopCommand::Switch(target) if target < previous => Some(SlideDirection::Right),
+        DesktopCommand::Switch(_) | DesktopCommand::Next => Some(SlideDirection::Left),
+        DesktopCommand::Prev => Some(SlideDirection::Right),
+        _ => None,
+    };
+    let cover = match slide {
+        Some(_) if config.switch_ms > 0 && state.desktops > 1 => match xconn.cover_screen() {
+            Ok(cover) => Some(cover),
+            Err(e) => {
+                log::warn!("Switching without a transition: {}", e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    let windows = match command {
+        DesktopCommand::Rename { .. } => Vec::new(),
+        _ => find_windows(&xconn, &config)?,
+    };
+    // New windows of a known application go back to its desktop before
+    // anything assigns them to the current one
+    desktop::assign_by_identity(&mut state, &windows, &config.identity_titles);
+    match command {
+        DesktopCommand::Switch(target) => {
+            desktop::switch_to_desktop(&xconn, &mut state, &windows, target)?;
+        }
+        DesktopCommand::Next => {
+            desktop::switch_next(&xconn, &mut state, &windows)?;
+        }
+        DesktopCommand::Prev => {
+            desktop::switch_prev(&xconn, &mut state, &windows)?;
+        }
+        DesktopCommand::MoveWindow { window, desktop: target } => {
+            let info = find_managed(&windows, window)?;
+            desktop::move_window(&xconn, &mut state, info.frame_window, target)?;
+            // No overview is keeping every window mapped, so apply the move now
+            if target == state.current {
+                xconn.map_window(info.frame_window)?;
+            } else {
+                xconn.unmap_window(info.frame_window)?;
+            }
+        }
+        DesktopCommand::Rename { desktop: target, name } => {
+            desktop::rename_desktop(&xconn, &mut state, target, &name)?;
+        }
+        DesktopCommand::ShowDesktop => {
+            // Minimized, or restored, since xpose last looked
+            for info in &windows {
+                if state.get_window_desktop(info.frame_window, state.current) == state.current {
+                    state.set_app_hidden(info.frame_window, !info.is_mapped);
+                }
+            }
+            desktop::toggle_show_desktop(&xconn, &mut state, &windows)?;
+        }
+        DesktopCommand::Activate(window) => {
+            let info = find_managed(&windows, window)?;
+            let frame = info.frame_window;
+            let target = state.get_window_desktop(frame, state.current);
+            // Unmapped here means minimized; elsewhere, go by what xpose recorded
+            let minimized = if target == state.current { !info.is_mapped } else { state.is_app_hidden(frame) };
+            if target != state.current && !state.is_sticky(frame) {
+                desktop::switch_to_desktop(&xconn, &mut state, &windows, target)?;
+            }
+            if minimized {
+                xconn.deiconify_window(info)?;
+                state.set_app_hidden(frame, false);
+            }
+            xconn.raise_and_focus(info)?;
+            state.set_last_selected(state.current, frame);
+            log::info!("Activated window 0x{:x} on desktop {}", frame, state.current);
+        }
+    }
+    if config.restore_geometry {
+        desktop::restore_geometry(&xconn, &state, &windows, state.current)?;
+    }
+    desktop::remember_identities(&mut state, &windows, &config.identity_titles);
+    state.save()?;
+
+    if state.current != previous {
+        if let Some(path) = config.wallpapers.get(&state.current) {
+            if let Err(e) = wallpaper::set_root_wallpaper(path) {
+                log::warn!("Cannot set wallpaper for desktop {}: {}", state.current, e);
+            }
+        }
+    }
+    if let (Some(cover), Some(direction)) = (cover, slide) {
+        xconn.sync()?;
+        xconn.slide_away(cover, direction, Duration::from_millis(config.switch_ms))?;
+    }
+    xconn.sync()
+}
+
+fn find_windows(xconn: &XConnection, config: &Config) -> Result<Vec<WindowInfo>> {
+    let (windows, _skipped, _stacking) = xconn.find_all_windows(config.window_filter())?;
+    Ok(windows)
+}
+
+/// A managed window by client or frame id.
+fn find_managed(windows: &[WindowInfo], window: u32) -> Result<&WindowInfo> {
+    windows
+        .iter()
+        .find(|info| info.frame_window == window || info.client_window == window)
+        .ok_or_else(|| XposeError::Other(format!("No managed window 0x{:x}", window)))
+}
+
+fn check_desktop(state: &DesktopState, desktop: u32) -> Result<()> {
+    if desktop >= state.desktops {
+        return Err(XposeError::Other(format!(
+            "No desktop {} (there are {})",
+            desktop + 1,
+            state.desktops
+        )));
+    }
+    Ok(())
+}
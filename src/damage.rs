@@ -0,0 +1,126 @@
+//! Throttling of damage-driven redraws.
+//!
+//! Every damaged window's capture is refreshed and its thumbnail redrawn,
+//! and the desktop bar with it. Windows that redraw all the time (video,
+//! terminals scrolling) would have that happen for every event batch, so
+//! damage is queued instead, and the overview repaints once per tick: at
+//! most `BUDGET` windows, the ones damaged first, with the rest left for the
+//! next tick.
+//!
+//! Windows that aren't shown anywhere (on another desktop with no desktop
+//! bar, or under a dragged window) aren't refreshed at all: their damage is
+//! set aside until they come into view again.
+
+use std::time::{Duration, Instant};
+
+/// Shortest time between damage repaints (about 60 a second).
+pub const TICK: Duration = Duration::from_millis(16);
+/// Most windows refreshed in one repaint.
+pub const BUDGET: usize = 6;
+
+/// Damaged windows (capture indices) waiting to be redrawn.
+#[derive(Debug, Default)]
+pub struct DamageQueue {
+    /// In the order they were first damaged
+    pending: Vec<usize>,
+    /// Damaged while out of view
+    deferred: Vec<usize>,
+    last_repaint: Option<Instant>,
+}
+
+impl DamageQueue {
+    pub fn mark(&mut self, index: usize) {
+        if !self.pending.contains(&index) {
+            self.pending.push(index);
+        }
+    }
+
+    /// Drop a window that went away.
+    pub fn remove(&mut self, index: usize) {
+        self.pending.retain(|&i| i != index);
+        self.deferred.retain(|&i| i != index);
+    }
+
+    /// How long until the next repaint is due; None with nothing waiting.
+    pub fn due_in(&self, now: Instant) -> Option<Duration> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(self.last_repaint.map_or(Duration::ZERO, |last| TICK.saturating_sub(now - last)))
+    }
+
+    /// The windows to redraw now, if a repaint is due. Only those `shown`
+    /// count; the others wait until they are.
+    pub fn take_due(&mut self, now: Instant, shown: impl Fn(usize) -> bool) -> Vec<usize> {
+        let (back, hidden): (Vec<usize>, Vec<usize>) = self.deferred.drain(..).partition(|&i| shown(i));
+        self.deferred = hidden;
+        for index in back {
+            self.mark(index);
+        }
+        let (pending, hidden): (Vec<usize>, Vec<usize>) = self.pending.drain(..).partition(|&i| shown(i));
+        self.pending = pending;
+        for index in hidden {
+            if !self.deferred.contains(&index) {
+                self.deferred.push(index);
+            }
+        }
+
+        if self.due_in(now).is_none_or(|wait| !wait.is_zero()) {
+            return Vec::new();
+        }
+        self.last_repaint = Some(now);
+        let count = self.pending.len().min(BUDGET);
+        self.pending.drain(..count).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damage_throttled() {
+        let start = Instant::now();
+        let mut queue = DamageQueue::default();
+        assert_eq!(queue.due_in(start), None);
+        for index in [3, 1, 3] {
+            queue.mark(index);
+        }
+        assert_eq!(queue.take_due(start, |_| true), [3, 1]);
+
+        // Not again within the tick
+        queue.mark(2);
+        assert_eq!(queue.due_in(start + TICK / 2), Some(TICK / 2));
+        assert!(queue.take_due(start + TICK / 2, |_| true).is_empty());
+        assert_eq!(queue.take_due(start + TICK, |_| true), [2]);
+
+        // A busy frame leaves the rest for the next
+        for index in 0..BUDGET + 2 {
+            queue.mark(index);
+        }
+        queue.remove(0);
+        let first = queue.take_due(start + TICK * 2, |_| true);
+        assert_eq!(first.len(), BUDGET);
+        assert_eq!(first[0], 1);
+        assert_eq!(queue.take_due(start + TICK * 3, |_| true), [BUDGET + 1]);
+    }
+
+    #[test]
+    fn test_damage_out_of_view() {
+        let start = Instant::now();
+        let mut queue = DamageQueue::default();
+        queue.mark(1);
+        queue.mark(2);
+        assert_eq!(queue.take_due(start, |i| i != 2), [1]);
+        // Nothing to wait for while it stays hidden
+        assert_eq!(queue.due_in(start), None);
+        assert!(queue.take_due(start + TICK, |i| i != 2).is_empty());
+        assert_eq!(queue.take_due(start + TICK * 2, |_| true), [2]);
+
+        // Gone before it came back
+        queue.mark(3);
+        assert!(queue.take_due(start + TICK * 3, |_| false).is_empty());
+        queue.remove(3);
+        assert!(queue.take_due(start + TICK * 4, |_| true).is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+//! D-Bus control interface (cargo feature `dbus`).
+//!
+//! `xpose dbus` owns `org.xpose.Control` on the session bus and serves an
+//! interface of the same name at `/org/xpose/Control`, so panels and scripts
+//! can drive xpose without starting a process per action:
+//!
+//! - `ShowOverview()` opens the overview, `Toggle()` opens or closes it
+//! - `SwitchDesktop(u)` and `MoveWindow(t window, u desktop)` work like
+//!   `xpose switch` and `xpose move-window`
+//! - `ListDesktops() -> a(usb)` gives each desktop's number, name and whether
+//!   it is the current one
+//! - `ReloadConfig()` has an open overview apply edits to the config file
+//!
+//! Desktops count from 0, as in EWMH. The signals `DesktopChanged(u)`,
+//! `DesktopsChanged()` and `WindowMoved(t window, u desktop)` report changes
+//! made by anyone (see service.rs), and `Highlighted(s)` describes the window
+//! highlighted in the overview. With `Announce true` that description also
+//! goes out as a desktop notification, each replacing the last, for screen
+//! readers (see announce.rs).
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use zbus::fdo;
+use zbus::names::BusName;
+use zbus::zvariant::{DynamicType, Value};
+
+use crate::config::Config;
+use crate::desktop::DesktopState;
+use crate::error::{Result, XposeError};
+use crate::service::{self, DesktopEvent, Overview};
+
+const BUS_NAME: &str = "org.xpose.Control";
+const OBJECT_PATH: &str = "/org/xpose/Control";
+const INTERFACE: &str = "org.xpose.Control";
+/// How long an announcement stays up, in milliseconds.
+const ANNOUNCE_TIMEOUT: i32 = 3000;
+
+struct Control {
+    overview: Overview,
+}
+
+#[zbus::interface(name = "org.xpose.Control")]
+impl Control {
+    fn show_overview(&self) -> fdo::Result<()> {
+        self.overview.show();
+        Ok(())
+    }
+
+    fn toggle(&self) -> fdo::Result<()> {
+        self.overview.toggle().map_err(failed)
+    }
+
+    fn switch_desktop(&self, desktop: u32) -> fdo::Result<()> {
+        self.overview.switch_desktop(desktop).map_err(failed)
+    }
+
+    fn move_window(&self, window: u64, desktop: u32) -> fdo::Result<()> {
+        let window = u32::try_from(window)
+            .map_err(|_| fdo::Error::InvalidArgs(format!("No window 0x{:x}", window)))?;
+        self.overview.move_window(window, desktop).map_err(failed)
+    }
+
+    fn list_desktops(&self) -> fdo::Result<Vec<(u32, String, bool)>> {
+        let state = DesktopState::load().map_err(failed)?;
+        Ok((0..state.desktops)
+            .map(|desktop| (desktop, state.display_name(desktop), desktop == state.current))
+            .collect())
+    }
+
+    fn reload_config(&self) -> fdo::Result<()> {
+        self.overview.reload_config().map_err(failed)
+    }
+}
+
+fn failed(e: XposeError) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+/// Serve the interface until the X connection goes away. `show_overview`
+/// runs the overview.
+pub fn run(show_overview: fn() -> Result<()>) -> Result<()> {
+    let control = Control {
+        overview: Overview::start(show_overview),
+    };
+    let connection = zbus::blocking::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, control)?
+        .build()?;
+    log::info!("Serving {} on the session bus", BUS_NAME);
+    let announce = Config::load().announce;
+    // The last announcement, for the next to replace
+    let mut notification = 0;
+    service::watch_desktops(|event| match event {
+        DesktopEvent::DesktopChanged { desktop } => emit(&connection, "DesktopChanged", &(desktop,)),
+        DesktopEvent::DesktopsChanged => emit(&connection, "DesktopsChanged", &()),
+        DesktopEvent::WindowMoved { window, desktop } => {
+            emit(&connection, "WindowMoved", &(u64::from(window), desktop))
+        }
+        DesktopEvent::Highlighted { description } => {
+            emit(&connection, "Highlighted", &(description.as_str(),));
+            if announce {
+                match notify(&connection, notification, &description) {
+                    Ok(id) => notification = id,
+                    Err(e) => log::warn!("Cannot announce the highlighted window: {}", e),
+                }
+            }
+        }
+    })
+}
+
+/// Show `text` as a transient desktop notification in place of
+/// `replaces` (0 for none). Returns the notification's id.
+fn notify(connection: &zbus::blocking::Connection, replaces: u32, text: &str) -> zbus::Result<u32> {
+    let hints = HashMap::from([("transient", Value::from(true)), ("urgency", Value::from(0u8))]);
+    let reply = connection.call_method(
+        Some("org.freedesktop.Notifications"),
+        "/org/freedesktop/Notifications",
+        Some("org.freedesktop.Notifications"),
+        "Notify",
+        &("xpose", replaces, "", text, "", Vec::<&str>::new(), hints, ANNOUNCE_TIMEOUT),
+    )?;
+    reply.body().deserialize()
+}
+
+fn emit<B: Serialize + DynamicType>(connection: &zbus::blocking::Connection, signal: &str, body: &B) {
+    log::debug!("Emitting {}", signal);
+    if let Err(e) = connection.emit_signal(None::<BusName>, OBJECT_PATH, INTERFACE, signal, body) {
+        log::warn!("Cannot emit {}: {}", signal, e);
+    }
+}
@@ -0,0 +1,113 @@
+//! `xpose demo`: drives the overview with synthetic XTEST input.
+//!
+//! Hovers across the thumbnails, drags the first one onto another desktop and
+//! then switches to that desktop, which ends the session. Useful for
+//! reproducible screenshots/recordings and as a smoke test under Xvfb.
+
+use std::thread;
+use std::time::Duration;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::keymap::XK_ESCAPE;
+
+/// Delay between interpolated pointer steps (~60 Hz).
+const STEP_MS: u64 = 16;
+/// Pause on each thumbnail while hovering.
+const HOVER_PAUSE_MS: u64 = 300;
+/// Time to wait for the overview to exit before sending Escape.
+const WATCHDOG_MS: u64 = 5000;
+
+/// Screen positions the demo needs, taken from the overview once it is shown.
+pub struct DemoScene {
+    /// Center of each thumbnail in the grid
+    pub thumbnails: Vec<(i16, i16)>,
+    /// Center of each desktop preview, by desktop index
+    pub desktops: Vec<(i16, i16)>,
+    pub current_desktop: u32,
+}
+
+/// Run the demo script on its own connection in a background thread.
+pub fn spawn(scene: DemoScene) {
+    thread::spawn(move || {
+        if let Err(e) = drive(&scene) {
+            log::error!("Demo failed: {}", e);
+        }
+    });
+}
+
+fn drive(scene: &DemoScene) -> Result<()> {
+    let xconn = XConnection::new()?;
+    let mut pointer = scene.thumbnails.first().copied().unwrap_or((0, 0));
+    xconn.fake_motion(pointer.0, pointer.1)?;
+    xconn.flush()?;
+    pause(500);
+
+    log::info!("Demo: hovering {} thumbnails", scene.thumbnails.len());
+    for &target in &scene.thumbnails {
+        glide(&xconn, pointer, target)?;
+        pointer = target;
+        pause(HOVER_PAUSE_MS);
+    }
+
+    // Pick another desktop to drag to and switch to; stay put with only one
+    let target_desktop = if scene.desktops.len() > 1 {
+        (scene.current_desktop as usize + 1) % scene.desktops.len()
+    } else {
+        0
+    };
+    let desktop_center = match scene.desktops.get(target_desktop) {
+        Some(&center) => center,
+        None => return Ok(()),
+    };
+
+    if let (Some(&thumb), true) = (scene.thumbnails.first(), scene.desktops.len() > 1) {
+        log::info!("Demo: dragging a window to desktop {}", target_desktop);
+        glide(&xconn, pointer, thumb)?;
+        xconn.fake_button(1, true)?;
+        glide(&xconn, thumb, desktop_center)?;
+        xconn.fake_button(1, false)?;
+        xconn.flush()?;
+        pointer = desktop_center;
+        // Let the snap animation finish
+        pause(800);
+    }
+
+    log::info!("Demo: switching to desktop {}", target_desktop);
+    glide(&xconn, pointer, desktop_center)?;
+    xconn.fake_button(1, true)?;
+    xconn.fake_button(1, false)?;
+    xconn.flush()?;
+
+    // Normally the process exits with the desktop switch; make sure it does
+    pause(WATCHDOG_MS);
+    let Some(escape) = xconn.load_keymap()?.keycode(XK_ESCAPE) else {
+        log::warn!("Demo: overview still open, and no key types Escape");
+        return Ok(());
+    };
+    log::warn!("Demo: overview still open, sending Escape");
+    xconn.fake_key(escape)?;
+    xconn.flush()?;
+    Ok(())
+}
+
+/// Move the pointer from `from` to `to` in small steps so motion handling
+/// (hover, drag thresholds, drag scaling) sees a realistic path.
+fn glide(xconn: &XConnection, from: (i16, i16), to: (i16, i16)) -> Result<()> {
+    let (dx, dy) = (to.0 as f64 - from.0 as f64, to.1 as f64 - from.1 as f64);
+    let distance = dx.hypot(dy);
+    let steps = (distance / 20.0).ceil().max(1.0) as i32;
+    for step in 1..=steps {
+        let t = step as f64 / steps as f64;
+        let x = from.0 as f64 + dx * t;
+        let y = from.1 as f64 + dy * t;
+        xconn.fake_motion(x as i16, y as i16)?;
+        xconn.flush()?;
+        pause(STEP_MS);
+    }
+    Ok(())
+}
+
+fn pause(ms: u64) {
+    thread::sleep(Duration::from_millis(ms));
+}
@@ -0,0 +1,448 @@
+//! Desktops kept by a tiling window manager instead of xpose
+//! (`DesktopBackend i3` or `bspwm`).
+//!
+//! By default xpose keeps its own desktops and shows one by unmapping the
+//! windows of the others. Under i3 or bspwm the window manager has
+//! workspaces of its own, and unmapping its windows behind its back confuses
+//! it, so a backend reads the workspaces, with the windows on each, into the
+//! desktop state when the overview opens, and carries switches, moves and
+//! renames out through the window manager's IPC. What is shown is left to
+//! the window manager; workspaces are added and removed there.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde::Deserialize;
+use x11rb::protocol::xproto::Window;
+
+use crate::error::{Result, XposeError};
+use crate::window_finder::WindowInfo;
+
+use super::state::MAX_DESKTOPS;
+use super::DesktopState;
+
+/// Which desktops the overview shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    /// xpose's own
+    #[default]
+    Xpose,
+    I3,
+    Bspwm,
+}
+
+impl BackendKind {
+    /// Parse a config value: xpose, i3 or bspwm.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "xpose" => Some(Self::Xpose),
+            "i3" => Some(Self::I3),
+            "bspwm" => Some(Self::Bspwm),
+            _ => None,
+        }
+    }
+}
+
+/// A window manager workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    pub name: String,
+    pub focused: bool,
+    /// Client windows on it
+    pub windows: Vec<Window>,
+}
+
+/// A window manager that keeps the desktops.
+pub trait DesktopBackend {
+    /// The window manager's name, for messages.
+    fn name(&self) -> &'static str;
+
+    /// Its workspaces, in its order.
+    fn workspaces(&mut self) -> Result<Vec<Workspace>>;
+
+    /// Show a workspace.
+    fn switch_to(&mut self, workspace: &str) -> Result<()>;
+
+    /// Move a client window to a workspace.
+    fn move_window(&mut self, client: Window, workspace: &str) -> Result<()>;
+
+    /// Rename a workspace.
+    fn rename(&mut self, from: &str, to: &str) -> Result<()>;
+}
+
+/// Connect to the window manager `kind` names; None for xpose's own
+/// desktops.
+pub fn connect(kind: BackendKind) -> Result<Option<Box<dyn DesktopBackend>>> {
+    match kind {
+        BackendKind::Xpose => Ok(None),
+        BackendKind::I3 => Ok(Some(Box::new(I3::connect()?))),
+        BackendKind::Bspwm => Ok(Some(Box::new(Bspwm::socket_path()?))),
+    }
+}
+
+/// Make the workspaces the desktop state's desktops: their number, names and
+/// windows, with the focused one current. The state is marked adopted, so
+/// none of it is saved or published.
+pub fn adopt_workspaces(state: &mut DesktopState, workspaces: &[Workspace], windows: &[WindowInfo]) {
+    state.adopted = true;
+    let workspaces = &workspaces[..workspaces.len().min(MAX_DESKTOPS as usize)];
+    state.desktops = (workspaces.len() as u32).max(1);
+    state.current = workspaces.iter().position(|ws| ws.focused).unwrap_or(0) as u32;
+    state.names.clear();
+    for (desktop, workspace) in workspaces.iter().enumerate() {
+        state.set_name(desktop as u32, &workspace.name);
+        for info in windows.iter().filter(|info| workspace.windows.contains(&info.client_window)) {
+            state.set_window_desktop(info.frame_window, desktop as u32);
+        }
+    }
+    state.sanitize();
+}
+
+/// Carry out what changed in the desktop state since `adopt_workspaces`:
+/// windows moved to other workspaces, then the switch to the current one.
+pub fn apply_workspaces(
+    backend: &mut dyn DesktopBackend,
+    workspaces: &[Workspace],
+    state: &DesktopState,
+    windows: &[WindowInfo],
+) -> Result<()> {
+    for info in windows {
+        let Some(desktop) = state.get_window_desktop_assignment(info.frame_window) else {
+            continue;
+        };
+        let Some(target) = workspaces.get(desktop as usize) else {
+            continue;
+        };
+        if !target.windows.contains(&info.client_window) {
+            log::info!("Moving 0x{:x} to {} workspace {}", info.client_window, backend.name(), target.name);
+            backend.move_window(info.client_window, &target.name)?;
+        }
+    }
+    match workspaces.get(state.current as usize) {
+        Some(current) if !current.focused => {
+            log::info!("Switching to {} workspace {}", backend.name(), current.name);
+            backend.switch_to(&current.name)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Quote a workspace name for an i3 command.
+fn i3_quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// i3's IPC socket (see i3's ipc documentation).
+struct I3 {
+    stream: UnixStream,
+}
+
+const I3_MAGIC: &[u8] = b"i3-ipc";
+const I3_RUN_COMMAND: u32 = 0;
+const I3_GET_WORKSPACES: u32 = 1;
+const I3_GET_TREE: u32 = 4;
+
+#[derive(Deserialize)]
+struct I3Workspace {
+    name: String,
+    #[serde(default)]
+    focused: bool,
+}
+
+#[derive(Deserialize)]
+struct I3Node {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    window: Option<Window>,
+    #[serde(default)]
+    nodes: Vec<I3Node>,
+    #[serde(default)]
+    floating_nodes: Vec<I3Node>,
+}
+
+impl I3Node {
+    fn children(&self) -> impl Iterator<Item = &I3Node> {
+        self.nodes.iter().chain(&self.floating_nodes)
+    }
+
+    /// Client windows anywhere below.
+    fn collect_windows(&self, windows: &mut Vec<Window>) {
+        windows.extend(self.window);
+        for child in self.children() {
+            child.collect_windows(windows);
+        }
+    }
+
+    /// Workspace name -> client windows, for every workspace below.
+    fn collect_workspaces<'a>(&'a self, found: &mut Vec<(&'a str, Vec<Window>)>) {
+        if self.kind == "workspace" {
+            let mut windows = Vec::new();
+            self.collect_windows(&mut windows);
+            found.push((self.name.as_deref().unwrap_or_default(), windows));
+            return;
+        }
+        for child in self.children() {
+            child.collect_workspaces(found);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct I3CommandResult {
+    success: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl I3 {
+    /// Connect to `$I3SOCK`, or the socket `i3 --get-socketpath` names.
+    fn connect() -> Result<Self> {
+        let path = match std::env::var_os("I3SOCK") {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let output = Command::new("i3").arg("--get-socketpath").output()?;
+                if !output.status.success() {
+                    return Err(XposeError::Other("i3 is not running".to_string()));
+                }
+                PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+            }
+        };
+        log::info!("Using i3 workspaces from {}", path.display());
+        Ok(Self {
+            stream: UnixStream::connect(path)?,
+        })
+    }
+
+    fn request(&mut self, kind: u32, payload: &str) -> Result<Vec<u8>> {
+        self.stream.write_all(&i3_message(kind, payload.as_bytes()))?;
+        let mut header = [0; 14];
+        self.stream.read_exact(&mut header)?;
+        let len = i3_reply_len(&header)?;
+        let mut reply = vec![0; len];
+        self.stream.read_exact(&mut reply)?;
+        Ok(reply)
+    }
+
+    fn command(&mut self, command: &str) -> Result<()> {
+        log::debug!("i3: {}", command);
+        let reply = self.request(I3_RUN_COMMAND, command)?;
+        let results: Vec<I3CommandResult> = serde_json::from_slice(&reply)?;
+        match results.into_iter().find(|result| !result.success) {
+            Some(failed) => Err(XposeError::Other(format!(
+                "i3 refused '{}': {}",
+                command,
+                failed.error.unwrap_or_default()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An i3 IPC message: magic, payload length and type (native byte order),
+/// payload.
+fn i3_message(kind: u32, payload: &[u8]) -> Vec<u8> {
+    let mut message = I3_MAGIC.to_vec();
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+/// The payload length from an i3 reply header.
+fn i3_reply_len(header: &[u8; 14]) -> Result<usize> {
+    if &header[..6] != I3_MAGIC {
+        return Err(XposeError::Other("Not an i3 IPC reply".to_string()));
+    }
+    Ok(u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize)
+}
+
+/// Workspaces from i3's GET_WORKSPACES reply, with their windows from the
+/// GET_TREE reply.
+fn parse_i3_workspaces(workspaces: &[u8], tree: &[u8]) -> Result<Vec<Workspace>> {
+    let workspaces: Vec<I3Workspace> = serde_json::from_slice(workspaces)?;
+    let tree: I3Node = serde_json::from_slice(tree)?;
+    let mut windows = Vec::new();
+    tree.collect_workspaces(&mut windows);
+    Ok(workspaces
+        .into_iter()
+        .map(|ws| Workspace {
+            windows: windows
+                .iter()
+                .find(|(name, _)| *name == ws.name)
+                .map(|(_, windows)| windows.clone())
+                .unwrap_or_default(),
+            name: ws.name,
+            focused: ws.focused,
+        })
+        .collect())
+}
+
+impl DesktopBackend for I3 {
+    fn name(&self) -> &'static str {
+        "i3"
+    }
+
+    fn workspaces(&mut self) -> Result<Vec<Workspace>> {
+        let workspaces = self.request(I3_GET_WORKSPACES, "")?;
+        let tree = self.request(I3_GET_TREE, "")?;
+        parse_i3_workspaces(&workspaces, &tree)
+    }
+
+    fn switch_to(&mut self, workspace: &str) -> Result<()> {
+        self.command(&format!("workspace {}", i3_quote(workspace)))
+    }
+
+    fn move_window(&mut self, client: Window, workspace: &str) -> Result<()> {
+        self.command(&format!("[id={}] move container to workspace {}", client, i3_quote(workspace)))
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        self.command(&format!("rename workspace {} to {}", i3_quote(from), i3_quote(to)))
+    }
+}
+
+/// bspwm's socket, spoken to the way `bspc` does: one connection per
+/// message.
+struct Bspwm {
+    path: PathBuf,
+}
+
+/// First byte of a bspwm reply that reports a failure.
+const BSPWM_FAILURE: u8 = 7;
+
+impl Bspwm {
+    /// `$BSPWM_SOCKET`, or the default path for `$DISPLAY`.
+    fn socket_path() -> Result<Self> {
+        let path = match std::env::var_os("BSPWM_SOCKET") {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let display = std::env::var("DISPLAY").unwrap_or_default();
+                bspwm_default_socket(&display)
+                    .ok_or_else(|| XposeError::Other(format!("Cannot find bspwm's socket for DISPLAY '{}'", display)))?
+            }
+        };
+        log::info!("Using bspwm desktops from {}", path.display());
+        Ok(Self { path })
+    }
+
+    fn request(&self, args: &[&str]) -> Result<String> {
+        let mut stream = UnixStream::connect(&self.path)?;
+        let mut message = Vec::new();
+        for arg in args {
+            message.extend_from_slice(arg.as_bytes());
+            message.push(0);
+        }
+        stream.write_all(&message)?;
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply)?;
+        parse_bspwm_reply(&reply).map_err(|e| XposeError::Other(format!("bspc {}: {}", args.join(" "), e)))
+    }
+}
+
+/// bspwm's socket path for a display name like `:0` or `host:1.0`.
+fn bspwm_default_socket(display: &str) -> Option<PathBuf> {
+    let (host, rest) = display.rsplit_once(':')?;
+    let (display, screen) = rest.split_once('.').unwrap_or((rest, "0"));
+    let display: u32 = display.parse().ok()?;
+    let screen: u32 = screen.parse().ok()?;
+    Some(PathBuf::from(format!("/tmp/bspwm{}_{}_{}-socket", host, display, screen)))
+}
+
+/// A bspwm reply's text, or what it says went wrong.
+fn parse_bspwm_reply(reply: &[u8]) -> std::result::Result<String, String> {
+    match reply.split_first() {
+        Some((&BSPWM_FAILURE, message)) => Err(String::from_utf8_lossy(message).trim().to_string()),
+        _ => Ok(String::from_utf8_lossy(reply).into_owned()),
+    }
+}
+
+impl DesktopBackend for Bspwm {
+    fn name(&self) -> &'static str {
+        "bspwm"
+    }
+
+    fn workspaces(&mut self) -> Result<Vec<Workspace>> {
+        let focused = self.request(&["query", "-D", "-d", "focused", "--names"])?;
+        let ids = self.request(&["query", "-D"])?;
+        let names = self.request(&["query", "-D", "--names"])?;
+        let mut workspaces = Vec::new();
+        // Desktops by id, since names need not be unique across monitors
+        for (id, name) in ids.lines().zip(names.lines()) {
+            // An empty desktop has no nodes to list, and says so as a failure
+            let nodes = self.request(&["query", "-N", "-n", ".window", "-d", id]).unwrap_or_default();
+            workspaces.push(Workspace {
+                name: name.to_string(),
+                focused: name == focused.trim(),
+                windows: nodes
+                    .lines()
+                    .filter_map(|node| Window::from_str_radix(node.trim_start_matches("0x"), 16).ok())
+                    .collect(),
+            });
+        }
+        Ok(workspaces)
+    }
+
+    fn switch_to(&mut self, workspace: &str) -> Result<()> {
+        self.request(&["desktop", workspace, "-f"]).map(drop)
+    }
+
+    fn move_window(&mut self, client: Window, workspace: &str) -> Result<()> {
+        self.request(&["node", &format!("0x{:08X}", client), "-d", workspace]).map(drop)
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        self.request(&["desktop", from, "-n", to]).map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_i3_workspaces() {
+        let workspaces = br#"[{"num":1,"name":"1","focused":false},{"num":2,"name":"2: web","focused":true},
+                              {"num":3,"name":"3","focused":false}]"#;
+        let tree = br#"{"type":"root","nodes":[{"type":"output","name":"__i3","nodes":[
+                          {"type":"workspace","name":"__i3_scratch","floating_nodes":[{"type":"floating_con","window":9}]}]},
+                        {"type":"output","name":"DP-1","nodes":[{"type":"con","name":"content","nodes":[
+                          {"type":"workspace","name":"1","nodes":[{"type":"con","window":4194307,"nodes":[]}]},
+                          {"type":"workspace","name":"2: web","nodes":[{"type":"con","nodes":[{"type":"con","window":5}]}],
+                           "floating_nodes":[{"type":"floating_con","nodes":[{"type":"con","window":6}]}]}]}]}]}"#;
+        let parsed = parse_i3_workspaces(workspaces, tree).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].windows, [4194307]);
+        assert_eq!(parsed[1].name, "2: web");
+        assert!(parsed[1].focused);
+        assert_eq!(parsed[1].windows, [5, 6]);
+        assert!(parsed[2].windows.is_empty());
+        assert_eq!(i3_quote(r#"say "hi""#), r#""say \"hi\"""#);
+    }
+
+    #[test]
+    fn test_adopted_state_is_never_saved() {
+        let dir = std::env::temp_dir().join(format!("xpose-adopted-{}", std::process::id()));
+        std::env::set_var("XPOSE_STATE_DIR", &dir);
+        let workspaces = [Workspace { name: "web".to_string(), focused: true, windows: Vec::new() }];
+        let mut state = DesktopState::default();
+        adopt_workspaces(&mut state, &workspaces, &[]);
+        assert!(state.adopted);
+        state.save().unwrap();
+        std::env::remove_var("XPOSE_STATE_DIR");
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_bspwm_protocol() {
+        assert_eq!(bspwm_default_socket(":0"), Some(PathBuf::from("/tmp/bspwm_0_0-socket")));
+        assert_eq!(bspwm_default_socket("host:1.2"), Some(PathBuf::from("/tmp/bspwmhost_1_2-socket")));
+        assert_eq!(bspwm_default_socket(""), None);
+        assert_eq!(parse_bspwm_reply(b"I\nII\n"), Ok("I\nII\n".to_string()));
+        assert_eq!(parse_bspwm_reply(b"\x07desktop: Descriptor not found.\n"), Err("desktop: Descriptor not found.".to_string()));
+    }
+}
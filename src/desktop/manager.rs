@@ -1,9 +1,14 @@
-use x11rb::protocol::xproto::Window;
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+use x11rb::protocol::xproto::{ConfigureWindowAux, ConnectionExt as _, Window};
 
 use crate::connection::XConnection;
+use crate::ewmh;
 use crate::error::Result;
 use crate::window_finder::WindowInfo;
 
+use super::state::Geometry;
 use super::DesktopState;
 
 /// Switch to a specific desktop (0-indexed internally).
@@ -24,8 +29,9 @@ pub fn switch_to_desktop(
     detect_new_windows(state, windows);
     state.cleanup_dead_windows(&window_ids);
 
-    // Save current desktop's stacking order before switching
+    // Save current desktop's stacking order and window geometry before switching
     save_stacking_order(xconn, state, state.current)?;
+    record_geometry(state, windows, state.current);
 
     update_window_visibility(xconn, state, windows, target)?;
 
@@ -57,6 +63,56 @@ fn detect_new_windows(state: &mut DesktopState, windows: &[WindowInfo]) {
     }
 }
 
+/// What a window's desktop is remembered under across application
+/// restarts: its WM_CLASS, plus the first of the class's `IdentityTitle`
+/// patterns its title matches. None for windows without a class.
+pub fn identity(info: &WindowInfo, titles: &HashMap<String, Vec<Regex>>) -> Option<String> {
+    let class = info.wm_class.as_deref().filter(|c| !c.is_empty())?;
+    let title = info.wm_name.as_deref().unwrap_or("");
+    let pattern = titles.get(class).and_then(|patterns| patterns.iter().find(|re| re.is_match(title)));
+    Some(match pattern {
+        Some(re) => format!("{}|{}", class, re.as_str()),
+        None => class.to_string(),
+    })
+}
+
+/// The desktop a window not seen before belongs on by its identity, if
+/// a window like it lived on one.
+pub fn identity_desktop(state: &DesktopState, info: &WindowInfo, titles: &HashMap<String, Vec<Regex>>) -> Option<u32> {
+    let desktop = *state.identities.get(&identity(info, titles)?)?;
+    (desktop < state.desktops).then_some(desktop)
+}
+
+/// Assign windows not seen before by their identity (see `identity_desktop`).
+/// A window of an application that is still running is a new window, not a
+/// restarted one, and is left for the current desktop.
+pub fn assign_by_identity(state: &mut DesktopState, windows: &[WindowInfo], titles: &HashMap<String, Vec<Regex>>) {
+    let (known, new): (Vec<&WindowInfo>, Vec<&WindowInfo>) = windows
+        .iter()
+        .partition(|info| state.get_window_desktop_assignment(info.frame_window).is_some());
+    let running: HashSet<String> = known.iter().filter_map(|info| identity(info, titles)).collect();
+    for info in new {
+        if identity(info, titles).is_some_and(|key| running.contains(&key)) {
+            continue;
+        }
+        if let Some(desktop) = identity_desktop(state, info, titles) {
+            log::info!("Window 0x{:x} goes back to desktop {} by its WM_CLASS", info.frame_window, desktop);
+            state.set_window_desktop(info.frame_window, desktop);
+        }
+    }
+}
+
+/// Remember the desktop of each assigned window under its identity.
+pub fn remember_identities(state: &mut DesktopState, windows: &[WindowInfo], titles: &HashMap<String, Vec<Regex>>) {
+    for info in windows {
+        if let (Some(key), Some(desktop)) =
+            (identity(info, titles), state.get_window_desktop_assignment(info.frame_window))
+        {
+            state.identities.insert(key, desktop);
+        }
+    }
+}
+
 /// Update window visibility based on target desktop.
 fn update_window_visibility(
     xconn: &XConnection,
@@ -90,6 +146,50 @@ pub fn save_stacking_order(xconn: &XConnection, state: &mut DesktopState, deskto
     Ok(())
 }
 
+/// Record where the windows on `desktop`, the one shown, are. Windows on
+/// other desktops are left alone: one just moved there hasn't been placed
+/// on it yet.
+pub fn record_geometry(state: &mut DesktopState, windows: &[WindowInfo], desktop: u32) {
+    for info in windows {
+        if state.get_window_desktop_assignment(info.frame_window) == Some(desktop) {
+            let geometry = Geometry {
+                x: info.x,
+                y: info.y,
+                width: info.width,
+                height: info.height,
+            };
+            state.record_geometry(info.frame_window, desktop, geometry);
+        }
+    }
+}
+
+/// Put the windows shown on `desktop` back where they were when last on it,
+/// for windows moved there from elsewhere (see `RestoreGeometry`).
+pub fn restore_geometry(xconn: &XConnection, state: &DesktopState, windows: &[WindowInfo], desktop: u32) -> Result<()> {
+    for info in windows {
+        if !state.is_visible_on(info.frame_window, desktop) {
+            continue;
+        }
+        let Some(geometry) = state.geometry_on(info.frame_window, desktop) else {
+            continue;
+        };
+        if (geometry.x, geometry.y, geometry.width, geometry.height) == (info.x, info.y, info.width, info.height) {
+            continue;
+        }
+        log::debug!("Restoring geometry of 0x{:x} on desktop {}: {:?}", info.frame_window, desktop, geometry);
+        xconn.conn.configure_window(
+            info.frame_window,
+            &ConfigureWindowAux::new()
+                .x(geometry.x as i32)
+                .y(geometry.y as i32)
+                .width(geometry.width as u32)
+                .height(geometry.height as u32),
+        )?;
+    }
+    xconn.flush()?;
+    Ok(())
+}
+
 /// Restore the stacking order for a desktop.
 fn restore_stacking_order(xconn: &XConnection, state: &DesktopState, desktop: u32) -> Result<()> {
     if let Some(order) = state.stacking.get(&desktop) {
@@ -111,7 +211,6 @@ fn restore_stacking_order(xconn: &XConnection, state: &DesktopState, desktop: u3
 }
 
 /// Switch to the next desktop (wraps around).
-#[allow(dead_code)]
 pub fn switch_next(xconn: &XConnection, state: &mut DesktopState, windows: &[WindowInfo]) -> Result<u32> {
     let next = (state.current + 1) % state.desktops;
     switch_to_desktop(xconn, state, windows, next)?;
@@ -119,7 +218,6 @@ pub fn switch_next(xconn: &XConnection, state: &mut DesktopState, windows: &[Win
 }
 
 /// Switch to the previous desktop (wraps around).
-#[allow(dead_code)]
 pub fn switch_prev(xconn: &XConnection, state: &mut DesktopState, windows: &[WindowInfo]) -> Result<u32> {
     let prev = if state.current == 0 {
         state.desktops - 1
@@ -172,6 +270,11 @@ pub fn move_window(
     // Add window to new desktop's stacking order
     state.add_to_stacking(window_id, desktop);
 
+    // Let EWMH tools (and the next discovery after a WM restart) see the move
+    if let Some(client) = xconn.find_client_window(window_id)? {
+        xconn.set_wm_desktop(client, desktop)?;
+    }
+
     // Note: We do NOT change window visibility here because xpose keeps all windows
     // mapped for live capture. Visibility will be restored when xpose exits.
 
@@ -192,6 +295,12 @@ pub fn set_desktop_count(
             "Desktop count must be at least 1".to_string(),
         ));
     }
+    if count > super::state::MAX_DESKTOPS {
+        return Err(crate::error::XposeError::Other(format!(
+            "Desktop count must be at most {}",
+            super::state::MAX_DESKTOPS
+        )));
+    }
 
     // Move windows from removed desktops to the last valid one (0-indexed)
     if count < state.desktops {
@@ -201,6 +310,14 @@ pub fn set_desktop_count(
                 *win_desktop = max_valid;
             }
         }
+        state.names.retain(|&desktop, _| desktop < count);
+        state.last_selected.retain(|&desktop, _| desktop < count);
+        for desktop in state.identities.values_mut() {
+            *desktop = (*desktop).min(max_valid);
+        }
+        for desktop in count..state.desktops {
+            state.move_geometry(desktop, max_valid);
+        }
     }
 
     state.desktops = count;
@@ -232,6 +349,9 @@ pub fn map_all_windows(xconn: &XConnection, windows: &[WindowInfo]) -> Result<bo
 }
 
 /// Restore window visibility based on current desktop (used when xpose exits).
+///
+/// Also publishes every window's desktop as `_NET_WM_DESKTOP`, since deleting
+/// or reordering desktops renumbers them; sticky windows as on all desktops.
 pub fn restore_window_visibility(
     xconn: &XConnection,
     state: &DesktopState,
@@ -241,6 +361,11 @@ pub fn restore_window_visibility(
         if !state.is_visible_on(info.frame_window, state.current) {
             xconn.unmap_window(info.frame_window)?;
         }
+        if state.is_sticky(info.frame_window) {
+            xconn.set_wm_desktop(info.client_window, ewmh::ALL_DESKTOPS)?;
+        } else if let Some(desktop) = state.get_window_desktop_assignment(info.frame_window) {
+            xconn.set_wm_desktop(info.client_window, desktop)?;
+        }
     }
     xconn.flush()?;
     Ok(())
@@ -249,6 +374,7 @@ pub fn restore_window_visibility(
 /// Delete a specific desktop and move its windows to an adjacent desktop.
 /// - If deleting the first desktop (index 0), windows move to the new desktop 0 (was 1)
 /// - Otherwise, windows move to the previous desktop (index - 1)
+///
 /// Returns error if only 1 desktop exists.
 pub fn delete_desktop(
     xconn: &XConnection,
@@ -311,6 +437,37 @@ pub fn delete_desktop(
         state.stacking.insert(new_key, order);
     }
 
+    // Windows keep where they were, on their new desktop
+    state.move_geometry(desktop_to_delete, target_desktop);
+    let old_geometry = std::mem::take(&mut state.geometry);
+    for (desk, windows) in old_geometry {
+        // Deleting desktop 0 merges it into what was desktop 1
+        let new_key = if desk > desktop_to_delete { desk - 1 } else { desk };
+        state.geometry.entry(new_key).or_default().extend(windows);
+    }
+    for desktop in state.identities.values_mut() {
+        if *desktop == desktop_to_delete {
+            *desktop = target_desktop;
+        }
+        if *desktop > desktop_to_delete {
+            *desktop -= 1;
+        }
+    }
+    state.last_selected.remove(&desktop_to_delete);
+    let old_selected = std::mem::take(&mut state.last_selected);
+    for (desk, window) in old_selected {
+        let new_key = if desk > desktop_to_delete { desk - 1 } else { desk };
+        state.last_selected.insert(new_key, window);
+    }
+
+    // The deleted desktop's name goes with it; later names shift down
+    state.names.remove(&desktop_to_delete);
+    let old_names = std::mem::take(&mut state.names);
+    for (desk, name) in old_names {
+        let new_key = if desk > desktop_to_delete { desk - 1 } else { desk };
+        state.names.insert(new_key, name);
+    }
+
     // Update desktop count
     state.desktops -= 1;
 
@@ -400,6 +557,32 @@ pub fn reorder_desktop(
         }
     }
 
+    let old_geometry = std::mem::take(&mut state.geometry);
+    for (old_idx, windows) in old_geometry {
+        if let Some(&new_idx) = index_map.get(&old_idx) {
+            state.geometry.insert(new_idx, windows);
+        }
+    }
+    for desktop in state.identities.values_mut() {
+        if let Some(&new_idx) = index_map.get(desktop) {
+            *desktop = new_idx;
+        }
+    }
+    let old_selected = std::mem::take(&mut state.last_selected);
+    for (old_idx, window) in old_selected {
+        if let Some(&new_idx) = index_map.get(&old_idx) {
+            state.last_selected.insert(new_idx, window);
+        }
+    }
+
+    // Names follow their desktops
+    let old_names = std::mem::take(&mut state.names);
+    for (old_idx, name) in old_names {
+        if let Some(&new_idx) = index_map.get(&old_idx) {
+            state.names.insert(new_idx, name);
+        }
+    }
+
     // Update current desktop
     if let Some(&new_current) = index_map.get(&state.current) {
         state.current = new_current;
@@ -410,3 +593,118 @@ pub fn reorder_desktop(
 
     Ok(())
 }
+
+/// Move every window on `from` to `to`, appending them on top of `to`'s
+/// stacking order. Returns the windows that moved.
+///
+/// Like `move_window`, this leaves visibility alone while xpose is active.
+pub fn move_all_windows(
+    xconn: &XConnection,
+    state: &mut DesktopState,
+    from: u32,
+    to: u32,
+) -> Result<Vec<Window>> {
+    if from >= state.desktops || to >= state.desktops {
+        return Err(crate::error::XposeError::Other(format!(
+            "Invalid desktop {}. Valid range: 0-{}",
+            from.max(to),
+            state.desktops - 1
+        )));
+    }
+    if from == to {
+        return Ok(Vec::new());
+    }
+
+    if state.current == from || state.current == to {
+        save_stacking_order(xconn, state, state.current)?;
+    }
+
+    let mut moved: Vec<Window> = Vec::new();
+    for (key, desktop) in state.windows.iter_mut() {
+        if *desktop == from {
+            if let Ok(window_id) = key.parse::<Window>() {
+                *desktop = to;
+                moved.push(window_id);
+            }
+        }
+    }
+
+    // Keep the moved windows' relative order, above what `to` already has
+    if let Some(from_stacking) = state.stacking.remove(&from) {
+        let to_stacking = state.stacking.entry(to).or_default();
+        to_stacking.retain(|id| !from_stacking.contains(id));
+        to_stacking.extend(from_stacking);
+    }
+    state.move_geometry(from, to);
+
+    for &window_id in &moved {
+        if let Some(client) = xconn.find_client_window(window_id)? {
+            xconn.set_wm_desktop(client, to)?;
+        }
+    }
+
+    log::info!("Moved {} windows from desktop {} to desktop {}", moved.len(), from, to);
+    state.save()?;
+
+    Ok(moved)
+}
+
+/// Name a desktop (an empty name clears it) and publish the new names.
+pub fn rename_desktop(
+    xconn: &XConnection,
+    state: &mut DesktopState,
+    desktop: u32,
+    name: &str,
+) -> Result<()> {
+    if desktop >= state.desktops {
+        return Err(crate::error::XposeError::Other(format!(
+            "Desktop {} does not exist",
+            desktop + 1
+        )));
+    }
+
+    state.set_name(desktop, name);
+    log::info!("Desktop {} is now named {:?}", desktop, state.display_name(desktop));
+
+    state.sync_to_x(xconn)?;
+    state.save()?;
+
+    Ok(())
+}
+
+/// Show the desktop: minimize every window on the current desktop, or, if
+/// that is in effect there, bring back the windows it minimized. Returns
+/// whether the windows were minimized.
+///
+/// Windows minimized before stay so either way, and the state records
+/// which were hidden (see `DesktopState::desktop_shown_on`).
+pub fn toggle_show_desktop(xconn: &XConnection, state: &mut DesktopState, windows: &[WindowInfo]) -> Result<bool> {
+    let shown = state.desktop_shown_on(state.current);
+    let on_desktop = state.windows_shown_on(state.current);
+    // Brought back by other means, or about to be
+    for window_id in &on_desktop {
+        state.show_desktop_hidden.remove(&window_id.to_string());
+    }
+    if !shown.is_empty() {
+        for info in windows.iter().filter(|info| shown.contains(&info.frame_window)) {
+            xconn.deiconify_window(info)?;
+            state.set_app_hidden(info.frame_window, false);
+        }
+        log::info!("Restored {} windows on desktop {}", shown.len(), state.current);
+        xconn.set_showing_desktop(false)?;
+        return Ok(false);
+    }
+
+    let mut hidden = 0;
+    for info in windows {
+        if on_desktop.contains(&info.frame_window) && !state.is_app_hidden(info.frame_window) {
+            xconn.iconify_window(info)?;
+            state.set_app_hidden(info.frame_window, true);
+            state.show_desktop_hidden.insert(info.frame_window.to_string());
+            hidden += 1;
+        }
+    }
+    log::info!("Minimized {} windows to show desktop {}", hidden, state.current);
+    xconn.set_showing_desktop(true)?;
+    Ok(true)
+}
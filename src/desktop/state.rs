@@ -6,9 +6,23 @@ use serde::{Deserialize, Serialize};
 use x11rb::protocol::xproto::Window;
 
 use crate::connection::XConnection;
-use crate::error::Result;
-
+use crate::error::{Result, XposeError};
+use crate::paths;
+use crate::schema::{self, Migration};
+
+const STATE_FILE: &str = "desktop_state.json";
+/// Format changes, oldest first; add one with every change that old files
+/// won't deserialize into as they are.
+const MIGRATIONS: &[Migration] = &[
+    // 1: versioned; nothing else changed
+    |_| {},
+];
+const STATE_VERSION: u32 = MIGRATIONS.len() as u32;
 const DEFAULT_DESKTOP_COUNT: u32 = 4;
+/// Upper bound on desktops; keeps the desktop bar layout within i16 coordinates.
+pub const MAX_DESKTOPS: u32 = 32;
+/// Longest desktop name kept, in characters.
+pub const MAX_DESKTOP_NAME_CHARS: usize = 32;
 
 /// Persistent state for virtual desktop management.
 ///
@@ -18,6 +32,9 @@ const DEFAULT_DESKTOP_COUNT: u32 = 4;
 /// All desktop numbers are 0-indexed (0, 1, 2, 3, ...).
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct DesktopState {
+    /// Format version (see schema.rs)
+    #[serde(default)]
+    pub version: u32,
     /// Current desktop (0-indexed)
     pub current: u32,
     /// Total number of desktops
@@ -31,6 +48,55 @@ pub struct DesktopState {
     /// desktop number (0-indexed) -> ordered list of window IDs
     #[serde(default)]
     pub stacking: HashMap<u32, Vec<String>>,
+    /// Window ID (as string) -> original X of windows moved off-screen for an
+    /// overview session. Empty unless a session is running or was cut off
+    /// (see recovery.rs).
+    #[serde(default)]
+    pub displaced: HashMap<String, i16>,
+    /// Desktop number (0-indexed) -> user-given name. Unnamed desktops
+    /// go by their number.
+    #[serde(default)]
+    pub names: HashMap<u32, String>,
+    /// Where each window was on each desktop it has been on, by desktop
+    /// number (0-indexed) -> window ID (as string). Recorded when leaving a
+    /// desktop and when the overview opens.
+    #[serde(default)]
+    pub geometry: HashMap<u32, HashMap<String, Geometry>>,
+    /// Desktop number (0-indexed) -> window ID (as string) last picked in
+    /// the overview there, which starts out highlighted next time.
+    #[serde(default)]
+    pub last_selected: HashMap<u32, String>,
+    /// Window identity (see `manager::identity`) -> desktop number
+    /// (0-indexed) it was last seen on. Applications come back from a
+    /// restart with new window IDs; this puts them back on their desktop.
+    #[serde(default)]
+    pub identities: HashMap<String, u32>,
+    /// Window ID (as string) -> where the window was before xpose maximized
+    /// it (for window managers that can't; see placement.rs).
+    #[serde(default)]
+    pub maximized: HashMap<String, Geometry>,
+    /// Windows (IDs as strings) pinned to show on every desktop, on top of
+    /// the one they are assigned to.
+    #[serde(default)]
+    pub sticky: HashSet<String>,
+    /// Windows (IDs as strings) minimized by "show desktop", which brings
+    /// them back when invoked again on their desktop.
+    #[serde(default)]
+    pub show_desktop_hidden: HashSet<String>,
+    /// Set while the desktops are a window manager's workspaces (see
+    /// backend.rs). They are never saved over xpose's own desktops, nor
+    /// published on the root, where the window manager keeps its own.
+    #[serde(skip)]
+    pub adopted: bool,
+}
+
+/// A window frame's position and size.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
 }
 
 impl DesktopState {
@@ -43,33 +109,92 @@ impl DesktopState {
         }
 
         let content = fs::read_to_string(&path)?;
-        let state: DesktopState = serde_json::from_str(&content)?;
+        // Refuse rather than start over and lose every assignment
+        let (state, version) = Self::from_json(&content)
+            .map_err(|e| XposeError::Other(format!("Cannot read {}: {}", path.display(), e)))?;
+        if version > STATE_VERSION {
+            log::warn!("{} is from a newer xpose (format {})", path.display(), version);
+            schema::keep_copy(&path, &format!("v{}", version));
+        }
         Ok(state)
     }
 
+    /// Parse state JSON of any version and clamp it to a consistent range.
+    /// Also returns the version the JSON had.
+    pub fn from_json(content: &str) -> Result<(Self, u32)> {
+        let mut value: serde_json::Value = serde_json::from_str(content)?;
+        let version = schema::upgrade(&mut value, MIGRATIONS).map_err(XposeError::Other)?;
+        let mut state: DesktopState = serde_json::from_value(value)?;
+        state.sanitize();
+        Ok((state, version))
+    }
+
+    /// Clamp desktop indices so a hand-edited or stale file can't
+    /// reference desktops that don't exist.
+    pub fn sanitize(&mut self) {
+        self.desktops = self.desktops.clamp(1, MAX_DESKTOPS);
+        let last = self.desktops - 1;
+        if self.current > last {
+            log::warn!("Desktop state: current desktop {} out of range, using {}", self.current, last);
+            self.current = last;
+        }
+        for desktop in self.windows.values_mut() {
+            if *desktop > last {
+                *desktop = last;
+            }
+        }
+        self.stacking.retain(|&desktop, _| desktop <= last);
+        self.names.retain(|&desktop, _| desktop <= last);
+        self.geometry.retain(|&desktop, _| desktop <= last);
+        self.last_selected.retain(|&desktop, _| desktop <= last);
+        for desktop in self.identities.values_mut() {
+            *desktop = (*desktop).min(last);
+        }
+    }
+
     fn default_state() -> Self {
         DesktopState {
+            version: STATE_VERSION,
             current: 0,
             desktops: DEFAULT_DESKTOP_COUNT,
             windows: HashMap::new(),
             app_hidden: HashSet::new(),
             stacking: HashMap::new(),
+            displaced: HashMap::new(),
+            names: HashMap::new(),
+            geometry: HashMap::new(),
+            last_selected: HashMap::new(),
+            identities: HashMap::new(),
+            maximized: HashMap::new(),
+            sticky: HashSet::new(),
+            show_desktop_hidden: HashSet::new(),
+            adopted: false,
         }
     }
 
-    /// Save state to file.
+    /// Save state to file; adopted workspaces are not saved.
     pub fn save(&self) -> Result<()> {
+        if self.adopted {
+            return Ok(());
+        }
         let path = Self::state_path()?;
 
         if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+            paths::create_private_dir(parent)?;
         }
 
         let content = serde_json::to_string_pretty(self)?;
-        fs::write(&path, content)?;
+        paths::write_atomic(&path, content.as_bytes())?;
         Ok(())
     }
 
+    /// Keep other xpose processes from changing the state until the lock
+    /// is dropped. Take it before `load` when the state will be saved.
+    pub fn lock() -> Result<paths::StateLock> {
+        paths::lock_state_file(STATE_FILE)
+            .map_err(|e| XposeError::Other(format!("Cannot lock desktop state: {}", e)))
+    }
+
     /// Sync state from X properties (for cross-instance communication).
     pub fn sync_from_x(&mut self, xconn: &XConnection) -> Result<()> {
         if let Some(current) = xconn.get_current_desktop()? {
@@ -78,16 +203,48 @@ impl DesktopState {
         if let Some(count) = xconn.get_num_desktops()? {
             self.desktops = count;
         }
+        self.sanitize();
         Ok(())
     }
 
     /// Write state to X properties.
     pub fn sync_to_x(&self, xconn: &XConnection) -> Result<()> {
+        if self.adopted {
+            return Ok(());
+        }
         xconn.set_current_desktop(self.current)?;
         xconn.set_num_desktops(self.desktops)?;
+        let names: Vec<String> = (0..self.desktops).map(|d| self.display_name(d)).collect();
+        xconn.set_desktop_names(&names)?;
+        xconn.set_showing_desktop(!self.desktop_shown_on(self.current).is_empty())?;
+        xconn.claim_desktop_hints()?;
         Ok(())
     }
 
+    /// The name given to a desktop, if any.
+    pub fn name(&self, desktop: u32) -> Option<&str> {
+        self.names.get(&desktop).map(String::as_str)
+    }
+
+    /// A desktop's name, or its 1-based number when it has none.
+    pub fn display_name(&self, desktop: u32) -> String {
+        match self.name(desktop) {
+            Some(name) => name.to_string(),
+            None => (desktop + 1).to_string(),
+        }
+    }
+
+    /// Name a desktop. Surrounding whitespace is trimmed and an empty
+    /// name removes the existing one.
+    pub fn set_name(&mut self, desktop: u32, name: &str) {
+        let name: String = name.trim().chars().take(MAX_DESKTOP_NAME_CHARS).collect();
+        if name.is_empty() {
+            self.names.remove(&desktop);
+        } else {
+            self.names.insert(desktop, name);
+        }
+    }
+
     /// Get desktop for a window, assigning to current desktop if new.
     ///
     /// Returns the desktop number (0-indexed).
@@ -111,13 +268,17 @@ impl DesktopState {
     /// Check if window should be visible on the given desktop.
     ///
     /// All desktop numbers are 0-indexed.
-    /// Returns false for app-hidden windows regardless of desktop.
+    /// Returns false for app-hidden windows regardless of desktop, and true
+    /// for sticky ones.
     pub fn is_visible_on(&self, window_id: Window, desktop: u32) -> bool {
         let key = window_id.to_string();
 
         if self.app_hidden.contains(&key) {
             return false;
         }
+        if self.sticky.contains(&key) {
+            return true;
+        }
 
         match self.windows.get(&key) {
             Some(&win_desktop) => win_desktop == desktop,
@@ -136,11 +297,25 @@ impl DesktopState {
     }
 
     /// Check if window is hidden by the application.
-    #[allow(dead_code)]
     pub fn is_app_hidden(&self, window_id: Window) -> bool {
         self.app_hidden.contains(&window_id.to_string())
     }
 
+    /// Pin a window to every desktop, or unpin it.
+    pub fn set_sticky(&mut self, window_id: Window, sticky: bool) {
+        let key = window_id.to_string();
+        if sticky {
+            self.sticky.insert(key);
+        } else {
+            self.sticky.remove(&key);
+        }
+    }
+
+    /// Whether a window shows on every desktop.
+    pub fn is_sticky(&self, window_id: Window) -> bool {
+        self.sticky.contains(&window_id.to_string())
+    }
+
     /// Remove windows that no longer exist from state.
     pub fn cleanup_dead_windows(&mut self, live_windows: &[Window]) {
         let live_set: HashSet<String> = live_windows.iter().map(|id| id.to_string()).collect();
@@ -150,6 +325,58 @@ impl DesktopState {
         for order in self.stacking.values_mut() {
             order.retain(|k| live_set.contains(k));
         }
+        for windows in self.geometry.values_mut() {
+            windows.retain(|k, _| live_set.contains(k));
+        }
+        self.last_selected.retain(|_, k| live_set.contains(k));
+        self.maximized.retain(|k, _| live_set.contains(k));
+        self.sticky.retain(|k| live_set.contains(k));
+        self.show_desktop_hidden.retain(|k| live_set.contains(k));
+    }
+
+    /// Forget a single window that was destroyed.
+    pub fn forget_window(&mut self, window_id: Window) {
+        let key = window_id.to_string();
+        self.windows.remove(&key);
+        self.app_hidden.remove(&key);
+        for order in self.stacking.values_mut() {
+            order.retain(|k| k != &key);
+        }
+        for windows in self.geometry.values_mut() {
+            windows.remove(&key);
+        }
+        self.last_selected.retain(|_, k| k != &key);
+        self.maximized.remove(&key);
+        self.sticky.remove(&key);
+        self.show_desktop_hidden.remove(&key);
+    }
+
+    /// The window last picked in the overview on a desktop.
+    pub fn last_selected(&self, desktop: u32) -> Option<Window> {
+        self.last_selected.get(&desktop)?.parse().ok()
+    }
+
+    /// Remember the window picked in the overview on a desktop.
+    pub fn set_last_selected(&mut self, desktop: u32, window_id: Window) {
+        self.last_selected.insert(desktop, window_id.to_string());
+    }
+
+    /// Record where a window is on a desktop.
+    pub fn record_geometry(&mut self, window_id: Window, desktop: u32, geometry: Geometry) {
+        self.geometry.entry(desktop).or_default().insert(window_id.to_string(), geometry);
+    }
+
+    /// Where a window was when last seen on a desktop.
+    pub fn geometry_on(&self, window_id: Window, desktop: u32) -> Option<Geometry> {
+        self.geometry.get(&desktop)?.get(&window_id.to_string()).copied()
+    }
+
+    /// Carry the geometry recorded on `from` over to `to`, for windows
+    /// moving there with their desktop.
+    pub fn move_geometry(&mut self, from: u32, to: u32) {
+        if let Some(windows) = self.geometry.remove(&from) {
+            self.geometry.entry(to).or_default().extend(windows);
+        }
     }
 
     /// Remove a window from a desktop's stacking order
@@ -163,7 +390,7 @@ impl DesktopState {
     /// Add a window to the end (top) of a desktop's stacking order
     pub fn add_to_stacking(&mut self, window_id: Window, desktop: u32) {
         let key = window_id.to_string();
-        let order = self.stacking.entry(desktop).or_insert_with(Vec::new);
+        let order = self.stacking.entry(desktop).or_default();
 
         // Remove if already present
         order.retain(|id| id != &key);
@@ -178,6 +405,11 @@ impl DesktopState {
         self.windows.get(&key).copied()
     }
 
+    /// Whether no window is assigned to a desktop.
+    pub fn is_desktop_empty(&self, desktop: u32) -> bool {
+        !self.windows.values().any(|&d| d == desktop)
+    }
+
     /// Get all windows assigned to a specific desktop (0-indexed).
     /// Returns window IDs in stacking order (bottom to top) if available.
     pub fn windows_on_desktop(&self, desktop: u32) -> Vec<Window> {
@@ -214,7 +446,128 @@ impl DesktopState {
         result
     }
 
+    /// Windows shown on a desktop (0-indexed): those assigned to it, then
+    /// the sticky ones from other desktops, in its stacking order where it
+    /// has them.
+    pub fn windows_shown_on(&self, desktop: u32) -> Vec<Window> {
+        let mut result = self.windows_on_desktop(desktop);
+        let order = self.stacking.get(&desktop);
+        let mut sticky: Vec<(usize, Window)> = self
+            .sticky
+            .iter()
+            .filter(|key| self.windows.contains_key(*key))
+            .filter_map(|key| {
+                let position = order.and_then(|o| o.iter().position(|k| k == key)).unwrap_or(usize::MAX);
+                Some((position, key.parse().ok()?))
+            })
+            .filter(|(_, id)| !result.contains(id))
+            .collect();
+        sticky.sort_unstable();
+        result.extend(sticky.into_iter().map(|(_, id)| id));
+        result
+    }
+
+    /// The windows "show desktop" minimized on a desktop (0-indexed) that
+    /// are still minimized; empty unless it is in effect there.
+    pub fn desktop_shown_on(&self, desktop: u32) -> Vec<Window> {
+        self.windows_shown_on(desktop)
+            .into_iter()
+            .filter(|&id| self.show_desktop_hidden.contains(&id.to_string()) && self.is_app_hidden(id))
+            .collect()
+    }
+
     fn state_path() -> Result<PathBuf> {
-        Ok(PathBuf::from("/tmp/xpose/desktop_state.json"))
+        Ok(paths::state_file(STATE_FILE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unversioned_state() {
+        let (state, version) = DesktopState::from_json(r#"{"current": 1, "desktops": 3, "windows": {"42": 2}}"#).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.windows.get("42"), Some(&2));
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(DesktopState::from_json(&json).unwrap().1, STATE_VERSION);
+    }
+
+    #[test]
+    fn test_geometry_follows_windows() {
+        let mut state = DesktopState::default_state();
+        let at = |x| Geometry { x, y: 0, width: 100, height: 100 };
+        state.record_geometry(1, 0, at(10));
+        state.record_geometry(1, 2, at(20));
+        state.record_geometry(2, 3, at(30));
+        state.move_geometry(3, 2);
+        assert_eq!(state.geometry_on(1, 2), Some(at(20)));
+        assert_eq!(state.geometry_on(2, 2), Some(at(30)));
+        assert_eq!(state.geometry_on(2, 3), None);
+
+        state.forget_window(1);
+        assert_eq!(state.geometry_on(1, 0), None);
+    }
+
+    #[test]
+    fn test_sticky_shows_everywhere() {
+        let mut state = DesktopState::default_state();
+        state.set_window_desktop(1, 0);
+        state.set_window_desktop(2, 1);
+        state.set_sticky(1, true);
+        assert!(state.is_visible_on(1, 1));
+        assert_eq!(state.windows_shown_on(1), vec![2, 1]);
+        // Still only assigned to its own desktop
+        assert_eq!(state.windows_on_desktop(1), vec![2]);
+
+        state.set_app_hidden(1, true);
+        assert!(!state.is_visible_on(1, 0));
+        state.forget_window(1);
+        assert!(!state.is_sticky(1));
+    }
+
+    #[test]
+    fn test_desktop_shown() {
+        let mut state = DesktopState::default_state();
+        state.set_window_desktop(1, 0);
+        state.set_window_desktop(2, 0);
+        state.set_window_desktop(3, 1);
+        for id in [1, 2, 3] {
+            state.set_app_hidden(id, true);
+            state.show_desktop_hidden.insert(id.to_string());
+        }
+        let mut shown = state.desktop_shown_on(0);
+        shown.sort_unstable();
+        assert_eq!(shown, vec![1, 2]);
+        // Restored by other means in the meantime
+        state.set_app_hidden(2, false);
+        assert_eq!(state.desktop_shown_on(0), vec![1]);
+        state.forget_window(1);
+        assert!(state.desktop_shown_on(0).is_empty());
+        assert_eq!(state.desktop_shown_on(1), vec![3]);
+    }
+
+    #[test]
+    fn test_identity_survives_restart() {
+        use crate::desktop::{assign_by_identity, remember_identities};
+        use crate::window_finder::WindowInfo;
+        let window = |frame, class: &str, title: &str| WindowInfo::for_test(frame, Some(class), Some(title));
+        let titles = HashMap::from([("XTerm".to_string(), vec![regex::Regex::new("^mutt").unwrap()])]);
+        let mut state = DesktopState::default_state();
+        state.set_window_desktop(1, 2);
+        state.set_window_desktop(2, 3);
+        remember_identities(&mut state, &[window(1, "XTerm", "mutt"), window(2, "XTerm", "bash")], &titles);
+
+        // Restarted: new frames, and the pattern tells the two apart
+        assign_by_identity(&mut state, &[window(3, "XTerm", "bash"), window(4, "XTerm", "mutt - inbox")], &titles);
+        assert_eq!(state.get_window_desktop_assignment(3), Some(3));
+        assert_eq!(state.get_window_desktop_assignment(4), Some(2));
+
+        // Another window of a running application is a new one
+        assign_by_identity(&mut state, &[window(3, "XTerm", "bash"), window(5, "XTerm", "bash")], &titles);
+        assert_eq!(state.get_window_desktop_assignment(5), None);
     }
 }
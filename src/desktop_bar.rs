@@ -1,9 +1,11 @@
-/// Desktop bar UI for virtual desktop management (Mission Control style).
+//! Desktop bar UI for virtual desktop management (Mission Control style).
 
 use x11rb::protocol::xproto::Window;
 
 use crate::capture::CapturedWindow;
 use crate::desktop::DesktopState;
+use crate::layout::{distance_to_rect, Insets, LayoutConfig};
+use crate::monitor::Monitor;
 
 // Layout constants
 pub const PREVIEW_PADDING: u16 = 15;
@@ -11,7 +13,72 @@ const PLUS_BUTTON_SIZE: u16 = 40;
 const PLUS_BUTTON_MARGIN: u16 = 20;
 const DELETE_BUTTON_SIZE: u16 = 16;
 const DELETE_BUTTON_MARGIN: u16 = 4;
+const CONFIRM_BUTTON_WIDTH: u16 = 48;
+const CONFIRM_BUTTON_HEIGHT: u16 = 22;
+const CONFIRM_BUTTON_GAP: u16 = 8;
+
+/// Accent colors for desktops, cycled by desktop index.
+const DESKTOP_ACCENTS: [u32; 8] = [
+    0x4C_9A_FF, // blue
+    0xFF_8C_42, // orange
+    0x5C_D6_7A, // green
+    0xE8_5D_9B, // pink
+    0xB3_8C_FF, // purple
+    0xF2_D0_4B, // yellow
+    0x3E_D6_D0, // teal
+    0xF2_5F_5C, // red
+];
+
+/// Accent color identifying a desktop (0-indexed) in the all-desktops grid.
+pub fn desktop_accent(desktop: u32) -> u32 {
+    DESKTOP_ACCENTS[desktop as usize % DESKTOP_ACCENTS.len()]
+}
+
+/// Screen rectangle: x, y, width, height.
+pub type Rect = (i16, i16, u16, u16);
+
+/// Screen edge the desktop bar sits on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BarPosition {
+    #[default]
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
 
+impl BarPosition {
+    /// Parse a config value: top, bottom, left or right.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "top" => Some(Self::Top),
+            "bottom" => Some(Self::Bottom),
+            "left" => Some(Self::Left),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    /// Whether previews are stacked top to bottom rather than left to right.
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+}
+
+/// The part of the screen the bar runs along an edge of: the whole screen,
+/// or, when some monitors leave the bar out, the bounding box of those that
+/// show it. None if no monitor does.
+pub fn bar_area(monitors: &[Monitor], config: &LayoutConfig, screen_width: u16, screen_height: u16) -> Option<Rect> {
+    let shown: Vec<&Monitor> = monitors.iter().filter(|m| config.monitor(m).show_desktop_bar).collect();
+    if shown.len() == monitors.len() {
+        return Some((0, 0, screen_width, screen_height));
+    }
+    let left = shown.iter().map(|m| m.x).min()?;
+    let top = shown.iter().map(|m| m.y).min()?;
+    let right = shown.iter().map(|m| m.x as i32 + m.width as i32).max()?;
+    let bottom = shown.iter().map(|m| m.y as i32 + m.height as i32).max()?;
+    Some((left, top, (right - left as i32) as u16, (bottom - top as i32) as u16))
+}
 
 /// Result of hit testing the desktop bar.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,6 +87,19 @@ pub enum DesktopBarHit {
     Desktop(u32),
     DeleteButton(u32),
     PlusButton,
+    /// "Yes" on a delete confirmation.
+    ConfirmDelete(u32),
+    /// "No" on a delete confirmation.
+    CancelDelete(u32),
+}
+
+/// Confirmation shown over a desktop preview before deleting a desktop that
+/// still has windows.
+#[derive(Debug, Clone)]
+pub struct DeleteConfirm {
+    pub desktop_index: u32,
+    /// Where the windows will go, e.g. "Move 3 windows to desktop 2?"
+    pub message: String,
 }
 
 /// Layout for a mini-window thumbnail within a desktop preview.
@@ -42,6 +122,7 @@ pub struct DesktopPreviewLayout {
     pub height: u16,
     pub is_current: bool,
     pub mini_windows: Vec<MiniWindowLayout>,  // Windows to render in this preview
+    pub name: Option<String>,                 // User-given desktop name, shown on the preview
     // Delete button position (relative to preview origin)
     pub delete_button_x: i16,
     pub delete_button_y: i16,
@@ -57,57 +138,100 @@ pub struct PlusButtonLayout {
 }
 
 /// Complete desktop bar state and layout.
+///
+/// Previews run along the bar: left to right on the top or bottom edge, top
+/// to bottom on the left or right edge. Positions "along the bar" are X or Y
+/// coordinates accordingly.
 #[derive(Clone)]
 pub struct DesktopBar {
-    #[allow(dead_code)]
     pub num_desktops: u32,
     #[allow(dead_code)]
     pub current_desktop: u32,
     pub preview_layouts: Vec<DesktopPreviewLayout>,
     pub plus_button: PlusButtonLayout,
+    pub position: BarPosition,
+    /// Screen rectangle the bar covers
+    pub rect: Rect,
+    /// Thickness of the bar: its height, or its width on a side edge
     pub bar_height: u16,
     pub preview_width: u16,
+    #[allow(dead_code)]
     pub preview_height: u16,
+    pub delete_confirm: Option<DeleteConfirm>,
 }
 
 impl DesktopBar {
-    /// Create desktop bar from xdeskie properties.
-    pub fn new(num_desktops: u32, current_desktop: u32, screen_width: u16, bar_height: u16) -> Self {
-        // Calculate preview height proportionally (2/3 of bar height)
-        let preview_height = (bar_height as f64 * 2.0 / 3.0) as u16;
-        let preview_width = (preview_height as f64 * 16.0 / 9.0) as u16;
-
-        // Calculate total width of all previews + padding
-        let total_previews_width = (num_desktops as u16 * preview_width)
+    /// Create desktop bar from xdeskie properties. The bar runs along the
+    /// `position` edge of `area` (see `bar_area`).
+    pub fn new(
+        num_desktops: u32,
+        current_desktop: u32,
+        area: Rect,
+        bar_height: u16,
+        position: BarPosition,
+    ) -> Self {
+        let vertical = position.is_vertical();
+        // Calculate preview size proportionally (2/3 of the bar's thickness)
+        let (preview_width, preview_height) = if vertical {
+            let width = (bar_height as f64 * 2.0 / 3.0) as u16;
+            (width, (width as f64 * 9.0 / 16.0) as u16)
+        } else {
+            let height = (bar_height as f64 * 2.0 / 3.0) as u16;
+            ((height as f64 * 16.0 / 9.0) as u16, height)
+        };
+        let (x, y, width, height) = area;
+        let rect = match position {
+            BarPosition::Top => (x, y, width, bar_height),
+            BarPosition::Bottom => (x, y + height.saturating_sub(bar_height) as i16, width, bar_height),
+            BarPosition::Left => (x, y, bar_height, height),
+            BarPosition::Right => (x + width.saturating_sub(bar_height) as i16, y, bar_height, height),
+        };
+
+        // Along the bar (length) and across it (thickness)
+        let (bar_length, preview_length, preview_thickness) = if vertical {
+            (height, preview_height, preview_width)
+        } else {
+            (width, preview_width, preview_height)
+        };
+        let (along_origin, across_origin) = if vertical { (rect.1, rect.0) } else { (rect.0, rect.1) };
+
+        // Calculate total length of all previews + padding
+        let total_previews_length = (num_desktops as u16 * preview_length)
             + ((num_desktops.saturating_sub(1)) as u16 * PREVIEW_PADDING);
 
-        // Center the previews horizontally
-        let start_x = (screen_width.saturating_sub(total_previews_width)) / 2;
-        let preview_y = (bar_height.saturating_sub(preview_height)) / 2;
+        // Center the previews along the bar
+        let start = along_origin + ((bar_length.saturating_sub(total_previews_length)) / 2) as i16;
+        let across = across_origin + ((bar_height.saturating_sub(preview_thickness)) / 2) as i16;
+        let point = |along: i16, across: i16| if vertical { (across, along) } else { (along, across) };
 
         // Build preview layouts
         let mut preview_layouts = Vec::with_capacity(num_desktops as usize);
         for i in 0..num_desktops {
-            let x = start_x as i16 + (i as u16 * (preview_width + PREVIEW_PADDING)) as i16;
+            let (x, y) = point(start + (i as u16 * (preview_length + PREVIEW_PADDING)) as i16, across);
             preview_layouts.push(DesktopPreviewLayout {
                 desktop_index: i,
                 x,
-                y: preview_y as i16,
+                y,
                 width: preview_width,
                 height: preview_height,
                 is_current: i == current_desktop,
                 mini_windows: Vec::new(),
+                name: None,
                 // Delete button in top-right corner
-                delete_button_x: (preview_width - DELETE_BUTTON_SIZE - DELETE_BUTTON_MARGIN) as i16,
+                delete_button_x: preview_width.saturating_sub(DELETE_BUTTON_SIZE + DELETE_BUTTON_MARGIN) as i16,
                 delete_button_y: DELETE_BUTTON_MARGIN as i16,
                 delete_button_size: DELETE_BUTTON_SIZE,
             });
         }
 
-        // Plus button on the right edge
+        // Plus button at the far end of the bar
+        let (x, y) = point(
+            along_origin + bar_length.saturating_sub(PLUS_BUTTON_MARGIN + PLUS_BUTTON_SIZE) as i16,
+            across_origin + (bar_height.saturating_sub(PLUS_BUTTON_SIZE) / 2) as i16,
+        );
         let plus_button = PlusButtonLayout {
-            x: (screen_width - PLUS_BUTTON_MARGIN - PLUS_BUTTON_SIZE) as i16,
-            y: ((bar_height - PLUS_BUTTON_SIZE) / 2) as i16,
+            x,
+            y,
             size: PLUS_BUTTON_SIZE,
         };
 
@@ -116,19 +240,137 @@ impl DesktopBar {
             current_desktop,
             preview_layouts,
             plus_button,
+            position,
+            rect,
             bar_height,
             preview_width,
             preview_height,
+            delete_confirm: None,
         }
     }
 
+    /// Mark another desktop's preview as the current one.
+    pub fn set_current(&mut self, desktop: u32) {
+        self.current_desktop = desktop;
+        for preview in &mut self.preview_layouts {
+            preview.is_current = preview.desktop_index == desktop;
+        }
+    }
+
+    /// Room the bar takes from the window grid, as insets from the edges of
+    /// the root window.
+    pub fn reserved(&self, screen_width: u16, screen_height: u16) -> Insets {
+        let (x, y, width, height) = (self.rect.0 as i32, self.rect.1 as i32, self.rect.2 as i32, self.rect.3 as i32);
+        let clamp = |v: i32| v.clamp(0, u16::MAX as i32) as u16;
+        let mut insets = Insets::default();
+        match self.position {
+            BarPosition::Top => insets.top = clamp(y + height),
+            BarPosition::Bottom => insets.bottom = clamp(screen_height as i32 - y),
+            BarPosition::Left => insets.left = clamp(x + width),
+            BarPosition::Right => insets.right = clamp(screen_width as i32 - x),
+        }
+        insets
+    }
+
     /// Check if a point is within the bar area.
-    pub fn contains_point(&self, _x: i16, y: i16) -> bool {
-        y >= 0 && y < self.bar_height as i16
+    pub fn contains_point(&self, x: i16, y: i16) -> bool {
+        let (bx, by, width, height) = self.rect;
+        x >= bx && x < bx + width as i16 && y >= by && y < by + height as i16
+    }
+
+    /// The coordinate of a point along the bar.
+    pub fn along(&self, x: i16, y: i16) -> i16 {
+        if self.position.is_vertical() {
+            y
+        } else {
+            x
+        }
+    }
+
+    /// Where a preview sits along the bar.
+    pub fn preview_offset(&self, preview: &DesktopPreviewLayout) -> i16 {
+        self.along(preview.x, preview.y)
+    }
+
+    /// A copy of `preview` moved to `offset` along the bar.
+    pub fn moved_preview(&self, preview: &DesktopPreviewLayout, offset: i16) -> DesktopPreviewLayout {
+        let mut moved = preview.clone();
+        if self.position.is_vertical() {
+            moved.y = offset;
+        } else {
+            moved.x = offset;
+        }
+        moved
+    }
+
+    /// Size of a preview along the bar.
+    pub fn preview_length(&self) -> u16 {
+        if self.position.is_vertical() {
+            self.preview_height
+        } else {
+            self.preview_width
+        }
+    }
+
+    /// The far end of the bar, where new desktops slide in from.
+    pub fn end(&self) -> i16 {
+        let (x, y, width, height) = self.rect;
+        if self.position.is_vertical() {
+            y + height as i16
+        } else {
+            x + width as i16
+        }
+    }
+
+    /// Offset that moves the bar `hidden` (0.0 to 1.0) of the way off its
+    /// screen edge, for the slide-in animation.
+    pub fn slide_offset(&self, hidden: f64) -> (i16, i16) {
+        let distance = (self.bar_height as f64 * hidden) as i16;
+        match self.position {
+            BarPosition::Top => (0, -distance),
+            BarPosition::Bottom => (0, distance),
+            BarPosition::Left => (-distance, 0),
+            BarPosition::Right => (distance, 0),
+        }
+    }
+
+    /// How far a point is from the previews, measured away from the bar's
+    /// edge; zero or less once it reaches them.
+    pub fn distance_to_previews(&self, x: i16, y: i16) -> i16 {
+        let Some(preview) = self.preview_layouts.first() else {
+            let (bx, by, width, height) = self.rect;
+            return match self.position {
+                BarPosition::Top => y - (by + height as i16),
+                BarPosition::Bottom => by - y,
+                BarPosition::Left => x - (bx + width as i16),
+                BarPosition::Right => bx - x,
+            };
+        };
+        match self.position {
+            BarPosition::Top => y - (preview.y + preview.height as i16),
+            BarPosition::Bottom => preview.y - y,
+            BarPosition::Left => x - (preview.x + preview.width as i16),
+            BarPosition::Right => preview.x - x,
+        }
     }
 
     /// Hit test: returns which element (if any) is at the given coordinates.
     pub fn hit_test(&self, x: i16, y: i16) -> DesktopBarHit {
+        // An open confirmation sits on top of everything
+        if let Some(ref confirm) = self.delete_confirm {
+            if let Some((yes, no)) = self.confirm_buttons(confirm.desktop_index) {
+                let inside = |(bx, by, bw, bh): Rect| {
+                    x >= bx && x < bx + bw as i16 && y >= by && y < by + bh as i16
+                };
+                if inside(yes) {
+                    return DesktopBarHit::ConfirmDelete(confirm.desktop_index);
+                }
+                if inside(no) {
+                    return DesktopBarHit::CancelDelete(confirm.desktop_index);
+                }
+            }
+        }
+
         // Check plus button first
         let pb = &self.plus_button;
         if x >= pb.x && x < pb.x + pb.size as i16 && y >= pb.y && y < pb.y + pb.size as i16 {
@@ -161,6 +403,73 @@ impl DesktopBar {
         DesktopBarHit::None
     }
 
+    /// Like `hit_test`, forgiving a miss by up to `tolerance` pixels: just
+    /// off a button counts as on it (buttons being small, before the
+    /// preview it is on), and just off the previews as on the nearest one.
+    pub fn hit_test_near(&self, x: i16, y: i16, tolerance: u16) -> DesktopBarHit {
+        let hit = self.hit_test(x, y);
+        if tolerance == 0 || !matches!(hit, DesktopBarHit::Desktop(_) | DesktopBarHit::None) {
+            return hit;
+        }
+        let nearest = |targets: Vec<(DesktopBarHit, Rect)>| {
+            targets
+                .into_iter()
+                .map(|(hit, rect)| (distance_to_rect(x, y, rect), hit))
+                .filter(|&(distance, _)| distance <= i32::from(tolerance))
+                .min_by_key(|&(distance, _)| distance)
+                .map(|(_, hit)| hit)
+        };
+
+        let buttons = match self.delete_confirm {
+            Some(ref confirm) => self
+                .confirm_buttons(confirm.desktop_index)
+                .map(|(yes, no)| {
+                    vec![
+                        (DesktopBarHit::ConfirmDelete(confirm.desktop_index), yes),
+                        (DesktopBarHit::CancelDelete(confirm.desktop_index), no),
+                    ]
+                })
+                .unwrap_or_default(),
+            None => {
+                let pb = &self.plus_button;
+                let mut buttons = vec![(DesktopBarHit::PlusButton, (pb.x, pb.y, pb.size, pb.size))];
+                if self.num_desktops > 1 {
+                    buttons.extend(self.preview_layouts.iter().map(|p| {
+                        let (del_x, del_y) = (p.x + p.delete_button_x, p.y + p.delete_button_y);
+                        let size = p.delete_button_size;
+                        (DesktopBarHit::DeleteButton(p.desktop_index), (del_x, del_y, size, size))
+                    }));
+                }
+                buttons
+            }
+        };
+        if let Some(button) = nearest(buttons) {
+            return button;
+        }
+        if hit == DesktopBarHit::None {
+            let previews = self
+                .preview_layouts
+                .iter()
+                .map(|p| (DesktopBarHit::Desktop(p.desktop_index), (p.x, p.y, p.width, p.height)))
+                .collect();
+            return nearest(previews).unwrap_or(hit);
+        }
+        hit
+    }
+
+    /// Screen rectangles (x, y, width, height) of the "Yes" and "No" buttons
+    /// of a delete confirmation over a desktop preview, side by side just
+    /// below its middle.
+    pub fn confirm_buttons(&self, desktop_index: u32) -> Option<(Rect, Rect)> {
+        let preview = self.preview_layouts.iter().find(|p| p.desktop_index == desktop_index)?;
+        let center_x = preview.x + (preview.width / 2) as i16;
+        let y = preview.y + (preview.height / 2) as i16 + (CONFIRM_BUTTON_GAP / 2) as i16;
+        let half_gap = (CONFIRM_BUTTON_GAP / 2) as i16;
+        let yes = (center_x - half_gap - CONFIRM_BUTTON_WIDTH as i16, y, CONFIRM_BUTTON_WIDTH, CONFIRM_BUTTON_HEIGHT);
+        let no = (center_x + half_gap, y, CONFIRM_BUTTON_WIDTH, CONFIRM_BUTTON_HEIGHT);
+        Some((yes, no))
+    }
+
     /// Get the center position of a desktop preview (for snap animation target).
     pub fn get_preview_center(&self, desktop_index: u32) -> Option<(i16, i16)> {
         self.preview_layouts
@@ -169,76 +478,53 @@ impl DesktopBar {
             .map(|p| (p.x + (p.width / 2) as i16, p.y + (p.height / 2) as i16))
     }
 
-    /// Determine where a dragged desktop would be inserted based on cursor X position.
+    /// Screen rectangle (x, y, width, height) of a window's miniature in a
+    /// desktop preview.
+    pub fn mini_window_rect(&self, desktop_index: u32, window_id: Window) -> Option<(i16, i16, u16, u16)> {
+        let preview = self.preview_layouts.iter().find(|p| p.desktop_index == desktop_index)?;
+        let mini = preview.mini_windows.iter().find(|m| m.window_id == window_id)?;
+        Some((preview.x + mini.x, preview.y + mini.y, mini.width, mini.height))
+    }
+
+    /// Determine where a dragged desktop would be inserted based on the
+    /// cursor position along the bar.
     /// Returns the desktop index that the dragged item would be inserted BEFORE.
     /// Returns num_desktops if inserting at the end.
-    pub fn calculate_insert_position(&self, cursor_x: i16, dragged_desktop: u32) -> u32 {
+    pub fn calculate_insert_position(&self, cursor: i16, dragged_desktop: u32) -> u32 {
         for preview in &self.preview_layouts {
             if preview.desktop_index == dragged_desktop {
                 continue; // Skip the dragged preview
             }
-            let center_x = preview.x + (preview.width / 2) as i16;
-            if cursor_x < center_x {
+            let center = self.preview_offset(preview) + (self.preview_length() / 2) as i16;
+            if cursor < center {
                 return preview.desktop_index;
             }
         }
         self.num_desktops // Insert at end
     }
 
-    /// Calculate preview X positions with a gap for insert animation.
-    /// Returns list of (desktop_index, x_position) excluding the dragged desktop.
+    /// Calculate preview positions along the bar with a gap for insert animation.
+    /// Returns list of (desktop_index, offset) excluding the dragged desktop.
+    /// The gap opens in front of `insert_before`; when inserting at the end
+    /// it is past the last preview, so the others just close up.
     pub fn calculate_layouts_with_gap(
         &self,
         dragged_index: u32,
         insert_before: u32,
     ) -> Vec<(u32, i16)> {
-        let gap_width = (self.preview_width + PREVIEW_PADDING) as i16;
+        let step = (self.preview_length() + PREVIEW_PADDING) as i16;
+        let mut offset = self.preview_layouts.first().map(|p| self.preview_offset(p)).unwrap_or(0);
         let mut result = Vec::new();
 
-        // Find the starting X of the first preview
-        let base_x = self
-            .preview_layouts
-            .first()
-            .map(|p| p.x)
-            .unwrap_or(0);
-
-        let mut x = base_x;
-        let mut passed_dragged = false;
-
         for preview in &self.preview_layouts {
             if preview.desktop_index == dragged_index {
-                passed_dragged = true;
-                continue; // Skip dragged
+                continue;
             }
-
-            // If we haven't passed the dragged one yet, we need to check if
-            // we're at the insert position (gap should open here)
-            let _effective_index = if passed_dragged {
-                preview.desktop_index - 1
-            } else {
-                preview.desktop_index
-            };
-
-            // Insert gap before this preview if this is the insert position
-            if preview.desktop_index == insert_before
-                || (insert_before >= self.num_desktops && preview.desktop_index == self.num_desktops - 1 && preview.desktop_index != dragged_index)
-            {
-                // For end insertion, gap goes after the last non-dragged preview
-                if insert_before < self.num_desktops {
-                    x += gap_width;
-                }
-            }
-
-            result.push((preview.desktop_index, x));
-            x += (self.preview_width + PREVIEW_PADDING) as i16;
-
-            // Add gap at the end if inserting at the end
-            if insert_before >= self.num_desktops
-                && preview.desktop_index == self.num_desktops - 1
-                && preview.desktop_index != dragged_index
-            {
-                // Gap is implicitly at the end
+            if preview.desktop_index == insert_before {
+                offset += step;
             }
+            result.push((preview.desktop_index, offset));
+            offset += step;
         }
 
         result
@@ -247,6 +533,7 @@ impl DesktopBar {
     /// Calculate mini-window layouts for all desktop previews.
     /// Takes window captures and desktop state to determine which windows
     /// appear on which desktop, and calculates their scaled positions.
+    /// Desktop names are picked up from the state as well.
     pub fn calculate_mini_layouts(
         &mut self,
         captures: &[CapturedWindow],
@@ -254,83 +541,93 @@ impl DesktopBar {
         screen_width: u16,
         screen_height: u16,
     ) {
-        // Scale factors for screen -> preview mapping
-        let scale_x = self.preview_width as f64 / screen_width as f64;
-        let scale_y = self.preview_height as f64 / screen_height as f64;
+        for preview in &mut self.preview_layouts {
+            preview.calculate_mini_windows(captures, desktop_state, screen_width, screen_height);
+        }
+    }
+}
 
-        log::debug!(
-            "Calculating mini layouts: scale_x={:.4}, scale_y={:.4}",
-            scale_x,
-            scale_y
+impl DesktopPreviewLayout {
+    /// Lay out this desktop's windows as miniatures scaled from the screen to
+    /// the preview, and pick up its name from the state.
+    pub fn calculate_mini_windows(
+        &mut self,
+        captures: &[CapturedWindow],
+        desktop_state: &DesktopState,
+        screen_width: u16,
+        screen_height: u16,
+    ) {
+        // Scale factors for screen -> preview mapping
+        let scale_x = self.width as f64 / screen_width as f64;
+        let scale_y = self.height as f64 / screen_height as f64;
+
+        self.mini_windows.clear();
+        self.name = desktop_state.name(self.desktop_index).map(str::to_string);
+
+        // Get window IDs for this desktop (0-indexed), sticky ones included
+        let window_ids = desktop_state.windows_shown_on(self.desktop_index);
+        log::info!(
+            "Desktop {} preview: desktop_state.windows_shown_on({}) returned {} windows",
+            self.desktop_index,
+            self.desktop_index,
+            window_ids.len()
         );
 
-        for preview in &mut self.preview_layouts {
-            preview.mini_windows.clear();
-
-            // Get window IDs for this desktop (0-indexed)
-            let window_ids = desktop_state.windows_on_desktop(preview.desktop_index);
-            log::info!(
-                "Desktop {} preview: desktop_state.windows_on_desktop({}) returned {} windows",
-                preview.desktop_index,
-                preview.desktop_index,
-                window_ids.len()
-            );
-
-            for window_id in window_ids {
-                // Find the capture for this window (try both client and frame)
-                let capture = captures.iter().find(|c| {
-                    c.info.client_window == window_id || c.info.frame_window == window_id
+        for window_id in window_ids {
+            // Find the capture for this window (try both client and frame)
+            let capture = captures.iter().find(|c| {
+                c.info.client_window == window_id || c.info.frame_window == window_id
+            });
+
+            if let Some(cap) = capture {
+                log::info!(
+                    "  Desktop {}: Adding window {:?} (0x{:x})",
+                    self.desktop_index,
+                    cap.info.wm_name,
+                    window_id
+                );
+                // Scale window position and size to preview coordinates
+                let mini_x = (cap.info.x as f64 * scale_x) as i16;
+                let mini_y = (cap.info.y as f64 * scale_y) as i16;
+                let mini_w = (cap.info.width as f64 * scale_x).max(4.0) as u16;
+                let mini_h = (cap.info.height as f64 * scale_y).max(3.0) as u16;
+
+                self.mini_windows.push(MiniWindowLayout {
+                    window_id: cap.info.frame_window,
+                    x: mini_x,
+                    y: mini_y,
+                    width: mini_w,
+                    height: mini_h,
                 });
 
-                if let Some(cap) = capture {
-                    log::info!(
-                        "  Desktop {}: Adding window {:?} (0x{:x})",
-                        preview.desktop_index,
-                        cap.info.wm_name,
-                        window_id
-                    );
-                    // Scale window position and size to preview coordinates
-                    let mini_x = (cap.info.x as f64 * scale_x) as i16;
-                    let mini_y = (cap.info.y as f64 * scale_y) as i16;
-                    let mini_w = (cap.info.width as f64 * scale_x).max(4.0) as u16;
-                    let mini_h = (cap.info.height as f64 * scale_y).max(3.0) as u16;
-
-                    preview.mini_windows.push(MiniWindowLayout {
-                        window_id: cap.info.frame_window,
-                        x: mini_x,
-                        y: mini_y,
-                        width: mini_w,
-                        height: mini_h,
-                    });
-
-                    log::debug!(
-                        "Desktop {}: window {:?} at ({}, {}) {}x{}",
-                        preview.desktop_index,
-                        cap.info.wm_name,
-                        mini_x,
-                        mini_y,
-                        mini_w,
-                        mini_h
-                    );
-                }
+                log::debug!(
+                    "Desktop {}: window {:?} at ({}, {}) {}x{}",
+                    self.desktop_index,
+                    cap.info.wm_name,
+                    mini_x,
+                    mini_y,
+                    mini_w,
+                    mini_h
+                );
             }
-
-            log::debug!(
-                "Desktop {} has {} mini windows",
-                preview.desktop_index,
-                preview.mini_windows.len()
-            );
         }
+
+        log::debug!(
+            "Desktop {} has {} mini windows",
+            self.desktop_index,
+            self.mini_windows.len()
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::layout::MonitorConfig;
 
     #[test]
     fn test_desktop_bar_layout() {
-        let bar = DesktopBar::new(4, 0, 1920, 240);
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
 
         assert_eq!(bar.num_desktops, 4);
         assert_eq!(bar.current_desktop, 0);
@@ -339,9 +636,15 @@ mod tests {
         assert!(!bar.preview_layouts[1].is_current);
     }
 
+    #[test]
+    fn test_desktop_accent_cycles() {
+        assert_ne!(desktop_accent(0), desktop_accent(1));
+        assert_eq!(desktop_accent(0), desktop_accent(DESKTOP_ACCENTS.len() as u32));
+    }
+
     #[test]
     fn test_hit_test_desktop() {
-        let bar = DesktopBar::new(4, 0, 1920, 240);
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
         let preview = &bar.preview_layouts[0];
 
         // Hit inside first preview
@@ -349,18 +652,67 @@ mod tests {
         assert_eq!(hit, DesktopBarHit::Desktop(0));
     }
 
+    #[test]
+    fn test_mini_window_rect() {
+        let mut bar = DesktopBar::new(2, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
+        bar.preview_layouts[1].mini_windows.push(MiniWindowLayout {
+            window_id: 0x400001,
+            x: 10,
+            y: 20,
+            width: 30,
+            height: 40,
+        });
+        let preview = &bar.preview_layouts[1];
+        assert_eq!(
+            bar.mini_window_rect(1, 0x400001),
+            Some((preview.x + 10, preview.y + 20, 30, 40))
+        );
+        assert_eq!(bar.mini_window_rect(0, 0x400001), None);
+    }
+
+    #[test]
+    fn test_hit_test_delete_confirm() {
+        let mut bar = DesktopBar::new(3, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
+        let (yes, no) = bar.confirm_buttons(1).unwrap();
+        // Without a confirmation open the buttons are just part of the preview
+        assert_eq!(bar.hit_test(yes.0 + 1, yes.1 + 1), DesktopBarHit::Desktop(1));
+
+        bar.delete_confirm = Some(DeleteConfirm {
+            desktop_index: 1,
+            message: "Move 2 windows to desktop 1?".to_string(),
+        });
+        assert_eq!(bar.hit_test(yes.0 + 1, yes.1 + 1), DesktopBarHit::ConfirmDelete(1));
+        assert_eq!(bar.hit_test(no.0 + 1, no.1 + 1), DesktopBarHit::CancelDelete(1));
+        assert!(yes.0 + (yes.2 as i16) < no.0);
+    }
+
     #[test]
     fn test_hit_test_plus_button() {
-        let bar = DesktopBar::new(4, 0, 1920, 240);
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
         let pb = &bar.plus_button;
 
         let hit = bar.hit_test(pb.x + 5, pb.y + 5);
         assert_eq!(hit, DesktopBarHit::PlusButton);
     }
 
+    #[test]
+    fn test_hit_test_near() {
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
+        let pb = &bar.plus_button;
+        assert_eq!(bar.hit_test(pb.x - 3, pb.y), DesktopBarHit::None);
+        assert_eq!(bar.hit_test_near(pb.x - 3, pb.y, 4), DesktopBarHit::PlusButton);
+        assert_eq!(bar.hit_test_near(pb.x - 3, pb.y, 0), DesktopBarHit::None);
+
+        // Between two previews, the nearer one
+        let (first, second) = (&bar.preview_layouts[0], &bar.preview_layouts[1]);
+        let end = first.x + first.width as i16;
+        assert_eq!(bar.hit_test_near(end + 1, first.y + 50, 8), DesktopBarHit::Desktop(0));
+        assert_eq!(bar.hit_test_near(second.x - 2, first.y + 50, 8), DesktopBarHit::Desktop(1));
+    }
+
     #[test]
     fn test_hit_test_none() {
-        let bar = DesktopBar::new(4, 0, 1920, 240);
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
 
         // Hit in empty area
         let hit = bar.hit_test(5, 5);
@@ -369,7 +721,7 @@ mod tests {
 
     #[test]
     fn test_contains_point() {
-        let bar = DesktopBar::new(4, 0, 1920, 240);
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
 
         assert!(bar.contains_point(100, 50));
         assert!(bar.contains_point(100, 200)); // 200 is inside bar_height=240
@@ -379,7 +731,7 @@ mod tests {
 
     #[test]
     fn test_get_preview_center() {
-        let bar = DesktopBar::new(4, 0, 1920, 240);
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
 
         // First desktop should have a center
         let center = bar.get_preview_center(0);
@@ -389,4 +741,81 @@ mod tests {
         let none = bar.get_preview_center(99);
         assert!(none.is_none());
     }
+
+    #[test]
+    fn test_drag_gap_layout() {
+        let bar = DesktopBar::new(4, 0, (0, 0, 1920, 1080), 240, BarPosition::Top);
+        let base = bar.preview_layouts[0].x;
+        let step = (bar.preview_width + PREVIEW_PADDING) as i16;
+
+        // Dragging desktop 0 over desktop 2 opens a gap in front of it
+        let drop_x = bar.preview_layouts[2].x + 1;
+        assert_eq!(bar.calculate_insert_position(drop_x, 0), 2);
+        assert_eq!(
+            bar.calculate_layouts_with_gap(0, 2),
+            vec![(1, base), (2, base + 2 * step), (3, base + 3 * step)]
+        );
+
+        // Past the last preview: inserted at the end, no gap needed
+        assert_eq!(bar.calculate_insert_position(1919, 1), 4);
+        assert_eq!(
+            bar.calculate_layouts_with_gap(1, 4),
+            vec![(0, base), (2, base + step), (3, base + 2 * step)]
+        );
+    }
+
+    #[test]
+    fn test_side_bar_layout() {
+        let bar = DesktopBar::new(3, 0, (0, 0, 1920, 1080), 240, BarPosition::Right);
+        assert_eq!(bar.rect, (1680, 0, 240, 1080));
+        assert_eq!(bar.reserved(1920, 1080), Insets { right: 240, ..Default::default() });
+
+        // Previews are stacked down the bar, inside it
+        let (first, second) = (&bar.preview_layouts[0], &bar.preview_layouts[1]);
+        assert_eq!(first.x, second.x);
+        assert!(second.y > first.y);
+        assert!(bar.contains_point(first.x, first.y));
+        assert!(!bar.contains_point(100, first.y));
+        assert_eq!(bar.hit_test(first.x + 5, first.y + 5), DesktopBarHit::Desktop(0));
+
+        // Dragging goes by the Y coordinate
+        assert_eq!(bar.calculate_insert_position(bar.end() - 1, 0), 3);
+        assert_eq!(bar.calculate_insert_position(first.y, 2), 0);
+        assert_eq!(bar.distance_to_previews(first.x - 10, 500), 10);
+        assert_eq!(bar.slide_offset(1.0), (240, 0));
+        assert_eq!(BarPosition::parse("Right"), Some(BarPosition::Right));
+        assert_eq!(BarPosition::parse("middle"), None);
+    }
+
+    #[test]
+    fn test_bar_area_skips_monitors() {
+        let monitor = |name: &str, x: i16| Monitor {
+            name: name.to_string(),
+            x,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            primary: x == 0,
+        };
+        let monitors = [monitor("left", 0), monitor("right", 1920)];
+        let mut config = LayoutConfig::default();
+        assert_eq!(bar_area(&monitors, &config, 3840, 1080), Some((0, 0, 3840, 1080)));
+
+        config.monitors.insert(
+            "left".to_string(),
+            MonitorConfig { show_desktop_bar: false, ..Default::default() },
+        );
+        let area = bar_area(&monitors, &config, 3840, 1080).unwrap();
+        assert_eq!(area, (1920, 0, 1920, 1080));
+        // A left bar moves to the left edge of the right monitor
+        let bar = DesktopBar::new(2, 0, area, 240, BarPosition::Left);
+        assert_eq!(bar.rect, (1920, 0, 240, 1080));
+        assert_eq!(bar.reserved(3840, 1080).left, 2160);
+
+        config.monitors.insert(
+            "right".to_string(),
+            MonitorConfig { show_desktop_bar: false, ..Default::default() },
+        );
+        assert_eq!(bar_area(&monitors, &config, 3840, 1080), None);
+    }
 }
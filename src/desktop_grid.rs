@@ -0,0 +1,143 @@
+//! Zoomed-out desktop grid: every desktop as a large tile filling the screen.
+//!
+//! The tiles are laid out like desktop bar previews, just bigger, so the
+//! same preview rendering and miniature layout code draws them. Windows can
+//! be dragged between tiles.
+
+use x11rb::protocol::xproto::Window;
+
+use crate::capture::CapturedWindow;
+use crate::desktop::DesktopState;
+use crate::desktop_bar::DesktopPreviewLayout;
+
+/// Space around and between tiles.
+const GRID_PADDING: u16 = 40;
+
+/// Layout of the desktop grid.
+#[derive(Debug, Clone)]
+pub struct DesktopGrid {
+    pub tiles: Vec<DesktopPreviewLayout>,
+}
+
+impl DesktopGrid {
+    /// Lay out `num_desktops` tiles in a near-square grid, each with the
+    /// screen's aspect ratio, centered on the screen.
+    pub fn new(num_desktops: u32, current_desktop: u32, screen_width: u16, screen_height: u16) -> Self {
+        let count = num_desktops.max(1);
+        let columns = (count as f64).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+
+        // Largest tile with the screen's aspect that fits every cell
+        let cell_width = (screen_width.saturating_sub(GRID_PADDING * (columns as u16 + 1))) / columns as u16;
+        let cell_height = (screen_height.saturating_sub(GRID_PADDING * (rows as u16 + 1))) / rows as u16;
+        let aspect = screen_width.max(1) as f64 / screen_height.max(1) as f64;
+        let (width, height) = if cell_width as f64 / aspect <= cell_height as f64 {
+            (cell_width, (cell_width as f64 / aspect) as u16)
+        } else {
+            ((cell_height as f64 * aspect) as u16, cell_height)
+        };
+
+        let grid_width = columns as u16 * width + (columns as u16 - 1) * GRID_PADDING;
+        let grid_height = rows as u16 * height + (rows as u16 - 1) * GRID_PADDING;
+        let start_x = screen_width.saturating_sub(grid_width) / 2;
+        let start_y = screen_height.saturating_sub(grid_height) / 2;
+
+        let tiles = (0..num_desktops)
+            .map(|i| DesktopPreviewLayout {
+                desktop_index: i,
+                x: (start_x + (i % columns) as u16 * (width + GRID_PADDING)) as i16,
+                y: (start_y + (i / columns) as u16 * (height + GRID_PADDING)) as i16,
+                width,
+                height,
+                is_current: i == current_desktop,
+                mini_windows: Vec::new(),
+                name: None,
+                // Desktops are deleted from the bar, not here
+                delete_button_x: 0,
+                delete_button_y: 0,
+                delete_button_size: 0,
+            })
+            .collect();
+
+        Self { tiles }
+    }
+
+    /// Lay out each desktop's windows in its tile.
+    pub fn calculate_mini_layouts(
+        &mut self,
+        captures: &[CapturedWindow],
+        desktop_state: &DesktopState,
+        screen_width: u16,
+        screen_height: u16,
+    ) {
+        for tile in &mut self.tiles {
+            tile.calculate_mini_windows(captures, desktop_state, screen_width, screen_height);
+        }
+    }
+
+    pub fn tile(&self, desktop_index: u32) -> Option<&DesktopPreviewLayout> {
+        self.tiles.iter().find(|t| t.desktop_index == desktop_index)
+    }
+
+    /// The desktop whose tile contains the point.
+    pub fn hit_test(&self, x: i16, y: i16) -> Option<u32> {
+        self.tiles
+            .iter()
+            .find(|t| x >= t.x && x < t.x + t.width as i16 && y >= t.y && y < t.y + t.height as i16)
+            .map(|t| t.desktop_index)
+    }
+
+    /// The topmost window miniature at the point, with its desktop.
+    pub fn window_at(&self, x: i16, y: i16) -> Option<(u32, Window)> {
+        let desktop = self.hit_test(x, y)?;
+        let tile = self.tile(desktop)?;
+        // Miniatures are stacked bottom to top
+        tile.mini_windows
+            .iter()
+            .rev()
+            .find(|m| {
+                let (mx, my) = (tile.x + m.x, tile.y + m.y);
+                x >= mx && x < mx + m.width as i16 && y >= my && y < my + m.height as i16
+            })
+            .map(|m| (desktop, m.window_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::desktop_bar::MiniWindowLayout;
+
+    #[test]
+    fn test_grid_tiles_fit_screen() {
+        let grid = DesktopGrid::new(5, 1, 1920, 1080);
+        assert_eq!(grid.tiles.len(), 5);
+        // 5 desktops: 3 columns, 2 rows
+        assert_eq!(grid.tiles[0].y, grid.tiles[2].y);
+        assert!(grid.tiles[3].y > grid.tiles[0].y);
+        assert!(grid.tiles[1].is_current);
+        for tile in &grid.tiles {
+            assert!(tile.x >= 0 && tile.x as u16 + tile.width <= 1920);
+            assert!(tile.y >= 0 && tile.y as u16 + tile.height <= 1080);
+        }
+    }
+
+    #[test]
+    fn test_grid_hit_test() {
+        let mut grid = DesktopGrid::new(4, 0, 1920, 1080);
+        let tile = &grid.tiles[3];
+        let (x, y) = (tile.x + 5, tile.y + 5);
+        assert_eq!(grid.hit_test(x, y), Some(3));
+        assert_eq!(grid.hit_test(0, 0), None);
+
+        grid.tiles[3].mini_windows.push(MiniWindowLayout {
+            window_id: 0x600001,
+            x: 0,
+            y: 0,
+            width: 20,
+            height: 20,
+        });
+        assert_eq!(grid.window_at(x, y), Some((3, 0x600001)));
+        assert_eq!(grid.window_at(x + 30, y + 30), None);
+    }
+}
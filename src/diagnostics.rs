@@ -0,0 +1,121 @@
+//! X protocol error accounting.
+//!
+//! Requests are normally unchecked, so an error arrives later as an event and
+//! only its opcode hints at where it came from. In debug mode (RUST_LOG=debug)
+//! the renderer's hot paths check their requests instead, which costs a round
+//! trip each but attributes every error to the code that sent it. Counts per
+//! subsystem are shown in the debug HUD and logged at exit.
+
+use std::cell::Cell;
+
+use x11rb::cookie::VoidCookie;
+use x11rb::errors::ReplyError;
+use x11rb::connection::RequestConnection;
+use x11rb::x11_utils::X11Error;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+/// Part of xpose a failed request came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Capture,
+    Renderer,
+    DesktopBar,
+    Other,
+}
+
+impl Subsystem {
+    const ALL: [Subsystem; 4] = [
+        Subsystem::Capture,
+        Subsystem::Renderer,
+        Subsystem::DesktopBar,
+        Subsystem::Other,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Subsystem::Capture => "capture",
+            Subsystem::Renderer => "renderer",
+            Subsystem::DesktopBar => "desktop-bar",
+            Subsystem::Other => "other",
+        }
+    }
+
+    /// Best guess for an unchecked error, based on the request's extension.
+    pub fn from_error(error: &X11Error) -> Self {
+        match error.extension_name.as_deref() {
+            Some("RENDER") => Subsystem::Renderer,
+            Some("Composite") | Some("DAMAGE") => Subsystem::Capture,
+            _ => Subsystem::Other,
+        }
+    }
+}
+
+/// Per-subsystem protocol error counters.
+#[derive(Debug, Default)]
+pub struct ErrorStats {
+    counts: [Cell<u32>; 4],
+}
+
+impl ErrorStats {
+    /// Log an error with its request origin and count it.
+    pub fn record(&self, subsystem: Subsystem, origin: &str, error: &X11Error) {
+        let slot = &self.counts[subsystem as usize];
+        slot.set(slot.get().saturating_add(1));
+        log::warn!(
+            "X error {:?} [{}] {}: request {} (major {}, minor {}), bad value 0x{:x}, seq {}",
+            error.error_kind,
+            subsystem.name(),
+            origin,
+            error.request_name.unwrap_or("?"),
+            error.major_opcode,
+            error.minor_opcode,
+            error.bad_value,
+            error.sequence
+        );
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().map(Cell::get).sum()
+    }
+
+    /// One-line summary, e.g. "X errors: capture 0, renderer 2, desktop-bar 0, other 1".
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = Subsystem::ALL
+            .iter()
+            .map(|&s| format!("{} {}", s.name(), self.counts[s as usize].get()))
+            .collect();
+        format!("X errors: {}", parts.join(", "))
+    }
+}
+
+impl XConnection {
+    /// Check a request in debug mode, attributing a failure to `subsystem`.
+    /// Protocol errors are counted and logged, not returned, so debug builds
+    /// behave like release ones; outside debug mode the cookie is dropped and
+    /// any error shows up as an event instead.
+    pub fn check_request(
+        &self,
+        subsystem: Subsystem,
+        origin: &str,
+        cookie: VoidCookie<'_, impl RequestConnection>,
+    ) -> Result<()> {
+        if !self.checked_requests {
+            return Ok(());
+        }
+        match cookie.check() {
+            Ok(()) => Ok(()),
+            Err(ReplyError::X11Error(e)) => {
+                self.errors.record(subsystem, origin, &e);
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Count an error that arrived as an event.
+    pub fn record_async_error(&self, error: &X11Error) {
+        self.errors.record(Subsystem::from_error(error), "async", error);
+    }
+}
@@ -1,4 +1,7 @@
 use thiserror::Error;
+use x11rb::errors::{ConnectionError, ReplyError, ReplyOrIdError};
+use x11rb::protocol::ErrorKind;
+use x11rb::x11_utils::X11Error;
 
 #[derive(Error, Debug)]
 #[allow(dead_code)]
@@ -21,6 +24,13 @@ pub enum XposeError {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[cfg(feature = "dbus")]
+    #[error("D-Bus error: {0}")]
+    Dbus(#[from] zbus::Error),
+
+    #[error("PNG encoding error: {0}")]
+    Png(#[from] png::EncodingError),
+
     #[error("No windows found to display")]
     NoWindows,
 
@@ -36,8 +46,44 @@ pub enum XposeError {
     #[error("Damage extension not available")]
     NoDamage,
 
+    #[error("Lost connection to the X server")]
+    ConnectionLost,
+
     #[error("{0}")]
     Other(String),
 }
 
+impl XposeError {
+    /// Whether the server rejected a request because the window it named
+    /// no longer exists (destroyed between discovery and use).
+    pub fn is_window_gone(&self) -> bool {
+        match self {
+            XposeError::Reply(ReplyError::X11Error(e))
+            | XposeError::ReplyOrId(ReplyOrIdError::X11Error(e)) => is_bad_window(e),
+            _ => false,
+        }
+    }
+}
+
+impl XposeError {
+    /// Whether the connection to the X server broke (server gone, socket
+    /// closed), as opposed to the server rejecting a request.
+    pub fn is_connection_lost(&self) -> bool {
+        let error = match self {
+            XposeError::ConnectionLost => return true,
+            XposeError::ConnectionError(e)
+            | XposeError::Reply(ReplyError::ConnectionError(e))
+            | XposeError::ReplyOrId(ReplyOrIdError::ConnectionError(e)) => e,
+            _ => return false,
+        };
+        matches!(error, ConnectionError::IoError(_) | ConnectionError::UnknownError)
+    }
+}
+
+/// Whether an X error is BadWindow (or BadDrawable, which requests taking
+/// any drawable report for a destroyed window).
+pub fn is_bad_window(error: &X11Error) -> bool {
+    matches!(error.error_kind, ErrorKind::Window | ErrorKind::Drawable)
+}
+
 pub type Result<T> = std::result::Result<T, XposeError>;
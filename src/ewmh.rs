@@ -0,0 +1,353 @@
+//! EWMH interoperability: requests from pagers, panels and `wmctrl`, and
+//! the window manager's own client list when it publishes one. The overview
+//! takes xpose's own `_XPOSE_DISMISS` request the same way, and window moves
+//! go out as `_XPOSE_WINDOW_DESKTOP` messages for pagers.
+//!
+//! TWM itself knows nothing of EWMH, so xpose speaks for the desktop
+//! properties it keeps: it lists them in `_NET_SUPPORTED` when no EWMH window
+//! manager does, and keeps `_NET_SHOWING_DESKTOP` up to date, as well as
+//! `_NET_ACTIVE_WINDOW` for the windows it activates. The overview marks
+//! itself as a dock kept off taskbars and pagers, so panels and compositors
+//! leave it alone.
+//!
+//! Where the window manager restricts a window with
+//! `_NET_WM_ALLOWED_ACTIONS`, the overview hides the close and minimize
+//! buttons it doesn't allow and won't maximize it.
+
+use std::collections::HashMap;
+
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::connection::{Atoms, XConnection};
+use crate::error::Result;
+use crate::window_finder::WindowInfo;
+
+/// `_NET_WM_DESKTOP` value for windows shown on all desktops.
+pub const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// WM_CLASS of the overview window (instance, then class).
+const OVERVIEW_CLASS: &[u8] = b"xpose\0Xpose\0";
+
+/// What the window manager lets the user do to a window. Everything,
+/// unless it says otherwise in `_NET_WM_ALLOWED_ACTIONS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllowedActions {
+    pub close: bool,
+    pub minimize: bool,
+    pub maximize: bool,
+}
+
+impl Default for AllowedActions {
+    fn default() -> Self {
+        Self { close: true, minimize: true, maximize: true }
+    }
+}
+
+impl AllowedActions {
+    /// From the atoms of a `_NET_WM_ALLOWED_ACTIONS` list. Maximizing
+    /// takes both directions.
+    fn from_list(list: &[Atom], atoms: &Atoms) -> Self {
+        Self {
+            close: list.contains(&atoms._NET_WM_ACTION_CLOSE),
+            minimize: list.contains(&atoms._NET_WM_ACTION_MINIMIZE),
+            maximize: list.contains(&atoms._NET_WM_ACTION_MAXIMIZE_HORZ)
+                && list.contains(&atoms._NET_WM_ACTION_MAXIMIZE_VERT),
+        }
+    }
+}
+
+impl XConnection {
+    /// Receive client messages sent to the root window. EWMH tools send them
+    /// with SubstructureRedirect|SubstructureNotify, and the window manager
+    /// already holds the redirect, so we listen for the notify half.
+    pub fn watch_root_client_messages(&self) -> Result<()> {
+        self.conn.change_window_attributes(
+            self.root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::SUBSTRUCTURE_NOTIFY),
+        )?;
+        Ok(())
+    }
+
+    /// Decode a `_NET_CURRENT_DESKTOP` request (e.g. `wmctrl -s N`).
+    /// Returns the requested desktop if the message is one.
+    pub fn desktop_switch_request(&self, event: &ClientMessageEvent) -> Option<u32> {
+        if event.window != self.root || event.type_ != self.atoms._NET_CURRENT_DESKTOP || event.format != 32 {
+            return None;
+        }
+        let desktop = event.data.as_data32()[0];
+        log::info!("EWMH request: switch to desktop {}", desktop);
+        Some(desktop)
+    }
+
+    /// Decode a `_NET_SHOWING_DESKTOP` request (e.g. `wmctrl -k on`).
+    /// Returns whether the desktop should be shown if the message is one.
+    pub fn showing_desktop_request(&self, event: &ClientMessageEvent) -> Option<bool> {
+        if event.window != self.root || event.type_ != self.atoms._NET_SHOWING_DESKTOP || event.format != 32 {
+            return None;
+        }
+        let show = event.data.as_data32()[0] != 0;
+        log::info!("EWMH request: showing desktop {}", show);
+        Some(show)
+    }
+
+    /// Whether `event` asks the overview to close, as the D-Bus service's
+    /// `Toggle` does.
+    pub fn dismiss_request(&self, event: &ClientMessageEvent) -> bool {
+        event.window == self.root && event.type_ == self.atoms._XPOSE_DISMISS
+    }
+
+    /// Whether `event` asks the overview to read the config file again, as
+    /// the services' `reload-config` does.
+    pub fn reload_config_request(&self, event: &ClientMessageEvent) -> bool {
+        event.window == self.root && event.type_ == self.atoms._XPOSE_RELOAD_CONFIG
+    }
+
+    /// Send a client message to the root window the way EWMH tools do, for
+    /// whoever listens there (the window manager, a running overview, pagers).
+    pub fn send_root_message(&self, type_: Atom, data: &[u32]) -> Result<()> {
+        self.send_window_message(self.root, type_, data)
+    }
+
+    /// The same, about `window` (e.g. a `_NET_WM_STATE` request for it).
+    pub fn send_window_message(&self, window: Window, type_: Atom, data: &[u32]) -> Result<()> {
+        let mut words = [0; 5];
+        words[..data.len()].copy_from_slice(data);
+        let event = ClientMessageEvent::new(32, window, type_, words);
+        self.conn.send_event(
+            false,
+            self.root,
+            EventMask::SUBSTRUCTURE_NOTIFY | EventMask::SUBSTRUCTURE_REDIRECT,
+            event,
+        )?;
+        self.flush()
+    }
+
+    /// Whether an EWMH-compliant window manager is running: the window named
+    /// by `_NET_SUPPORTING_WM_CHECK` on the root must carry the same property
+    /// pointing at itself. A stale root property left by a previous WM fails
+    /// this check.
+    pub fn has_ewmh_wm(&self) -> Result<bool> {
+        let Some(check) = self.get_window_property(self.root, self.atoms._NET_SUPPORTING_WM_CHECK)? else {
+            return Ok(false);
+        };
+        // The check window may be gone if the WM died; that's not an error
+        match self.get_window_property(check, self.atoms._NET_SUPPORTING_WM_CHECK) {
+            Ok(Some(window)) => Ok(window == check),
+            Ok(None) | Err(_) => Ok(false),
+        }
+    }
+
+    /// Whether the window manager lists `atom` in `_NET_SUPPORTED`.
+    pub fn wm_supports(&self, atom: Atom) -> Result<bool> {
+        if !self.has_ewmh_wm()? {
+            return Ok(false);
+        }
+        let reply = self
+            .conn
+            .get_property(false, self.root, self.atoms._NET_SUPPORTED, AtomEnum::ATOM, 0, u32::MAX / 4)?
+            .reply()?;
+        Ok(reply.value32().is_some_and(|mut atoms| atoms.any(|a| a == atom)))
+    }
+
+    /// The windows the window manager restricts, by frame. Windows without
+    /// `_NET_WM_ALLOWED_ACTIONS` (all of them under TWM) are left out.
+    pub fn allowed_actions(&self, windows: &[WindowInfo]) -> Result<HashMap<Window, AllowedActions>> {
+        let cookies = windows
+            .iter()
+            .map(|info| {
+                self.conn.get_property(
+                    false,
+                    info.client_window,
+                    self.atoms._NET_WM_ALLOWED_ACTIONS,
+                    AtomEnum::ATOM,
+                    0,
+                    64,
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let mut restricted = HashMap::new();
+        for (info, cookie) in windows.iter().zip(cookies) {
+            // A window gone since discovery has nothing to restrict
+            let Some(list) = cookie.reply().ok().and_then(|reply| reply.value32().map(|atoms| atoms.collect::<Vec<_>>()))
+            else {
+                continue;
+            };
+            let allowed = AllowedActions::from_list(&list, &self.atoms);
+            if allowed != AllowedActions::default() {
+                log::debug!("Window 0x{:x} allows only {:?}", info.client_window, allowed);
+                restricted.insert(info.frame_window, allowed);
+            }
+        }
+        Ok(restricted)
+    }
+
+    /// Read a single WINDOW from a property.
+    pub fn get_window_property(&self, window: Window, atom: Atom) -> Result<Option<Window>> {
+        let reply = self
+            .conn
+            .get_property(false, window, atom, AtomEnum::WINDOW, 0, 1)?
+            .reply()?;
+        Ok(reply.value32().and_then(|mut v| v.next()))
+    }
+
+    /// Client windows from `_NET_CLIENT_LIST_STACKING` (bottom to top), or
+    /// None when no EWMH window manager publishes the list.
+    pub fn client_list_stacking(&self) -> Result<Option<Vec<Window>>> {
+        if !self.has_ewmh_wm()? {
+            return Ok(None);
+        }
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                self.atoms._NET_CLIENT_LIST_STACKING,
+                AtomEnum::WINDOW,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+        Ok(reply.value32().map(|clients| clients.collect()))
+    }
+
+    /// Publish desktop names as `_NET_DESKTOP_NAMES`: NUL-terminated UTF-8
+    /// strings, one per desktop.
+    pub fn set_desktop_names(&self, names: &[String]) -> Result<()> {
+        let mut value = Vec::new();
+        for name in names {
+            value.extend_from_slice(name.as_bytes());
+            value.push(0);
+        }
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_DESKTOP_NAMES,
+            self.atoms.UTF8_STRING,
+            &value,
+        )?;
+        Ok(())
+    }
+
+    /// Publish whether windows are minimized to show the desktop, as
+    /// `_NET_SHOWING_DESKTOP`.
+    pub fn set_showing_desktop(&self, showing: bool) -> Result<()> {
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_SHOWING_DESKTOP,
+            AtomEnum::CARDINAL,
+            &[u32::from(showing)],
+        )?;
+        Ok(())
+    }
+
+    /// Record `window` (a client) as `_NET_ACTIVE_WINDOW` after focusing
+    /// it, for taskbars. An EWMH window manager also gets the activation
+    /// request a pager would send, so its own focus model follows.
+    pub fn set_active_window(&self, window: Window) -> Result<()> {
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_ACTIVE_WINDOW,
+            AtomEnum::WINDOW,
+            &[window],
+        )?;
+        if self.has_ewmh_wm()? {
+            // Source 2: a pager, acting for the user
+            self.send_window_message(window, self.atoms._NET_ACTIVE_WINDOW, &[2, x11rb::CURRENT_TIME, 0])?;
+        }
+        Ok(())
+    }
+
+    /// List the desktop properties xpose keeps in `_NET_SUPPORTED`, so
+    /// pagers that check it trust them. An EWMH window manager owns that
+    /// list, so it is left alone when one runs.
+    pub fn claim_desktop_hints(&self) -> Result<()> {
+        if self.has_ewmh_wm()? {
+            return Ok(());
+        }
+        let atoms = &self.atoms;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            atoms._NET_SUPPORTED,
+            AtomEnum::ATOM,
+            &[
+                atoms._NET_NUMBER_OF_DESKTOPS,
+                atoms._NET_CURRENT_DESKTOP,
+                atoms._NET_DESKTOP_NAMES,
+                atoms._NET_WM_DESKTOP,
+                atoms._NET_SHOWING_DESKTOP,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Name the overview window and mark it as a dock that taskbars and
+    /// pagers skip.
+    pub fn set_overview_hints(&self, window: Window) -> Result<()> {
+        let atoms = &self.atoms;
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_CLASS,
+            AtomEnum::STRING,
+            OVERVIEW_CLASS,
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            atoms._NET_WM_WINDOW_TYPE,
+            AtomEnum::ATOM,
+            &[atoms._NET_WM_WINDOW_TYPE_DOCK],
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            atoms._NET_WM_STATE,
+            AtomEnum::ATOM,
+            &[atoms._NET_WM_STATE_SKIP_TASKBAR, atoms._NET_WM_STATE_SKIP_PAGER],
+        )?;
+        Ok(())
+    }
+
+    /// Read `_NET_WM_DESKTOP` from a client window.
+    pub fn get_wm_desktop(&self, client: Window) -> Result<Option<u32>> {
+        let reply = self
+            .conn
+            .get_property(false, client, self.atoms._NET_WM_DESKTOP, AtomEnum::CARDINAL, 0, 1)?
+            .reply()?;
+        Ok(reply.value32().and_then(|mut v| v.next()))
+    }
+
+    /// Publish a client's desktop as `_NET_WM_DESKTOP`. A change is also
+    /// announced with an `_XPOSE_WINDOW_DESKTOP` message (client, desktop) to
+    /// the root, so pagers can follow moves without watching every window.
+    pub fn set_wm_desktop(&self, client: Window, desktop: u32) -> Result<()> {
+        match self.get_wm_desktop(client) {
+            Ok(Some(current)) if current == desktop => return Ok(()),
+            Err(e) if e.is_window_gone() => return Ok(()),
+            Err(e) => return Err(e),
+            Ok(_) => {}
+        }
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            client,
+            self.atoms._NET_WM_DESKTOP,
+            AtomEnum::CARDINAL,
+            &[desktop],
+        )?;
+        self.send_root_message(self.atoms._XPOSE_WINDOW_DESKTOP, &[client, desktop])
+    }
+
+    /// Decode an `_XPOSE_WINDOW_DESKTOP` announcement: the client that moved
+    /// and its new desktop.
+    pub fn window_desktop_announcement(&self, event: &ClientMessageEvent) -> Option<(Window, u32)> {
+        if event.window != self.root || event.type_ != self.atoms._XPOSE_WINDOW_DESKTOP || event.format != 32 {
+            return None;
+        }
+        let data = event.data.as_data32();
+        Some((data[0], data[1]))
+    }
+}
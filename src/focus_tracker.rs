@@ -0,0 +1,101 @@
+//! Focus history for most-recently-used ordering.
+//!
+//! `xpose track-focus` runs in the background (start it from .xinitrc) and
+//! saves, for each window, when it last had the focus. Under an EWMH window
+//! manager it follows `_NET_ACTIVE_WINDOW` on the root; TWM doesn't set that,
+//! so otherwise the input focus is polled. The times go into the window
+//! state (see state.rs), which the overview also updates on selection.
+
+use std::time::Duration;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::Event;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::state::WindowState;
+
+/// How often the input focus is checked without `_NET_ACTIVE_WINDOW`.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Track the focus until the X connection goes away.
+pub fn run() -> Result<()> {
+    let xconn = XConnection::new()?;
+    let mut last = None;
+    if xconn.has_ewmh_wm()? {
+        log::info!("Tracking focus through _NET_ACTIVE_WINDOW");
+        xconn.conn.change_window_attributes(
+            xconn.root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        xconn.flush()?;
+        loop {
+            let focused = xconn.active_window()?.map(|w| xconn.top_level(w)).transpose()?.flatten();
+            record(focused, &mut last);
+            // Wait for the next change of the active window
+            loop {
+                if let Event::PropertyNotify(event) = xconn.conn.wait_for_event()? {
+                    if event.atom == xconn.atoms._NET_ACTIVE_WINDOW {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    log::info!("Tracking focus by polling every {:?}", POLL_INTERVAL);
+    loop {
+        let focus = xconn.conn.get_input_focus()?.reply()?.focus;
+        // No focus, or PointerRoot: nothing to record
+        let focused = if focus == x11rb::NONE || focus == u32::from(InputFocus::POINTER_ROOT) {
+            None
+        } else {
+            xconn.top_level(focus)?
+        };
+        record(focused, &mut last);
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Save the focus time of `focused` if the focus moved to it.
+fn record(focused: Option<Window>, last: &mut Option<Window>) {
+    let Some(frame) = focused else {
+        return;
+    };
+    if *last == Some(frame) {
+        return;
+    }
+    *last = Some(frame);
+    log::debug!("Focus moved to 0x{:x}", frame);
+    if let Err(e) = WindowState::save_focus(frame) {
+        log::warn!("Cannot save focus history: {}", e);
+    }
+}
+
+impl XConnection {
+    /// The window in `_NET_ACTIVE_WINDOW`, if any.
+    fn active_window(&self) -> Result<Option<Window>> {
+        Ok(self
+            .get_window_property(self.root, self.atoms._NET_ACTIVE_WINDOW)?
+            .filter(|&w| w != x11rb::NONE))
+    }
+
+    /// The child of the root holding `window`: the frame of a reparented
+    /// client, or the client itself. None for the root, or if the window
+    /// is gone.
+    fn top_level(&self, mut window: Window) -> Result<Option<Window>> {
+        loop {
+            if window == self.root {
+                return Ok(None);
+            }
+            let Ok(reply) = self.conn.query_tree(window)?.reply() else {
+                return Ok(None);
+            };
+            if reply.parent == self.root {
+                return Ok(Some(window));
+            }
+            window = reply.parent;
+        }
+    }
+}
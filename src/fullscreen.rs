@@ -0,0 +1,125 @@
+//! Keeping the overview out of the way of fullscreen games and video.
+//!
+//! With `FullscreenActivation refuse`, the overview doesn't open while the
+//! focused window is fullscreen (`_NET_WM_STATE_FULLSCREEN`); with `twice`,
+//! it opens only if triggered again within a second, so a stray hotkey press
+//! is harmless. Either way, thumbnails of fullscreen windows stop following
+//! their damage once the overview is open, so a game redrawing every frame
+//! can't bog it down.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::paths;
+
+/// How soon the second trigger must follow the first under `twice`.
+const SECOND_TRIGGER: Duration = Duration::from_secs(1);
+/// Where `twice` keeps the time of the first trigger.
+const TRIGGER_FILE: &str = "fullscreen-trigger";
+
+/// Whether the overview opens over a fullscreen window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FullscreenActivation {
+    #[default]
+    Allow,
+    Refuse,
+    /// Only on a second trigger in quick succession
+    Twice,
+}
+
+impl FullscreenActivation {
+    /// Parse a config value: allow, refuse or twice.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "allow" => Some(Self::Allow),
+            "refuse" => Some(Self::Refuse),
+            "twice" => Some(Self::Twice),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the overview may open now.
+pub fn allows_overview(xconn: &XConnection, activation: FullscreenActivation) -> Result<bool> {
+    if activation == FullscreenActivation::Allow || !xconn.focused_is_fullscreen()? {
+        return Ok(true);
+    }
+    if activation == FullscreenActivation::Refuse {
+        log::info!("Not opening over a fullscreen window");
+        return Ok(false);
+    }
+
+    let path = paths::runtime_dir().join(TRIGGER_FILE);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let last = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .map(Duration::from_millis);
+    if is_second_trigger(last, now) {
+        let _ = std::fs::remove_file(&path);
+        return Ok(true);
+    }
+    log::info!("Fullscreen window focused, opening on a second trigger");
+    paths::create_private_dir(&paths::runtime_dir())?;
+    paths::write_atomic(&path, now.as_millis().to_string().as_bytes())?;
+    Ok(false)
+}
+
+/// Whether a trigger at `now` follows one at `last` closely enough.
+fn is_second_trigger(last: Option<Duration>, now: Duration) -> bool {
+    last.is_some_and(|last| last <= now && now - last <= SECOND_TRIGGER)
+}
+
+impl XConnection {
+    /// Whether the focused window, or the window holding it, is fullscreen.
+    fn focused_is_fullscreen(&self) -> Result<bool> {
+        let mut window = self.conn.get_input_focus()?.reply()?.focus;
+        // No focus, or PointerRoot
+        if window == x11rb::NONE || window == u32::from(InputFocus::POINTER_ROOT) {
+            return Ok(false);
+        }
+        // Focus may sit on a subwindow of the client, and the client in a frame
+        while window != self.root {
+            if self.is_fullscreen(window)? {
+                return Ok(true);
+            }
+            let Ok(reply) = self.conn.query_tree(window)?.reply() else {
+                return Ok(false);
+            };
+            window = reply.parent;
+        }
+        Ok(false)
+    }
+
+    /// Whether `window` has `_NET_WM_STATE_FULLSCREEN`.
+    pub fn is_fullscreen(&self, window: Window) -> Result<bool> {
+        let Ok(reply) = self
+            .conn
+            .get_property(false, window, self.atoms._NET_WM_STATE, AtomEnum::ATOM, 0, 32)?
+            .reply()
+        else {
+            return Ok(false);
+        };
+        Ok(reply
+            .value32()
+            .is_some_and(|mut states| states.any(|state| state == self.atoms._NET_WM_STATE_FULLSCREEN)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_trigger() {
+        let at = Duration::from_millis;
+        assert!(is_second_trigger(Some(at(10_000)), at(10_400)));
+        assert!(!is_second_trigger(Some(at(10_000)), at(11_500)));
+        assert!(!is_second_trigger(None, at(10_000)));
+        // A clock that went back
+        assert!(!is_second_trigger(Some(at(10_000)), at(9_000)));
+    }
+}
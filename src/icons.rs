@@ -0,0 +1,266 @@
+//! Application icons on thumbnails (`ShowIcons true`).
+//!
+//! A window's icon comes from its `_NET_WM_ICON` when it has one. Otherwise
+//! its WM_CLASS is looked up among the installed `.desktop` entries (by
+//! `StartupWMClass`, else by file name), and the entry's `Icon` in the
+//! configured icon theme, the freedesktop `hicolor` fallback theme and
+//! `/usr/share/pixmaps`. Only PNG icons are read; an application that ships
+//! nothing but SVG goes without.
+//!
+//! Icons are laid onto the badge's background before uploading, so they
+//! need no alpha channel on the server, and are scaled into the badge as
+//! they are drawn.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::image::RgbaImage;
+use crate::resources::{OwnedPicture, OwnedPixmap};
+use crate::window_finder::WindowInfo;
+
+/// Icon size looked for, about what a badge shows.
+const WANTED_SIZE: u32 = 48;
+/// Theme directory sizes to try, best first.
+const THEME_SIZES: &[u32] = &[48, 64, 32, 128, 256, 24, 16];
+/// The badge behind the icon (as in `render_pin`).
+const BADGE_COLOR: [u8; 3] = [0x55, 0x55, 0x55];
+/// Longest `_NET_WM_ICON` read, in 32-bit values: room for 128x128 and the
+/// smaller sizes that usually come with it.
+const MAX_ICON_DATA: u32 = 128 * 128 * 2;
+
+/// An uploaded icon, freed when dropped.
+#[derive(Debug)]
+pub struct Icon {
+    /// Drawn through `picture`; only held to be freed with it
+    pub _pixmap: OwnedPixmap,
+    pub picture: OwnedPicture,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl XConnection {
+    /// Find and upload the icons of `windows`, by frame. Windows without
+    /// one are left out.
+    pub fn load_icons(&self, windows: &[WindowInfo], theme: Option<&str>) -> HashMap<Window, Icon> {
+        let cookies: Vec<_> = windows
+            .iter()
+            .map(|info| {
+                self.conn
+                    .get_property(false, info.client_window, self.atoms._NET_WM_ICON, AtomEnum::CARDINAL, 0, MAX_ICON_DATA)
+                    .ok()
+            })
+            .collect();
+        // Read the .desktop entries only if some window needs them
+        let mut entries: Option<HashMap<String, String>> = None;
+
+        let mut icons = HashMap::new();
+        for (info, cookie) in windows.iter().zip(cookies) {
+            let from_window = cookie
+                .and_then(|cookie| cookie.reply().ok())
+                .and_then(|reply| reply.value32().map(|data| data.collect::<Vec<u32>>()))
+                .and_then(|data| pick_net_wm_icon(&data, WANTED_SIZE));
+            let image = from_window.or_else(|| {
+                let class = info.wm_class.as_deref()?;
+                let entries = entries.get_or_insert_with(desktop_entries);
+                let name = class.split(' ').rev().find_map(|part| entries.get(&part.to_lowercase()))?;
+                let path = find_icon_file(name, theme)?;
+                RgbaImage::load(&path)
+                    .inspect_err(|e| log::debug!("Cannot load icon {}: {}", path.display(), e))
+                    .ok()
+            });
+            let Some(image) = image else {
+                continue;
+            };
+            match self.upload_image(&on_badge(&image)) {
+                Ok((pixmap, picture)) => {
+                    icons.insert(
+                        info.frame_window,
+                        Icon {
+                            _pixmap: pixmap,
+                            picture,
+                            width: image.width,
+                            height: image.height,
+                        },
+                    );
+                }
+                Err(e) => log::debug!("Cannot upload icon of {:?}: {}", info.wm_class, e),
+            }
+        }
+        log::debug!("Loaded {} icons", icons.len());
+        icons
+    }
+}
+
+/// The best of the images in a `_NET_WM_ICON` value (width, height, then
+/// ARGB pixels, repeated): the smallest at least `wanted` wide, else the
+/// largest.
+fn pick_net_wm_icon(data: &[u32], wanted: u32) -> Option<RgbaImage> {
+    let mut images = Vec::new();
+    let mut rest = data;
+    while let [width, height, pixels @ ..] = rest {
+        let len = (*width as usize).checked_mul(*height as usize)?;
+        if len == 0 || len > pixels.len() || *width > u32::from(u16::MAX) || *height > u32::from(u16::MAX) {
+            break;
+        }
+        images.push((*width, *height, &pixels[..len]));
+        rest = &pixels[len..];
+    }
+    let &(width, height, pixels) = images
+        .iter()
+        .filter(|(width, _, _)| *width >= wanted)
+        .min_by_key(|(width, _, _)| *width)
+        .or_else(|| images.iter().max_by_key(|(width, _, _)| *width))?;
+    let data = pixels
+        .iter()
+        .flat_map(|argb| {
+            let [a, r, g, b] = argb.to_be_bytes();
+            [r, g, b, a]
+        })
+        .collect();
+    Some(RgbaImage {
+        width: width as u16,
+        height: height as u16,
+        data,
+    })
+}
+
+/// The icon laid over the badge color, opaque.
+fn on_badge(image: &RgbaImage) -> RgbaImage {
+    let data = image
+        .data
+        .chunks_exact(4)
+        .flat_map(|px| {
+            let alpha = u16::from(px[3]);
+            let blend = |i: usize| ((u16::from(px[i]) * alpha + u16::from(BADGE_COLOR[i]) * (255 - alpha)) / 255) as u8;
+            [blend(0), blend(1), blend(2), 255]
+        })
+        .collect();
+    RgbaImage {
+        width: image.width,
+        height: image.height,
+        data,
+    }
+}
+
+/// Where applications and icons are installed: `$XDG_DATA_HOME` and
+/// `$XDG_DATA_DIRS`.
+fn data_dirs() -> Vec<PathBuf> {
+    let system = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs::data_dir()
+        .into_iter()
+        .chain(system.split(':').map(PathBuf::from))
+        .collect()
+}
+
+/// Lowercased WM_CLASS (or `.desktop` file name) -> icon name, from every
+/// installed application entry. Earlier data directories win.
+fn desktop_entries() -> HashMap<String, String> {
+    let mut entries = HashMap::new();
+    for dir in data_dirs() {
+        let Ok(files) = fs::read_dir(dir.join("applications")) else {
+            continue;
+        };
+        for path in files.flatten().map(|file| file.path()) {
+            if path.extension().is_none_or(|ext| ext != "desktop") {
+                continue;
+            }
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let Some((wm_class, icon)) = parse_desktop_entry(&contents) else {
+                continue;
+            };
+            if let Some(wm_class) = wm_class {
+                entries.entry(wm_class.to_lowercase()).or_insert_with(|| icon.clone());
+            }
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                entries.entry(stem.to_lowercase()).or_insert(icon);
+            }
+        }
+    }
+    entries
+}
+
+/// `StartupWMClass` and `Icon` of a `.desktop` file's `[Desktop Entry]`;
+/// None without an icon.
+fn parse_desktop_entry(contents: &str) -> Option<(Option<String>, String)> {
+    let mut in_entry = false;
+    let (mut wm_class, mut icon) = (None, None);
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        // Localized keys (Icon[de]=) don't match
+        match line.split_once('=').map(|(key, value)| (key.trim(), value.trim())) {
+            Some(("StartupWMClass", value)) if !value.is_empty() => wm_class = Some(value.to_string()),
+            Some(("Icon", value)) if !value.is_empty() => icon = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Some((wm_class, icon?))
+}
+
+/// The PNG for an icon name (or an `Icon` that is already a path).
+fn find_icon_file(name: &str, theme: Option<&str>) -> Option<PathBuf> {
+    if Path::new(name).is_absolute() {
+        return Some(PathBuf::from(name)).filter(|path| path.is_file());
+    }
+    let file = format!("{}.png", name);
+    let dirs = data_dirs();
+    for theme in theme.into_iter().chain(["hicolor"]) {
+        for size in THEME_SIZES {
+            for dir in &dirs {
+                let path = dir.join(format!("icons/{theme}/{size}x{size}/apps")).join(&file);
+                if path.is_file() {
+                    return Some(path);
+                }
+            }
+        }
+    }
+    dirs.iter()
+        .map(|dir| dir.join("pixmaps").join(&file))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_net_wm_icon() {
+        let mut data = vec![2, 2];
+        data.extend([0xFF112233; 4]);
+        data.extend([4, 1]);
+        data.extend([0x80FFFFFF; 4]);
+        let icon = pick_net_wm_icon(&data, 3).unwrap();
+        assert_eq!((icon.width, icon.height), (4, 1));
+        assert_eq!(&icon.data[..4], [0xFF, 0xFF, 0xFF, 0x80]);
+        // None big enough: the largest
+        assert_eq!(pick_net_wm_icon(&data, 48).unwrap().width, 4);
+        // Truncated data keeps the images before it
+        assert_eq!(pick_net_wm_icon(&data[..9], 48).unwrap().width, 2);
+        assert!(pick_net_wm_icon(&[16, 16, 0], 48).is_none());
+    }
+
+    #[test]
+    fn test_parse_desktop_entry() {
+        let entry = "[Desktop Entry]\nName=Files\nIcon[de]=ordner\nIcon=org.gnome.Nautilus\n\
+                     StartupWMClass=Org.gnome.Nautilus\n\n[Desktop Action new-window]\nIcon=other\n";
+        assert_eq!(
+            parse_desktop_entry(entry),
+            Some((Some("Org.gnome.Nautilus".to_string()), "org.gnome.Nautilus".to_string()))
+        );
+        assert_eq!(parse_desktop_entry("[Desktop Entry]\nName=No icon\n"), None);
+    }
+}
@@ -0,0 +1,315 @@
+//! Full-size image transfers to and from the X server (screenshots,
+//! thumbnail cache, wallpapers), and PNG/JPEG decoding.
+//!
+//! Grabs use an MIT-SHM segment when the extension is available so large
+//! images don't have to be serialized through the GetImage reply, falling back
+//! to the core protocol otherwise.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::ptr;
+
+use x11rb::connection::Connection;
+use x11rb::image::Image;
+use x11rb::protocol::shm;
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::error::{Result, XposeError};
+use crate::resources::{OwnedPicture, OwnedPixmap};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SIGNATURE: &[u8] = &[0xFF, 0xD8];
+
+/// Below this many pixels a plain GetImage is cheaper than setting up SHM.
+const SHM_MIN_PIXELS: usize = 64 * 64;
+
+/// A decoded image with tightly packed RGBA8 pixels.
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    pub width: u16,
+    pub height: u16,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Decode a PNG or JPEG file, told apart by their signatures.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        let image = if bytes.starts_with(PNG_SIGNATURE) {
+            Self::decode_png(&bytes)
+        } else if bytes.starts_with(JPEG_SIGNATURE) {
+            Self::decode_jpeg(&bytes)
+        } else {
+            Err("not a PNG or JPEG file".to_string())
+        };
+        image.map_err(|e| XposeError::Other(format!("Cannot decode {}: {}", path.display(), e)))
+    }
+
+    fn decode_png(bytes: &[u8]) -> std::result::Result<Self, String> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+        buf.truncate(frame.buffer_size());
+
+        let data = match frame.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf
+                .chunks_exact(3)
+                .flat_map(|px| [px[0], px[1], px[2], 0xFF])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .flat_map(|px| [px[0], px[0], px[0], px[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v, 0xFF]).collect(),
+            other => return Err(format!("unsupported PNG color type {:?}", other)),
+        };
+        Self::from_parts(frame.width, frame.height, data)
+    }
+
+    fn decode_jpeg(bytes: &[u8]) -> std::result::Result<Self, String> {
+        let mut decoder = jpeg_decoder::Decoder::new(bytes);
+        let pixels = decoder.decode().map_err(|e| e.to_string())?;
+        let info = decoder.info().ok_or("missing JPEG header")?;
+
+        let data = match info.pixel_format {
+            jpeg_decoder::PixelFormat::RGB24 => pixels
+                .chunks_exact(3)
+                .flat_map(|px| [px[0], px[1], px[2], 0xFF])
+                .collect(),
+            jpeg_decoder::PixelFormat::L8 => pixels.iter().flat_map(|&v| [v, v, v, 0xFF]).collect(),
+            other => return Err(format!("unsupported JPEG pixel format {:?}", other)),
+        };
+        Self::from_parts(info.width as u32, info.height as u32, data)
+    }
+
+    fn from_parts(width: u32, height: u32, data: Vec<u8>) -> std::result::Result<Self, String> {
+        match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok(Self { width, height, data }),
+            _ => Err(format!("unsupported image size {}x{}", width, height)),
+        }
+    }
+
+    /// Write the image to a PNG file.
+    pub fn save_png(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// A System V shared memory segment attached to both us and the X server.
+/// Detaches on both sides when dropped.
+struct ShmSegment<'a> {
+    xconn: &'a XConnection,
+    seg: shm::Seg,
+    addr: *mut libc::c_void,
+    size: usize,
+}
+
+impl<'a> ShmSegment<'a> {
+    fn new(xconn: &'a XConnection, size: usize) -> Result<Self> {
+        // SAFETY: plain SysV shm calls; every failure path is checked before use.
+        let shmid = unsafe { libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600) };
+        if shmid < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let addr = unsafe { libc::shmat(shmid, ptr::null(), 0) };
+        if addr as isize == -1 {
+            let err = std::io::Error::last_os_error();
+            unsafe { libc::shmctl(shmid, libc::IPC_RMID, ptr::null_mut()) };
+            return Err(err.into());
+        }
+
+        let seg = match xconn.generate_id() {
+            Ok(id) => id,
+            Err(e) => {
+                unsafe {
+                    libc::shmdt(addr);
+                    libc::shmctl(shmid, libc::IPC_RMID, ptr::null_mut());
+                }
+                return Err(e);
+            }
+        };
+
+        // Checked request: a remote server can't attach and answers BadAccess
+        let attached = shm::attach(&xconn.conn, seg, shmid as u32, false)
+            .map_err(XposeError::from)
+            .and_then(|cookie| cookie.check().map_err(XposeError::from));
+
+        // Mark for removal now - the segment lives until both sides detach,
+        // so it can't leak even if we crash before dropping.
+        unsafe { libc::shmctl(shmid, libc::IPC_RMID, ptr::null_mut()) };
+
+        if let Err(e) = attached {
+            unsafe { libc::shmdt(addr) };
+            return Err(e);
+        }
+
+        Ok(Self { xconn, seg, addr, size })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: addr points to `size` bytes mapped by shmat and kept until drop.
+        unsafe { std::slice::from_raw_parts(self.addr as *const u8, self.size) }
+    }
+}
+
+impl Drop for ShmSegment<'_> {
+    fn drop(&mut self) {
+        let _ = shm::detach(&self.xconn.conn, self.seg);
+        let _ = self.xconn.conn.flush();
+        unsafe { libc::shmdt(self.addr) };
+    }
+}
+
+impl XConnection {
+    /// Upload an image into a new root-depth pixmap, with a picture for
+    /// compositing it. Alpha is dropped.
+    pub fn upload_image(&self, image: &RgbaImage) -> Result<(OwnedPixmap, OwnedPicture)> {
+        let mut upload = Image::allocate_native(image.width, image.height, self.root_depth, self.conn.setup())
+            .map_err(|e| XposeError::Other(format!("Cannot allocate image: {}", e)))?;
+        for (i, px) in image.data.chunks_exact(4).enumerate() {
+            let x = (i % image.width as usize) as u16;
+            let y = (i / image.width as usize) as u16;
+            let pixel = (px[0] as u32) << 16 | (px[1] as u32) << 8 | px[2] as u32;
+            upload.put_pixel(x, y, pixel);
+        }
+
+        let pixmap = self.create_owned_pixmap(self.root, image.width, image.height)?;
+        let gc = self.create_owned_gc(pixmap.id(), &CreateGCAux::new())?;
+        upload.put(&self.conn, pixmap.id(), gc.id(), 0, 0)?;
+        let picture = self.create_owned_picture(pixmap.id())?;
+        Ok((pixmap, picture))
+    }
+
+    /// Read a rectangle of a drawable as RGBA pixels.
+    /// Uses MIT-SHM when available, otherwise a regular GetImage request.
+    pub fn get_image_rgba(
+        &self,
+        drawable: Drawable,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<RgbaImage> {
+        if width == 0 || height == 0 {
+            return Err(XposeError::Other("Cannot grab an empty image".to_string()));
+        }
+
+        // Small grabs (label backgrounds) aren't worth a shared memory segment
+        if self.has_shm && width as usize * height as usize >= SHM_MIN_PIXELS {
+            match self.get_image_shm(drawable, x, y, width, height) {
+                Ok(image) => return Ok(image),
+                Err(e) => log::debug!("MIT-SHM grab failed, using GetImage: {}", e),
+            }
+        }
+
+        let reply = self
+            .conn
+            .get_image(ImageFormat::Z_PIXMAP, drawable, x, y, width, height, !0)?
+            .reply()?;
+        self.zpixmap_to_rgba(&reply.data, reply.depth, width, height)
+    }
+
+    /// Grab through a temporary shared memory segment.
+    fn get_image_shm(
+        &self,
+        drawable: Drawable,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<RgbaImage> {
+        // Sized for 32 bits per pixel, which covers both depth 24 and 32 drawables
+        let size = width as usize * height as usize * 4;
+        let segment = ShmSegment::new(self, size)?;
+
+        let reply = shm::get_image(
+            &self.conn,
+            drawable,
+            x,
+            y,
+            width,
+            height,
+            !0,
+            ImageFormat::Z_PIXMAP.into(),
+            segment.seg,
+            0,
+        )?
+        .reply()?;
+
+        self.zpixmap_to_rgba(segment.as_slice(), reply.depth, width, height)
+    }
+
+    /// Bytes per scanline of a ZPixmap image at the given depth.
+    fn zpixmap_stride(&self, depth: u8, width: u16) -> Result<usize> {
+        let format = self
+            .conn
+            .setup()
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == depth)
+            .ok_or_else(|| XposeError::Other(format!("No pixmap format for depth {}", depth)))?;
+
+        let bits = width as usize * format.bits_per_pixel as usize;
+        let pad = format.scanline_pad as usize;
+        Ok(bits.div_ceil(pad) * pad / 8)
+    }
+
+    /// Convert raw ZPixmap data (24/32-bit TrueColor) to RGBA.
+    fn zpixmap_to_rgba(&self, data: &[u8], depth: u8, width: u16, height: u16) -> Result<RgbaImage> {
+        let bpp = self
+            .conn
+            .setup()
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == depth)
+            .map(|f| f.bits_per_pixel)
+            .unwrap_or(0);
+        if bpp != 32 {
+            return Err(XposeError::Other(format!(
+                "Unsupported image format: depth {} at {} bpp",
+                depth, bpp
+            )));
+        }
+
+        let stride = self.zpixmap_stride(depth, width)?;
+        if data.len() < stride * height as usize {
+            return Err(XposeError::Other("Short image data from server".to_string()));
+        }
+
+        let lsb_first = self.conn.setup().image_byte_order == ImageOrder::LSB_FIRST;
+        let has_alpha = depth == 32;
+
+        let mut out = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in data.chunks_exact(stride).take(height as usize) {
+            for px in row[..width as usize * 4].chunks_exact(4) {
+                // Pixel value is 0xAARRGGBB in the server's byte order
+                let (a, r, g, b) = if lsb_first {
+                    (px[3], px[2], px[1], px[0])
+                } else {
+                    (px[0], px[1], px[2], px[3])
+                };
+                out.extend_from_slice(&[r, g, b, if has_alpha { a } else { 0xFF }]);
+            }
+        }
+
+        Ok(RgbaImage {
+            width,
+            height,
+            data: out,
+        })
+    }
+}
@@ -1,7 +1,22 @@
+use std::collections::HashMap;
+
 use x11rb::protocol::xproto::*;
 
-use crate::desktop_bar::{DesktopBar, DesktopBarHit};
-use crate::layout::ThumbnailLayout;
+use crate::desktop::state::MAX_DESKTOP_NAME_CHARS;
+use crate::desktop_bar::{DesktopBar, DesktopBarHit, DesktopPreviewLayout, PREVIEW_PADDING};
+use crate::desktop_grid::DesktopGrid;
+use crate::ewmh::AllowedActions;
+use crate::keymap::{
+    keysym_to_char, Keymap, XK_BACKSPACE, XK_DELETE, XK_DOWN, XK_ESCAPE, XK_F2, XK_F3, XK_KP_ENTER, XK_LEFT,
+    XK_PAGE_DOWN, XK_PAGE_UP, XK_RETURN, XK_RIGHT, XK_TAB, XK_UP,
+};
+use crate::desktop::state::Geometry;
+use crate::layout::{self, ThumbnailLayout};
+use crate::monitor::Monitor;
+use crate::placement;
+
+/// Longest search query, in characters.
+const MAX_SEARCH_CHARS: usize = 64;
 
 /// Actions that can result from user input.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -10,22 +25,49 @@ pub enum InputAction {
     SelectWindow(usize),
     /// Mouse is hovering over a thumbnail (or None if not over any).
     Hover(Option<usize>),
-    /// User wants to dismiss the overview (Escape or click outside).
+    /// Highlight moved with the arrow keys, to a thumbnail or to a desktop
+    /// preview (the other one None).
+    Highlight(Option<usize>, Option<u32>),
+    /// User wants to dismiss the overview (Escape or click on a thumbnail with another button).
     Dismiss,
+    /// User clicked empty background (root x, root y, button); dismisses the overview.
+    DismissClick(i16, i16, u8),
     /// No action needed.
     None,
     /// User clicked on a desktop preview.
     ActivateDesktop(u32),
     /// User clicked the plus button.
     ClickPlusButton,
-    /// User clicked the delete button on a desktop.
+    /// Delete a desktop: its delete button was clicked (and confirmed, if
+    /// it still had windows).
     DeleteDesktop(u32),
+    /// User clicked the delete button on a desktop; asks first if the
+    /// desktop has windows.
+    RequestDeleteDesktop(u32),
+    /// Delete confirmation answered with "No" (or dismissed).
+    CancelDeleteDesktop(u32),
     /// User started dragging a window.
     StartDrag(usize),
     /// Drag position updated.
     DragMove(i16, i16),
     /// Window dropped on a desktop.
     DropOnDesktop(usize, u32),
+    /// Window dropped on a desktop with Shift held: move it there, then
+    /// switch to that desktop on exit with the window raised.
+    FollowToDesktop(usize, u32),
+    /// Window dropped on the plus button, to go to a new desktop.
+    DropOnNewDesktop(usize),
+    /// Window dropped at a side of another's thumbnail (dragged, onto):
+    /// move it to that place in the grid's order.
+    ReorderWindow(usize, usize),
+    /// Window dropped on the middle of another's thumbnail (dragged, onto):
+    /// put the two in a group, piled up in the grid.
+    GroupWindows(usize, usize),
+    /// Take the window out of its group: `u` while it was highlighted.
+    Ungroup(usize),
+    /// Window dropped at the side of a monitor: select it, tiled to that
+    /// half (or quarter, in a corner) of the monitor.
+    TileWindow(usize, Geometry),
     /// Drag was cancelled.
     CancelDrag,
     /// Mouse hovering over desktop preview.
@@ -38,6 +80,104 @@ pub enum InputAction {
     DropDesktopAt(u32, u32),
     /// Desktop drag was cancelled.
     CancelDesktopDrag,
+    /// Desktop dropped on another with Shift held: move all of its windows
+    /// there (from, to).
+    MoveAllWindows(u32, u32),
+    /// Desktop name being typed (desktop, text so far); sent when editing
+    /// starts and after every change.
+    EditDesktopName(u32, String),
+    /// Desktop name edit confirmed with Enter.
+    RenameDesktop(u32, String),
+    /// Desktop name edit abandoned; the old name stays.
+    CancelRename(u32),
+    /// The search query changed (typed after `/`); the grid shows what
+    /// matches it.
+    Search(String),
+    /// Search abandoned with Escape; the grid shows every window again.
+    EndSearch,
+    /// Switch to the desktop `n` after the current one (before it, if
+    /// negative) without leaving the overview: Page Up/Down, or the wheel.
+    ScrollDesktop(i32),
+    /// Zoom out to the desktop grid, or back in to the window grid.
+    ToggleDesktopGrid,
+    /// Show every desktop's windows in the grid, grouped by desktop, or
+    /// only the current desktop's again.
+    ToggleAllDesktops,
+    /// A window miniature is being dragged in the desktop grid, now at (x, y).
+    DesktopGridDrag(Window, i16, i16),
+    /// A window miniature was dropped in the desktop grid, on a desktop's
+    /// tile or (None) outside all of them.
+    DesktopGridDrop(Window, Option<u32>),
+    /// Ask the window to close: its close button was clicked, the close
+    /// button (e.g. middle) pressed on it, or Delete pressed while it was
+    /// highlighted.
+    CloseWindow(usize),
+    /// The same with Ctrl held: mark the window to be killed, asking for the
+    /// same again (or Enter) to confirm.
+    RequestKillWindow(usize),
+    /// Kill confirmed: disconnect the window's client (XKillClient).
+    KillWindow(usize),
+    /// Kill confirmation abandoned; the window stays.
+    CancelKillWindow(usize),
+    /// Minimize the window into the strip along the bottom, or restore it
+    /// from there: its minimize button was clicked, or Ctrl+M pressed while
+    /// it was highlighted.
+    ToggleMinimize(usize),
+    /// Pin the window to every desktop, or unpin it: `p` while it was
+    /// highlighted.
+    ToggleSticky(usize),
+    /// Enlarge the window over the grid, dimmed around it, to read it before
+    /// picking it: Shift+click, or Shift+Enter.
+    Spotlight(usize),
+    /// Back to the grid from the enlarged window: Escape, or a click off it.
+    EndSpotlight(usize),
+    /// Grow the highlighted window into a large preview in the middle of its
+    /// monitor, kept live: Space.
+    QuickLook(usize),
+    /// Shrink the preview back into the grid: Space again, Escape or a click.
+    EndQuickLook(usize),
+    /// Select the window, maximizing it (or restoring it if maximized) as
+    /// the overview closes: Alt+click, or Alt+Enter.
+    SelectMaximized(usize),
+    /// Close the overview minimizing every window on the desktop, or
+    /// bringing back the ones this minimized last time: `d`.
+    ShowDesktop,
+    /// Copy the highlighted window's title to the clipboard: `y`.
+    CopyTitle(usize),
+    /// Copy the highlighted window's id to the clipboard: Shift+y.
+    CopyWindowId(usize),
+}
+
+/// Which pointer button does what, by X button number (1 left, 2 middle,
+/// 3 right).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonMap {
+    /// Picks a window or desktop and presses the desktop bar's buttons
+    pub select: u8,
+    /// Drags windows and desktop previews
+    pub drag: u8,
+    /// Closes the window under the pointer; takes precedence over the others
+    pub close: Option<u8>,
+    /// Dismisses the overview; when unset, any button without another use does
+    pub dismiss: Option<u8>,
+}
+
+impl Default for ButtonMap {
+    fn default() -> Self {
+        Self {
+            select: 1,
+            drag: 1,
+            close: None,
+            dismiss: None,
+        }
+    }
+}
+
+/// A desktop name being edited in the desktop bar.
+#[derive(Debug, Clone)]
+struct RenameState {
+    desktop: u32,
+    text: String,
 }
 
 /// Tracks the state of a window drag operation.
@@ -135,14 +275,137 @@ impl DesktopDragState {
     }
 }
 
+/// Tracks a window miniature pressed in the desktop grid.
+#[derive(Debug, Clone)]
+struct GridDragState {
+    desktop_index: u32,
+    window_id: Window,
+    start_x: i16,
+    start_y: i16,
+    is_active: bool,
+}
+
+impl GridDragState {
+    const DRAG_THRESHOLD: i16 = 5;
+
+    /// Update drag position, returns true if the drag is active.
+    fn update(&mut self, x: i16, y: i16) -> bool {
+        if !self.is_active {
+            let dx = (x - self.start_x).abs();
+            let dy = (y - self.start_y).abs();
+            self.is_active = dx > Self::DRAG_THRESHOLD || dy > Self::DRAG_THRESHOLD;
+        }
+        self.is_active
+    }
+}
+
+/// Where the keyboard highlight, or something picked up from the keyboard,
+/// can go: a thumbnail or a desktop preview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Spot {
+    Window(usize),
+    Desktop(u32),
+}
+
+/// Something picked up with `m`, moved with the arrow keys and placed with
+/// Enter. It rides on the same drag state as the pointer's, so it is drawn,
+/// dropped and put back the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Carry {
+    /// A window, over the thumbnail or desktop preview it would land on
+    Window(Spot),
+    /// A desktop preview, moved this many places along the bar
+    Desktop(i32),
+}
+
+/// An arrow key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Left,
+    Up,
+    Right,
+    Down,
+}
+
+impl Direction {
+    fn from_keysym(keysym: u32) -> Option<Self> {
+        match keysym {
+            XK_LEFT => Some(Self::Left),
+            XK_UP => Some(Self::Up),
+            XK_RIGHT => Some(Self::Right),
+            XK_DOWN => Some(Self::Down),
+            _ => None,
+        }
+    }
+}
+
+/// Of the candidates (with their centers), the one an arrow key goes to
+/// from `from`: the nearest ahead, sideways distance counting double so a
+/// step keeps to its row or column.
+fn nearest_towards<T>(
+    from: (i16, i16),
+    direction: Direction,
+    candidates: impl IntoIterator<Item = (T, (i16, i16))>,
+) -> Option<T> {
+    candidates
+        .into_iter()
+        .filter_map(|(item, (x, y))| {
+            let (dx, dy) = (i32::from(x) - i32::from(from.0), i32::from(y) - i32::from(from.1));
+            let (ahead, aside) = match direction {
+                Direction::Left => (-dx, dy),
+                Direction::Right => (dx, dy),
+                Direction::Up => (-dy, dx),
+                Direction::Down => (dy, dx),
+            };
+            (ahead > 0).then_some((ahead + 2 * aside.abs(), item))
+        })
+        .min_by_key(|(score, _)| *score)
+        .map(|(_, item)| item)
+}
+
+fn center(x: i16, y: i16, width: u16, height: u16) -> (i16, i16) {
+    (x + (width / 2) as i16, y + (height / 2) as i16)
+}
+
 /// Handles mouse and keyboard input for the overview window.
 pub struct InputHandler {
     layouts: Vec<ThumbnailLayout>,
     desktop_bar: Option<DesktopBar>,
     hovered_index: Option<usize>,
+    /// Thumbnail under the pointer; the highlight only follows the pointer
+    /// when this changes, so a highlight set from the keyboard stays put
+    pointer_index: Option<usize>,
     hovered_desktop: Option<u32>,
     drag_state: Option<DragState>,
     desktop_drag_state: Option<DesktopDragState>,
+    rename: Option<RenameState>,
+    /// The query being typed, while searching
+    search: Option<String>,
+    /// Window marked to be killed, waiting for confirmation
+    kill_confirm: Option<usize>,
+    /// Where the window in the spotlight is shown enlarged; it takes the
+    /// next key or click
+    spotlight: Option<ThumbnailLayout>,
+    /// Window grown into the quick-look preview; it takes the next key or
+    /// click
+    quick_look: Option<usize>,
+    /// Set while zoomed out to the desktop grid; input goes to it alone.
+    desktop_grid: Option<DesktopGrid>,
+    grid_drag_state: Option<GridDragState>,
+    buttons: ButtonMap,
+    /// Button that started the press being tracked by the drag states
+    pressed_button: u8,
+    /// Where windows can be tiled to, by dropping them at a side
+    monitors: Vec<Monitor>,
+    /// Windows the window manager restricts, by index; the others allow
+    /// everything
+    restricted: HashMap<usize, AllowedActions>,
+    /// Set while something picked up from the keyboard is being moved; the
+    /// pointer leaves it alone
+    carry: Option<Carry>,
+    /// How far off a thumbnail or desktop bar button a press still counts
+    /// as on it, in pixels
+    hit_tolerance: u16,
 }
 
 impl InputHandler {
@@ -151,28 +414,83 @@ impl InputHandler {
             layouts,
             desktop_bar,
             hovered_index: None,
+            pointer_index: None,
             hovered_desktop: None,
             drag_state: None,
             desktop_drag_state: None,
+            rename: None,
+            search: None,
+            kill_confirm: None,
+            spotlight: None,
+            quick_look: None,
+            desktop_grid: None,
+            grid_drag_state: None,
+            buttons: ButtonMap::default(),
+            pressed_button: 0,
+            monitors: Vec::new(),
+            restricted: HashMap::new(),
+            carry: None,
+            hit_tolerance: 0,
         }
     }
 
+    /// Use a different button mapping (after a config change).
+    pub fn set_buttons(&mut self, buttons: ButtonMap) {
+        self.buttons = buttons;
+    }
+
+    /// Tile windows dropped at the sides of these monitors; none without.
+    pub fn set_monitors(&mut self, monitors: Vec<Monitor>) {
+        self.monitors = monitors;
+    }
+
+    /// Count points up to `pixels` off a thumbnail or desktop bar button as
+    /// on it.
+    pub fn set_hit_tolerance(&mut self, pixels: u16) {
+        self.hit_tolerance = pixels;
+    }
+
+    /// Leave out the close, minimize and maximize actions these windows
+    /// don't allow.
+    pub fn set_restricted(&mut self, restricted: HashMap<usize, AllowedActions>) {
+        self.restricted = restricted;
+    }
+
+    fn allowed(&self, index: usize) -> AllowedActions {
+        self.restricted.get(&index).copied().unwrap_or_default()
+    }
+
     /// Get the currently hovered thumbnail index.
     #[allow(dead_code)]
     pub fn hovered(&self) -> Option<usize> {
         self.hovered_index
     }
 
+    /// Show a window enlarged at `layout` until the next key or click, or
+    /// stop with None.
+    pub fn set_spotlight(&mut self, layout: Option<ThumbnailLayout>) {
+        self.spotlight = layout;
+    }
+
+    /// Show a window in the quick-look preview until the next key or click,
+    /// or stop with None.
+    pub fn set_quick_look(&mut self, index: Option<usize>) {
+        self.quick_look = index;
+    }
+
+    /// Highlight a thumbnail without the pointer on it.
+    pub fn set_hovered(&mut self, index: Option<usize>) {
+        self.hovered_index = index;
+    }
+
     /// Get the currently hovered desktop index.
-    #[allow(dead_code)]
     pub fn hovered_desktop(&self) -> Option<u32> {
         self.hovered_desktop
     }
 
     /// Check if a drag is currently active.
-    #[allow(dead_code)]
     pub fn is_dragging(&self) -> bool {
-        self.drag_state.as_ref().map_or(false, |d| d.is_active)
+        self.drag_state.as_ref().is_some_and(|d| d.is_active)
     }
 
     /// Get the current drag state.
@@ -196,22 +514,138 @@ impl InputHandler {
         self.desktop_bar = new_bar;
     }
 
+    /// Enter (Some) or leave (None) the desktop grid, or update its layout.
+    pub fn update_desktop_grid(&mut self, grid: Option<DesktopGrid>) {
+        if grid.is_none() {
+            self.grid_drag_state = None;
+        }
+        self.hovered_desktop = None;
+        self.desktop_grid = grid;
+    }
+
     /// Handle a button press event.
     pub fn handle_button_press(&mut self, event: &ButtonPressEvent) -> InputAction {
-        log::debug!("Button press at ({}, {})", event.event_x, event.event_y);
+        log::debug!("Button {} press at ({}, {})", event.detail, event.event_x, event.event_y);
+        // A click puts back whatever was picked up from the keyboard
+        if let Some(carry) = self.carry {
+            return self.cancel_carry(carry);
+        }
+        let button = event.detail;
+        let buttons = self.buttons;
+        let selects = button == buttons.select;
+        // A second button pressed mid-drag doesn't take over the drag
+        if self.drag_state.is_none() && self.desktop_drag_state.is_none() && self.grid_drag_state.is_none() {
+            self.pressed_button = button;
+        }
+
+        // Clicking anywhere abandons a name edit
+        if let Some(rename) = self.rename.take() {
+            return InputAction::CancelRename(rename.desktop);
+        }
+
+        // A click on the window in the spotlight picks it, anywhere else
+        // goes back to the grid
+        if let Some(layout) = self.spotlight.take() {
+            if selects && layout.contains(event.event_x, event.event_y) {
+                return InputAction::SelectWindow(layout.window_index);
+            }
+            return InputAction::EndSpotlight(layout.window_index);
+        }
+        if let Some(index) = self.quick_look.take() {
+            return InputAction::EndQuickLook(index);
+        }
 
-        // Left mouse button only
-        if event.detail != 1 {
+        // A window marked to be killed goes on Ctrl with the same close
+        // again; any other click spares it
+        if let Some(index) = self.kill_confirm.take() {
+            if event.state.contains(KeyButMask::CONTROL) && self.close_target(event) == Some(index) {
+                return InputAction::KillWindow(index);
+            }
+            return InputAction::CancelKillWindow(index);
+        }
+
+        // An open delete confirmation takes the next click: "Yes" deletes,
+        // anywhere else keeps the desktop
+        if let Some(desktop) = self.pending_delete() {
+            let hit = self
+                .desktop_bar
+                .as_ref()
+                .map(|bar| bar.hit_test_near(event.event_x, event.event_y, self.hit_tolerance));
+            return match hit {
+                Some(DesktopBarHit::ConfirmDelete(desktop)) if selects => InputAction::DeleteDesktop(desktop),
+                _ => InputAction::CancelDeleteDesktop(desktop),
+            };
+        }
+
+        if let Some(ref grid) = self.desktop_grid {
+            if !selects && button != buttons.drag {
+                return InputAction::ToggleDesktopGrid;
+            }
+            if let Some((desktop_index, window_id)) = grid.window_at(event.event_x, event.event_y) {
+                self.grid_drag_state = Some(GridDragState {
+                    desktop_index,
+                    window_id,
+                    start_x: event.event_x,
+                    start_y: event.event_y,
+                    is_active: false,
+                });
+                return InputAction::None;
+            }
+            return match grid.hit_test(event.event_x, event.event_y) {
+                Some(desktop) if selects => InputAction::ActivateDesktop(desktop),
+                Some(_) => InputAction::None,
+                // Clicking between the tiles zooms back in
+                None => InputAction::ToggleDesktopGrid,
+            };
+        }
+
+        if let Some(index) = self.close_target(event) {
+            log::info!("Closing window {} (button {})", index, button);
+            return self.close(index, event.state);
+        }
+        if let Some(index) = self.minimize_target(event) {
+            log::info!("Clicked minimize button on window {}", index);
+            return InputAction::ToggleMinimize(index);
+        }
+        if buttons.close == Some(button) {
+            return InputAction::None;
+        }
+
+        // The wheel, unless set aside to dismiss, goes through the desktops
+        let scroll = match button {
+            4 | 6 => Some(-1),
+            5 | 7 => Some(1),
+            _ => None,
+        };
+        if let Some(step) = scroll.filter(|_| !selects && button != buttons.drag && buttons.dismiss != Some(button)) {
+            let busy = self.drag_state.is_some() || self.desktop_drag_state.is_some() || self.search.is_some();
+            return if busy { InputAction::None } else { InputAction::ScrollDesktop(step) };
+        }
+
+        if !selects && button != buttons.drag {
+            // Unless one button is set aside for it, any other button dismisses
+            if buttons.dismiss.is_some_and(|dismiss| dismiss != button) {
+                return InputAction::None;
+            }
+            if self.is_background(event.event_x, event.event_y) {
+                return InputAction::DismissClick(event.root_x, event.root_y, event.detail);
+            }
             return InputAction::Dismiss;
         }
 
         // Check desktop bar first
         if let Some(ref bar) = self.desktop_bar {
             if bar.contains_point(event.event_x, event.event_y) {
-                match bar.hit_test(event.event_x, event.event_y) {
+                match bar.hit_test_near(event.event_x, event.event_y, self.hit_tolerance) {
+                    // The drag button can only pick up previews
+                    DesktopBarHit::Desktop(idx) if !selects => {
+                        self.desktop_drag_state = Some(DesktopDragState::new(idx, event.event_x, event.event_y));
+                        return InputAction::None;
+                    }
+                    _ if !selects => return InputAction::None,
                     DesktopBarHit::DeleteButton(idx) => {
                         log::info!("Clicked delete button for desktop {}", idx);
-                        return InputAction::DeleteDesktop(idx);
+                        return InputAction::RequestDeleteDesktop(idx);
                     }
                     DesktopBarHit::Desktop(idx) => {
                         // Start potential desktop drag (don't activate immediately)
@@ -224,10 +658,14 @@ impl InputHandler {
                         log::info!("Clicked plus button");
                         return InputAction::ClickPlusButton;
                     }
-                    DesktopBarHit::None => {
-                        log::debug!("Clicked in bar area but not on element");
+                    // Only hit while a confirmation is open, handled above
+                    DesktopBarHit::ConfirmDelete(_) | DesktopBarHit::CancelDelete(_) => {
                         return InputAction::None;
                     }
+                    DesktopBarHit::None => {
+                        log::debug!("Clicked bar background, zooming out to desktop grid");
+                        return InputAction::ToggleDesktopGrid;
+                    }
                 }
             }
         }
@@ -239,35 +677,41 @@ impl InputHandler {
             return InputAction::None; // Wait to see if drag or click
         }
 
+        if !selects {
+            return InputAction::None;
+        }
         // Click outside any element dismisses
         log::debug!("No element hit, dismissing");
-        InputAction::Dismiss
+        InputAction::DismissClick(event.root_x, event.root_y, event.detail)
     }
 
     /// Handle a button release event.
     pub fn handle_button_release(&mut self, event: &ButtonReleaseEvent) -> InputAction {
+        // Only the button that started a press ends it
+        if event.detail != self.pressed_button || self.carry.is_some() {
+            return InputAction::None;
+        }
+        let selects = self.pressed_button == self.buttons.select;
+
+        if let Some(drag) = self.grid_drag_state.take() {
+            let target = self.desktop_grid.as_ref().and_then(|g| g.hit_test(event.event_x, event.event_y));
+            if drag.is_active {
+                return InputAction::DesktopGridDrop(drag.window_id, target);
+            }
+            // A click on a miniature picks its desktop
+            return if selects { InputAction::ActivateDesktop(drag.desktop_index) } else { InputAction::None };
+        }
+
         // Handle desktop drag release first
         if let Some(drag) = self.desktop_drag_state.take() {
             if drag.is_active {
-                // Calculate insert position
-                if let Some(ref bar) = self.desktop_bar {
-                    let insert_pos = bar.calculate_insert_position(event.event_x, drag.desktop_index);
-                    // Only trigger reorder if position actually changes
-                    if insert_pos != drag.desktop_index && insert_pos != drag.desktop_index + 1 {
-                        log::info!(
-                            "Dropping desktop {} at position {}",
-                            drag.desktop_index,
-                            insert_pos
-                        );
-                        return InputAction::DropDesktopAt(drag.desktop_index, insert_pos);
-                    }
-                }
-                log::debug!("Desktop drag cancelled (no position change)");
-                return InputAction::CancelDesktopDrag;
-            } else {
+                return self.drop_desktop(&drag, event.event_x, event.event_y, event.state);
+            } else if selects {
                 // Was a click, not a drag - activate the desktop
                 log::info!("Activated desktop {} (click)", drag.desktop_index);
                 return InputAction::ActivateDesktop(drag.desktop_index);
+            } else {
+                return InputAction::None;
             }
         }
 
@@ -276,53 +720,683 @@ impl InputHandler {
             if drag.is_active {
                 // Check if dropping on a desktop
                 if let Some(ref bar) = self.desktop_bar {
-                    if let DesktopBarHit::Desktop(desktop_idx) | DesktopBarHit::DeleteButton(desktop_idx) =
-                        bar.hit_test(event.event_x, event.event_y)
-                    {
-                        log::info!(
-                            "Dropped window {} on desktop {}",
-                            drag.window_index,
-                            desktop_idx
-                        );
-                        return InputAction::DropOnDesktop(drag.window_index, desktop_idx);
+                    match bar.hit_test_near(event.event_x, event.event_y, self.hit_tolerance) {
+                        DesktopBarHit::Desktop(desktop_idx) | DesktopBarHit::DeleteButton(desktop_idx) => {
+                            if event.state.contains(KeyButMask::SHIFT) {
+                                log::info!(
+                                    "Dropped window {} on desktop {}, following it",
+                                    drag.window_index,
+                                    desktop_idx
+                                );
+                                return InputAction::FollowToDesktop(drag.window_index, desktop_idx);
+                            }
+                            log::info!(
+                                "Dropped window {} on desktop {}",
+                                drag.window_index,
+                                desktop_idx
+                            );
+                            return InputAction::DropOnDesktop(drag.window_index, desktop_idx);
+                        }
+                        DesktopBarHit::PlusButton => {
+                            log::info!("Dropped window {} on plus button", drag.window_index);
+                            return InputAction::DropOnNewDesktop(drag.window_index);
+                        }
+                        _ => {}
                     }
                 }
+                if let Some(target) = self.find_thumbnail_at(event.event_x, event.event_y).filter(|&t| t != drag.window_index) {
+                    let middle = self
+                        .layouts
+                        .iter()
+                        .find(|l| l.window_index == target)
+                        .is_some_and(|l| l.middle_contains(event.event_x, event.event_y));
+                    if middle {
+                        log::info!("Dropped window {} on window {}, grouping", drag.window_index, target);
+                        return InputAction::GroupWindows(drag.window_index, target);
+                    }
+                    log::info!("Dropped window {} on window {}, reordering", drag.window_index, target);
+                    return InputAction::ReorderWindow(drag.window_index, target);
+                }
+                let on_bar = self.desktop_bar.as_ref().is_some_and(|bar| bar.contains_point(event.event_x, event.event_y));
+                if let Some(geometry) = placement::tile_at(&self.monitors, event.event_x, event.event_y).filter(|_| !on_bar) {
+                    log::info!("Dropped window {} at a side, tiling it", drag.window_index);
+                    return InputAction::TileWindow(drag.window_index, geometry);
+                }
                 log::debug!("Drag cancelled (not dropped on desktop)");
                 return InputAction::CancelDrag;
-            } else {
+            } else if selects {
                 // Was a click, not a drag
                 log::debug!("Selected window {} (click)", drag.window_index);
+                if event.state.contains(KeyButMask::MOD1) && self.allowed(drag.window_index).maximize {
+                    return InputAction::SelectMaximized(drag.window_index);
+                }
+                if event.state.contains(KeyButMask::SHIFT) {
+                    return InputAction::Spotlight(drag.window_index);
+                }
                 return InputAction::SelectWindow(drag.window_index);
+            } else {
+                return InputAction::None;
             }
         }
         InputAction::None
     }
 
-    /// Handle a key press event.
-    pub fn handle_key_press(&self, event: &KeyPressEvent) -> InputAction {
-        // Keycode 9 is typically Escape on most X11 keymaps
-        // Keycode 36 is typically Enter/Return
-        match event.detail {
-            9 => InputAction::Dismiss, // Escape
-            36 => {
-                // Enter - select hovered window if any
-                if let Some(index) = self.hovered_index {
+    /// Where a dragged desktop preview let go at (x, y) goes: in between
+    /// the others, or with Shift held its windows onto the one under it.
+    fn drop_desktop(&self, drag: &DesktopDragState, x: i16, y: i16, state: KeyButMask) -> InputAction {
+        // Shift+drop onto another preview merges instead of reordering
+        if state.contains(KeyButMask::SHIFT) {
+            let hit = self.desktop_bar.as_ref().map(|bar| bar.hit_test_near(x, y, self.hit_tolerance));
+            if let Some(DesktopBarHit::Desktop(target)) = hit {
+                if target != drag.desktop_index {
+                    log::info!("Moving all windows from desktop {} to {}", drag.desktop_index, target);
+                    return InputAction::MoveAllWindows(drag.desktop_index, target);
+                }
+            }
+        }
+        // Calculate insert position
+        if let Some(ref bar) = self.desktop_bar {
+            let cursor = bar.along(x, y);
+            let insert_pos = bar.calculate_insert_position(cursor, drag.desktop_index);
+            // Only trigger reorder if position actually changes
+            if insert_pos != drag.desktop_index && insert_pos != drag.desktop_index + 1 {
+                log::info!(
+                    "Dropping desktop {} at position {}",
+                    drag.desktop_index,
+                    insert_pos
+                );
+                return InputAction::DropDesktopAt(drag.desktop_index, insert_pos);
+            }
+        }
+        log::debug!("Desktop drag cancelled (no position change)");
+        InputAction::CancelDesktopDrag
+    }
+
+    /// Handle a key press event, given the keysym it maps to.
+    pub fn handle_key_press(&mut self, event: &KeyPressEvent, keymap: &Keymap) -> InputAction {
+        let keysym = keymap.keysym(event.detail, event.state);
+        if self.rename.is_some() {
+            return self.handle_rename_key(keysym);
+        }
+        if let Some(index) = self.spotlight.as_ref().map(|layout| layout.window_index) {
+            return match keysym {
+                XK_RETURN | XK_KP_ENTER => {
+                    self.spotlight = None;
+                    InputAction::SelectWindow(index)
+                }
+                XK_ESCAPE => {
+                    self.spotlight = None;
+                    InputAction::EndSpotlight(index)
+                }
+                _ => InputAction::None,
+            };
+        }
+        if let Some(index) = self.quick_look {
+            return match keysym {
+                XK_RETURN | XK_KP_ENTER => {
+                    self.quick_look = None;
                     InputAction::SelectWindow(index)
-                } else {
-                    InputAction::None
+                }
+                _ if keysym == XK_ESCAPE || keysym_to_char(keysym) == Some(' ') => {
+                    self.quick_look = None;
+                    InputAction::EndQuickLook(index)
+                }
+                _ => InputAction::None,
+            };
+        }
+        if self.search.is_some() && self.desktop_grid.is_none() && !self.is_dragging() && self.carry.is_none() {
+            return self.handle_search_key(keysym);
+        }
+        if let Some(desktop) = self.pending_delete() {
+            return match keysym {
+                XK_RETURN | XK_KP_ENTER => InputAction::DeleteDesktop(desktop),
+                XK_ESCAPE => InputAction::CancelDeleteDesktop(desktop),
+                _ => InputAction::None,
+            };
+        }
+        if let Some(index) = self.kill_confirm.take() {
+            let again = keysym == XK_DELETE && event.state.contains(KeyButMask::CONTROL);
+            return match keysym {
+                XK_RETURN | XK_KP_ENTER => InputAction::KillWindow(index),
+                _ if again && self.hovered_index == Some(index) => InputAction::KillWindow(index),
+                _ => InputAction::CancelKillWindow(index),
+            };
+        }
+        if self.desktop_grid.is_some() {
+            return self.handle_grid_key(keymap.keysym(event.detail, KeyButMask::default()));
+        }
+        if let Some(carry) = self.carry {
+            return self.handle_carry_key(carry, keysym, event.state);
+        }
+        if let Some(direction) = Direction::from_keysym(keysym).filter(|_| !self.is_dragging()) {
+            return self.move_highlight(direction);
+        }
+
+        // Digits go by the unshifted key, so Shift+1 is still desktop 1
+        let plain = keymap.keysym(event.detail, KeyButMask::default());
+        if let Some(desktop) = self.digit_desktop(plain) {
+            if self.is_dragging() {
+                return InputAction::None;
+            }
+            if !event.state.contains(KeyButMask::SHIFT) {
+                return InputAction::ActivateDesktop(desktop);
+            }
+            // Shift+digit sends the highlighted window there
+            return match self.hovered_index {
+                Some(index) => InputAction::DropOnDesktop(index, desktop),
+                None => InputAction::None,
+            };
+        }
+
+        match keysym {
+            // Escape during a drag only cancels the drag
+            XK_ESCAPE if self.desktop_drag_state.as_ref().is_some_and(|d| d.is_active) => {
+                self.desktop_drag_state = None;
+                InputAction::CancelDesktopDrag
+            }
+            XK_ESCAPE if self.is_dragging() => {
+                self.drag_state = None;
+                InputAction::CancelDrag
+            }
+            XK_ESCAPE => InputAction::Dismiss,
+            XK_RETURN | XK_KP_ENTER => {
+                // Enter - select hovered window if any, else the highlighted desktop
+                match (self.hovered_index, self.hovered_desktop) {
+                    (Some(index), _) if event.state.contains(KeyButMask::MOD1) && self.allowed(index).maximize => {
+                        InputAction::SelectMaximized(index)
+                    }
+                    (Some(index), _) if event.state.contains(KeyButMask::SHIFT) && !self.is_dragging() => {
+                        InputAction::Spotlight(index)
+                    }
+                    (Some(index), _) => InputAction::SelectWindow(index),
+                    (None, Some(desktop)) if !self.is_dragging() => InputAction::ActivateDesktop(desktop),
+                    (None, _) => InputAction::None,
+                }
+            }
+            _ if keysym_to_char(plain) == Some('m') && event.state.contains(KeyButMask::CONTROL) => {
+                match self.hovered_index.filter(|&index| !self.is_dragging() && self.allowed(index).minimize) {
+                    Some(index) => InputAction::ToggleMinimize(index),
+                    None => InputAction::None,
+                }
+            }
+            _ if keysym_to_char(plain) == Some('p')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1) =>
+            {
+                match self.hovered_index.filter(|_| !self.is_dragging()) {
+                    Some(index) => InputAction::ToggleSticky(index),
+                    None => InputAction::None,
+                }
+            }
+            _ if keysym_to_char(plain) == Some('u')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1) =>
+            {
+                match self.hovered_index.filter(|_| !self.is_dragging()) {
+                    Some(index) => InputAction::Ungroup(index),
+                    None => InputAction::None,
                 }
             }
+            _ if keysym_to_char(plain) == Some('y')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1) =>
+            {
+                match self.hovered_index.filter(|_| !self.is_dragging()) {
+                    Some(index) if event.state.contains(KeyButMask::SHIFT) => InputAction::CopyWindowId(index),
+                    Some(index) => InputAction::CopyTitle(index),
+                    None => InputAction::None,
+                }
+            }
+            _ if keysym_to_char(plain) == Some('m')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1)
+                && !self.is_dragging()
+                && self.desktop_drag_state.is_none() =>
+            {
+                self.pick_up()
+            }
+            _ if keysym_to_char(plain) == Some('n')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1)
+                && !self.is_dragging()
+                && self.desktop_bar.is_some() =>
+            {
+                InputAction::ClickPlusButton
+            }
+            _ if keysym_to_char(keysym) == Some('/')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1)
+                && !self.is_dragging()
+                && self.desktop_drag_state.is_none() =>
+            {
+                self.search = Some(String::new());
+                InputAction::Search(String::new())
+            }
+            _ if keysym_to_char(plain) == Some(' ')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1)
+                && !self.is_dragging()
+                && self.desktop_drag_state.is_none() =>
+            {
+                match self.hovered_index {
+                    Some(index) => InputAction::QuickLook(index),
+                    None => InputAction::None,
+                }
+            }
+            _ if keysym_to_char(plain) == Some('d')
+                && !event.state.intersects(KeyButMask::CONTROL | KeyButMask::MOD1)
+                && !self.is_dragging() =>
+            {
+                InputAction::ShowDesktop
+            }
+            XK_DELETE if !self.is_dragging() => match (self.hovered_index, self.hovered_desktop) {
+                (Some(index), _) if self.allowed(index).close => self.close(index, event.state),
+                // The last desktop has no delete button either
+                (None, Some(desktop)) if self.desktop_bar.as_ref().is_some_and(|bar| bar.num_desktops > 1) => {
+                    InputAction::RequestDeleteDesktop(desktop)
+                }
+                _ => InputAction::None,
+            },
+            // F3, the Mission Control key on many keyboards
+            XK_F3 if !self.is_dragging() && self.desktop_drag_state.is_none() => InputAction::ToggleDesktopGrid,
+            XK_TAB if !self.is_dragging() && self.desktop_drag_state.is_none() => InputAction::ToggleAllDesktops,
+            XK_PAGE_UP if !self.is_dragging() && self.desktop_drag_state.is_none() => InputAction::ScrollDesktop(-1),
+            XK_PAGE_DOWN if !self.is_dragging() && self.desktop_drag_state.is_none() => InputAction::ScrollDesktop(1),
+            XK_F2 => {
+                // F2 - rename the hovered desktop
+                let Some(desktop) = self.hovered_desktop else {
+                    return InputAction::None;
+                };
+                let text = self
+                    .desktop_bar
+                    .as_ref()
+                    .and_then(|bar| bar.preview_layouts.iter().find(|p| p.desktop_index == desktop))
+                    .and_then(|p| p.name.clone())
+                    .unwrap_or_default();
+                log::info!("Renaming desktop {}", desktop);
+                self.rename = Some(RenameState { desktop, text: text.clone() });
+                InputAction::EditDesktopName(desktop, text)
+            }
             _ => {
-                log::debug!("Unhandled keycode: {}", event.detail);
+                log::debug!("Unhandled keycode: {} (keysym 0x{:x})", event.detail, keysym);
                 InputAction::None
             }
         }
     }
 
+    /// Keys pressed in the desktop grid.
+    fn handle_grid_key(&mut self, keysym: u32) -> InputAction {
+        if let Some(desktop) = self.digit_desktop(keysym) {
+            return InputAction::ActivateDesktop(desktop);
+        }
+        match keysym {
+            // Escape cancels a miniature drag first, then zooms back in
+            XK_ESCAPE if self.grid_drag_state.as_ref().is_some_and(|d| d.is_active) => {
+                let drag = self.grid_drag_state.take();
+                drag.map_or(InputAction::None, |d| InputAction::DesktopGridDrop(d.window_id, None))
+            }
+            XK_ESCAPE | XK_F3 => InputAction::ToggleDesktopGrid,
+            XK_RETURN | XK_KP_ENTER => match self.hovered_desktop {
+                Some(desktop) => InputAction::ActivateDesktop(desktop),
+                None => InputAction::None,
+            },
+            _ => match Direction::from_keysym(keysym) {
+                Some(direction) if self.grid_drag_state.is_none() => self.move_grid_highlight(direction),
+                _ => InputAction::None,
+            },
+        }
+    }
+
+    /// Move the highlight in the desktop grid with an arrow key; from
+    /// nothing it starts at the current desktop.
+    fn move_grid_highlight(&mut self, direction: Direction) -> InputAction {
+        let Some(ref grid) = self.desktop_grid else {
+            return InputAction::None;
+        };
+        let tile_center = |t: &DesktopPreviewLayout| center(t.x, t.y, t.width, t.height);
+        let to = match self.hovered_desktop.and_then(|desktop| grid.tile(desktop)) {
+            Some(from) => nearest_towards(
+                tile_center(from),
+                direction,
+                grid.tiles
+                    .iter()
+                    .filter(|t| t.desktop_index != from.desktop_index)
+                    .map(|t| (t.desktop_index, tile_center(t))),
+            ),
+            None => grid.tiles.iter().find(|t| t.is_current).or(grid.tiles.first()).map(|t| t.desktop_index),
+        };
+        if to.is_none() {
+            return InputAction::None;
+        }
+        self.hovered_desktop = to;
+        InputAction::HoverDesktop(to)
+    }
+
+    /// The highlighted thumbnail, else the highlighted desktop preview.
+    fn highlight_spot(&self) -> Option<Spot> {
+        self.hovered_index.map(Spot::Window).or(self.hovered_desktop.map(Spot::Desktop))
+    }
+
+    fn spot_center(&self, spot: Spot) -> Option<(i16, i16)> {
+        match spot {
+            Spot::Window(index) => {
+                let l = self.layouts.iter().find(|l| l.window_index == index)?;
+                Some(center(l.x, l.y, l.width, l.height))
+            }
+            Spot::Desktop(desktop) => self.desktop_bar.as_ref()?.get_preview_center(desktop),
+        }
+    }
+
+    /// The thumbnail or desktop preview an arrow key goes to from `from`.
+    fn spot_towards(&self, from: Spot, direction: Direction) -> Option<Spot> {
+        let windows = self.layouts.iter().map(|l| Spot::Window(l.window_index));
+        let desktops = self
+            .desktop_bar
+            .iter()
+            .flat_map(|bar| bar.preview_layouts.iter().map(|p| Spot::Desktop(p.desktop_index)));
+        let spots: Vec<(Spot, (i16, i16))> = windows
+            .chain(desktops)
+            .filter(|&spot| spot != from)
+            .filter_map(|spot| Some((spot, self.spot_center(spot)?)))
+            .collect();
+        nearest_towards(self.spot_center(from)?, direction, spots)
+    }
+
+    /// Move the highlight with an arrow key, between the thumbnails and on
+    /// to the desktop previews; from nothing it starts at the first
+    /// thumbnail.
+    fn move_highlight(&mut self, direction: Direction) -> InputAction {
+        let to = match self.highlight_spot() {
+            Some(from) => self.spot_towards(from, direction),
+            None => layout::reading_order(&self.layouts)
+                .first()
+                .map(|&index| Spot::Window(index))
+                .or(self.desktop_bar.as_ref().map(|bar| Spot::Desktop(bar.current_desktop))),
+        };
+        let Some(to) = to else {
+            return InputAction::None;
+        };
+        (self.hovered_index, self.hovered_desktop) = match to {
+            Spot::Window(index) => (Some(index), None),
+            Spot::Desktop(desktop) => (None, Some(desktop)),
+        };
+        InputAction::Highlight(self.hovered_index, self.hovered_desktop)
+    }
+
+    /// Pick up the highlighted window or desktop preview (`m`), as if the
+    /// pointer had started dragging it from its middle.
+    fn pick_up(&mut self) -> InputAction {
+        let Some(spot) = self.highlight_spot() else {
+            return InputAction::None;
+        };
+        let Some((x, y)) = self.spot_center(spot) else {
+            return InputAction::None;
+        };
+        match spot {
+            Spot::Window(index) => {
+                log::info!("Picked up window {}", index);
+                let mut drag = DragState::new(index, x, y);
+                drag.is_active = true;
+                self.drag_state = Some(drag);
+                self.carry = Some(Carry::Window(spot));
+                InputAction::StartDrag(index)
+            }
+            Spot::Desktop(desktop) => {
+                log::info!("Picked up desktop {}", desktop);
+                let mut drag = DesktopDragState::new(desktop, x, y);
+                drag.is_active = true;
+                self.desktop_drag_state = Some(drag);
+                self.carry = Some(Carry::Desktop(0));
+                InputAction::StartDesktopDrag(desktop)
+            }
+        }
+    }
+
+    /// Keys pressed while something picked up with `m` is carried: arrows
+    /// move it, Enter places it and Escape puts it back.
+    fn handle_carry_key(&mut self, carry: Carry, keysym: u32, state: KeyButMask) -> InputAction {
+        if keysym == XK_ESCAPE {
+            return self.cancel_carry(carry);
+        }
+        if let Some(direction) = Direction::from_keysym(keysym) {
+            return match carry {
+                Carry::Window(over) => self.carry_window(over, direction),
+                Carry::Desktop(moved) => self.carry_desktop(moved, direction),
+            };
+        }
+        let letter = keysym_to_char(keysym).map(|c| c.to_ascii_lowercase());
+        match carry {
+            Carry::Window(over) if matches!(keysym, XK_RETURN | XK_KP_ENTER) => self.place_window(over, state),
+            Carry::Desktop(_) if matches!(keysym, XK_RETURN | XK_KP_ENTER) => {
+                self.carry = None;
+                match self.desktop_drag_state.take() {
+                    Some(drag) => self.drop_desktop(&drag, drag.current_x, drag.current_y, state),
+                    None => InputAction::None,
+                }
+            }
+            // `n` drops it on the plus button, `g` groups it with the window under it
+            Carry::Window(_) if letter == Some('n') && self.desktop_bar.is_some() => {
+                self.end_window_carry().map_or(InputAction::None, InputAction::DropOnNewDesktop)
+            }
+            Carry::Window(Spot::Window(target))
+                if letter == Some('g') && self.drag_state.as_ref().is_some_and(|d| d.window_index != target) =>
+            {
+                self.end_window_carry()
+                    .map_or(InputAction::None, |index| InputAction::GroupWindows(index, target))
+            }
+            _ => InputAction::None,
+        }
+    }
+
+    /// Move a carried window on to the next thumbnail or desktop preview.
+    fn carry_window(&mut self, over: Spot, direction: Direction) -> InputAction {
+        let Some(to) = self.spot_towards(over, direction) else {
+            return InputAction::None;
+        };
+        let Some((x, y)) = self.spot_center(to) else {
+            return InputAction::None;
+        };
+        let Some(drag) = self.drag_state.as_mut() else {
+            return InputAction::None;
+        };
+        (drag.current_x, drag.current_y) = (x, y);
+        self.carry = Some(Carry::Window(to));
+        // Lights up the desktop preview under it, as a pointer drag does
+        self.hovered_desktop = match to {
+            Spot::Desktop(desktop) => Some(desktop),
+            Spot::Window(_) => None,
+        };
+        InputAction::DragMove(x, y)
+    }
+
+    /// Move a carried desktop preview one place along the bar.
+    fn carry_desktop(&mut self, moved: i32, direction: Direction) -> InputAction {
+        let (Some(bar), Some(drag)) = (self.desktop_bar.as_ref(), self.desktop_drag_state.as_mut()) else {
+            return InputAction::None;
+        };
+        let step = match (direction, bar.position.is_vertical()) {
+            (Direction::Left, false) | (Direction::Up, true) => -1,
+            (Direction::Right, false) | (Direction::Down, true) => 1,
+            _ => return InputAction::None,
+        };
+        let Some(place) = bar.preview_layouts.iter().position(|p| p.desktop_index == drag.desktop_index) else {
+            return InputAction::None;
+        };
+        let Some((x, y)) = bar.get_preview_center(drag.desktop_index) else {
+            return InputAction::None;
+        };
+        let moved = (moved + step).clamp(-(place as i32), (bar.preview_layouts.len() - 1 - place) as i32);
+        // Onto the middle of the preview that many places on, or just before
+        // it going back: where a pointer would let go to put it there
+        let offset = (moved * i32::from(bar.preview_length() + PREVIEW_PADDING) - i32::from(moved < 0)) as i16;
+        (drag.current_x, drag.current_y) = if bar.position.is_vertical() { (x, y + offset) } else { (x + offset, y) };
+        let (x, y) = (drag.current_x, drag.current_y);
+        self.carry = Some(Carry::Desktop(moved));
+        InputAction::DesktopDragMove(x, y)
+    }
+
+    /// Let a carried window go where it is: onto a desktop (following it
+    /// there with Shift), into another's place in the grid, or back.
+    fn place_window(&mut self, over: Spot, state: KeyButMask) -> InputAction {
+        let Some(index) = self.end_window_carry() else {
+            return InputAction::None;
+        };
+        log::info!("Placing window {} on {:?}", index, over);
+        match over {
+            Spot::Desktop(desktop) if state.contains(KeyButMask::SHIFT) => InputAction::FollowToDesktop(index, desktop),
+            Spot::Desktop(desktop) => InputAction::DropOnDesktop(index, desktop),
+            Spot::Window(target) if target != index => InputAction::ReorderWindow(index, target),
+            Spot::Window(_) => InputAction::CancelDrag,
+        }
+    }
+
+    /// Stop carrying a window, giving the one that was carried.
+    fn end_window_carry(&mut self) -> Option<usize> {
+        self.carry = None;
+        self.hovered_desktop = None;
+        self.drag_state.take().map(|drag| drag.window_index)
+    }
+
+    /// Put back what was being carried.
+    fn cancel_carry(&mut self, carry: Carry) -> InputAction {
+        match carry {
+            Carry::Window(_) => {
+                self.end_window_carry();
+                InputAction::CancelDrag
+            }
+            Carry::Desktop(_) => {
+                self.carry = None;
+                self.desktop_drag_state = None;
+                InputAction::CancelDesktopDrag
+            }
+        }
+    }
+
+    /// The desktop whose delete confirmation is showing, if any.
+    /// The window a button press closes: the one under the close button
+    /// (e.g. middle), or the highlighted one if the select button hit the
+    /// close button on its thumbnail.
+    fn close_target(&self, event: &ButtonPressEvent) -> Option<usize> {
+        let (x, y) = (event.event_x, event.event_y);
+        let index = self.find_thumbnail_at(x, y).filter(|&index| self.allowed(index).close)?;
+        if self.buttons.close == Some(event.detail) {
+            return Some(index);
+        }
+        // Only the highlighted thumbnail shows its close button
+        let on_close_button = self.hovered_index == Some(index)
+            && self
+                .layouts
+                .iter()
+                .find(|l| l.window_index == index)
+                .is_some_and(|l| l.close_button_contains(x, y));
+        (event.detail == self.buttons.select && on_close_button).then_some(index)
+    }
+
+    /// The highlighted window, if the select button hit the minimize button
+    /// on its thumbnail.
+    fn minimize_target(&self, event: &ButtonPressEvent) -> Option<usize> {
+        let index = self
+            .hovered_index
+            .filter(|&index| event.detail == self.buttons.select && self.allowed(index).minimize)?;
+        self.layouts
+            .iter()
+            .find(|l| l.window_index == index)
+            .filter(|l| l.minimize_button_contains(event.event_x, event.event_y))
+            .map(|_| index)
+    }
+
+    /// Close a window, or with Ctrl held mark it to be killed.
+    fn close(&mut self, index: usize, state: KeyButMask) -> InputAction {
+        if !state.contains(KeyButMask::CONTROL) {
+            return InputAction::CloseWindow(index);
+        }
+        self.kill_confirm = Some(index);
+        InputAction::RequestKillWindow(index)
+    }
+
+    fn pending_delete(&self) -> Option<u32> {
+        self.desktop_bar.as_ref()?.delete_confirm.as_ref().map(|c| c.desktop_index)
+    }
+
+    /// The desktop a 1-9 key stands for, if that desktop exists.
+    fn digit_desktop(&self, keysym: u32) -> Option<u32> {
+        let desktop = match keysym {
+            0x31..=0x39 => keysym - 0x31,
+            _ => return None,
+        };
+        let bar = self.desktop_bar.as_ref()?;
+        (desktop < bar.num_desktops).then_some(desktop)
+    }
+
+    /// Keys typed while a desktop name is being edited.
+    fn handle_rename_key(&mut self, keysym: u32) -> InputAction {
+        if matches!(keysym, XK_ESCAPE | XK_RETURN | XK_KP_ENTER) {
+            return match self.rename.take() {
+                Some(rename) if keysym == XK_ESCAPE => InputAction::CancelRename(rename.desktop),
+                Some(rename) => InputAction::RenameDesktop(rename.desktop, rename.text),
+                None => InputAction::None,
+            };
+        }
+
+        let Some(rename) = self.rename.as_mut() else {
+            return InputAction::None;
+        };
+        if keysym == XK_BACKSPACE {
+            rename.text.pop();
+        } else {
+            match keysym_to_char(keysym) {
+                Some(c) if rename.text.chars().count() < MAX_DESKTOP_NAME_CHARS => rename.text.push(c),
+                _ => return InputAction::None,
+            }
+        }
+        InputAction::EditDesktopName(rename.desktop, rename.text.clone())
+    }
+
+    /// Keys typed while searching: text edits the query, the arrows move the
+    /// highlight among the matches and Enter picks the highlighted one.
+    /// Escape, or Backspace with nothing left to delete, ends the search.
+    fn handle_search_key(&mut self, keysym: u32) -> InputAction {
+        if let Some(direction) = Direction::from_keysym(keysym) {
+            return self.move_highlight(direction);
+        }
+        let Some(query) = self.search.as_mut() else {
+            return InputAction::None;
+        };
+        match keysym {
+            XK_RETURN | XK_KP_ENTER => match self.hovered_index {
+                Some(index) => InputAction::SelectWindow(index),
+                None => InputAction::None,
+            },
+            XK_BACKSPACE if query.pop().is_some() => InputAction::Search(query.clone()),
+            XK_ESCAPE | XK_BACKSPACE => {
+                self.search = None;
+                InputAction::EndSearch
+            }
+            _ => match keysym_to_char(keysym) {
+                Some(c) if query.chars().count() < MAX_SEARCH_CHARS => {
+                    query.push(c);
+                    InputAction::Search(query.clone())
+                }
+                _ => InputAction::None,
+            },
+        }
+    }
+
     /// Handle a pointer motion event.
     pub fn handle_motion(&mut self, event: &MotionNotifyEvent) -> InputAction {
+        if self.carry.is_some() || self.spotlight.is_some() || self.quick_look.is_some() {
+            return InputAction::None;
+        }
+        // Pressing the select button alone (when it isn't also the drag
+        // button) never drags
+        let can_drag = self.pressed_button == self.buttons.drag;
+        if let Some(ref grid) = self.desktop_grid {
+            let new_hover = grid.hit_test(event.event_x, event.event_y);
+            let hover_changed = new_hover != self.hovered_desktop;
+            self.hovered_desktop = new_hover;
+            if let Some(drag) = self.grid_drag_state.as_mut().filter(|_| can_drag) {
+                if drag.update(event.event_x, event.event_y) {
+                    return InputAction::DesktopGridDrag(drag.window_id, event.event_x, event.event_y);
+                }
+            }
+            return if hover_changed { InputAction::HoverDesktop(new_hover) } else { InputAction::None };
+        }
+
         // Update desktop drag state if active
-        if let Some(ref mut drag) = self.desktop_drag_state {
+        if let Some(drag) = self.desktop_drag_state.as_mut().filter(|_| can_drag) {
             let became_active = drag.update(event.event_x, event.event_y);
             if became_active {
                 return InputAction::StartDesktopDrag(drag.desktop_index);
@@ -332,8 +1406,9 @@ impl InputHandler {
             }
         }
 
-        // Update window drag state if active
-        if let Some(ref mut drag) = self.drag_state {
+        // Update window drag state if active; without a desktop bar there is
+        // nowhere to drop a window, so a press on a thumbnail stays a click
+        if let Some(drag) = self.drag_state.as_mut().filter(|_| can_drag && self.desktop_bar.is_some()) {
             let became_active = drag.update(event.event_x, event.event_y);
             if became_active {
                 return InputAction::StartDrag(drag.window_index);
@@ -341,7 +1416,7 @@ impl InputHandler {
             if drag.is_active {
                 // Update hover state for desktop bar during drag
                 if let Some(ref bar) = self.desktop_bar {
-                    let new_hover = match bar.hit_test(event.event_x, event.event_y) {
+                    let new_hover = match bar.hit_test_near(event.event_x, event.event_y, self.hit_tolerance) {
                         DesktopBarHit::Desktop(idx) | DesktopBarHit::DeleteButton(idx) => Some(idx),
                         _ => None,
                     };
@@ -356,7 +1431,7 @@ impl InputHandler {
         // Check desktop bar hover
         if let Some(ref bar) = self.desktop_bar {
             if bar.contains_point(event.event_x, event.event_y) {
-                let new_hover = match bar.hit_test(event.event_x, event.event_y) {
+                let new_hover = match bar.hit_test_near(event.event_x, event.event_y, self.hit_tolerance) {
                     DesktopBarHit::Desktop(idx) => Some(idx),
                     _ => None,
                 };
@@ -373,15 +1448,28 @@ impl InputHandler {
 
         // Check thumbnail hover
         let new_hover = self.find_thumbnail_at(event.event_x, event.event_y);
-        if new_hover != self.hovered_index {
-            self.hovered_index = new_hover;
-            return InputAction::Hover(new_hover);
+        if new_hover != self.pointer_index {
+            self.pointer_index = new_hover;
+            if new_hover != self.hovered_index {
+                self.hovered_index = new_hover;
+                return InputAction::Hover(new_hover);
+            }
         }
 
         InputAction::None
     }
 
-    /// Find which thumbnail (if any) contains the given point.
+    /// Whether a point is outside the desktop bar and every thumbnail.
+    fn is_background(&self, x: i16, y: i16) -> bool {
+        let in_bar = self
+            .desktop_bar
+            .as_ref()
+            .is_some_and(|bar| bar.contains_point(x, y));
+        !in_bar && self.find_thumbnail_at(x, y).is_none()
+    }
+
+    /// Find which thumbnail (if any) contains the given point, or else is
+    /// nearest to it within the hit tolerance.
     fn find_thumbnail_at(&self, x: i16, y: i16) -> Option<usize> {
         for layout in &self.layouts {
             let in_x = x >= layout.x && x < layout.x + layout.width as i16;
@@ -400,11 +1488,175 @@ impl InputHandler {
                 return Some(layout.window_index);
             }
         }
-        None
+        // A quick click landing just off one, or in the gap between two,
+        // goes to the nearer
+        self.layouts
+            .iter()
+            .map(|l| (layout::distance_to_rect(x, y, (l.x, l.y, l.width, l.height)), l.window_index))
+            .filter(|&(distance, _)| distance <= i32::from(self.hit_tolerance))
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, index)| index)
     }
 }
 
 // TODO: Future enhancements
-// - Keyboard navigation (arrow keys to move between windows)
 // - Number keys to select specific windows
 // - Search/filter by window title
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thumbnail() -> ThumbnailLayout {
+        ThumbnailLayout {
+            x: 100,
+            y: 100,
+            width: 200,
+            height: 150,
+            window_index: 0,
+        }
+    }
+
+    fn press(button: u8, x: i16, y: i16) -> ButtonPressEvent {
+        ButtonPressEvent {
+            detail: button,
+            event_x: x,
+            event_y: y,
+            root_x: x,
+            root_y: y,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_button_map() {
+        let mut input = InputHandler::new(vec![thumbnail()], None);
+        // By default the left button selects and anything else dismisses
+        assert_eq!(input.handle_button_press(&press(3, 150, 150)), InputAction::Dismiss);
+        assert_eq!(input.handle_button_press(&press(1, 150, 150)), InputAction::None);
+        assert_eq!(input.handle_button_release(&press(1, 150, 150)), InputAction::SelectWindow(0));
+        // except the wheel, which goes through the desktops
+        assert_eq!(input.handle_button_press(&press(5, 150, 150)), InputAction::ScrollDesktop(1));
+        assert_eq!(input.handle_button_press(&press(4, 10, 10)), InputAction::ScrollDesktop(-1));
+
+        input.set_buttons(ButtonMap {
+            select: 3,
+            drag: 1,
+            close: Some(2),
+            dismiss: Some(8),
+        });
+        assert_eq!(input.handle_button_press(&press(2, 150, 150)), InputAction::CloseWindow(0));
+        assert_eq!(input.handle_button_press(&press(9, 150, 150)), InputAction::None);
+        assert_eq!(input.handle_button_press(&press(8, 10, 10)), InputAction::DismissClick(10, 10, 8));
+        // A click with the drag button alone picks nothing
+        assert_eq!(input.handle_button_press(&press(1, 150, 150)), InputAction::None);
+        assert_eq!(input.handle_button_release(&press(1, 150, 150)), InputAction::None);
+        assert_eq!(input.handle_button_press(&press(3, 150, 150)), InputAction::None);
+        assert_eq!(input.handle_button_release(&press(3, 150, 150)), InputAction::SelectWindow(0));
+    }
+
+    #[test]
+    fn test_close_button() {
+        let mut input = InputHandler::new(vec![thumbnail()], None);
+        // Top-right corner of the thumbnail at (100, 100), 200 wide
+        let (x, y) = (285, 110);
+        // Hidden until the thumbnail is highlighted
+        assert_eq!(input.handle_button_press(&press(1, x, y)), InputAction::None);
+        input.handle_button_release(&press(1, x, y));
+        input.set_hovered(Some(0));
+        assert_eq!(input.handle_button_press(&press(1, x, y)), InputAction::CloseWindow(0));
+        assert_eq!(input.handle_button_press(&press(1, 150, 150)), InputAction::None);
+        input.handle_button_release(&press(1, 150, 150));
+
+        // With Ctrl, only a second close kills
+        let ctrl_press = |x, y| ButtonPressEvent { state: KeyButMask::CONTROL, ..press(1, x, y) };
+        assert_eq!(input.handle_button_press(&ctrl_press(x, y)), InputAction::RequestKillWindow(0));
+        assert_eq!(input.handle_button_press(&press(1, x, y)), InputAction::CancelKillWindow(0));
+        assert_eq!(input.handle_button_press(&ctrl_press(x, y)), InputAction::RequestKillWindow(0));
+        assert_eq!(input.handle_button_press(&ctrl_press(x, y)), InputAction::KillWindow(0));
+
+        // Where the window manager forbids closing, the corner just selects
+        input.set_restricted(HashMap::from([(0, AllowedActions { close: false, ..Default::default() })]));
+        assert_eq!(input.handle_button_press(&press(1, x, y)), InputAction::None);
+        assert_eq!(input.handle_button_release(&press(1, x, y)), InputAction::SelectWindow(0));
+    }
+
+    #[test]
+    fn test_spotlight() {
+        let mut input = InputHandler::new(vec![thumbnail()], None);
+        let shift_press = |x, y| ButtonPressEvent { state: KeyButMask::SHIFT, ..press(1, x, y) };
+        input.handle_button_press(&shift_press(150, 150));
+        assert_eq!(input.handle_button_release(&shift_press(150, 150)), InputAction::Spotlight(0));
+
+        // Enlarged, it takes the next click: off it goes back, on it picks
+        let enlarged = ThumbnailLayout { x: 500, y: 300, width: 800, height: 600, window_index: 0 };
+        input.set_spotlight(Some(enlarged.clone()));
+        assert_eq!(input.handle_button_press(&press(1, 150, 150)), InputAction::EndSpotlight(0));
+        input.handle_button_release(&press(1, 150, 150));
+        input.set_spotlight(Some(enlarged));
+        assert_eq!(input.handle_button_press(&press(1, 900, 600)), InputAction::SelectWindow(0));
+    }
+
+    #[test]
+    fn test_keyboard_highlight_stays() {
+        let second = ThumbnailLayout { x: 400, window_index: 1, ..thumbnail() };
+        let mut input = InputHandler::new(vec![thumbnail(), second], None);
+        input.set_hovered(Some(1));
+        let motion = |x, y| MotionNotifyEvent { event_x: x, event_y: y, ..Default::default() };
+        // Moving over the background keeps the highlight; reaching a thumbnail moves it
+        assert_eq!(input.handle_motion(&motion(10, 10)), InputAction::None);
+        assert_eq!(input.hovered(), Some(1));
+        assert_eq!(input.handle_motion(&motion(150, 150)), InputAction::Hover(Some(0)));
+        assert_eq!(input.handle_motion(&motion(10, 10)), InputAction::Hover(None));
+
+        // Near misses count with a tolerance, going to the nearer thumbnail
+        input.set_hit_tolerance(8);
+        assert_eq!(input.handle_motion(&motion(95, 150)), InputAction::Hover(Some(0)));
+        assert_eq!(input.handle_motion(&motion(396, 150)), InputAction::Hover(Some(1)));
+        assert_eq!(input.handle_motion(&motion(350, 150)), InputAction::Hover(None));
+    }
+
+    #[test]
+    fn test_keyboard_moves() {
+        let second = ThumbnailLayout { x: 400, window_index: 1, ..thumbnail() };
+        let below = ThumbnailLayout { y: 300, window_index: 2, ..thumbnail() };
+        let mut input = InputHandler::new(vec![thumbnail(), second, below], None);
+        // Arrows start at the first thumbnail and keep to rows and columns
+        assert_eq!(input.move_highlight(Direction::Left), InputAction::Highlight(Some(0), None));
+        assert_eq!(input.move_highlight(Direction::Right), InputAction::Highlight(Some(1), None));
+        assert_eq!(input.move_highlight(Direction::Right), InputAction::None);
+        assert_eq!(input.move_highlight(Direction::Down), InputAction::Highlight(Some(2), None));
+        assert_eq!(input.move_highlight(Direction::Up), InputAction::Highlight(Some(0), None));
+
+        // Picked up, it moves like a drag and lands in the other's place
+        assert_eq!(input.pick_up(), InputAction::StartDrag(0));
+        assert!(input.is_dragging());
+        let none = KeyButMask::default();
+        assert_eq!(input.handle_carry_key(Carry::Window(Spot::Window(0)), XK_RIGHT, none), InputAction::DragMove(500, 175));
+        let motion = MotionNotifyEvent { event_x: 10, event_y: 10, ..Default::default() };
+        assert_eq!(input.handle_motion(&motion), InputAction::None);
+        assert_eq!(input.handle_carry_key(input.carry.unwrap(), XK_RETURN, none), InputAction::ReorderWindow(0, 1));
+        assert!(input.carry.is_none() && !input.is_dragging());
+
+        // Escape puts it back
+        input.pick_up();
+        assert_eq!(input.handle_carry_key(input.carry.unwrap(), XK_ESCAPE, none), InputAction::CancelDrag);
+        assert!(input.drag_state().is_none());
+    }
+
+    #[test]
+    fn test_search_keys() {
+        let mut input = InputHandler::new(vec![thumbnail()], None);
+        input.search = Some(String::new());
+        // Letters that are keys of their own go into the query
+        assert_eq!(input.handle_search_key(u32::from(b'd')), InputAction::Search("d".to_string()));
+        assert_eq!(input.handle_search_key(u32::from(b'm')), InputAction::Search("dm".to_string()));
+        assert_eq!(input.handle_search_key(XK_BACKSPACE), InputAction::Search("d".to_string()));
+        assert_eq!(input.handle_search_key(XK_RETURN), InputAction::None);
+        assert_eq!(input.handle_search_key(XK_DOWN), InputAction::Highlight(Some(0), None));
+        assert_eq!(input.handle_search_key(XK_RETURN), InputAction::SelectWindow(0));
+        assert_eq!(input.handle_search_key(XK_BACKSPACE), InputAction::Search(String::new()));
+        assert_eq!(input.handle_search_key(XK_BACKSPACE), InputAction::EndSearch);
+        assert!(input.search.is_none());
+    }
+}
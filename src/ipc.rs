@@ -0,0 +1,256 @@
+//! JSON control over a Unix socket.
+//!
+//! `xpose daemon` listens on `$XDG_RUNTIME_DIR/xpose.sock` (see paths.rs).
+//! Clients write one JSON object per line and get one back per line:
+//!
+//! ```text
+//! {"command": "show"}                                 open the overview
+//! {"command": "toggle"}                               open or close it
+//! {"command": "switch", "desktop": 1}                 like `xpose switch 2`
+//! {"command": "move-window", "window": 27262983, "desktop": 0}
+//! {"command": "query-state"}
+//! {"command": "list-windows"}                         like `xpose list-windows --json`
+//! {"command": "reload-config"}                        apply config file edits now
+//! {"command": "subscribe"}
+//! ```
+//!
+//! Desktops count from 0, as in EWMH. Replies are `{"ok": true}`, with a
+//! `state` for `query-state` and `windows` for `list-windows`, or
+//! `{"ok": false, "error": "..."}`.
+//!
+//! After `subscribe`, the connection only carries changes, one per line, as
+//! they happen (see service.rs):
+//!
+//! ```text
+//! {"event": "desktop-changed", "desktop": 2}
+//! {"event": "desktops-changed"}
+//! {"event": "window-moved", "window": 27262983, "desktop": 0}
+//! ```
+//!
+//! With `--systemd` the socket may come from socket activation instead, and
+//! the daemon reports when it is ready (see systemd.rs).
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::desktop::DesktopState;
+use crate::error::{Result, XposeError};
+use crate::list;
+use crate::paths;
+use crate::service::{self, Overview};
+use crate::systemd;
+
+/// A line from a client.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+enum Request {
+    Show,
+    Toggle,
+    Switch { desktop: u32 },
+    MoveWindow { window: u32, desktop: u32 },
+    QueryState,
+    ListWindows,
+    ReloadConfig,
+    Subscribe,
+}
+
+/// Connections that asked for changes.
+type Subscribers = Arc<Mutex<Vec<UnixStream>>>;
+
+/// How long a subscriber may keep an event waiting before it is dropped, so
+/// one that stopped reading can't hold up the others.
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Serve the socket until the process is killed. `show_overview` runs the
+/// overview.
+pub fn run(show_overview: fn() -> Result<()>, systemd: bool) -> Result<()> {
+    let listener = match systemd.then(systemd::activated_listener).flatten() {
+        Some(listener) => {
+            log::info!("Listening on the socket from systemd");
+            listener
+        }
+        None => {
+            let path = paths::socket_path();
+            let listener = bind(&path)?;
+            log::info!("Listening on {}", path.display());
+            listener
+        }
+    };
+    let overview = Arc::new(Overview::start(show_overview));
+    let subscribers = Subscribers::default();
+    let watched = subscribers.clone();
+    std::thread::spawn(move || {
+        let result = service::watch_desktops(|event| {
+            let line = format!("{}\n", serde_json::to_string(&event).unwrap_or_default());
+            // Whoever hung up or stopped reading is dropped
+            watched.lock().unwrap().retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+        });
+        if let Err(e) = result {
+            log::error!("Cannot follow desktop changes: {}", e);
+        }
+    });
+    if systemd {
+        systemd::notify("READY=1");
+    }
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("Cannot accept a connection: {}", e);
+                continue;
+            }
+        };
+        let overview = overview.clone();
+        let subscribers = subscribers.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = serve(stream, &overview, &subscribers) {
+                log::debug!("Client went away: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Listen on `path`, replacing a socket left behind by a daemon that died
+/// but not one still answering there.
+fn bind(path: &Path) -> Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        paths::create_private_dir(dir)?;
+    }
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(XposeError::Other(format!("Another xpose daemon is listening on {}", path.display())));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+fn serve(stream: UnixStream, overview: &Overview, subscribers: &Subscribers) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                writeln!(writer, "{}", json!({"ok": false, "error": format!("Bad request: {}", e)}))?;
+                continue;
+            }
+        };
+        log::info!("Socket request: {:?}", request);
+        if request == Request::Subscribe {
+            writeln!(writer, "{}", json!({"ok": true}))?;
+            writer.set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT))?;
+            subscribers.lock().unwrap().push(writer);
+            return Ok(());
+        }
+        let reply = match handle(request, overview) {
+            Ok(mut reply) => {
+                reply.insert("ok".to_string(), Value::Bool(true));
+                Value::Object(reply)
+            }
+            Err(e) => json!({"ok": false, "error": e}),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// Carry out one request. Returns what goes in the reply besides `ok`.
+fn handle(request: Request, overview: &Overview) -> std::result::Result<Map<String, Value>, String> {
+    let done = match request {
+        Request::Show => {
+            overview.show();
+            Ok(())
+        }
+        Request::Toggle => overview.toggle(),
+        Request::Switch { desktop } => overview.switch_desktop(desktop),
+        Request::MoveWindow { window, desktop } => overview.move_window(window, desktop),
+        Request::ReloadConfig => overview.reload_config(),
+        Request::QueryState => {
+            let state = DesktopState::load().map_err(|e| e.to_string())?;
+            return Ok(Map::from_iter([("state".to_string(), state_json(&state, overview.is_showing()))]));
+        }
+        Request::ListWindows => {
+            let windows = list::windows().map_err(|e| e.to_string())?;
+            let windows = serde_json::to_value(windows).map_err(|e| e.to_string())?;
+            return Ok(Map::from_iter([("windows".to_string(), windows)]));
+        }
+        // The connection becomes an event stream instead, see serve
+        Request::Subscribe => Ok(()),
+    };
+    done.map(|()| Map::new()).map_err(|e| e.to_string())
+}
+
+/// The desktops and their windows (frame ids), for `query-state`.
+fn state_json(state: &DesktopState, showing: bool) -> Value {
+    let desktops: Vec<Value> = (0..state.desktops)
+        .map(|desktop| {
+            let mut windows: Vec<u32> = state
+                .windows
+                .iter()
+                .filter(|&(_, &d)| d == desktop)
+                .filter_map(|(key, _)| key.parse().ok())
+                .collect();
+            windows.sort_unstable();
+            json!({
+                "desktop": desktop,
+                "name": state.display_name(desktop),
+                "windows": windows,
+            })
+        })
+        .collect();
+    json!({
+        "current": state.current,
+        "overview": showing,
+        "desktops": desktops,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests() {
+        let parse = |line: &str| serde_json::from_str::<Request>(line).ok();
+        assert_eq!(parse(r#"{"command": "toggle"}"#), Some(Request::Toggle));
+        assert_eq!(
+            parse(r#"{"command": "move-window", "window": 42, "desktop": 1}"#),
+            Some(Request::MoveWindow { window: 42, desktop: 1 })
+        );
+        assert_eq!(parse(r#"{"command": "switch"}"#), None);
+        assert_eq!(parse(r#"{"command": "switch", "desktop": 1, "window": 42}"#), None);
+        assert_eq!(parse(r#"{"command": "list-windows"}"#), Some(Request::ListWindows));
+        assert_eq!(parse(r#"{"command": "subscribe"}"#), Some(Request::Subscribe));
+        assert_eq!(parse(r#"{"command": "reload-config"}"#), Some(Request::ReloadConfig));
+        assert_eq!(parse(r#"{"command": "reboot"}"#), None);
+    }
+
+    #[test]
+    fn test_state_json() {
+        let state: DesktopState = serde_json::from_value(json!({
+            "current": 0,
+            "desktops": 2,
+            "windows": {"7": 1, "5": 1},
+            "names": {"0": "Mail"},
+        }))
+        .unwrap();
+        let json = state_json(&state, false);
+        assert_eq!(json["desktops"][0], json!({"desktop": 0, "name": "Mail", "windows": []}));
+        assert_eq!(json["desktops"][1]["windows"], json!([5, 7]));
+        assert_eq!(json["overview"], false);
+    }
+}
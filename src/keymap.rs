@@ -0,0 +1,124 @@
+//! Keycode to keysym translation for keyboard input in the overview.
+//!
+//! The server's keyboard mapping is read once at startup. Only the two
+//! unmodified/shifted columns are used, which covers the keys xpose reacts
+//! to and plain text typed into the desktop bar.
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+pub const XK_BACKSPACE: Keysym = 0xff08;
+pub const XK_TAB: Keysym = 0xff09;
+pub const XK_RETURN: Keysym = 0xff0d;
+pub const XK_ESCAPE: Keysym = 0xff1b;
+pub const XK_LEFT: Keysym = 0xff51;
+pub const XK_UP: Keysym = 0xff52;
+pub const XK_RIGHT: Keysym = 0xff53;
+pub const XK_DOWN: Keysym = 0xff54;
+pub const XK_PAGE_UP: Keysym = 0xff55;
+pub const XK_PAGE_DOWN: Keysym = 0xff56;
+pub const XK_KP_ENTER: Keysym = 0xff8d;
+pub const XK_F2: Keysym = 0xffbf;
+pub const XK_F3: Keysym = 0xffc0;
+pub const XK_DELETE: Keysym = 0xffff;
+
+/// The server's keycode to keysym table.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<Keysym>,
+}
+
+impl Keymap {
+    /// Keysym for a key press, taking Shift and Caps Lock into account.
+    /// Returns 0 (NoSymbol) for unmapped keycodes.
+    pub fn keysym(&self, keycode: u8, state: KeyButMask) -> Keysym {
+        let per = self.keysyms_per_keycode as usize;
+        let Some(row) = keycode
+            .checked_sub(self.min_keycode)
+            .map(|i| i as usize * per)
+            .and_then(|start| self.keysyms.get(start..start + per))
+        else {
+            return 0;
+        };
+
+        let plain = row.first().copied().unwrap_or(0);
+        let shifted = row.get(1).copied().unwrap_or(0);
+        let is_letter = char::from_u32(plain).is_some_and(|c| c.is_ascii_lowercase());
+        let shift = state.contains(KeyButMask::SHIFT) ^ (is_letter && state.contains(KeyButMask::LOCK));
+
+        match (shift, shifted) {
+            (false, _) => plain,
+            (true, 0) if is_letter => plain - 0x20,
+            (true, 0) => plain,
+            (true, sym) => sym,
+        }
+    }
+
+    /// The first keycode typing `keysym` unshifted, if any key does.
+    pub fn keycode(&self, keysym: Keysym) -> Option<u8> {
+        let per = self.keysyms_per_keycode.max(1) as usize;
+        self.keysyms
+            .chunks(per)
+            .position(|row| row.first() == Some(&keysym))
+            .and_then(|row| self.min_keycode.checked_add(u8::try_from(row).ok()?))
+    }
+}
+
+/// The character a keysym types, if it is a printable one. Latin-1 keysyms
+/// are their own code points; Unicode keysyms carry theirs with 0x01000000.
+pub fn keysym_to_char(keysym: Keysym) -> Option<char> {
+    let code = match keysym {
+        0x20..=0x7e | 0xa0..=0xff => keysym,
+        0x0100_0100..=0x0110_ffff => keysym - 0x0100_0000,
+        _ => return None,
+    };
+    char::from_u32(code).filter(|c| !c.is_control())
+}
+
+impl XConnection {
+    /// Read the keyboard mapping for every keycode.
+    pub fn load_keymap(&self) -> Result<Keymap> {
+        let setup = self.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let reply = self.conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        Ok(Keymap {
+            min_keycode: min,
+            keysyms_per_keycode: reply.keysyms_per_keycode,
+            keysyms: reply.keysyms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keysym_lookup() {
+        // keycode 8: a/A, keycode 9: 1/exclam, keycode 10: Escape
+        let keymap = Keymap {
+            min_keycode: 8,
+            keysyms_per_keycode: 2,
+            keysyms: vec![0x61, 0x41, 0x31, 0x21, XK_ESCAPE, 0],
+        };
+        assert_eq!(keymap.keysym(8, KeyButMask::default()), 0x61);
+        assert_eq!(keymap.keysym(8, KeyButMask::SHIFT), 0x41);
+        assert_eq!(keymap.keysym(8, KeyButMask::LOCK), 0x41);
+        assert_eq!(keymap.keysym(9, KeyButMask::LOCK), 0x31);
+        assert_eq!(keymap.keysym(10, KeyButMask::SHIFT), XK_ESCAPE);
+        assert_eq!(keymap.keysym(7, KeyButMask::default()), 0);
+        assert_eq!(keymap.keysym(11, KeyButMask::default()), 0);
+        assert_eq!(keymap.keycode(XK_ESCAPE), Some(10));
+        assert_eq!(keymap.keycode(0x41), None);
+
+        assert_eq!(keysym_to_char(0x41), Some('A'));
+        assert_eq!(keysym_to_char(0xe9), Some('é'));
+        assert_eq!(keysym_to_char(0x0100_20ac), Some('€'));
+        assert_eq!(keysym_to_char(XK_RETURN), None);
+    }
+}
@@ -1,3 +1,8 @@
+use std::collections::HashMap;
+
+use x11rb::protocol::xproto::Window;
+
+use crate::monitor::Monitor;
 use crate::window_finder::WindowInfo;
 
 #[derive(Debug, Clone)]
@@ -9,10 +14,238 @@ pub struct ThumbnailLayout {
     pub window_index: usize,
 }
 
+/// How far each window under a pile's top shows from behind it.
+const PILE_STEP: u16 = 6;
+
+/// Height kept along the bottom of the grid for minimized windows.
+pub const MINIMIZED_STRIP_HEIGHT: u16 = 96;
+/// Height of a minimized window's thumbnail, when the strip has room.
+const MINIMIZED_THUMB_HEIGHT: u16 = 72;
+const MINIMIZED_SPACING: u16 = 16;
+
+/// Height kept above each desktop's band of thumbnails for its name, when
+/// the grid shows every desktop.
+pub const DESKTOP_LABEL_HEIGHT: u16 = 32;
+
+/// Side of the close button on a highlighted thumbnail.
+const CLOSE_BUTTON_SIZE: u16 = 20;
+/// Gap between the close button and the thumbnail's corner.
+const CLOSE_BUTTON_INSET: u16 = 4;
+/// Size of the application icon on a thumbnail.
+const ICON_BADGE_SIZE: u16 = 32;
+
+impl ThumbnailLayout {
+    /// The close button in the top-right corner (x, y, size); None on a
+    /// thumbnail too small to spare the room.
+    pub fn close_button(&self) -> Option<(i16, i16, u16)> {
+        let room = CLOSE_BUTTON_SIZE + 2 * CLOSE_BUTTON_INSET;
+        if self.width < 2 * room || self.height < 2 * room {
+            return None;
+        }
+        let x = self.x + (self.width - CLOSE_BUTTON_SIZE - CLOSE_BUTTON_INSET) as i16;
+        let y = self.y + CLOSE_BUTTON_INSET as i16;
+        Some((x, y, CLOSE_BUTTON_SIZE))
+    }
+
+    /// The pin marking a sticky window, in the top-left corner; None on a
+    /// thumbnail too small to spare the room.
+    pub fn pin_badge(&self) -> Option<(i16, i16, u16)> {
+        let (_, y, size) = self.close_button()?;
+        Some((self.x + CLOSE_BUTTON_INSET as i16, y, size))
+    }
+
+    /// The minimize button, left of the close button; None if the thumbnail
+    /// can't spare the room for both.
+    pub fn minimize_button(&self) -> Option<(i16, i16, u16)> {
+        let (x, y, size) = self.close_button()?;
+        if self.width < 3 * (CLOSE_BUTTON_SIZE + 2 * CLOSE_BUTTON_INSET) {
+            return None;
+        }
+        Some((x - (size + CLOSE_BUTTON_INSET) as i16, y, size))
+    }
+
+    /// The application icon, in the bottom-left corner; None on a thumbnail
+    /// too small to spare the room.
+    pub fn icon_badge(&self) -> Option<(i16, i16, u16)> {
+        let room = ICON_BADGE_SIZE + 2 * CLOSE_BUTTON_INSET;
+        if self.width < 3 * room || self.height < 2 * room {
+            return None;
+        }
+        let y = self.y + (self.height - ICON_BADGE_SIZE - CLOSE_BUTTON_INSET) as i16;
+        Some((self.x + CLOSE_BUTTON_INSET as i16, y, ICON_BADGE_SIZE))
+    }
+
+    /// Whether (x, y) is on the minimize button.
+    pub fn minimize_button_contains(&self, x: i16, y: i16) -> bool {
+        self.minimize_button().is_some_and(|(bx, by, size)| {
+            x >= bx && x < bx + size as i16 && y >= by && y < by + size as i16
+        })
+    }
+
+    /// Whether (x, y) is on the thumbnail.
+    pub fn contains(&self, x: i16, y: i16) -> bool {
+        x >= self.x && x < self.x + self.width as i16 && y >= self.y && y < self.y + self.height as i16
+    }
+
+    /// Whether (x, y) is on the middle half of the thumbnail, across:
+    /// dropping a window there groups it, at either side moves it.
+    pub fn middle_contains(&self, x: i16, y: i16) -> bool {
+        let quarter = (self.width / 4) as i16;
+        x >= self.x + quarter
+            && x < self.x + self.width as i16 - quarter
+            && y >= self.y
+            && y < self.y + self.height as i16
+    }
+
+    /// Where the top window of a pile goes in this slot, leaving room up and
+    /// to the right for the two behind it to show.
+    pub fn pile_top(&self) -> ThumbnailLayout {
+        let inset = 2 * PILE_STEP;
+        ThumbnailLayout {
+            y: self.y + inset as i16,
+            width: self.width.saturating_sub(inset),
+            height: self.height.saturating_sub(inset),
+            ..self.clone()
+        }
+    }
+
+    /// The edges of the windows behind a pile's top, back to front.
+    pub fn pile_behind(&self) -> [ThumbnailLayout; 2] {
+        let top = self.pile_top();
+        let step = |n: u16| ThumbnailLayout {
+            x: top.x + (n * PILE_STEP) as i16,
+            y: top.y - (n * PILE_STEP) as i16,
+            ..top.clone()
+        };
+        [step(2), step(1)]
+    }
+
+    /// Whether (x, y) is on the close button.
+    pub fn close_button_contains(&self, x: i16, y: i16) -> bool {
+        self.close_button().is_some_and(|(bx, by, size)| {
+            x >= bx && x < bx + size as i16 && y >= by && y < by + size as i16
+        })
+    }
+
+    /// Whether the thumbnail lies wholly under `rect` (x, y, width, height).
+    pub fn covered_by(&self, rect: (i16, i16, u16, u16)) -> bool {
+        let (x, y, width, height) = rect;
+        self.x >= x
+            && self.y >= y
+            && i32::from(self.x) + i32::from(self.width) <= i32::from(x) + i32::from(width)
+            && i32::from(self.y) + i32::from(self.height) <= i32::from(y) + i32::from(height)
+    }
+}
+
+/// Room kept free of thumbnails along each screen edge (for the desktop bar).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Insets {
+    pub top: u16,
+    pub bottom: u16,
+    pub left: u16,
+    pub right: u16,
+}
+
+impl Insets {
+    /// The part of these root window insets that falls on `monitor`.
+    fn on_monitor(&self, monitor: &Monitor, screen_width: u16, screen_height: u16) -> Insets {
+        let clamp = |v: i32, max: u16| v.clamp(0, max as i32) as u16;
+        let (x, y) = (monitor.x as i32, monitor.y as i32);
+        let (width, height) = (monitor.width as i32, monitor.height as i32);
+        Insets {
+            top: clamp(self.top as i32 - y, monitor.height),
+            bottom: clamp(y + height - (screen_height as i32 - self.bottom as i32), monitor.height),
+            left: clamp(self.left as i32 - x, monitor.width),
+            right: clamp(x + width - (screen_width as i32 - self.right as i32), monitor.width),
+        }
+    }
+}
+
+/// How the windows on a monitor are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MonitorLayout {
+    /// A grid of their own on the monitor.
+    #[default]
+    Grid,
+    /// In the primary monitor's grid, leaving this monitor empty.
+    Primary,
+}
+
+impl MonitorLayout {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "grid" => Some(Self::Grid),
+            "primary" => Some(Self::Primary),
+            _ => None,
+        }
+    }
+}
+
+/// The order windows fill a grid's cells in, unless the user arranged it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GridOrder {
+    /// Near where each window is on screen.
+    #[default]
+    Position,
+    /// Most recently focused first, row by row.
+    Recent,
+}
+
+impl GridOrder {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "position" => Some(Self::Position),
+            "recent" => Some(Self::Recent),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for one monitor (by RandR output name), overriding the global ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorConfig {
+    /// Whether the desktop bar extends over this monitor
+    pub show_desktop_bar: bool,
+    pub layout: MonitorLayout,
+    /// Space kept free around the grid, instead of `LayoutConfig::margin`
+    pub margin: Option<u16>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            show_desktop_bar: true,
+            layout: MonitorLayout::Grid,
+            margin: None,
+        }
+    }
+}
+
 pub struct LayoutConfig {
     pub padding: u16,
     pub margin: u16,
     pub max_scale: f64,
+    /// Per-monitor overrides, by monitor name
+    pub monitors: HashMap<String, MonitorConfig>,
+    /// Monitor name -> frames in the order the user arranged its grid in
+    /// (see state.rs); their windows fill its cells in that order rather
+    /// than by where they are on screen
+    pub arranged: HashMap<String, Vec<Window>>,
+    /// While searching, the frames of the matching windows, best first (see
+    /// search.rs): the others are left out, and these fill every grid's
+    /// cells in this order
+    pub ranked: Option<Vec<Window>>,
+    /// With `GridOrder::Recent`, every frame, most recently focused first
+    /// (see state.rs): grids the user didn't arrange fill their cells in
+    /// this order
+    pub recent: Option<Vec<Window>>,
+}
+
+impl LayoutConfig {
+    /// The settings for `monitor`: its overrides, or the defaults.
+    pub fn monitor(&self, monitor: &Monitor) -> MonitorConfig {
+        self.monitors.get(&monitor.name).cloned().unwrap_or_default()
+    }
 }
 
 impl Default for LayoutConfig {
@@ -21,28 +254,36 @@ impl Default for LayoutConfig {
             padding: 20,
             margin: 50,
             max_scale: 0.9,
+            monitors: HashMap::new(),
+            arranged: HashMap::new(),
+            ranked: None,
+            recent: None,
         }
     }
 }
 
 /// Calculate thumbnail layouts for all windows in a grid.
 /// Windows are assigned to grid positions based on their screen location
-/// to preserve spatial relationships (Apple-style layout).
+/// to preserve spatial relationships (Apple-style layout), or with
+/// `in_order` to the cells in turn, row by row.
 pub fn calculate_layout(
     windows: &[WindowInfo],
     screen_width: u16,
     screen_height: u16,
     config: &LayoutConfig,
-    top_reserved: u16,
+    reserved: Insets,
+    in_order: bool,
 ) -> Vec<ThumbnailLayout> {
     if windows.is_empty() {
         return Vec::new();
     }
 
-    let available_width = screen_width.saturating_sub(2 * config.margin);
+    let available_width = screen_width
+        .saturating_sub(2 * config.margin)
+        .saturating_sub(reserved.left + reserved.right);
     let available_height = screen_height
         .saturating_sub(2 * config.margin)
-        .saturating_sub(top_reserved);
+        .saturating_sub(reserved.top + reserved.bottom);
 
     // Calculate optimal grid dimensions
     let count = windows.len();
@@ -58,16 +299,19 @@ pub fn calculate_layout(
     // Grid dimensions for cell center calculations
     let grid_width = (cols as u16 * cell_width) + ((cols as u16).saturating_sub(1) * config.padding);
     let grid_height = (rows as u16 * cell_height) + ((rows as u16).saturating_sub(1) * config.padding);
-    let grid_offset_x = (screen_width.saturating_sub(grid_width)) / 2;
-    // Center grid in available space below the bar
-    let available_for_grid = screen_height.saturating_sub(top_reserved);
-    let grid_offset_y = top_reserved + (available_for_grid.saturating_sub(grid_height)) / 2;
+    // Center grid in the space the bar leaves
+    let width_for_grid = screen_width.saturating_sub(reserved.left + reserved.right);
+    let grid_offset_x = reserved.left + (width_for_grid.saturating_sub(grid_width)) / 2;
+    let height_for_grid = screen_height.saturating_sub(reserved.top + reserved.bottom);
+    let grid_offset_y = reserved.top + (height_for_grid.saturating_sub(grid_height)) / 2;
 
     // Screen center for distance calculations (ripple effect)
     let screen_center_x = screen_width as f64 / 2.0;
     let screen_center_y = screen_height as f64 / 2.0;
 
-    let cell_assignments = if rows == 1 {
+    let cell_assignments = if in_order {
+        (0..count).collect()
+    } else if rows == 1 {
         // Single row: sort by X position, use distance-from-center as tiebreaker
         let mut indexed: Vec<(usize, f64, f64)> = windows
             .iter()
@@ -292,6 +536,263 @@ pub fn calculate_layout(
     layouts
 }
 
+/// Calculate thumbnail layouts with a separate grid per monitor.
+/// Each window goes to the monitor holding its center (the primary monitor
+/// if none does, or if that monitor's layout is `MonitorLayout::Primary`),
+/// so thumbnails never straddle the gap between two screens.
+/// `reserved` is the desktop bar along an edge of the root window
+/// (`screen_width` x `screen_height`); each monitor showing the bar only
+/// loses the part of it that overlaps the monitor.
+/// Layouts are returned in `windows` order, like `calculate_layout`.
+pub fn calculate_monitor_layout(
+    windows: &[WindowInfo],
+    monitors: &[Monitor],
+    config: &LayoutConfig,
+    reserved: Insets,
+    screen_width: u16,
+    screen_height: u16,
+) -> Vec<ThumbnailLayout> {
+    let settings: Vec<MonitorConfig> = monitors.iter().map(|m| config.monitor(m)).collect();
+    let has_grid = |i: &usize| settings[*i].layout == MonitorLayout::Grid;
+    // If the primary monitor hands its windows on, the first monitor with a
+    // grid takes them; if none has one, the overrides are ignored
+    let primary = monitors.iter().position(|m| m.primary).unwrap_or(0);
+    let primary = Some(primary).filter(has_grid).or_else(|| (0..monitors.len()).find(has_grid));
+    let mut per_monitor: Vec<Vec<usize>> = vec![Vec::new(); monitors.len()];
+    for (i, window) in windows.iter().enumerate() {
+        let cx = window.x as i32 + window.width as i32 / 2;
+        let cy = window.y as i32 + window.height as i32 / 2;
+        let monitor = monitors.iter().position(|m| m.contains(cx, cy));
+        let monitor = match (monitor, primary) {
+            (Some(monitor), _) if has_grid(&monitor) => monitor,
+            (_, Some(primary)) => primary,
+            (monitor, None) => monitor.unwrap_or(0),
+        };
+        per_monitor[monitor].push(i);
+    }
+
+    let mut layouts: Vec<Option<ThumbnailLayout>> = vec![None; windows.len()];
+    for ((monitor, settings), mut indices) in monitors.iter().zip(&settings).zip(per_monitor) {
+        if indices.is_empty() {
+            continue;
+        }
+        // A grid the user arranged (or a search ranked) fills its cells in
+        // that order, as does any other grid in focus order
+        let arranged = config.ranked.as_ref().or(config.arranged.get(&monitor.name)).or(config.recent.as_ref());
+        if let Some(order) = arranged {
+            indices.sort_by_key(|&i| order.iter().position(|&frame| frame == windows[i].frame_window).unwrap_or(usize::MAX));
+        }
+        let margin_config;
+        let config = match settings.margin {
+            Some(margin) => {
+                margin_config =
+                    LayoutConfig {
+                        margin,
+                        monitors: HashMap::new(),
+                        arranged: HashMap::new(),
+                        ranked: None,
+                        recent: None,
+                        ..*config
+                    };
+                &margin_config
+            }
+            None => config,
+        };
+
+        // Lay out in monitor coordinates, then move onto the monitor
+        let local: Vec<WindowInfo> = indices
+            .iter()
+            .map(|&i| WindowInfo {
+                x: windows[i].x.saturating_sub(monitor.x),
+                y: windows[i].y.saturating_sub(monitor.y),
+                ..windows[i].clone()
+            })
+            .collect();
+        let reserved = if settings.show_desktop_bar {
+            reserved.on_monitor(monitor, screen_width, screen_height)
+        } else {
+            Insets::default()
+        };
+
+        for layout in calculate_layout(&local, monitor.width, monitor.height, config, reserved, arranged.is_some()) {
+            let index = indices[layout.window_index];
+            layouts[index] = Some(ThumbnailLayout {
+                x: layout.x + monitor.x,
+                y: layout.y + monitor.y,
+                window_index: index,
+                ..layout
+            });
+        }
+    }
+
+    layouts.into_iter().flatten().collect()
+}
+
+/// Lay windows out in a band per desktop, one above the other down
+/// `monitor`, each under room for the desktop's name. `desktops[i]` is the
+/// desktop `windows[i]` is grouped under; bands go in desktop order and
+/// share the height evenly, each filled by `config.ranked` while searching.
+/// Layouts are returned in `windows` order.
+pub fn desktop_bands_layout(
+    windows: &[WindowInfo],
+    desktops: &[u32],
+    monitor: &Monitor,
+    config: &LayoutConfig,
+    reserved: Insets,
+    screen_width: u16,
+    screen_height: u16,
+) -> Vec<ThumbnailLayout> {
+    let mut bands = desktops.to_vec();
+    bands.sort_unstable();
+    bands.dedup();
+    if bands.is_empty() {
+        return Vec::new();
+    }
+    let reserved = reserved.on_monitor(monitor, screen_width, screen_height);
+    let top = reserved.top + config.margin;
+    let band_height = monitor.height.saturating_sub(top + reserved.bottom + config.margin) / bands.len() as u16;
+    // The margin goes around all the bands, not around each
+    let band_config =
+        LayoutConfig {
+            margin: 0,
+            monitors: HashMap::new(),
+            arranged: HashMap::new(),
+            ranked: None,
+            recent: None,
+            ..*config
+        };
+
+    let mut layouts: Vec<Option<ThumbnailLayout>> = vec![None; windows.len()];
+    for (band, &desktop) in bands.iter().enumerate() {
+        let mut indices: Vec<usize> = (0..windows.len()).filter(|&i| desktops[i] == desktop).collect();
+        if let Some(ranked) = &config.ranked {
+            indices.sort_by_key(|&i| ranked.iter().position(|&frame| frame == windows[i].frame_window).unwrap_or(usize::MAX));
+        }
+        let local: Vec<WindowInfo> = indices
+            .iter()
+            .map(|&i| WindowInfo {
+                x: windows[i].x.saturating_sub(monitor.x),
+                y: windows[i].y.saturating_sub(monitor.y),
+                ..windows[i].clone()
+            })
+            .collect();
+        let band_top = top + band as u16 * band_height;
+        let band_reserved = Insets {
+            top: band_top + DESKTOP_LABEL_HEIGHT,
+            bottom: monitor.height.saturating_sub(band_top + band_height),
+            left: reserved.left + config.margin,
+            right: reserved.right + config.margin,
+        };
+        let in_order = config.ranked.is_some();
+        for layout in calculate_layout(&local, monitor.width, monitor.height, &band_config, band_reserved, in_order) {
+            let index = indices[layout.window_index];
+            layouts[index] = Some(ThumbnailLayout {
+                x: layout.x + monitor.x,
+                y: layout.y + monitor.y,
+                window_index: index,
+                ..layout
+            });
+        }
+    }
+
+    layouts.into_iter().flatten().collect()
+}
+
+/// How far (x, y) is outside `rect` (x, y, width, height), in pixels along
+/// whichever axis it is furthest out; 0 inside. Within `n` of it is within
+/// the rectangle grown by `n` on every side.
+pub fn distance_to_rect(x: i16, y: i16, rect: (i16, i16, u16, u16)) -> i32 {
+    let (rx, ry, width, height) = rect;
+    let outside = |p: i16, start: i16, len: u16| {
+        let (p, start) = (i32::from(p), i32::from(start));
+        (start - p).max(p - (start + i32::from(len) - 1)).max(0)
+    };
+    outside(x, rx, width).max(outside(y, ry, height))
+}
+
+/// The window indices of `layouts` as the grid reads, row by row from the
+/// top, each row left to right.
+pub fn reading_order(layouts: &[ThumbnailLayout]) -> Vec<usize> {
+    let center_y = |l: &ThumbnailLayout| l.y as i32 + l.height as i32 / 2;
+    let mut by_y: Vec<&ThumbnailLayout> = layouts.iter().collect();
+    by_y.sort_by_key(|l| center_y(l));
+
+    // Thumbnails are centered in their cells, so a row's centers line up
+    let mut rows: Vec<Vec<&ThumbnailLayout>> = Vec::new();
+    for layout in by_y {
+        match rows.last_mut() {
+            Some(row) if center_y(layout) < center_y(row[0]) + row[0].height as i32 / 2 => row.push(layout),
+            _ => rows.push(vec![layout]),
+        }
+    }
+    rows.into_iter()
+        .flat_map(|mut row| {
+            row.sort_by_key(|l| l.x);
+            row.into_iter().map(|l| l.window_index)
+        })
+        .collect()
+}
+
+/// Lay out minimized windows in a row centered along the bottom of
+/// `monitor`, in the `MINIMIZED_STRIP_HEIGHT` above the `reserved` insets,
+/// shrinking them if the row doesn't fit.
+pub fn minimized_strip_layout(
+    windows: &[WindowInfo],
+    monitor: &Monitor,
+    reserved: Insets,
+    screen_width: u16,
+    screen_height: u16,
+) -> Vec<ThumbnailLayout> {
+    if windows.is_empty() {
+        return Vec::new();
+    }
+    let reserved = reserved.on_monitor(monitor, screen_width, screen_height);
+    let available = monitor.width.saturating_sub(reserved.left + reserved.right) as f64;
+    let spacing = MINIMIZED_SPACING as f64 * (windows.len() - 1) as f64;
+    let sizes: Vec<(u16, u16)> = windows
+        .iter()
+        .map(|w| scale_to_fit(w.width, w.height, MINIMIZED_THUMB_HEIGHT * 2, MINIMIZED_THUMB_HEIGHT, 1.0))
+        .collect();
+    let total: f64 = sizes.iter().map(|&(w, _)| w as f64).sum();
+    let scale = ((available - spacing) / total).clamp(0.0, 1.0);
+
+    let bottom = monitor.y as i32 + monitor.height as i32 - reserved.bottom as i32;
+    let strip_top = bottom - MINIMIZED_STRIP_HEIGHT as i32;
+    let row_width = total * scale + spacing;
+    let mut x = monitor.x as f64 + reserved.left as f64 + (available - row_width).max(0.0) / 2.0;
+    sizes
+        .iter()
+        .enumerate()
+        .map(|(window_index, &(width, height))| {
+            let (width, height) = (((width as f64 * scale) as u16).max(1), ((height as f64 * scale) as u16).max(1));
+            let layout = ThumbnailLayout {
+                x: x as i16,
+                y: (strip_top + (MINIMIZED_STRIP_HEIGHT - height) as i32 / 2) as i16,
+                width,
+                height,
+                window_index,
+            };
+            x += width as f64 + MINIMIZED_SPACING as f64;
+            layout
+        })
+        .collect()
+}
+
+/// A window's size scaled up or down to fill `fraction` of `monitor` across
+/// or down, whichever it reaches first, centred on the monitor.
+pub fn centered_layout(window: &WindowInfo, window_index: usize, monitor: &Monitor, fraction: f64) -> ThumbnailLayout {
+    let max_width = (monitor.width as f64 * fraction) as u16;
+    let max_height = (monitor.height as f64 * fraction) as u16;
+    let (width, height) = scale_to_fit(window.width, window.height, max_width, max_height, f64::INFINITY);
+    ThumbnailLayout {
+        x: monitor.x + ((monitor.width - width) / 2) as i16,
+        y: monitor.y + ((monitor.height - height) / 2) as i16,
+        width,
+        height,
+        window_index,
+    }
+}
+
 /// Calculate optimal grid dimensions for N windows.
 fn optimal_grid(count: usize, width: u16, height: u16) -> (usize, usize) {
     if count == 0 {
@@ -303,7 +804,7 @@ fn optimal_grid(count: usize, width: u16, height: u16) -> (usize, usize) {
     // Calculate cols to roughly match screen aspect ratio
     let cols_f = (count as f64 * aspect_ratio).sqrt();
     let cols = (cols_f.ceil() as usize).max(1);
-    let rows = ((count + cols - 1) / cols).max(1); // Ceiling division
+    let rows = count.div_ceil(cols).max(1);
 
     (cols, rows)
 }
@@ -341,6 +842,157 @@ fn scale_to_fit(
 mod tests {
     use super::*;
 
+    fn window_at(x: i16, y: i16) -> WindowInfo {
+        WindowInfo { x, y, width: 800, height: 600, ..WindowInfo::for_test(0, None, None) }
+    }
+
+    fn monitor_at(x: i16, primary: bool) -> Monitor {
+        Monitor {
+            name: format!("out-{}", x),
+            x,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            primary,
+        }
+    }
+
+    #[test]
+    fn test_monitor_layout_keeps_windows_on_their_monitor() {
+        let monitors = [monitor_at(0, true), monitor_at(1920, false)];
+        // Last window is off every monitor and goes to the primary one
+        let windows = [window_at(100, 100), window_at(2500, 300), window_at(-5000, 0)];
+        let layouts = calculate_monitor_layout(&windows, &monitors, &LayoutConfig::default(), Insets { top: 100, ..Default::default() }, 3840, 1080);
+
+        assert_eq!(layouts.len(), 3);
+        for (i, layout) in layouts.iter().enumerate() {
+            assert_eq!(layout.window_index, i);
+            assert!(layout.y >= 100);
+        }
+        let on = |layout: &ThumbnailLayout, m: &Monitor| {
+            m.contains(layout.x as i32, layout.y as i32)
+                && m.contains((layout.x as i32) + layout.width as i32 - 1, layout.y as i32)
+        };
+        assert!(on(&layouts[0], &monitors[0]));
+        assert!(on(&layouts[1], &monitors[1]));
+        assert!(on(&layouts[2], &monitors[0]));
+    }
+
+    #[test]
+    fn test_insets_on_monitor() {
+        let monitors = [monitor_at(0, true), monitor_at(1920, false)];
+        // A bar on the right edge of the root window only touches the second monitor
+        let right = Insets { right: 200, ..Default::default() };
+        assert_eq!(right.on_monitor(&monitors[0], 3840, 1080), Insets::default());
+        assert_eq!(right.on_monitor(&monitors[1], 3840, 1080), right);
+
+        let bottom = Insets { bottom: 240, ..Default::default() };
+        for layout in calculate_monitor_layout(&[window_at(100, 900)], &monitors, &LayoutConfig::default(), bottom, 3840, 1080) {
+            assert!(layout.y + layout.height as i16 <= 1080 - 240);
+        }
+    }
+
+    #[test]
+    fn test_monitor_overrides() {
+        let monitors = [monitor_at(0, true), monitor_at(1920, false)];
+        let mut config = LayoutConfig::default();
+        config.monitors.insert(
+            "out-0".to_string(),
+            MonitorConfig { layout: MonitorLayout::Primary, ..Default::default() },
+        );
+        config.monitors.insert(
+            "out-1920".to_string(),
+            MonitorConfig { show_desktop_bar: false, margin: Some(0), ..Default::default() },
+        );
+        let top = Insets { top: 200, ..Default::default() };
+
+        // The primary monitor passes its windows to the other one, which has no bar
+        let layouts = calculate_monitor_layout(&[window_at(100, 100)], &monitors, &config, top, 3840, 1080);
+        assert_eq!(layouts.len(), 1);
+        assert!(monitors[1].contains(layouts[0].x as i32, layouts[0].y as i32));
+        assert_eq!(layouts[0].y, (1080 - layouts[0].height as i16) / 2);
+    }
+
+    #[test]
+    fn test_desktop_bands() {
+        let monitor = monitor_at(0, true);
+        let windows = [window_at(0, 0), window_at(900, 0), window_at(0, 500), window_at(900, 500)];
+        let top = Insets { top: 100, ..Default::default() };
+        let config = LayoutConfig::default();
+        let layouts = desktop_bands_layout(&windows, &[3, 1, 3, 1], &monitor, &config, top, 1920, 1080);
+
+        assert_eq!(layouts.len(), 4);
+        for (i, layout) in layouts.iter().enumerate() {
+            assert_eq!(layout.window_index, i);
+        }
+        // Desktop 1's band comes first, with room for its name above it
+        let bottom = |l: &ThumbnailLayout| l.y + l.height as i16;
+        assert!(layouts[1].y >= (100 + config.margin + DESKTOP_LABEL_HEIGHT) as i16);
+        assert!(bottom(&layouts[1]).max(bottom(&layouts[3])) + DESKTOP_LABEL_HEIGHT as i16 <= layouts[0].y.min(layouts[2].y));
+        assert!(bottom(&layouts[0]) <= (1080 - config.margin) as i16);
+    }
+
+    #[test]
+    fn test_arranged_grid() {
+        let monitors = [monitor_at(0, true)];
+        let windows: Vec<WindowInfo> = [(0, 0), (1000, 0), (0, 500), (1000, 500)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, y))| WindowInfo { frame_window: i as u32 + 1, ..window_at(x, y) })
+            .collect();
+
+        // Unarranged, the grid follows the screen, or the focus order
+        let layouts = calculate_monitor_layout(&windows, &monitors, &LayoutConfig::default(), Insets::default(), 1920, 1080);
+        assert_eq!(reading_order(&layouts), vec![0, 1, 2, 3]);
+        let mut config = LayoutConfig { recent: Some(vec![3, 4, 1, 2]), ..Default::default() };
+        let layouts = calculate_monitor_layout(&windows, &monitors, &config, Insets::default(), 1920, 1080);
+        assert_eq!(reading_order(&layouts), vec![2, 3, 0, 1]);
+
+        // The user's arrangement goes over it
+        config.arranged.insert("out-0".to_string(), vec![4, 2, 3, 1]);
+        let layouts = calculate_monitor_layout(&windows, &monitors, &config, Insets::default(), 1920, 1080);
+        assert_eq!(reading_order(&layouts), vec![3, 1, 2, 0]);
+
+        // A search's ranking goes over the arrangement
+        config.ranked = Some(vec![2, 1, 4, 3]);
+        let layouts = calculate_monitor_layout(&windows, &monitors, &config, Insets::default(), 1920, 1080);
+        assert_eq!(reading_order(&layouts), vec![1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn test_minimized_strip() {
+        let monitor = monitor_at(0, true);
+        let bottom = Insets { bottom: 100, ..Default::default() };
+        let strip = minimized_strip_layout(&[window_at(0, 0), window_at(0, 0)], &monitor, bottom, 1920, 1080);
+        assert_eq!(strip.len(), 2);
+        for layout in &strip {
+            assert_eq!(layout.height, MINIMIZED_THUMB_HEIGHT);
+            assert!(layout.y >= (1080 - 100 - MINIMIZED_STRIP_HEIGHT) as i16);
+            assert!(layout.y + layout.height as i16 <= 1080 - 100);
+        }
+        // Centered, side by side
+        assert_eq!(strip[1].x - strip[0].x, (strip[0].width + MINIMIZED_SPACING) as i16);
+        assert!((strip[0].x - (1920 - strip[1].x - strip[1].width as i16)).abs() <= 1);
+
+        // Too many to fit at full size shrink to fit
+        let many = vec![window_at(0, 0); 40];
+        let strip = minimized_strip_layout(&many, &monitor, Insets::default(), 1920, 1080);
+        let last = strip.last().unwrap();
+        assert!(last.x + last.width as i16 <= 1920);
+        assert!(strip[0].height < MINIMIZED_THUMB_HEIGHT);
+    }
+
+    #[test]
+    fn test_centered_layout() {
+        let monitor = monitor_at(1920, false);
+        // A small window grows until it is 70% of the monitor's height
+        let window = WindowInfo { width: 400, height: 300, ..window_at(0, 0) };
+        let layout = centered_layout(&window, 3, &monitor, 0.7);
+        assert_eq!((layout.width, layout.height), (1008, 756));
+        assert_eq!((layout.x, layout.y), (1920 + 456, 162));
+        assert_eq!(layout.window_index, 3);
+    }
+
     #[test]
     fn test_optimal_grid() {
         // 1 window on widescreen -> fits in 2x1 grid (single cell used)
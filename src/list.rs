@@ -0,0 +1,175 @@
+//! `xpose list-windows`: the windows xpose manages and their desktops, for
+//! scripts, bars and rofi-style pickers. The control socket lists them the
+//! same way (see ipc.rs). `xpose --print-windows` lists them as lines to
+//! pick from, for `xpose --activate`.
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::connection::XConnection;
+use crate::desktop::DesktopState;
+use crate::error::Result;
+use crate::window_finder::WindowInfo;
+
+/// One managed window, as listed.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WindowEntry {
+    /// Client window id
+    pub id: u32,
+    /// Frame window id (the client's own id under non-reparenting WMs)
+    pub frame: u32,
+    pub class: Option<String>,
+    pub title: Option<String>,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    /// Desktop (0-indexed); None until xpose has seen the window
+    pub desktop: Option<u32>,
+    pub mapped: bool,
+}
+
+/// The managed windows, bottom to top.
+pub fn windows() -> Result<Vec<WindowEntry>> {
+    let config = Config::load();
+    let xconn = XConnection::new()?;
+    let state = DesktopState::load()?;
+    let (windows, _skipped, _stacking) = xconn.find_all_windows(config.window_filter())?;
+    Ok(entries(&windows, &state))
+}
+
+fn entries(windows: &[WindowInfo], state: &DesktopState) -> Vec<WindowEntry> {
+    windows
+        .iter()
+        .map(|info| WindowEntry {
+            id: info.client_window,
+            frame: info.frame_window,
+            class: info.wm_class.clone(),
+            title: info.wm_name.clone(),
+            // Where it belongs, not off-screen where an open overview put it
+            x: state.displaced.get(&info.frame_window.to_string()).copied().unwrap_or(info.x),
+            y: info.y,
+            width: info.width,
+            height: info.height,
+            desktop: state.get_window_desktop_assignment(info.frame_window),
+            mapped: info.is_mapped,
+        })
+        .collect()
+}
+
+/// Print the windows, one per line, or as a JSON array.
+pub fn run(json: bool) -> Result<()> {
+    let windows = windows()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&windows)?);
+        return Ok(());
+    }
+    for window in &windows {
+        // Desktops count from 1 here, as on the command line
+        let desktop = window.desktop.map_or("-".to_string(), |d| (d + 1).to_string());
+        println!(
+            "0x{:08x}  {:>2}  {:<16}  {}",
+            window.id,
+            desktop,
+            window.class.as_deref().unwrap_or("?"),
+            window.title.as_deref().unwrap_or("")
+        );
+    }
+    Ok(())
+}
+
+/// Print one line per window to pick from: the current desktop's first,
+/// top of the stack first, then the other desktops' in order.
+pub fn print_picker() -> Result<()> {
+    let config = Config::load();
+    let xconn = XConnection::new()?;
+    let mut state = DesktopState::load()?;
+    state.sync_from_x(&xconn)?;
+    let (windows, _skipped, _stacking) = xconn.find_all_windows(config.window_filter())?;
+    for line in picker_lines(&entries(&windows, &state), &state) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// The `--print-windows` lines: id, desktop, class and title, so the id can
+/// be read back from whichever line is picked.
+fn picker_lines(windows: &[WindowEntry], state: &DesktopState) -> Vec<String> {
+    let mut order: Vec<&WindowEntry> = windows.iter().rev().collect();
+    // Windows xpose hasn't placed yet are on the current desktop
+    order.sort_by_key(|window| window.desktop.filter(|&d| d != state.current).map_or(0, |d| d + 1));
+    order
+        .into_iter()
+        .map(|window| {
+            let desktop = window.desktop.unwrap_or(state.current);
+            // WM_CLASS is "instance Class"; the class reads better
+            let class = window.class.as_deref().and_then(|c| c.split(' ').next_back()).unwrap_or("?");
+            format!(
+                "0x{:08x}  [{}]  {}  {}",
+                window.id,
+                state.display_name(desktop),
+                class,
+                window.title.as_deref().unwrap_or("")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries() {
+        let state: DesktopState = serde_json::from_value(serde_json::json!({
+            "current": 0,
+            "desktops": 2,
+            "windows": {"20": 1},
+            "displaced": {"20": 150},
+        }))
+        .unwrap();
+        let window = |client, frame| WindowInfo {
+            client_window: client,
+            x: -5000,
+            y: 40,
+            width: 640,
+            height: 480,
+            ..WindowInfo::for_test(frame, Some("XTerm"), None)
+        };
+        let entries = entries(&[window(21, 20), window(31, 30)], &state);
+        assert_eq!((entries[0].id, entries[0].frame), (21, 20));
+        assert_eq!(entries[0].desktop, Some(1));
+        assert_eq!(entries[0].x, 150);
+        assert_eq!(entries[1].desktop, None);
+        assert_eq!(entries[1].x, -5000);
+    }
+
+    #[test]
+    fn test_picker_lines() {
+        let state: DesktopState = serde_json::from_value(serde_json::json!({
+            "current": 1,
+            "desktops": 3,
+            "windows": {},
+            "names": {"1": "Web"},
+        }))
+        .unwrap();
+        let entry = |id, desktop| WindowEntry {
+            id,
+            frame: id,
+            class: Some("navigator Firefox".to_string()),
+            title: Some(format!("window {}", id)),
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+            desktop,
+            mapped: true,
+        };
+        // Bottom to top, as discovered
+        let windows = [entry(1, Some(0)), entry(2, Some(1)), entry(3, Some(2)), entry(4, None), entry(5, Some(1))];
+        let lines = picker_lines(&windows, &state);
+        assert_eq!(lines[0], "0x00000005  [Web]  Firefox  window 5");
+        let ids: Vec<&str> = lines.iter().map(|line| &line[..10]).collect();
+        assert_eq!(ids, ["0x00000005", "0x00000004", "0x00000002", "0x00000001", "0x00000003"]);
+    }
+}
@@ -1,15 +1,58 @@
+mod alpha;
 mod animation;
+mod announce;
 mod capture;
+mod cli;
+mod clients;
+mod clipboard;
+mod compositor;
 mod config;
+mod config_watch;
 mod connection;
+mod control;
+mod damage;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod demo;
 mod desktop;
 mod desktop_bar;
+mod desktop_grid;
+mod diagnostics;
 mod error;
+mod ewmh;
+mod focus_tracker;
+mod fullscreen;
+mod icons;
+mod image;
 mod input;
+mod ipc;
+mod keymap;
 mod layout;
+mod list;
+mod monitor;
+mod paths;
+mod placement;
+mod present;
+mod recovery;
 mod renderer;
+mod resources;
+mod scaled;
+mod schema;
+mod screenshot;
+mod search;
+mod service;
+mod signals;
 mod state;
+mod systemd;
+mod text;
+mod theme;
+mod thumbnail_cache;
+mod toast;
+mod tooltip;
+mod transition;
+mod wallpaper;
 mod window_finder;
+mod xtest;
 
 use std::collections::{HashMap, HashSet};
 use std::time::Instant;
@@ -25,12 +68,35 @@ use std::thread;
 const REVERT_DURATION_MS: u64 = 200;
 const SNAP_DURATION_MS: u64 = 150;
 const GRID_TRANSITION_DURATION_MS: u64 = 250;
+/// Time between frames of animations run by the overview loop (~60fps)
+const FRAME_INTERVAL: std::time::Duration = std::time::Duration::from_millis(16);
+/// Time between redraws of pulsing urgent borders, coarser than animation
+/// frames since each costs a present.
+const URGENCY_PULSE_STEP: std::time::Duration = std::time::Duration::from_millis(50);
+/// How much of its monitor a window in the spotlight fills, across or down.
+const SPOTLIGHT_SIZE: f64 = 0.7;
+/// The same for the quick-look preview (Space).
+const QUICK_LOOK_SIZE: f64 = 0.8;
+
+/// Exit status when the X connection drops mid-session.
+const EXIT_CONNECTION_LOST: i32 = 3;
+
+/// Something for the event loop to act on.
+enum LoopInput {
+    Event(Event),
+    /// An action the overview raised itself, like removing a desktop its
+    /// last window just left.
+    Queued(InputAction),
+}
 
 /// Animation mode: snap to desktop or revert to grid.
 #[derive(Debug, Clone)]
 enum AnimationMode {
-    SnapToDesktop { desktop_idx: usize },
+    /// `follow` switches to the desktop and exits once the window lands.
+    SnapToDesktop { desktop_idx: usize, follow: bool },
     RevertToGrid,
+    /// Grow into the quick-look preview, which stays up once it lands.
+    QuickLook,
 }
 
 /// Animation state for drag revert or snap.
@@ -47,6 +113,7 @@ struct DragAnimation {
     end_height: u16,
     start_time: Instant,
     duration_ms: u64,
+    easing: Easing,
 }
 
 impl DragAnimation {
@@ -61,9 +128,7 @@ impl DragAnimation {
     }
 
     fn current_position(&self) -> (i16, i16, u16, u16) {
-        let t = self.progress();
-        // Ease-out cubic
-        let eased = 1.0 - (1.0 - t).powi(3);
+        let eased = self.easing.apply(self.progress());
 
         let x = self.start_x as f64 + (self.end_x - self.start_x) as f64 * eased;
         let y = self.start_y as f64 + (self.end_y - self.start_y) as f64 * eased;
@@ -80,10 +145,11 @@ struct GridTransitionAnimation {
     transitions: HashMap<usize, (ThumbnailLayout, ThumbnailLayout)>,
     start_time: Instant,
     duration_ms: u64,
+    easing: Easing,
 }
 
 impl GridTransitionAnimation {
-    fn new(old_layouts: &[ThumbnailLayout], new_layouts: &[ThumbnailLayout], duration_ms: u64) -> Self {
+    fn new(old_layouts: &[ThumbnailLayout], new_layouts: &[ThumbnailLayout], duration_ms: u64, easing: Easing) -> Self {
         let mut transitions = HashMap::new();
 
         // Map new layouts by window_index for quick lookup
@@ -102,10 +168,25 @@ impl GridTransitionAnimation {
             }
         }
 
+        // Windows joining the grid grow out of the center of their slot
+        for new_layout in new_layouts {
+            transitions.entry(new_layout.window_index).or_insert_with(|| {
+                let start = ThumbnailLayout {
+                    x: new_layout.x + (new_layout.width / 2) as i16,
+                    y: new_layout.y + (new_layout.height / 2) as i16,
+                    width: 1,
+                    height: 1,
+                    window_index: new_layout.window_index,
+                };
+                (start, new_layout.clone())
+            });
+        }
+
         Self {
             transitions,
             start_time: Instant::now(),
             duration_ms,
+            easing,
         }
     }
 
@@ -119,11 +200,59 @@ impl GridTransitionAnimation {
         self.progress() >= 1.0
     }
 
+    /// Slide one desktop's grid out and another's in, `offset` pixels across
+    /// from where it ends up; windows in both (pinned ones) move as usual.
+    fn slide(
+        old_layouts: &[ThumbnailLayout],
+        new_layouts: &[ThumbnailLayout],
+        offset: i16,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Self {
+        let shifted = |layout: &ThumbnailLayout, by: i16| ThumbnailLayout {
+            x: layout.x.saturating_add(by),
+            ..layout.clone()
+        };
+        let mut transitions: HashMap<usize, (ThumbnailLayout, ThumbnailLayout)> = new_layouts
+            .iter()
+            .map(|new| {
+                let old = old_layouts.iter().find(|old| old.window_index == new.window_index);
+                (new.window_index, (old.cloned().unwrap_or_else(|| shifted(new, offset)), new.clone()))
+            })
+            .collect();
+        for old in old_layouts {
+            transitions
+                .entry(old.window_index)
+                .or_insert_with(|| (old.clone(), shifted(old, offset.saturating_neg())));
+        }
+
+        Self {
+            transitions,
+            start_time: Instant::now(),
+            duration_ms,
+            easing,
+        }
+    }
+
+    /// Let `departed` windows, gone from the grid, shrink into the center of
+    /// their old slots.
+    fn with_departures(mut self, old_layouts: &[ThumbnailLayout], departed: &[usize]) -> Self {
+        for old in old_layouts.iter().filter(|l| departed.contains(&l.window_index)) {
+            let end = ThumbnailLayout {
+                x: old.x + (old.width / 2) as i16,
+                y: old.y + (old.height / 2) as i16,
+                width: 1,
+                height: 1,
+                window_index: old.window_index,
+            };
+            self.transitions.insert(old.window_index, (old.clone(), end));
+        }
+        self
+    }
+
     /// Get current interpolated layouts
     fn current_layouts(&self) -> Vec<ThumbnailLayout> {
-        let t = self.progress();
-        // Ease-out cubic for smooth deceleration
-        let eased = 1.0 - (1.0 - t).powi(3);
+        let eased = self.easing.apply(self.progress());
 
         self.transitions
             .iter()
@@ -145,12 +274,24 @@ impl GridTransitionAnimation {
     }
 }
 
-/// Animation state for desktop bar layout changes (slide left/right after deletion/reorder).
+/// The desktop bar previews growing into the desktop grid's tiles
+/// (`zoom_out`), or the tiles shrinking back into the bar.
+struct GridZoom {
+    grid: DesktopGrid,
+    start_time: Instant,
+    zoom_out: bool,
+}
+
+/// Animation state for desktop bar layout changes (slide along the bar after deletion/reorder).
 struct DesktopBarAnimation {
-    /// Map from desktop_index to (old_x, new_x)
+    /// Map from desktop_index to (old, new) position along the bar
     transitions: std::collections::HashMap<u32, (i16, i16)>,
+    /// Captures whose miniatures fly from one preview to another, with
+    /// where they take off and land
+    flights: Vec<(usize, Rect, Rect)>,
     start_time: Instant,
     duration_ms: u64,
+    easing: Easing,
 }
 
 impl DesktopBarAnimation {
@@ -164,16 +305,26 @@ impl DesktopBarAnimation {
         self.progress() >= 1.0
     }
 
-    /// Get interpolated X position for a desktop
-    fn current_x(&self, desktop_index: u32, original_x: i16) -> i16 {
-        let t = self.progress();
-        // Ease-out cubic for smooth deceleration
-        let eased = 1.0 - (1.0 - t).powi(3);
+    /// Where a flying miniature is now.
+    fn flight_rect(&self, from: Rect, to: Rect) -> Rect {
+        let eased = self.easing.apply(self.progress());
+        let lerp = |a: i32, b: i32| (a as f64 + (b - a) as f64 * eased) as i32;
+        (
+            lerp(from.0 as i32, to.0 as i32) as i16,
+            lerp(from.1 as i32, to.1 as i32) as i16,
+            lerp(from.2 as i32, to.2 as i32).max(1) as u16,
+            lerp(from.3 as i32, to.3 as i32).max(1) as u16,
+        )
+    }
+
+    /// Get interpolated position along the bar for a desktop
+    fn current_offset(&self, desktop_index: u32, original: i16) -> i16 {
+        let eased = self.easing.apply(self.progress());
 
-        if let Some(&(old_x, new_x)) = self.transitions.get(&desktop_index) {
-            (old_x as f64 + (new_x - old_x) as f64 * eased) as i16
+        if let Some(&(old, new)) = self.transitions.get(&desktop_index) {
+            (old as f64 + (new - old) as f64 * eased) as i16
         } else {
-            original_x
+            original
         }
     }
 }
@@ -181,9 +332,9 @@ impl DesktopBarAnimation {
 /// Animation state for desktop gap during drag.
 /// Smoothly interpolates desktop positions when the insert position changes.
 struct DragGapAnimation {
-    /// Current animated X position for each non-dragged desktop
+    /// Current animated position along the bar for each non-dragged desktop
     positions: std::collections::HashMap<u32, f64>,
-    /// Target X position for each desktop (from calculate_layouts_with_gap)
+    /// Target position for each desktop (from calculate_layouts_with_gap)
     targets: std::collections::HashMap<u32, i16>,
     /// Animation speed factor (higher = faster slide)
     lerp_factor: f64,
@@ -214,13 +365,21 @@ impl DragGapAnimation {
     fn get_positions(&self) -> Vec<(u32, i16)> {
         self.positions.iter().map(|(&idx, &pos)| (idx, pos.round() as i16)).collect()
     }
+
+    /// Whether every desktop has (all but) reached its target.
+    fn is_settled(&self) -> bool {
+        self.positions
+            .iter()
+            .all(|(idx, &pos)| self.targets.get(idx).is_none_or(|&target| (target as f64 - pos).abs() < 0.5))
+    }
 }
 
-/// Calculate drag scale factor and target size based on Y position.
-/// Interpolates from drag start position (scale=1.0) to desktop preview bottom (scale=target_scale).
+/// Calculate drag scale factor and target size based on how close the cursor is to the bar.
+/// Interpolates from drag start position (scale=1.0) to the inner edge of the
+/// desktop previews (scale=target_scale).
 fn calculate_drag_scale_and_target(
-    cursor_y: i16,
-    drag_start_y: i16,
+    cursor: (i16, i16),
+    drag_start: (i16, i16),
     layout: &ThumbnailLayout,
     desktop_bar: &Option<DesktopBar>,
     capture: &CapturedWindow,
@@ -237,29 +396,24 @@ fn calculate_drag_scale_and_target(
     // Calculate scale ratio: how much smaller is the target compared to the grid thumbnail
     let target_scale = target_width as f64 / layout.width as f64;
 
-    // Original position is where the user clicked (drag start), not thumbnail center
-    let original_y = drag_start_y;
+    // Distances from the previews; the original position is where the user
+    // clicked (drag start), not thumbnail center
+    let current_distance = bar.distance_to_previews(cursor.0, cursor.1);
+    let original_distance = bar.distance_to_previews(drag_start.0, drag_start.1);
 
-    // Target Y is the BOTTOM of the desktop previews (when cursor enters preview bounds)
-    let preview_bottom_y = bar.preview_layouts.first()
-        .map(|p| p.y + p.height as i16)
-        .unwrap_or(bar.bar_height as i16);
-
-    // If cursor is inside preview (at or above bottom), use minimum scale
-    if cursor_y <= preview_bottom_y {
+    // If cursor has reached the previews, use minimum scale
+    if current_distance <= 0 {
         return (target_scale, (target_width, target_height));
     }
 
-    // If cursor is at or below original click position, use full scale
-    if cursor_y >= original_y {
+    // If cursor is no closer than the original click position, use full scale
+    if current_distance >= original_distance {
         return (1.0, (target_width, target_height));
     }
 
-    // Interpolate between original click position and preview bottom
-    // t = 0 at original_y, t = 1 at preview_bottom_y
-    let total_distance = (original_y - preview_bottom_y) as f64;
-    let current_distance = (original_y - cursor_y) as f64;
-    let t = (current_distance / total_distance).clamp(0.0, 1.0);
+    // Interpolate between original click position and the previews
+    // t = 0 at the original distance, t = 1 at the previews
+    let t = (1.0 - current_distance as f64 / original_distance as f64).clamp(0.0, 1.0);
 
     // Linear interpolation for smooth, even scaling
     // Interpolate scale: 1.0 at t=0, target_scale at t=1
@@ -296,24 +450,58 @@ fn calculate_drag_rect(
 
 /// Recalculate grid layout for windows excluding removed ones.
 /// Filters out removed windows, recalculates layout, and remaps indices.
+#[allow(clippy::too_many_arguments)]
 fn recalculate_filtered_layout(
     captures: &[CapturedWindow],
     removed_windows: &HashSet<usize>,
+    minimized: &[usize],
+    piles: &[Vec<usize>],
+    by_desktop: Option<&desktop::DesktopState>,
+    monitors: &[Monitor],
+    config: &LayoutConfig,
+    reserved: Insets,
     screen_width: u16,
     screen_height: u16,
-    config: &LayoutConfig,
-    top_reserved: u16,
 ) -> Vec<ThumbnailLayout> {
+    // A pile takes one slot, its first window in the grid
+    let piled: HashSet<usize> = piles
+        .iter()
+        .flat_map(|pile| pile.iter().filter(|i| !removed_windows.contains(i) && !minimized.contains(i)).skip(1))
+        .copied()
+        .collect();
+    // While searching, only the matches
+    let unmatched = |i: &usize| {
+        let frame = captures[*i].info.frame_window;
+        config.ranked.as_ref().is_some_and(|ranked| !ranked.contains(&frame))
+    };
+    let off_grid = |i: &usize| removed_windows.contains(i) || piled.contains(i) || unmatched(i);
+
     // Filter out removed windows
     let filtered_captures: Vec<&CapturedWindow> = captures
         .iter()
         .enumerate()
-        .filter(|(i, _)| !removed_windows.contains(i))
+        .filter(|(i, _)| !off_grid(i))
         .map(|(_, capture)| capture)
         .collect();
 
+    // The strip of minimized windows goes along the bottom of the primary monitor
+    let grid_reserved = if minimized.is_empty() {
+        reserved
+    } else {
+        Insets { bottom: reserved.bottom + MINIMIZED_STRIP_HEIGHT, ..reserved }
+    };
+    let strip_infos: Vec<window_finder::WindowInfo> = minimized.iter().map(|&i| captures[i].info.clone()).collect();
+    let strip = monitors
+        .iter()
+        .find(|m| m.primary)
+        .or(monitors.first())
+        .map(|monitor| minimized_strip_layout(&strip_infos, monitor, reserved, screen_width, screen_height))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|layout| ThumbnailLayout { window_index: minimized[layout.window_index], ..layout });
+
     if filtered_captures.is_empty() {
-        return Vec::new();
+        return strip.collect();
     }
 
     // Create WindowInfo slice for layout calculation
@@ -322,20 +510,24 @@ fn recalculate_filtered_layout(
         .map(|c| c.info.clone())
         .collect();
 
-    // Calculate new layout for filtered windows
-    let new_layouts = calculate_layout(
-        &filtered_infos,
-        screen_width,
-        screen_height,
-        config,
-        top_reserved,
-    );
+    // Calculate new layout for filtered windows; every desktop's go in bands
+    // down the primary monitor
+    let new_layouts = match (by_desktop, monitors.iter().find(|m| m.primary).or(monitors.first())) {
+        (Some(desktop_state), Some(monitor)) => {
+            let desktops: Vec<u32> = filtered_infos
+                .iter()
+                .map(|info| grid_desktop(desktop_state, info.frame_window))
+                .collect();
+            desktop_bands_layout(&filtered_infos, &desktops, monitor, config, grid_reserved, screen_width, screen_height)
+        }
+        _ => calculate_monitor_layout(&filtered_infos, monitors, config, grid_reserved, screen_width, screen_height),
+    };
 
     // Remap indices back to original capture indices
     let filtered_indices: Vec<usize> = captures
         .iter()
         .enumerate()
-        .filter(|(i, _)| !removed_windows.contains(i))
+        .filter(|(i, _)| !off_grid(i))
         .map(|(i, _)| i)
         .collect();
 
@@ -346,90 +538,377 @@ fn recalculate_filtered_layout(
             layout.window_index = filtered_indices[new_idx];
             layout
         })
+        .chain(strip)
+        .collect()
+}
+
+/// The desktop a window is grouped under while the grid shows every
+/// desktop: its own, or the current one for sticky and unassigned windows.
+fn grid_desktop(desktop_state: &desktop::DesktopState, frame: Window) -> u32 {
+    desktop_state
+        .get_window_desktop_assignment(frame)
+        .filter(|_| !desktop_state.is_sticky(frame))
+        .unwrap_or(desktop_state.current)
+}
+
+/// Frames -> the name of the desktop they are grouped under, for the labels
+/// over each group while the grid shows every desktop; empty otherwise.
+fn desktop_labels(
+    captures: &[CapturedWindow],
+    desktop_state: &desktop::DesktopState,
+    all_desktops: bool,
+) -> HashMap<Window, (u32, String)> {
+    if !all_desktops {
+        return HashMap::new();
+    }
+    captures
+        .iter()
+        .map(|capture| capture.info.frame_window)
+        .filter(|&frame| !desktop_state.is_app_hidden(frame))
+        .map(|frame| {
+            let desktop = grid_desktop(desktop_state, frame);
+            (frame, (desktop, desktop_state.display_name(desktop)))
+        })
+        .collect()
+}
+
+/// The config's window filter, with `app_classes` (from --class or
+/// --current-app) in place of its include lists unless empty.
+fn window_filter<'a>(config: &'a Config, app_classes: &'a [String]) -> window_finder::WindowFilter<'a> {
+    if app_classes.is_empty() {
+        return config.window_filter();
+    }
+    window_finder::WindowFilter {
+        include_classes: app_classes,
+        include_titles: &[],
+        ..config.window_filter()
+    }
+}
+
+/// With `order = "recent"`, the frames of `windows` most recently focused
+/// first, for the grids to fill their cells in.
+fn recent_order(config: &Config, window_state: &WindowState, windows: &[window_finder::WindowInfo]) -> Option<Vec<Window>> {
+    (config.grid_order == GridOrder::Recent)
+        .then(|| window_state.recent_first(windows).into_iter().map(|i| windows[i].frame_window).collect())
+}
+
+/// Captures of the windows minimized on the current desktop, shown in the
+/// strip along the bottom.
+fn minimized_windows(
+    captures: &[CapturedWindow],
+    destroyed_captures: &HashSet<usize>,
+    filtered_captures: &HashSet<usize>,
+    desktop_state: &desktop::DesktopState,
+) -> Vec<usize> {
+    captures
+        .iter()
+        .enumerate()
+        .filter(|(i, capture)| {
+            let frame = capture.info.frame_window;
+            !destroyed_captures.contains(i)
+                && !filtered_captures.contains(i)
+                && desktop_state.is_app_hidden(frame)
+                && (desktop_state.is_sticky(frame)
+                    || desktop_state
+                        .get_window_desktop_assignment(frame)
+                        .is_none_or(|desktop| desktop == desktop_state.current))
+        })
+        .map(|(i, _)| i)
         .collect()
 }
 
-use animation::{AnimatedLayout, AnimationConfig, Animator};
+/// The groups not expanded this session, as piles of capture indices (top
+/// first), and the name and size of each for the renderer, by frame.
+fn collapsed_groups(
+    captures: &[CapturedWindow],
+    groups: &[WindowGroup],
+    expanded: &HashSet<String>,
+) -> (Vec<Vec<usize>>, HashMap<Window, PileLabel>) {
+    let mut piles = Vec::new();
+    let mut labels = HashMap::new();
+    for group in groups.iter().filter(|g| !expanded.contains(&g.name)) {
+        let pile: Vec<usize> = group
+            .members
+            .iter()
+            .filter_map(|key| captures.iter().position(|c| c.info.frame_window.to_string() == *key))
+            .collect();
+        for &i in &pile {
+            labels.insert(captures[i].info.frame_window, (group.name.clone(), pile.len()));
+        }
+        piles.push(pile);
+    }
+    (piles, labels)
+}
+
+/// Where a captured window is on screen, as a layout.
+fn window_rect(captures: &[CapturedWindow], window_index: usize) -> ThumbnailLayout {
+    let info = &captures[window_index].info;
+    ThumbnailLayout {
+        x: info.x,
+        y: info.y,
+        width: info.width,
+        height: info.height,
+        window_index,
+    }
+}
+
+use animation::{AnimatedLayout, AnimationConfig, Animator, Easing, FramePacer};
 use capture::CapturedWindow;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use cli::{AppFilter, OverviewOptions};
 use config::Config;
+use config_watch::ConfigWatcher;
 use connection::XConnection;
-use desktop_bar::DesktopBar;
+use damage::DamageQueue;
+use desktop::backend::DesktopBackend;
+use desktop::state::Geometry;
+use desktop_bar::{DeleteConfirm, DesktopBar, Rect};
+use desktop_grid::DesktopGrid;
 use error::Result;
 use input::{InputAction, InputHandler};
-use layout::{calculate_layout, LayoutConfig, ThumbnailLayout};
-use renderer::OverviewWindow;
-use state::WindowState;
+use layout::{
+    calculate_monitor_layout, desktop_bands_layout, minimized_strip_layout, GridOrder, Insets, LayoutConfig, ThumbnailLayout,
+    MINIMIZED_STRIP_HEIGHT,
+};
+use monitor::Monitor;
+use renderer::{OverviewWindow, PileLabel};
+use search::SearchView;
+use state::{WindowGroup, WindowState};
+use transition::SlideDirection;
 
 fn main() {
-    // Initialize logging to /tmp/xpose.log (append mode)
+    // Initialize logging to xpose.log in the state directory (fresh each run)
+    let log_path = paths::log_file();
+    if let Some(dir) = log_path.parent() {
+        let _ = paths::create_private_dir(dir);
+    }
     let log_file = std::fs::OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
-        .open("/tmp/xpose.log")
+        .open(&log_path)
         .expect("Failed to open log file");
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .target(env_logger::Target::Pipe(Box::new(log_file)))
-        .init();
+    let cli = cli::Cli::parse();
+
+    // --log-level wins over RUST_LOG
+    let mut logger = match cli.log_level {
+        Some(level) => {
+            let mut builder = env_logger::Builder::new();
+            builder.filter_level(level);
+            builder
+        }
+        None => env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")),
+    };
+    logger.target(env_logger::Target::Pipe(Box::new(log_file))).init();
+
+    // Until something listens for them, `pkill -USR1 xpose` must not kill anything
+    signals::ignore();
+
+    // Every connection (overview, desktop commands, recovery) goes by $DISPLAY
+    if let Some(ref display) = cli.display {
+        std::env::set_var("DISPLAY", display);
+    }
+    if let Some(ref path) = cli.config {
+        if !path.is_file() {
+            cli::Cli::command()
+                .error(ErrorKind::InvalidValue, format!("cannot read config file {}", path.display()))
+                .exit();
+        }
+        Config::set_path(path.clone());
+    }
+
+    let command = match cli.into_command() {
+        Ok(command) => command,
+        Err(e) => cli::Cli::command().error(ErrorKind::InvalidValue, e).exit(),
+    };
+
+    let result = match command {
+        cli::Command::Screenshot { target, path } => screenshot::export(target, &path),
+        cli::Command::Overview(options) => run(options),
+        cli::Command::Desktop(command) => control::run(command),
+        cli::Command::CheckConfig(path) => check_config(path),
+        cli::Command::TrackFocus => focus_tracker::run(),
+        cli::Command::ListWindows { json } => list::run(json),
+        cli::Command::PrintWindows => list::print_picker(),
+        cli::Command::ServeClipboard(text) => clipboard::serve(&text),
+        cli::Command::Daemon { systemd } => ipc::run(|| run(OverviewOptions::default()), systemd),
+        #[cfg(feature = "dbus")]
+        cli::Command::Dbus => dbus::run(|| run(OverviewOptions::default())),
+        #[cfg(not(feature = "dbus"))]
+        cli::Command::Dbus => Err(error::XposeError::Other(
+            "Built without D-Bus support (cargo feature \"dbus\")".to_string(),
+        )),
+    };
 
-    if let Err(e) = run() {
+    if let Err(e) = result {
+        if e.is_connection_lost() {
+            // Grabs and redirections die with the connection; put windows
+            // left off-screen back if the server is still reachable
+            log::error!("X connection lost: {}", e);
+            match recovery::recover_after_connection_loss() {
+                Ok(()) => log::info!("Reconnected and restored window state"),
+                Err(e) => log::error!("Recovery failed, will retry at next launch: {}", e),
+            }
+            eprintln!("xpose: {}", error::XposeError::ConnectionLost);
+            std::process::exit(EXIT_CONNECTION_LOST);
+        }
         log::error!("Error: {}", e);
+        eprintln!("xpose: {}", e);
         std::process::exit(1);
     }
 }
 
-fn run() -> Result<()> {
+/// `xpose check-config`: print each problem in the config file as
+/// `PATH:LINE: message` and fail if there were any.
+fn check_config(path: Option<std::path::PathBuf>) -> Result<()> {
+    let Some(path) = path.or_else(Config::path) else {
+        return Err(error::XposeError::Other("No config file to check".to_string()));
+    };
+    let issues = Config::check_file(&path)
+        .map_err(|e| error::XposeError::Other(format!("Cannot read {}: {}", path.display(), e)))?;
+    for issue in &issues {
+        match issue.line {
+            Some(line) => println!("{}:{}: {}", path.display(), line, issue.message),
+            None => println!("{}: {}", path.display(), issue.message),
+        }
+    }
+    if !issues.is_empty() {
+        return Err(error::XposeError::Other(format!("{} problem(s) in {}", issues.len(), path.display())));
+    }
+    println!("{}: OK", path.display());
+    Ok(())
+}
+
+/// Run the interactive overview. With `options.demo`, a scripted XTEST
+/// session drives it (see demo.rs).
+fn run(options: OverviewOptions) -> Result<()> {
     log::info!("========================================");
     log::info!("Starting xpose - new session");
     log::info!("========================================");
 
+    // SIGUSR1/SIGUSR2, unless a daemon running this overview takes them
+    let signals = signals::listen();
+
     // Load configuration
-    let config = Config::load();
-    let animation_speed = if config.animation_speed > 0.0 {
-        config.animation_speed
-    } else {
-        1.0
-    };
-    let entrance_anim = AnimationConfig::new(scale_duration(config.entrance_duration(), animation_speed));
-    let exit_anim = AnimationConfig::new(scale_duration(config.exit_duration(), animation_speed));
-    let snap_duration_ms = scale_duration_ms(SNAP_DURATION_MS, animation_speed);
-    let revert_duration_ms = scale_duration_ms(REVERT_DURATION_MS, animation_speed);
-    let grid_transition_duration_ms = scale_duration_ms(GRID_TRANSITION_DURATION_MS, animation_speed);
+    let mut config = Config::load();
+    let entrance_anim = AnimationConfig::new(
+        scale_duration(config.entrance_duration(), config.animation_speed),
+        config.entrance_easing,
+    );
+    let (mut exit_anim, mut snap_duration_ms, mut revert_duration_ms, mut grid_transition_duration_ms) =
+        session_durations(&config);
+    // Edits to the config file while the overview is open apply right away
+    let config_watcher = Config::path().and_then(|path| match ConfigWatcher::new(&path) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            log::debug!("Not watching the config file: {}", e);
+            None
+        }
+    });
 
     // Connect to X server
-    let xconn = XConnection::new()?;
+    let mut xconn = XConnection::new()?;
+    if options.demo && !xconn.has_xtest()? {
+        return Err(error::XposeError::Other("xpose demo needs the XTEST extension".to_string()));
+    }
     log::info!(
         "Connected to X server, screen {}x{}",
         xconn.screen_width,
         xconn.screen_height
     );
+    // Keep out of the way of a fullscreen game or video, if so configured
+    if !options.demo && !fullscreen::allows_overview(&xconn, config.fullscreen_activation)? {
+        return Ok(());
+    }
 
-    // Load desktop state (always enabled now)
+    // Load desktop state (always enabled now), kept locked for the session
+    // so desktop commands run meanwhile wait rather than being overwritten
+    let _desktop_lock = desktop::DesktopState::lock()?;
     let mut desktop_state = desktop::DesktopState::load()?;
 
     // Sync from X properties if they exist (for compatibility)
     desktop_state.sync_from_x(&xconn)?;
 
+    // A previous session that was cut off may have left windows off-screen
+    if !desktop_state.displaced.is_empty() {
+        log::warn!("Previous session did not clean up, restoring off-screen windows");
+        xconn.restore_displaced_windows(&mut desktop_state)?;
+        desktop_state.save()?;
+    }
+
+    // --class and --current-app narrow the grid to one application's
+    // windows, in place of the config's include lists
+    let app_classes: Vec<String> = match options.app {
+        Some(AppFilter::Class(ref class)) => vec![class.clone()],
+        Some(AppFilter::Current) => {
+            let class = xconn.focused_class()?;
+            if class.is_none() {
+                log::info!("No focused application, showing every window");
+            }
+            class.into_iter().collect()
+        }
+        None => Vec::new(),
+    };
+    if !app_classes.is_empty() {
+        log::info!("Showing the windows of {:?}", app_classes);
+    }
+    let filter = window_filter(&config, &app_classes);
+
+    // Find ALL windows including unmapped ones (for virtual desktop support)
+    // original_stacking_order contains frame window IDs in their X11 stacking order (bottom-to-top)
+    let (mut windows, skipped_windows, mut original_stacking_order) = xconn.find_all_windows(filter)?;
+
+    // A tiling window manager's workspaces stand in for xpose's desktops
+    let mut backend = desktop::backend::connect(config.desktop_backend).unwrap_or_else(|e| {
+        xconn.report_error(format!("Cannot reach the window manager's workspaces: {}", e));
+        None
+    });
+    let mut workspaces = match backend.as_deref_mut().map(|backend| backend.workspaces()) {
+        Some(Ok(workspaces)) => workspaces,
+        Some(Err(e)) => {
+            xconn.report_error(format!("Cannot read the window manager's workspaces: {}", e));
+            backend = None;
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    if backend.is_some() {
+        desktop::backend::adopt_workspaces(&mut desktop_state, &workspaces, &windows);
+    }
+
     log::info!(
         "Desktop state: {} desktops, current={}",
         desktop_state.desktops,
         desktop_state.current
     );
 
+    // With --monitor, that monitor takes every window
+    let mut monitors = layout_monitors(&xconn.monitors, options.monitor)?;
+
+    let mut layout_config = LayoutConfig {
+        monitors: config.monitors.clone(),
+        ..Default::default()
+    };
+
     // Initialize desktop bar
-    let bar_height = config.desktop_bar_height;
-    let mut desktop_bar = Some(DesktopBar::new(
+    let show_bar = config.show_desktop_bar && !options.no_bar;
+    let bar_height = if show_bar { config.desktop_bar_height } else { 0 };
+    let bar_position = config.bar_position;
+    let mut bar_area = show_bar
+        .then(|| desktop_bar::bar_area(&monitors, &layout_config, xconn.screen_width, xconn.screen_height))
+        .flatten();
+    let mut desktop_bar = bar_area.map(|area| DesktopBar::new(
         desktop_state.desktops,
         desktop_state.current,
-        xconn.screen_width,
+        area,
         bar_height,
+        bar_position,
     ));
-
-    // Find ALL windows including unmapped ones (for virtual desktop support)
-    // original_stacking_order contains frame window IDs in their X11 stacking order (bottom-to-top)
-    let (mut windows, skipped_windows, original_stacking_order) =
-        xconn.find_all_windows(&config.exclude_classes)?;
+    let mut reserved = desktop_bar
+        .as_ref()
+        .map_or(Insets::default(), |bar| bar.reserved(xconn.screen_width, xconn.screen_height));
 
     // Log existing window assignments from loaded state
     log::info!("Loaded desktop state has {} window assignments:", desktop_state.windows.len());
@@ -437,12 +916,48 @@ fn run() -> Result<()> {
         log::info!("  Window 0x{} -> desktop {}", key, desktop);
     }
 
+    let known_frames: HashSet<Window> = windows
+        .iter()
+        .map(|info| info.frame_window)
+        .filter(|&frame| desktop_state.get_window_desktop_assignment(frame).is_some())
+        .collect();
+    // New windows of restarted applications go back to where the application
+    // was, by WM_CLASS; _NET_WM_DESKTOP below still wins over that
+    desktop::assign_by_identity(&mut desktop_state, &windows, &config.identity_titles);
+
     // Assign any new windows to the current desktop
     // Windows that were already tracked keep their assignments
     log::info!("Processing {} windows (current desktop = {}):", windows.len(), desktop_state.current);
+    let mut destroyed_frames: HashSet<Window> = HashSet::new();
     for info in &windows {
-        let was_known = desktop_state.windows.contains_key(&info.frame_window.to_string());
+        let was_known = known_frames.contains(&info.frame_window);
+        if !was_known {
+            // Seed from _NET_WM_DESKTOP so assignments survive a WM restart (new frame IDs)
+            let wm_desktop = match xconn.get_wm_desktop(info.client_window) {
+                Ok(desktop) => desktop,
+                Err(e) if e.is_window_gone() => {
+                    log::info!("  GONE: {:?} (0x{:x}) destroyed during discovery",
+                        info.wm_name.as_deref().unwrap_or("?"), info.frame_window);
+                    destroyed_frames.insert(info.frame_window);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            match wm_desktop {
+                // Pinned, by xpose before a WM restart or by the application
+                Some(ewmh::ALL_DESKTOPS) => desktop_state.set_sticky(info.frame_window, true),
+                None => {}
+                Some(desktop) if desktop < desktop_state.desktops => {
+                    desktop_state.set_window_desktop(info.frame_window, desktop);
+                }
+                Some(desktop) => log::debug!("Ignoring out-of-range _NET_WM_DESKTOP {}", desktop),
+            }
+        }
         let assigned = desktop_state.get_window_desktop(info.frame_window, desktop_state.current);
+        // Minimized, or restored, outside xpose since it last ran
+        if assigned == desktop_state.current {
+            desktop_state.set_app_hidden(info.frame_window, !info.is_mapped);
+        }
         if !was_known {
             log::info!("  NEW: {:?} (0x{:x}) -> desktop {}",
                 info.wm_name.as_deref().unwrap_or("?"), info.frame_window, assigned);
@@ -451,6 +966,9 @@ fn run() -> Result<()> {
                 info.wm_name.as_deref().unwrap_or("?"), info.frame_window, assigned);
         }
     }
+    windows.retain(|info| !destroyed_frames.contains(&info.frame_window));
+    original_stacking_order.retain(|frame| !destroyed_frames.contains(frame));
+    desktop::remember_identities(&mut desktop_state, &windows, &config.identity_titles);
     desktop_state.save()?;
 
     if windows.is_empty() {
@@ -460,12 +978,45 @@ fn run() -> Result<()> {
 
     let current_desktop = desktop_state.current;
     let current_window_ids: HashSet<Window> = desktop_state
-        .windows_on_desktop(current_desktop)
+        .windows_shown_on(current_desktop)
         .into_iter()
         .collect();
 
+    // Where this desktop's windows are, for RestoreGeometry once they move
+    desktop::record_geometry(&mut desktop_state, &windows, current_desktop);
+
     // Create the overview window (but don't map it yet - wait until captures are complete)
-    let overview = xconn.create_overview_window()?;
+    let mut overview = xconn.create_overview_window(config.theme.theme())?;
+    overview.wallpapers = xconn.load_wallpapers(&config.wallpapers);
+    if config.present {
+        xconn.start_presenting(&mut overview)?;
+    }
+    if config.show_icons {
+        overview.icons = xconn.load_icons(&windows, config.icon_theme.as_deref());
+    }
+    overview.pinned = windows
+        .iter()
+        .map(|info| info.frame_window)
+        .filter(|&frame| desktop_state.is_sticky(frame))
+        .collect();
+    overview.urgent = windows
+        .iter()
+        .filter(|info| info.urgent)
+        .map(|info| info.frame_window)
+        .collect();
+    if !overview.urgent.is_empty() {
+        log::info!("{} windows ask for attention", overview.urgent.len());
+    }
+    overview.restricted = xconn.allowed_actions(&windows)?;
+
+    // Record where off-screen windows belong before moving them, so they can be
+    // put back even if this session is cut off (see recovery.rs)
+    for info in &windows {
+        if !current_window_ids.contains(&info.frame_window) {
+            desktop_state.displaced.insert(info.frame_window.to_string(), info.x);
+        }
+    }
+    desktop_state.save()?;
 
     // Grab the X server while restacking and mapping to avoid intermediate paints.
     xconn.conn.grab_server()?;
@@ -490,17 +1041,23 @@ fn run() -> Result<()> {
                 .sibling(overview.window)
                 .stack_mode(StackMode::BELOW),
         )?;
-        xconn.conn.configure_window(
-            info.client_window,
-            &ConfigureWindowAux::new()
-                .sibling(overview.window)
-                .stack_mode(StackMode::BELOW),
-        )?;
+        // Without reparenting the client is the frame, already restacked above
+        if info.is_reparented() {
+            xconn.conn.configure_window(
+                info.client_window,
+                &ConfigureWindowAux::new()
+                    .sibling(overview.window)
+                    .stack_mode(StackMode::BELOW),
+            )?;
+        }
     }
-    // Map all windows so we can capture them (they will be unmapped on exit as needed)
-    let mapped_any = desktop::map_all_windows(&xconn, &windows)?;
+    // Map all windows so we can capture them (they will be unmapped on exit as needed).
+    // A window manager that keeps the workspaces decides itself what is mapped.
+    let mapped_any = backend.is_none() && desktop::map_all_windows(&xconn, &windows)?;
     xconn.flush()?;
-    log::info!("Mapped all {} windows for live capture", windows.len());
+    if backend.is_none() {
+        log::info!("Mapped all {} windows for live capture", windows.len());
+    }
     // Give X server time to process all maps and make windows ready for capture
     xconn.sync()?;
     if mapped_any {
@@ -509,29 +1066,42 @@ fn run() -> Result<()> {
         xconn.sync()?;
     }
     xconn.conn.ungrab_server()?;
-    // Load saved state and apply consistent ordering
+    // Load saved state and apply consistent ordering, per monitor and desktop
+    let window_lock = WindowState::lock();
     let mut window_state = WindowState::load();
-    let current_hash = WindowState::compute_hash(&windows);
-
-    if current_hash == window_state.window_set_hash {
-        // Same window set - restore saved order
-        log::debug!("Restoring saved window order");
-        window_state.sort_windows(&mut windows);
-    } else {
-        // Window set changed - use new order and update state
-        log::debug!("Window set changed, using fresh layout");
-        window_state.update_from_windows(&windows);
-        window_state.save();
+    let order_changed = window_state.restore_order(&mut windows, |info| {
+        let desktop = desktop_state.get_window_desktop_assignment(info.frame_window).unwrap_or(current_desktop);
+        WindowState::order_group(info, &xconn.monitors, desktop)
+    });
+    let live_frames: Vec<Window> = windows.iter().map(|info| info.frame_window).collect();
+    let groups_changed = window_state.prune_groups(&live_frames);
+    // Release the lock right away; track-focus saves to the same file
+    let saved = window_lock.and_then(|_lock| {
+        if order_changed || groups_changed { window_state.save() } else { Ok(()) }
+    });
+    if let Err(e) = saved {
+        xconn.report_error(format!("Cannot save window order: {}", e));
     }
+    layout_config.arranged = window_state.arranged_grids(&windows, &xconn.monitors, |info| {
+        desktop_state.get_window_desktop_assignment(info.frame_window).unwrap_or(current_desktop)
+    });
+    layout_config.recent = recent_order(&config, &window_state, &windows);
 
     // Capture window contents (managed windows)
     // Failed captures get placeholders that we'll try to upgrade during animation
     let mut captures: Vec<CapturedWindow> = Vec::new();
     let mut placeholder_indices: HashSet<usize> = HashSet::new();
-    for window in &windows {
-        match xconn.capture_window(window) {
+    for (window, captured) in windows.iter().zip(xconn.capture_windows(&windows)) {
+        match captured {
             Ok(capture) => captures.push(capture),
             Err(e) => {
+                // A window destroyed since discovery gets no placeholder
+                if !xconn.window_exists(window.frame_window)? {
+                    log::info!("Window {:?} (0x{:x}) destroyed before capture, dropping it",
+                        window.wm_name, window.frame_window);
+                    destroyed_frames.insert(window.frame_window);
+                    continue;
+                }
                 log::debug!("Capture failed for {:?}, using placeholder: {}", window.wm_name, e);
                 // Create placeholder so window still appears in layout
                 match xconn.create_placeholder_capture(window) {
@@ -539,22 +1109,60 @@ fn run() -> Result<()> {
                         placeholder_indices.insert(captures.len());
                         captures.push(placeholder);
                     }
-                    Err(e2) => log::warn!("Failed to create placeholder for {:?}: {}", window.wm_name, e2),
+                    Err(e2) => xconn.report_error(format!(
+                        "Cannot capture {}: {}",
+                        window.wm_name.as_deref().unwrap_or("window"),
+                        e2
+                    )),
                 }
             }
         }
     }
+    windows.retain(|info| !destroyed_frames.contains(&info.frame_window));
+    original_stacking_order.retain(|frame| !destroyed_frames.contains(frame));
 
     if captures.is_empty() {
         log::info!("No windows could be captured");
-        xconn.destroy_overview(&overview)?;
+        xconn.destroy_overview(overview)?;
         return Ok(());
     }
 
+    // Fullscreen windows keep their first thumbnail when activation over them
+    // is restricted (see fullscreen.rs)
+    let mut paused_captures: HashSet<usize> = HashSet::new();
+    if config.fullscreen_activation != fullscreen::FullscreenActivation::Allow {
+        for (idx, capture) in captures.iter().enumerate() {
+            if xconn.is_fullscreen(capture.info.client_window)? {
+                log::info!("Pausing live updates of fullscreen {:?}", capture.info.wm_name);
+                paused_captures.insert(idx);
+            }
+        }
+    }
+
+    // Windows that were already mapped have valid contents. The rest were just
+    // mapped and stay black until they repaint, so show their cached snapshot
+    // until the first damage event (or placeholder upgrade) brings in live contents.
+    let mut live_captures: HashSet<usize> = HashSet::new();
+    for (idx, capture) in captures.iter_mut().enumerate() {
+        let is_placeholder = placeholder_indices.contains(&idx);
+        if capture.info.is_mapped && !is_placeholder {
+            live_captures.insert(idx);
+            continue;
+        }
+        match xconn.apply_cached_thumbnail(capture) {
+            Ok(true) => {
+                // Damage refreshes it once the window is viewable
+                placeholder_indices.remove(&idx);
+            }
+            Ok(false) => {}
+            Err(e) => log::debug!("Cached thumbnail unusable for {:?}: {}", capture.info.wm_name, e),
+        }
+    }
+
     // Capture skipped windows (for fade effect) - no placeholders needed
     let mut skipped_captures: Vec<CapturedWindow> = Vec::new();
-    for window in &skipped_windows {
-        match xconn.capture_window(window) {
+    for (window, captured) in skipped_windows.iter().zip(xconn.capture_windows(&skipped_windows)) {
+        match captured {
             Ok(capture) => skipped_captures.push(capture),
             Err(e) => log::debug!("Skipped window {:?} not captured: {}", window.wm_name, e),
         }
@@ -615,28 +1223,34 @@ fn run() -> Result<()> {
         );
     }
 
-    // Calculate layout for windows on the current desktop only
-    let config = LayoutConfig::default();
-    let grid_indices: Vec<usize> = captures
+    // Groups show as piles until expanded
+    let mut expanded_groups: HashSet<String> = HashSet::new();
+    let (mut piles, pile_labels) = collapsed_groups(&captures, &window_state.groups, &expanded_groups);
+    overview.piles = pile_labels;
+
+    // Calculate layout for windows on the current desktop only, with the
+    // minimized ones in a strip of their own
+    let off_grid: HashSet<usize> = captures
         .iter()
         .enumerate()
-        .filter(|(_, capture)| current_window_ids.contains(&capture.info.frame_window))
+        .filter(|(_, capture)| {
+            let frame = capture.info.frame_window;
+            !current_window_ids.contains(&frame) || desktop_state.is_app_hidden(frame)
+        })
         .map(|(i, _)| i)
         .collect();
-    let grid_infos: Vec<window_finder::WindowInfo> = grid_indices
-        .iter()
-        .map(|&idx| captures[idx].info.clone())
-        .collect();
-    let mut layouts = calculate_layout(
-        &grid_infos,
+    let mut layouts = recalculate_filtered_layout(
+        &captures,
+        &off_grid,
+        &minimized_windows(&captures, &HashSet::new(), &HashSet::new(), &desktop_state),
+        &piles,
+        None,
+        &monitors,
+        &layout_config,
+        reserved,
         xconn.screen_width,
         xconn.screen_height,
-        &config,
-        bar_height,
     );
-    for (layout, &capture_idx) in layouts.iter_mut().zip(grid_indices.iter()) {
-        layout.window_index = capture_idx;
-    }
 
     // Debug: print layout positions
     for (i, layout) in layouts.iter().enumerate() {
@@ -653,19 +1267,12 @@ fn run() -> Result<()> {
     }
 
     // Run entrance animation
-    let start_layouts: Vec<AnimatedLayout> = grid_infos
+    let start_layouts: Vec<AnimatedLayout> = layouts
         .iter()
-        .zip(grid_indices.iter())
-        .map(|(info, &capture_idx)| AnimatedLayout {
-            x: info.x,
-            y: info.y,
-            width: info.width,
-            height: info.height,
-            window_index: capture_idx,
-        })
+        .map(|layout| AnimatedLayout::from(&window_rect(&captures, layout.window_index)))
         .collect();
 
-    let animator = Animator::new(start_layouts, layouts.clone(), &entrance_anim);
+    let mut animator = Animator::new(start_layouts, layouts.clone(), &entrance_anim);
 
     // Build render order from original Z-order (bottom to top)
     let render_order: Vec<usize> = original_stacking_order
@@ -681,14 +1288,13 @@ fn run() -> Result<()> {
         let current = animator.current_layouts();
         xconn.clear_overview(&overview)?;
         if let Some(ref bar) = desktop_bar {
-            let bar_y_offset = -(bar_height as i16);
-            render_desktop_bar(&xconn, &overview, bar, bar_y_offset, None, None, &captures)?;
+            render_desktop_bar(&xconn, &overview, bar, bar.slide_offset(1.0), None, None, &captures)?;
         }
         // Render skipped windows at full opacity (matches progress=0 in animation loop).
         for capture in &skipped_captures {
             xconn.render_window_with_opacity(
-                capture.picture,
-                overview.picture,
+                capture.picture.id(),
+                overview.picture.id(),
                 capture.info.x,
                 capture.info.y,
                 capture.info.width,
@@ -699,13 +1305,8 @@ fn run() -> Result<()> {
         for &layout_idx in &render_order {
             let layout = &current[layout_idx];
             let idx = layout.window_index;
-            xconn.render_thumbnail_animated(
-                captures[idx].picture,
-                overview.picture,
-                captures[idx].info.width,
-                captures[idx].info.height,
-                layout,
-            )?;
+            let (picture, width, height) = xconn.thumbnail_source(&captures[idx], layout.width, layout.height);
+            xconn.render_thumbnail_animated(picture, overview.picture.id(), width, height, layout)?;
             xconn.draw_thumbnail_border_animated(&overview, layout, false)?;
         }
 
@@ -725,7 +1326,7 @@ fn run() -> Result<()> {
     xconn.conn.grab_pointer(
         true,
         overview.window,
-        (EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION).into(),
+        EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION,
         GrabMode::ASYNC,
         GrabMode::ASYNC,
         overview.window,
@@ -743,16 +1344,15 @@ fn run() -> Result<()> {
 
         // Render desktop bar (with slide-in animation)
         if let Some(ref bar) = desktop_bar {
-            let bar_y_offset = (-(bar_height as f64) * (1.0 - progress)) as i16;
-            render_desktop_bar(&xconn, &overview, bar, bar_y_offset, None, None, &captures)?;
+            render_desktop_bar(&xconn, &overview, bar, bar.slide_offset(1.0 - progress), None, None, &captures)?;
         }
 
         // Render skipped windows with fading opacity (1.0 → 0.0)
         let skip_opacity = 1.0 - progress;
         for capture in &skipped_captures {
             xconn.render_window_with_opacity(
-                capture.picture,
-                overview.picture,
+                capture.picture.id(),
+                overview.picture.id(),
                 capture.info.x,
                 capture.info.y,
                 capture.info.width,
@@ -765,38 +1365,85 @@ fn run() -> Result<()> {
         for &layout_idx in &render_order {
             let layout = &current[layout_idx];
             let idx = layout.window_index;
-            xconn.render_thumbnail_animated(
-                captures[idx].picture,
-                overview.picture,
-                captures[idx].info.width,
-                captures[idx].info.height,
-                layout,
-            )?;
+            let (picture, width, height) = xconn.thumbnail_source(&captures[idx], layout.width, layout.height);
+            xconn.render_thumbnail_animated(picture, overview.picture.id(), width, height, layout)?;
             xconn.draw_thumbnail_border_animated(&overview, layout, false)?;
         }
 
         xconn.present_overview(&overview)?;
-        thread::sleep(animator.frame_duration());
+        xconn.sync()?;
+        thread::sleep(animator.frame_wait());
     }
+    if animator.skipped_frames() > 0 {
+        log::info!("Entrance animation fell behind, skipped {} frames", animator.skipped_frames());
+    }
+
+    // The window last picked on this desktop starts out highlighted, so
+    // Enter goes straight back to it
+    let last_selected = desktop_state.last_selected(current_desktop).and_then(|frame| {
+        layouts.iter().map(|l| l.window_index).find(|&i| {
+            captures[i].info.frame_window == frame && desktop_state.is_visible_on(frame, current_desktop)
+        })
+    });
 
     // Render final static state
     if let Some(ref bar) = desktop_bar {
-        render_desktop_bar(&xconn, &overview, bar, 0, None, None, &captures)?;
+        render_desktop_bar(&xconn, &overview, bar, (0, 0), None, None, &captures)?;
     }
-    render_all_thumbnails(&xconn, &captures, &layouts, &overview, None, None)?;
+    render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_selected, None)?;
     xconn.present_overview(&overview)?;
 
     log::info!("Overview displayed, waiting for input");
 
+    // Follow resolution/output changes while the overview is open
+    xconn.watch_screen_changes()?;
+    // Desktop switch requests from pagers and wmctrl
+    xconn.watch_root_client_messages()?;
+
+    if options.demo {
+        demo::spawn(demo::DemoScene {
+            thumbnails: layouts
+                .iter()
+                .map(|l| (l.x + (l.width / 2) as i16, l.y + (l.height / 2) as i16))
+                .collect(),
+            desktops: (0..desktop_state.desktops)
+                .filter_map(|d| desktop_bar.as_ref()?.get_preview_center(d))
+                .collect(),
+            current_desktop,
+        });
+    }
+
     // Event loop
+    let keymap = xconn.load_keymap()?;
     let mut input_handler = InputHandler::new(layouts.clone(), desktop_bar.clone());
+    input_handler.set_buttons(config.buttons);
+    input_handler.set_hit_tolerance(config.hit_tolerance);
+    input_handler.set_monitors(monitors.clone());
+    input_handler.set_restricted(
+        captures
+            .iter()
+            .enumerate()
+            .filter_map(|(index, capture)| Some((index, *overview.restricted.get(&capture.info.frame_window)?)))
+            .collect(),
+    );
+    input_handler.set_hovered(last_selected);
     let mut selected_window: Option<usize> = None;
+    // Maximize (or restore) the selected window on the way out
+    let mut toggle_maximized = false;
+    // Or tile it where it was dropped
+    let mut tile_to: Option<Geometry> = None;
+    // Or minimize every window on the desktop (or bring them back)
+    let mut show_desktop = false;
     let mut selected_desktop: Option<u32> = None;
-    let mut last_hovered: Option<usize> = None;
+    let mut last_hovered: Option<usize> = last_selected;
+    // Highlight last described for screen readers (see announce.rs)
+    let mut announced: Option<usize> = None;
     let mut should_exit = false;
+    // Background click to forward once the overview is gone (ClickThrough)
+    let mut replay_click: Option<(i16, i16, u8)> = None;
 
-    // Track which windows have pending damage (for batching updates)
-    let mut damaged_windows: HashSet<usize> = HashSet::new();
+    // Windows with pending damage, redrawn once per tick (see damage.rs)
+    let mut damaged_windows = DamageQueue::default();
 
     // Drag animation state
     let mut drag_animation: Option<DragAnimation> = None;
@@ -810,21 +1457,126 @@ fn run() -> Result<()> {
         })
         .map(|(i, _)| i)
         .collect();
+    // Whether the grid shows every desktop's windows, grouped by desktop
+    let mut all_desktops = false;
 
     // Grid transition animation state
     let mut grid_transition_animation: Option<GridTransitionAnimation> = None;
 
+    // Captures whose window was destroyed while the overview was up
+    let mut destroyed_captures: HashSet<usize> = HashSet::new();
+    // Captures whose window the window filter left out after a config reload
+    let mut filtered_captures: HashSet<usize> = HashSet::new();
+    // Every re-layout goes by the same state: which windows are left out of
+    // the grid (on other desktops, destroyed or filtered out), minimized or
+    // piled, and where the grid goes. Its labels pick their colors again
+    // wherever they end up.
+    macro_rules! refresh_removed {
+        () => {
+            refresh_removed_windows(
+                &xconn,
+                &captures,
+                &destroyed_captures,
+                &filtered_captures,
+                &desktop_state,
+                all_desktops,
+                &mut removed_windows,
+            )
+        };
+    }
+    macro_rules! relayout {
+        () => {{
+            overview.forget_label_colors();
+            recalculate_filtered_layout(
+                &captures,
+                &removed_windows,
+                &minimized_windows(&captures, &destroyed_captures, &filtered_captures, &desktop_state),
+                &piles,
+                all_desktops.then_some(&desktop_state),
+                &monitors,
+                &layout_config,
+                reserved,
+                xconn.screen_width,
+                xconn.screen_height,
+            )
+        }};
+    }
+    // Window marked to be killed, drawn in red until confirmed or spared
+    let mut kill_pending: Option<usize> = None;
+
     // Desktop drag state
     let mut desktop_dragging: Option<u32> = None;
     let mut desktop_insert_position: Option<u32> = None;
-    let mut desktop_drag_cursor_x: i16 = 0;
+    let mut desktop_drag_cursor: i16 = 0;
     let mut desktop_bar_animation: Option<DesktopBarAnimation> = None;
+    let mut grid_zoom: Option<GridZoom> = None;
     let mut drag_gap_animation: Option<DragGapAnimation> = None;
 
+    // Actions raised by the overview itself, handled before the next events
+    let mut pending_actions: Vec<InputAction> = Vec::new();
+    // Set when a window dropped with Shift lands: switch to its desktop and exit
+    let mut follow_pending = false;
+
+    // Zoomed-out desktop grid, and the window miniature dragged in it
+    let mut desktop_grid: Option<DesktopGrid> = None;
+    let mut grid_drag: Option<(Window, i16, i16)> = None;
+
+    // Hover peek: when the pointer came to rest on the highlighted thumbnail,
+    // and the window shown full-size since it has been there PeekMs
+    let mut hovered_since: Option<Instant> = None;
+    let mut peeking: Option<usize> = None;
+    // The window whose tooltip is up since it has been there TooltipMs, and
+    // what the tooltip says
+    let mut tooltip: Option<(usize, Vec<String>)> = None;
+    // Where the window in the spotlight (Shift+click) is shown enlarged
+    let mut spotlight: Option<ThumbnailLayout> = None;
+    // Where the window in quick look (Space) is shown, once grown there
+    let mut quick_look: Option<ThumbnailLayout> = None;
+    // When the next animation frame is due, while anything is animating
+    let mut next_frame: Option<Instant> = None;
+    let mut frame_pacer = FramePacer::new(FRAME_INTERVAL);
+    // Urgent windows' borders pulse from the time the grid is up
+    let pulse_start = Instant::now();
+    let mut next_pulse: Option<Instant> = None;
+
     loop {
-        // Process all pending events (non-blocking after first)
-        let event = xconn.conn.wait_for_event()?;
-        let mut events = vec![event];
+        // A followed window has landed; leave once queued removals are done,
+        // as they can renumber its desktop
+        if follow_pending && pending_actions.is_empty() {
+            selected_desktop = Some(desktop_state.current);
+            break;
+        }
+
+        // Process all pending events (non-blocking after first). Wait no
+        // longer than until the next animation frame, hover peek or damage
+        // repaint is due, so those run whether or not events come in.
+        let mut events = Vec::new();
+        if pending_actions.is_empty() {
+            let now = Instant::now();
+            let peek_in = hovered_since
+                .filter(|_| config.peek_ms > 0 && peeking.is_none())
+                .map(|since| std::time::Duration::from_millis(config.peek_ms).saturating_sub(since.elapsed()));
+            let tooltip_in = hovered_since
+                .filter(|_| config.tooltip_ms > 0 && tooltip.is_none() && peeking.is_none())
+                .map(|since| std::time::Duration::from_millis(config.tooltip_ms).saturating_sub(since.elapsed()));
+            let frame_in = next_frame.map(|at| at.saturating_duration_since(now));
+            let pulse_in = next_pulse.map(|at| at.saturating_duration_since(now));
+            let timeout = [peek_in, tooltip_in, damaged_windows.due_in(now), frame_in, pulse_in]
+                .into_iter()
+                .flatten()
+                .min();
+            events.extend(xconn.wait_for_event_or_signal(signals.as_ref(), timeout)?);
+        }
+        // SIGUSR1 and SIGTERM close the overview, SIGUSR2 leaves it for the next desktop
+        for request in signals.iter().flat_map(|signals| signals.take()) {
+            log::info!("Signal: {:?}", request);
+            pending_actions.push(match request {
+                signals::Request::Toggle | signals::Request::Quit => InputAction::Dismiss,
+                signals::Request::NextDesktop => {
+                    InputAction::ActivateDesktop((desktop_state.current + 1) % desktop_state.desktops)
+                }
+            });
+        }
 
         // Collect any additional pending events to batch damage updates
         while let Some(event) = xconn.conn.poll_for_event()? {
@@ -832,32 +1584,135 @@ fn run() -> Result<()> {
         }
 
         let mut needs_present = false;
+        let mut screen_changed = false;
 
-        for event in events {
-            // Check if this is a DamageNotify event
-            if let Event::DamageNotify(ref damage_event) = event {
-                // Find which capture this damage belongs to
-                if let Some(idx) = captures.iter().position(|c| c.damage == damage_event.damage) {
-                    damaged_windows.insert(idx);
-                    // Subtract damage to acknowledge it
-                    xdamage::subtract(&xconn.conn, damage_event.damage, x11rb::NONE, x11rb::NONE)?;
-                }
-                continue;
+        // Groups made, left or expanded: the piles need laying out again
+        let mut regrouped = false;
+
+        // Edited, or asked for through `xpose daemon` or D-Bus (see service.rs)
+        let reload_requested = events
+            .iter()
+            .any(|event| matches!(event, Event::ClientMessage(e) if xconn.reload_config_request(e)));
+        let config_changed = config_watcher.as_ref().is_some_and(|watcher| watcher.changed());
+        if let Some(reloaded) = (config_changed || reload_requested).then(Config::reload).flatten() {
+            // The bar height only applies at the next launch, as do windows
+            // the filter now lets in: they have no captures
+            config = reloaded;
+            log::info!("Config reloaded");
+            (exit_anim, snap_duration_ms, revert_duration_ms, grid_transition_duration_ms) =
+                session_durations(&config);
+            input_handler.set_buttons(config.buttons);
+            input_handler.set_hit_tolerance(config.hit_tolerance);
+            overview.wallpapers = xconn.load_wallpapers(&config.wallpapers);
+            xconn.set_overview_theme(&mut overview, config.theme.theme())?;
+
+            let filter = window_filter(&config, &app_classes);
+            filtered_captures = (0..captures.len()).filter(|&i| filter.excludes(&captures[i].info)).collect();
+            refresh_removed!()?;
+            if last_hovered.is_some_and(|index| removed_windows.contains(&index)) {
+                last_hovered = None;
+                input_handler.set_hovered(None);
             }
+            regrouped = true;
 
-            let action = match event {
-                Event::ButtonPress(ref e) => input_handler.handle_button_press(e),
-                Event::ButtonRelease(ref e) => input_handler.handle_button_release(e),
-                Event::KeyPress(ref e) => input_handler.handle_key_press(e),
-                Event::MotionNotify(ref e) => input_handler.handle_motion(e),
-                Event::Expose(_) => {
-                    needs_present = true;
-                    InputAction::None
+            xconn.clear_overview(&overview)?;
+            if let Some(ref bar) = desktop_bar {
+                render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+            }
+            render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, None)?;
+            needs_present = true;
+        }
+        let mut vanished: Vec<usize> = Vec::new();
+        // The grid changed desktops in place: slide the old one out that way
+        let mut grid_slide: Option<SlideDirection> = None;
+
+        // Queued actions go first, as if they had come in before these events
+        let queued = std::mem::take(&mut pending_actions);
+        for input in queued.into_iter().map(LoopInput::Queued).chain(events.into_iter().map(LoopInput::Event)) {
+            let action = match input {
+                LoopInput::Queued(action) => action,
+                LoopInput::Event(event) => {
+                    // Check if this is a DamageNotify event
+                    if let Event::DamageNotify(ref damage_event) = event {
+                        // Find which capture this damage belongs to
+                        if let Some(idx) = captures.iter().position(|c| c.has_damage(damage_event.damage)) {
+                            if !paused_captures.contains(&idx) {
+                                damaged_windows.mark(idx);
+                            }
+                            // Subtract damage to acknowledge it
+                            xdamage::subtract(&xconn.conn, damage_event.damage, x11rb::NONE, x11rb::NONE)?;
+                        }
+                        continue;
+                    }
+
+                    match event {
+                        Event::ButtonPress(ref e) => input_handler.handle_button_press(e),
+                        Event::ButtonRelease(ref e) => input_handler.handle_button_release(e),
+                        Event::KeyPress(ref e) => input_handler.handle_key_press(e, &keymap),
+                        Event::MotionNotify(ref e) => input_handler.handle_motion(e),
+                        Event::Expose(_) => {
+                            needs_present = true;
+                            InputAction::None
+                        }
+                        Event::Error(ref e) => {
+                            xconn.record_async_error(e);
+                            // A BadWindow naming one of our windows means it died under us
+                            if error::is_bad_window(e) {
+                                if let Some(idx) = captures.iter().position(|c| {
+                                    c.info.frame_window == e.bad_value || c.info.client_window == e.bad_value
+                                }) {
+                                    if !destroyed_captures.contains(&idx) && !xconn.window_exists(captures[idx].info.frame_window)? {
+                                        vanished.push(idx);
+                                    }
+                                }
+                            }
+                            InputAction::None
+                        }
+                        Event::DestroyNotify(ref e) => {
+                            xconn.clients.borrow_mut().forget(e.window);
+                            if let Some(idx) = captures.iter().position(|c| c.info.frame_window == e.window) {
+                                vanished.push(idx);
+                            }
+                            InputAction::None
+                        }
+                        Event::ReparentNotify(ref e) => {
+                            let mut clients = xconn.clients.borrow_mut();
+                            clients.forget(e.window);
+                            clients.forget(e.parent);
+                            InputAction::None
+                        }
+                        Event::RandrScreenChangeNotify(_) => {
+                            screen_changed = true;
+                            InputAction::None
+                        }
+                        Event::ClientMessage(ref e) if xconn.dismiss_request(e) => {
+                            log::info!("Dismiss requested");
+                            InputAction::Dismiss
+                        }
+                        Event::ClientMessage(ref e) => match xconn.showing_desktop_request(e) {
+                            // Only a change of state toggles
+                            Some(show) if show == desktop_state.desktop_shown_on(desktop_state.current).is_empty() => {
+                                InputAction::ShowDesktop
+                            }
+                            Some(_) => InputAction::None,
+                            None => match xconn.desktop_switch_request(e) {
+                                Some(desktop) if desktop < desktop_state.desktops => InputAction::ActivateDesktop(desktop),
+                                _ => InputAction::None,
+                            },
+                        },
+                        _ => InputAction::None,
+                    }
                 }
-                _ => InputAction::None,
             };
 
             match action {
+                InputAction::SelectWindow(index) if overview.piles.contains_key(&captures[index].info.frame_window) => {
+                    // Picking a pile spreads its windows out in the grid
+                    let (name, _) = &overview.piles[&captures[index].info.frame_window];
+                    log::info!("Expanding group {}", name);
+                    expanded_groups.insert(name.clone());
+                    regrouped = true;
+                }
                 InputAction::SelectWindow(index) => {
                     log::info!("Selected window {} (was hovering: {:?})", index, last_hovered);
                     if let Some(hover_idx) = last_hovered {
@@ -866,12 +1721,47 @@ fn run() -> Result<()> {
                         }
                     }
                     selected_window = Some(index);
+                    // Picking a minimized window restores it
+                    desktop_state.set_app_hidden(captures[index].info.frame_window, false);
+                    should_exit = true;
+                }
+                InputAction::SelectMaximized(index) => {
+                    log::info!("Selected window {}, to be maximized or restored", index);
+                    selected_window = Some(index);
+                    desktop_state.set_app_hidden(captures[index].info.frame_window, false);
+                    toggle_maximized = true;
+                    should_exit = true;
+                }
+                InputAction::TileWindow(index, geometry) => {
+                    log::info!("Selected window {}, to be tiled", index);
+                    selected_window = Some(index);
+                    desktop_state.set_app_hidden(captures[index].info.frame_window, false);
+                    tile_to = Some(geometry);
+                    // Fly out from where it was dropped, not from its grid slot
+                    if let (Some((x, y, width, height)), Some(layout)) =
+                        (last_drag_rect.take(), layouts.iter_mut().find(|l| l.window_index == index))
+                    {
+                        (layout.x, layout.y, layout.width, layout.height) = (x, y, width, height);
+                    }
+                    dragging_window_index = None;
                     should_exit = true;
                 }
                 InputAction::Dismiss => {
                     log::info!("Dismissed");
                     should_exit = true;
                 }
+                InputAction::ShowDesktop => {
+                    log::info!("Showing the desktop");
+                    show_desktop = true;
+                    should_exit = true;
+                }
+                InputAction::DismissClick(x, y, button) => {
+                    log::info!("Dismissed by background click");
+                    if config.click_through {
+                        replay_click = Some((x, y, button));
+                    }
+                    should_exit = true;
+                }
                 InputAction::Hover(new_hover) => {
                     // Redraw affected thumbnails
                     if new_hover != last_hovered {
@@ -888,35 +1778,85 @@ fn run() -> Result<()> {
                         }
                         needs_present = true;
                         last_hovered = new_hover;
+                        hovered_since = new_hover.map(|_| Instant::now());
+                    }
+                }
+                InputAction::Highlight(window, desktop) => {
+                    if window != last_hovered {
+                        if let Some(old_idx) = last_hovered {
+                            redraw_thumbnail(&xconn, &captures, &layouts, &overview, old_idx, false)?;
+                        }
+                        if let Some(new_idx) = window {
+                            redraw_thumbnail(&xconn, &captures, &layouts, &overview, new_idx, true)?;
+                        }
+                        last_hovered = window;
+                        hovered_since = window.map(|_| Instant::now());
                     }
+                    if let Some(ref bar) = desktop_bar {
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), desktop, None, &captures)?;
+                    }
+                    needs_present = true;
                 }
                 InputAction::ActivateDesktop(idx) => {
                     log::info!("Switching to desktop {}", idx);
                     // Update state (windows stay mapped while xpose is active for live capture)
                     desktop_state.current = idx;
                     desktop_state.sync_to_x(&xconn)?;
-                    desktop_state.save()?;
+                    if let Err(e) = desktop_state.save() {
+                        xconn.report_error(format!("Cannot save desktop state: {}", e));
+                    }
 
                     // Store selected desktop for zoom animation on exit
                     selected_desktop = Some(idx);
                     should_exit = true;
                 }
-                InputAction::ClickPlusButton => {
-                    log::info!("Adding new desktop");
-
-                    // Store old bar for animation
-                    let old_bar = desktop_bar.clone();
+                InputAction::ClickPlusButton | InputAction::RequestDeleteDesktop(_) | InputAction::DeleteDesktop(_)
+                    if backend.is_some() =>
+                {
+                    report_workspaces_kept(&xconn, backend.as_deref());
+                }
+                InputAction::DropDesktopAt(..) | InputAction::MoveAllWindows(..) if backend.is_some() => {
+                    report_workspaces_kept(&xconn, backend.as_deref());
+                    pending_actions.push(InputAction::CancelDesktopDrag);
+                }
+                InputAction::ClickPlusButton | InputAction::DropOnNewDesktop(_) => {
+                    let dropped = match action {
+                        InputAction::DropOnNewDesktop(window_idx) => Some(window_idx),
+                        _ => None,
+                    };
+                    if desktop_state.desktops >= desktop::state::MAX_DESKTOPS || backend.is_some() {
+                        if backend.is_some() {
+                            report_workspaces_kept(&xconn, backend.as_deref());
+                        } else {
+                            log::info!("Desktop limit reached, not adding another");
+                        }
+                        if let (Some(window_idx), Some(rect)) = (dropped, last_drag_rect) {
+                            // Nowhere to put it; send the window back to the grid
+                            drag_animation = revert_animation(&layouts, window_idx, rect, revert_duration_ms, config.revert_easing);
+                            last_drag_rect = None;
+                        }
+                        if drag_animation.is_none() {
+                            dragging_window_index = None;
+                        }
+                        needs_present = true;
+                        continue;
+                    }
+                    log::info!("Adding new desktop");
+
+                    // Store old bar for animation
+                    let old_bar = desktop_bar.clone();
                     let old_count = desktop_state.desktops;
 
                     let new_count = desktop_state.desktops + 1;
                     desktop::set_desktop_count(&xconn, &mut desktop_state, &windows, new_count)?;
 
                     // Recreate desktop bar with new desktop count
-                    desktop_bar = Some(DesktopBar::new(
+                    desktop_bar = bar_area.map(|area| DesktopBar::new(
                         desktop_state.desktops,
                         desktop_state.current,
-                        xconn.screen_width,
+                        area,
                         bar_height,
+                        bar_position,
                     ));
 
                     // Create animation: existing desktops slide to new positions, new one appears
@@ -928,20 +1868,23 @@ fn run() -> Result<()> {
                                 if let Some(old_preview) = old.preview_layouts.iter()
                                     .find(|p| p.desktop_index == new_preview.desktop_index)
                                 {
-                                    if old_preview.x != new_preview.x {
-                                        transitions.insert(new_preview.desktop_index, (old_preview.x, new_preview.x));
+                                    let (from, to) = (old.preview_offset(old_preview), new_bar.preview_offset(new_preview));
+                                    if from != to {
+                                        transitions.insert(new_preview.desktop_index, (from, to));
                                     }
                                 }
                             } else {
-                                // New desktop - animate from right edge
-                                transitions.insert(new_preview.desktop_index, (xconn.screen_width as i16, new_preview.x));
+                                // New desktop - animate in from the far end of the bar
+                                transitions.insert(new_preview.desktop_index, (new_bar.end(), new_bar.preview_offset(new_preview)));
                             }
                         }
                         if !transitions.is_empty() {
                             desktop_bar_animation = Some(DesktopBarAnimation {
                                 transitions,
+                                flights: Vec::new(),
                                 start_time: std::time::Instant::now(),
                                 duration_ms: 200,
+                                easing: config.grid_easing,
                             });
                         }
                     }
@@ -956,52 +1899,81 @@ fn run() -> Result<()> {
                     }
                     input_handler.update_desktop_bar(desktop_bar.clone());
 
-                    // Animate the desktop bar
-                    if let (Some(ref anim), Some(ref bar)) = (&desktop_bar_animation, &desktop_bar) {
-                        while !anim.is_complete() {
-                            xconn.clear_overview(&overview)?;
-                            render_desktop_bar_animated(
-                                &xconn,
-                                &overview,
-                                bar,
-                                anim,
-                                &captures,
-                            )?;
-                            render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
-                            xconn.present_overview(&overview)?;
-                            std::thread::sleep(std::time::Duration::from_millis(16));
-                        }
-                    }
-                    desktop_bar_animation = None;
-
-                    // Final redraw
+                    // The previews slide from the next frame on
                     xconn.clear_overview(&overview)?;
                     if let Some(ref bar) = desktop_bar {
-                        render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                     }
                     render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+
+                    // A window dropped on the plus button moves to the new desktop
+                    if let Some(window_idx) = dropped {
+                        let new_desktop = desktop_state.desktops - 1;
+                        log::info!("Dropped window {} on new desktop {}", window_idx, new_desktop);
+                        if let (Some(rect), Some(ref bar)) = (last_drag_rect, &desktop_bar) {
+                            drag_animation = snap_animation(bar, &captures, window_idx, new_desktop, rect, snap_duration_ms, config.snap_easing, false);
+                        }
+                        if drag_animation.is_none() {
+                            dragging_window_index = None;
+                        }
+                        last_drag_rect = None;
+                    }
                     needs_present = true;
                 }
+                InputAction::RequestDeleteDesktop(idx) => {
+                    if desktop_state.is_desktop_empty(idx) {
+                        pending_actions.push(InputAction::DeleteDesktop(idx));
+                    } else if let Some(ref mut bar) = desktop_bar {
+                        // Say where the windows will end up before merging them
+                        let count = desktop_state.windows_on_desktop(idx).len();
+                        let target = if idx == 0 { 1 } else { idx - 1 };
+                        let target_name = match desktop_state.name(target) {
+                            Some(name) => name.to_string(),
+                            None => format!("desktop {}", target + 1),
+                        };
+                        let message = format!(
+                            "Move {} window{} to {}?",
+                            count,
+                            if count == 1 { "" } else { "s" },
+                            target_name
+                        );
+                        log::info!("Confirming deletion of desktop {}: {}", idx, message);
+                        bar.delete_confirm = Some(DeleteConfirm { desktop_index: idx, message });
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                        input_handler.update_desktop_bar(desktop_bar.clone());
+                        needs_present = true;
+                    }
+                }
+                InputAction::CancelDeleteDesktop(idx) => {
+                    log::info!("Keeping desktop {}", idx);
+                    if let Some(ref mut bar) = desktop_bar {
+                        bar.delete_confirm = None;
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                        needs_present = true;
+                    }
+                    input_handler.update_desktop_bar(desktop_bar.clone());
+                }
                 InputAction::DeleteDesktop(idx) => {
                     log::info!("Deleting desktop {}", idx);
 
                     // Can't delete the last desktop
                     if desktop_state.desktops <= 1 {
-                        log::warn!("Cannot delete the last desktop");
+                        xconn.report_error("Cannot delete the last desktop");
                     } else {
                         // Store old bar positions for animation
                         let old_bar = desktop_bar.clone();
 
                         // Perform deletion
                         if let Err(e) = desktop::delete_desktop(&xconn, &mut desktop_state, idx) {
-                            log::warn!("Failed to delete desktop: {}", e);
+                            xconn.report_error(format!("Failed to delete desktop: {}", e));
                         } else {
                             // Recreate desktop bar with new count
-                            desktop_bar = Some(DesktopBar::new(
+                            desktop_bar = bar_area.map(|area| DesktopBar::new(
                                 desktop_state.desktops,
                                 desktop_state.current,
-                                xconn.screen_width,
+                                area,
                                 bar_height,
+                                bar_position,
                             ));
 
                             // Start slide animation for remaining desktops
@@ -1017,16 +1989,19 @@ fn run() -> Result<()> {
                                     if let Some(old_preview) = old.preview_layouts.iter()
                                         .find(|p| p.desktop_index == old_idx)
                                     {
-                                        if old_preview.x != new_preview.x {
-                                            transitions.insert(new_preview.desktop_index, (old_preview.x, new_preview.x));
+                                        let (from, to) = (old.preview_offset(old_preview), new_bar.preview_offset(new_preview));
+                                        if from != to {
+                                            transitions.insert(new_preview.desktop_index, (from, to));
                                         }
                                     }
                                 }
                                 if !transitions.is_empty() {
                                     desktop_bar_animation = Some(DesktopBarAnimation {
                                         transitions,
+                                        flights: Vec::new(),
                                         start_time: std::time::Instant::now(),
                                         duration_ms: 250,
+                                        easing: config.grid_easing,
                                     });
                                 }
                             }
@@ -1041,65 +2016,23 @@ fn run() -> Result<()> {
                             }
                             input_handler.update_desktop_bar(desktop_bar.clone());
 
-                            // Update removed_windows based on new desktop state
-                            let old_removed = removed_windows.clone();
-                            removed_windows = captures
-                                .iter()
-                                .enumerate()
-                                .filter(|(_, capture)| {
-                                    !desktop_state.is_visible_on(capture.info.frame_window, desktop_state.current)
-                                })
-                                .map(|(i, _)| i)
-                                .collect();
-
-                            // Move windows that are now on current desktop back on-screen
-                            // (they were moved off-screen at startup because they were on other desktops)
-                            for (i, capture) in captures.iter().enumerate() {
-                                if old_removed.contains(&i) && !removed_windows.contains(&i) {
-                                    // This window was off-screen but is now on current desktop
-                                    log::info!("Moving window {:?} back on-screen after desktop deletion",
-                                              capture.info.wm_name);
-                                    xconn.conn.configure_window(
-                                        capture.info.frame_window,
-                                        &ConfigureWindowAux::new().x(capture.info.x as i32),
-                                    )?;
-                                }
+                            if desktop_grid.is_some() {
+                                desktop_grid = Some(build_desktop_grid(&xconn, &captures, &desktop_state));
+                                input_handler.update_desktop_grid(desktop_grid.clone());
                             }
-                            xconn.flush()?;
+
+                            // Update removed_windows based on new desktop state
+                            refresh_removed!()?;
+                            overview.desktop_labels = desktop_labels(&captures, &desktop_state, all_desktops);
 
                             // Recalculate grid layout for current desktop
-                            layouts = recalculate_filtered_layout(
-                                &captures,
-                                &removed_windows,
-                                xconn.screen_width,
-                                xconn.screen_height,
-                                &config,
-                                bar_height,
-                            );
+                            layouts = relayout!();
                             input_handler.update_layouts(layouts.clone());
 
-                            // Animate the desktop bar slide
-                            if let (Some(ref anim), Some(ref bar)) = (&desktop_bar_animation, &desktop_bar) {
-                                while !anim.is_complete() {
-                                    xconn.clear_overview(&overview)?;
-                                    render_desktop_bar_animated(
-                                        &xconn,
-                                        &overview,
-                                        bar,
-                                        anim,
-                                        &captures,
-                                    )?;
-                                    render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
-                                    xconn.present_overview(&overview)?;
-                                    std::thread::sleep(std::time::Duration::from_millis(16));
-                                }
-                            }
-                            desktop_bar_animation = None;
-
-                            // Final redraw
+                            // The previews slide from the next frame on
                             xconn.clear_overview(&overview)?;
                             if let Some(ref bar) = desktop_bar {
-                                render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                                render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                             }
                             render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
                             needs_present = true;
@@ -1115,17 +2048,36 @@ fn run() -> Result<()> {
                         let initial_positions: Vec<(u32, i16)> = bar.preview_layouts
                             .iter()
                             .filter(|p| p.desktop_index != desktop_idx)
-                            .map(|p| (p.desktop_index, p.x))
+                            .map(|p| (p.desktop_index, bar.preview_offset(p)))
                             .collect();
                         drag_gap_animation = Some(DragGapAnimation::new(&initial_positions));
+
+                        // Lift it where it is; picked up from the keyboard,
+                        // no pointer motion follows to draw it
+                        if let Some((x, y)) = bar.get_preview_center(desktop_idx) {
+                            desktop_drag_cursor = bar.along(x, y);
+                            desktop_insert_position = Some(bar.calculate_insert_position(desktop_drag_cursor, desktop_idx));
+                            xconn.clear_overview(&overview)?;
+                            render_desktop_bar_with_drag(
+                                &xconn,
+                                &overview,
+                                bar,
+                                desktop_idx,
+                                desktop_insert_position,
+                                desktop_drag_cursor,
+                                &captures,
+                                drag_gap_animation.as_ref().map(|a| a.get_positions()),
+                            )?;
+                            render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+                        }
                     }
                     needs_present = true;
                 }
-                InputAction::DesktopDragMove(cursor_x, _cursor_y) => {
-                    desktop_drag_cursor_x = cursor_x;
+                InputAction::DesktopDragMove(cursor_x, cursor_y) => {
                     if let (Some(dragged), Some(ref bar)) = (desktop_dragging, &desktop_bar) {
+                        desktop_drag_cursor = bar.along(cursor_x, cursor_y);
                         // Calculate insert position
-                        desktop_insert_position = Some(bar.calculate_insert_position(cursor_x, dragged));
+                        desktop_insert_position = Some(bar.calculate_insert_position(desktop_drag_cursor, dragged));
 
                         // Update animation targets and step
                         let animated_positions = if let Some(ref mut anim) = drag_gap_animation {
@@ -1145,7 +2097,7 @@ fn run() -> Result<()> {
                             bar,
                             dragged,
                             desktop_insert_position,
-                            cursor_x,
+                            desktop_drag_cursor,
                             &captures,
                             animated_positions,
                         )?;
@@ -1167,18 +2119,19 @@ fn run() -> Result<()> {
                         .unwrap_or_default();
 
                     // Store dragged desktop's current position (at cursor)
-                    let preview_width = desktop_bar.as_ref().map(|b| b.preview_width).unwrap_or(160);
-                    let dragged_x = desktop_drag_cursor_x - (preview_width / 2) as i16;
+                    let preview_length = desktop_bar.as_ref().map(|b| b.preview_length()).unwrap_or(160);
+                    let dragged_offset = desktop_drag_cursor - (preview_length / 2) as i16;
 
                     if let Err(e) = desktop::reorder_desktop(&xconn, &mut desktop_state, from_desktop, to_position) {
-                        log::warn!("Failed to reorder desktop: {}", e);
+                        xconn.report_error(format!("Failed to reorder desktop: {}", e));
                     } else {
                         // Recreate desktop bar
-                        desktop_bar = Some(DesktopBar::new(
+                        desktop_bar = bar_area.map(|area| DesktopBar::new(
                             desktop_state.desktops,
                             desktop_state.current,
-                            xconn.screen_width,
+                            area,
                             bar_height,
+                            bar_position,
                         ));
 
                         // Create animation from drag positions to final positions
@@ -1198,21 +2151,24 @@ fn run() -> Result<()> {
                                     }
                                 };
 
-                                let old_x = if old_desktop_idx == from_desktop {
-                                    dragged_x // Dragged desktop was at cursor
+                                let new_offset = new_bar.preview_offset(new_preview);
+                                let old_offset = if old_desktop_idx == from_desktop {
+                                    dragged_offset // Dragged desktop was at cursor
                                 } else {
-                                    gap_positions.get(&old_desktop_idx).copied().unwrap_or(new_preview.x)
+                                    gap_positions.get(&old_desktop_idx).copied().unwrap_or(new_offset)
                                 };
 
-                                if old_x != new_preview.x {
-                                    transitions.insert(new_preview.desktop_index, (old_x, new_preview.x));
+                                if old_offset != new_offset {
+                                    transitions.insert(new_preview.desktop_index, (old_offset, new_offset));
                                 }
                             }
                             if !transitions.is_empty() {
                                 desktop_bar_animation = Some(DesktopBarAnimation {
                                     transitions,
+                                    flights: Vec::new(),
                                     start_time: std::time::Instant::now(),
                                     duration_ms: 200,
+                                    easing: config.grid_easing,
                                 });
                             }
                         }
@@ -1226,38 +2182,370 @@ fn run() -> Result<()> {
                             );
                         }
                         input_handler.update_desktop_bar(desktop_bar.clone());
-
-                        // Animate the desktop bar slide
-                        if let (Some(ref anim), Some(ref bar)) = (&desktop_bar_animation, &desktop_bar) {
-                            while !anim.is_complete() {
-                                xconn.clear_overview(&overview)?;
-                                render_desktop_bar_animated(
-                                    &xconn,
-                                    &overview,
-                                    bar,
-                                    anim,
-                                    &captures,
-                                )?;
-                                render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
-                                xconn.present_overview(&overview)?;
-                                std::thread::sleep(std::time::Duration::from_millis(16));
-                            }
-                        }
-                        desktop_bar_animation = None;
                     }
 
                     desktop_dragging = None;
                     desktop_insert_position = None;
                     drag_gap_animation = None;
 
-                    // Final redraw
+                    // The previews slide from the next frame on
+                    xconn.clear_overview(&overview)?;
+                    if let Some(ref bar) = desktop_bar {
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                    }
+                    render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+                    needs_present = true;
+                }
+                InputAction::MoveAllWindows(from_desktop, to_desktop) => {
+                    desktop_dragging = None;
+                    desktop_insert_position = None;
+                    drag_gap_animation = None;
+
+                    match desktop::move_all_windows(&xconn, &mut desktop_state, from_desktop, to_desktop) {
+                        Err(e) => xconn.report_error(format!("Failed to move windows: {}", e)),
+                        Ok(moved) => {
+                            if config.remove_empty_desktops && backend.is_none() {
+                                queue_empty_desktop_removal(&desktop_state, &[from_desktop], &mut pending_actions);
+                            }
+
+                            // Where each moved window sits in the source preview now
+                            let starts: Vec<(usize, (i16, i16, u16, u16))> = desktop_bar
+                                .as_ref()
+                                .map(|bar| {
+                                    captures
+                                        .iter()
+                                        .enumerate()
+                                        .filter(|(_, c)| moved.contains(&c.info.frame_window))
+                                        .filter_map(|(i, c)| {
+                                            bar.mini_window_rect(from_desktop, c.info.frame_window).map(|r| (i, r))
+                                        })
+                                        .collect()
+                                })
+                                .unwrap_or_default();
+
+                            if let Some(ref mut bar) = desktop_bar {
+                                bar.calculate_mini_layouts(
+                                    &captures,
+                                    &desktop_state,
+                                    xconn.screen_width,
+                                    xconn.screen_height,
+                                );
+                            }
+                            input_handler.update_desktop_bar(desktop_bar.clone());
+
+                            // Fly the miniatures over to the target preview
+                            if let Some(ref bar) = desktop_bar {
+                                let flights: Vec<_> = starts
+                                    .into_iter()
+                                    .filter_map(|(i, from)| {
+                                        bar.mini_window_rect(to_desktop, captures[i].info.frame_window)
+                                            .map(|to| (i, from, to))
+                                    })
+                                    .collect();
+                                if !flights.is_empty() {
+                                    desktop_bar_animation = Some(DesktopBarAnimation {
+                                        transitions: std::collections::HashMap::new(),
+                                        flights,
+                                        start_time: Instant::now(),
+                                        duration_ms: grid_transition_duration_ms,
+                                        easing: config.grid_easing,
+                                    });
+                                }
+                            }
+
+                            // The grid follows if the current desktop gained or lost windows
+                            if from_desktop == desktop_state.current || to_desktop == desktop_state.current {
+                                refresh_removed!()?;
+                                overview.desktop_labels = desktop_labels(&captures, &desktop_state, all_desktops);
+                                let old_layouts = layouts.clone();
+                                layouts = relayout!();
+                                grid_transition_animation = Some(GridTransitionAnimation::new(
+                                    &old_layouts,
+                                    &layouts,
+                                    grid_transition_duration_ms,
+                                    config.grid_easing,
+                                ));
+                                input_handler.update_layouts(layouts.clone());
+                            }
+                        }
+                    }
+
+                    // The miniatures fly from the next frame on
                     xconn.clear_overview(&overview)?;
                     if let Some(ref bar) = desktop_bar {
-                        render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                     }
                     render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
                     needs_present = true;
                 }
+                InputAction::ToggleDesktopGrid => {
+                    // Zoomed on the frame tick; until it is over, the zoom
+                    // covers everything else
+                    if let Some(grid) = desktop_grid.take() {
+                        log::info!("Zooming back in from the desktop grid");
+                        input_handler.update_desktop_grid(None);
+                        grid_drag = None;
+                        grid_zoom = desktop_bar.is_some().then(|| GridZoom { grid, start_time: Instant::now(), zoom_out: false });
+                        xconn.clear_overview(&overview)?;
+                        if let Some(ref bar) = desktop_bar {
+                            render_desktop_bar(&xconn, &overview, bar, (0, 0), None, None, &captures)?;
+                        }
+                        render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+                    } else {
+                        log::info!("Zooming out to the desktop grid");
+                        let grid = build_desktop_grid(&xconn, &captures, &desktop_state);
+                        grid_zoom = desktop_bar
+                            .is_some()
+                            .then(|| GridZoom { grid: grid.clone(), start_time: Instant::now(), zoom_out: true });
+                        input_handler.update_desktop_grid(Some(grid.clone()));
+                        desktop_grid = Some(grid);
+                    }
+                    needs_present = true;
+                }
+                InputAction::DesktopGridDrag(window_id, x, y) => {
+                    grid_drag = Some((window_id, x, y));
+                    needs_present = true;
+                }
+                InputAction::CloseWindow(index) => {
+                    // The grid closes up once the window is destroyed
+                    let info = &captures[index].info;
+                    match xconn.close_window(info) {
+                        Ok(true) => log::info!("Asked window 0x{:x} to close", info.client_window),
+                        Ok(false) => xconn.report_error(format!(
+                            "{} cannot be closed from here (no WM_DELETE_WINDOW); Ctrl+close kills it",
+                            info.wm_name.as_deref().unwrap_or("This window")
+                        )),
+                        Err(e) => xconn.report_error(format!("Cannot close window: {}", e)),
+                    }
+                }
+                InputAction::ToggleMinimize(index) => {
+                    let info = &captures[index].info;
+                    let minimize = !desktop_state.is_app_hidden(info.frame_window);
+                    let done = if minimize { xconn.iconify_window(info) } else { xconn.deiconify_window(info) };
+                    match done {
+                        Ok(()) => {
+                            log::info!("{} window 0x{:x}", if minimize { "Minimized" } else { "Restored" }, info.client_window);
+                            desktop_state.set_app_hidden(info.frame_window, minimize);
+                            refresh_removed!()?;
+                            overview.desktop_labels = desktop_labels(&captures, &desktop_state, all_desktops);
+                            // Slides between the grid and the strip
+                            let old_layouts = layouts.clone();
+                            layouts = relayout!();
+                            grid_transition_animation = Some(GridTransitionAnimation::new(
+                                &old_layouts,
+                                &layouts,
+                                grid_transition_duration_ms,
+                                config.grid_easing,
+                            ));
+                            input_handler.update_layouts(layouts.clone());
+                            if let Some(ref mut bar) = desktop_bar {
+                                bar.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+                            }
+                            input_handler.update_desktop_bar(desktop_bar.clone());
+                            needs_present = true;
+                        }
+                        Err(e) => xconn.report_error(format!("Cannot minimize window: {}", e)),
+                    }
+                }
+                InputAction::ToggleSticky(index) => {
+                    let frame = captures[index].info.frame_window;
+                    let sticky = !desktop_state.is_sticky(frame);
+                    log::info!("{} window 0x{:x}", if sticky { "Pinned" } else { "Unpinned" }, frame);
+                    desktop_state.set_sticky(frame, sticky);
+                    if sticky {
+                        overview.pinned.insert(frame);
+                    } else {
+                        overview.pinned.remove(&frame);
+                    }
+                    redraw_thumbnail(&xconn, &captures, &layouts, &overview, index, last_hovered == Some(index))?;
+                    // It shows on every desktop's preview now, or only its own
+                    if let Some(ref mut bar) = desktop_bar {
+                        bar.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                    }
+                    input_handler.update_desktop_bar(desktop_bar.clone());
+                    // A pinned window joins the current desktop's group
+                    regrouped |= all_desktops;
+                    needs_present = true;
+                }
+                InputAction::Search(query) => {
+                    // Ranked among what the grid shows without a search
+                    let shown = captures
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| !removed_windows.contains(i))
+                        .map(|(_, capture)| &capture.info);
+                    let ranked = search::rank(&query, shown);
+                    log::debug!("Search {:?} matches {} windows", query, ranked.len());
+                    if !query.is_empty() {
+                        layout_config.ranked = Some(ranked.iter().map(|(frame, _)| *frame).collect());
+                        // The best match is highlighted, for Enter to pick
+                        last_hovered = ranked
+                            .first()
+                            .and_then(|(frame, _)| captures.iter().position(|c| c.info.frame_window == *frame));
+                        input_handler.set_hovered(last_hovered);
+                    } else {
+                        layout_config.ranked = None;
+                    }
+                    overview.search = Some(SearchView { query, matched: ranked.into_iter().collect() });
+                    regrouped = true;
+                }
+                InputAction::EndSearch => {
+                    log::info!("Search ended");
+                    layout_config.ranked = None;
+                    overview.search = None;
+                    regrouped = true;
+                }
+                InputAction::ToggleAllDesktops => {
+                    all_desktops = !all_desktops;
+                    let shown = if all_desktops { "every desktop" } else { "the current desktop" };
+                    log::info!("Showing {} in the grid", shown);
+                    refresh_removed!()?;
+                    if last_hovered.is_some_and(|index| removed_windows.contains(&index)) {
+                        last_hovered = None;
+                        input_handler.set_hovered(None);
+                    }
+                    // Laid out again below, as when groups change
+                    regrouped = true;
+                }
+                InputAction::ScrollDesktop(step) => {
+                    let target = desktop_state.current.checked_add_signed(step).filter(|&d| d < desktop_state.desktops);
+                    let Some(target) = target.filter(|_| !all_desktops && desktop_grid.is_none()) else {
+                        continue;
+                    };
+                    log::info!("Switching the grid to desktop {}", target);
+                    let forward = target > desktop_state.current;
+                    grid_slide = Some(if forward { SlideDirection::Left } else { SlideDirection::Right });
+                    desktop_state.current = target;
+                    desktop_state.sync_to_x(&xconn)?;
+                    if let Err(e) = desktop_state.save() {
+                        xconn.report_error(format!("Cannot save desktop state: {}", e));
+                    }
+                    if let Some(ref mut bar) = desktop_bar {
+                        bar.set_current(target);
+                    }
+                    input_handler.update_desktop_bar(desktop_bar.clone());
+                    refresh_removed!()?;
+                    if last_hovered.is_some_and(|index| removed_windows.contains(&index)) {
+                        last_hovered = None;
+                        input_handler.set_hovered(None);
+                    }
+                    // Laid out again below, sliding
+                    regrouped = true;
+                }
+                InputAction::RequestKillWindow(index) => {
+                    log::info!("Confirming kill of window 0x{:x}", captures[index].info.client_window);
+                    kill_pending = Some(index);
+                    needs_present = true;
+                }
+                InputAction::Spotlight(index) => {
+                    if let Some(monitor) = thumbnail_monitor(&layouts, &monitors, index) {
+                        log::info!("Spotlight on window {}", index);
+                        let layout = layout::centered_layout(&captures[index].info, index, monitor, SPOTLIGHT_SIZE);
+                        input_handler.set_spotlight(Some(layout.clone()));
+                        spotlight = Some(layout);
+                        needs_present = true;
+                    }
+                }
+                InputAction::EndSpotlight(_) => {
+                    spotlight = None;
+                    xconn.clear_overview(&overview)?;
+                    if let Some(ref bar) = desktop_bar {
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                    }
+                    render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, None)?;
+                    needs_present = true;
+                }
+                InputAction::QuickLook(index) => {
+                    // Flies out of the grid as a dropped window flies back in
+                    let from = find_layout(&layouts, index);
+                    if let (Some(from), Some(monitor)) = (from, thumbnail_monitor(&layouts, &monitors, index)) {
+                        log::info!("Quick look at window {}", index);
+                        let to = layout::centered_layout(&captures[index].info, index, monitor, QUICK_LOOK_SIZE);
+                        drag_animation = Some(DragAnimation {
+                            mode: AnimationMode::QuickLook,
+                            window_index: index,
+                            start_x: from.x,
+                            start_y: from.y,
+                            start_width: from.width,
+                            start_height: from.height,
+                            end_x: to.x,
+                            end_y: to.y,
+                            end_width: to.width,
+                            end_height: to.height,
+                            start_time: Instant::now(),
+                            duration_ms: revert_duration_ms,
+                            easing: config.revert_easing,
+                        });
+                        dragging_window_index = Some(index);
+                        input_handler.set_quick_look(Some(index));
+                    }
+                }
+                InputAction::EndQuickLook(index) => {
+                    // From wherever it got to, if it is still growing
+                    let growing = drag_animation.as_ref().filter(|anim| anim.window_index == index);
+                    let rect = growing
+                        .map(|anim| anim.current_position())
+                        .or(quick_look.take().map(|layout| (layout.x, layout.y, layout.width, layout.height)));
+                    drag_animation = rect.and_then(|rect| {
+                        revert_animation(&layouts, index, rect, revert_duration_ms, config.revert_easing)
+                    });
+                    dragging_window_index = drag_animation.as_ref().map(|_| index);
+                    if drag_animation.is_none() {
+                        xconn.clear_overview(&overview)?;
+                        if let Some(ref bar) = desktop_bar {
+                            render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                        }
+                        render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, None)?;
+                    }
+                    needs_present = true;
+                }
+                InputAction::CancelKillWindow(index) => {
+                    kill_pending = None;
+                    redraw_thumbnail(&xconn, &captures, &layouts, &overview, index, last_hovered == Some(index))?;
+                    needs_present = true;
+                }
+                InputAction::KillWindow(index) => {
+                    // Like a close, the grid closes up once the window is destroyed
+                    kill_pending = None;
+                    redraw_thumbnail(&xconn, &captures, &layouts, &overview, index, last_hovered == Some(index))?;
+                    needs_present = true;
+                    let info = &captures[index].info;
+                    match xconn.kill_window(info) {
+                        Ok(()) => log::info!("Killed the client of window 0x{:x}", info.client_window),
+                        Err(e) => xconn.report_error(format!("Cannot kill window: {}", e)),
+                    }
+                }
+                InputAction::DesktopGridDrop(window_id, target) => {
+                    grid_drag = None;
+                    let target = target.filter(|&d| desktop_state.get_window_desktop_assignment(window_id) != Some(d));
+                    if let Some(desktop_idx) = target {
+                        let old_desktop = desktop_state.get_window_desktop_assignment(window_id);
+                        match desktop::move_window(&xconn, &mut desktop_state, window_id, desktop_idx) {
+                            Ok(()) => log::info!("Moved window 0x{:x} to desktop {}", window_id, desktop_idx),
+                            Err(e) => xconn.report_error(format!("Failed to move window: {}", e)),
+                        }
+                        if config.remove_empty_desktops && backend.is_none() {
+                            queue_empty_desktop_removal(&desktop_state, old_desktop.as_slice(), &mut pending_actions);
+                        }
+
+                        if let Some(ref mut bar) = desktop_bar {
+                            bar.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+                        }
+                        input_handler.update_desktop_bar(desktop_bar.clone());
+                        if let Some(ref mut grid) = desktop_grid {
+                            grid.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+                        }
+                        input_handler.update_desktop_grid(desktop_grid.clone());
+
+                        // The window grid behind may have gained or lost the window
+                        refresh_removed!()?;
+                        overview.desktop_labels = desktop_labels(&captures, &desktop_state, all_desktops);
+                        layouts = relayout!();
+                        input_handler.update_layouts(layouts.clone());
+                    }
+                    needs_present = true;
+                }
                 InputAction::CancelDesktopDrag => {
                     log::debug!("Desktop drag cancelled");
                     desktop_dragging = None;
@@ -1267,7 +2555,7 @@ fn run() -> Result<()> {
                     // Redraw without gap
                     xconn.clear_overview(&overview)?;
                     if let Some(ref bar) = desktop_bar {
-                        render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                     }
                     render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
                     needs_present = true;
@@ -1288,9 +2576,13 @@ fn run() -> Result<()> {
                             let offset_y = drag.start_y - thumb_center_y;
                             drag.set_click_offset(offset_x, offset_y);
 
-                            // Scale based on Y position relative to snap target size
+                            // Scale based on distance to the bar relative to snap target size
                             let (scale, _) = calculate_drag_scale_and_target(
-                                drag.current_y, drag.start_y, layout, &desktop_bar, &captures[index],
+                                (drag.current_x, drag.current_y),
+                                (drag.start_x, drag.start_y),
+                                layout,
+                                &desktop_bar,
+                                &captures[index],
                             );
                             let rect = calculate_drag_rect(
                                 drag.current_x, drag.current_y,
@@ -1301,12 +2593,11 @@ fn run() -> Result<()> {
 
                             xconn.clear_overview(&overview)?;
                             if let Some(ref bar) = desktop_bar {
-                                render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                                render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                             }
                             render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
-                            xconn.render_dragged_window(
-                                capture.picture, overview.picture,
-                                capture.info.width, capture.info.height,
+                            xconn.render_dragged_capture(
+                                capture, overview.picture.id(),
                                 rect.0, rect.1, rect.2, rect.3,
                             )?;
                         }
@@ -1314,16 +2605,16 @@ fn run() -> Result<()> {
                     needs_present = true;
                 }
                 InputAction::DragMove(x, y) => {
-                    // Calculate drag scale based on Y position
+                    // Calculate drag scale based on distance to the bar
                     if let Some(drag) = input_handler.drag_state() {
                         let Some(layout) = find_layout(&layouts, drag.window_index) else {
                             continue;
                         };
                         let capture = &captures[drag.window_index];
 
-                        // Scale based on Y position relative to snap target size
+                        // Scale based on distance to the bar relative to snap target size
                         let (scale, _) = calculate_drag_scale_and_target(
-                            y, drag.start_y, layout, &desktop_bar, capture,
+                            (x, y), (drag.start_x, drag.start_y), layout, &desktop_bar, capture,
                         );
                         let rect = calculate_drag_rect(
                             x, y, layout.width, layout.height, scale,
@@ -1333,82 +2624,221 @@ fn run() -> Result<()> {
 
                         xconn.clear_overview(&overview)?;
                         if let Some(ref bar) = desktop_bar {
-                            render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                            render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                         }
                         render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
-                        xconn.render_dragged_window(
-                            capture.picture, overview.picture,
-                            capture.info.width, capture.info.height,
+                        let on_bar = desktop_bar.as_ref().is_some_and(|bar| bar.contains_point(x, y));
+                        if let Some(geometry) = placement::tile_at(&monitors, x, y).filter(|_| !on_bar) {
+                            xconn.draw_tile_preview(&overview, geometry)?;
+                        }
+                        xconn.render_dragged_capture(
+                            capture, overview.picture.id(),
                             rect.0, rect.1, rect.2, rect.3,
                         )?;
                     }
                     needs_present = true;
                 }
-                InputAction::DropOnDesktop(window_idx, desktop_idx) => {
+                InputAction::DropOnDesktop(window_idx, desktop_idx)
+                | InputAction::FollowToDesktop(window_idx, desktop_idx) => {
+                    let follow = matches!(action, InputAction::FollowToDesktop(..));
+                    if drag_animation.is_some() {
+                        // Shift+digit pressed again before the last move landed
+                        continue;
+                    }
                     log::info!("Dropped window {} on desktop {} (UI only)", window_idx, desktop_idx);
+                    // Sent with Shift+digit there is no drag; fly from the grid slot
+                    let start_rect = last_drag_rect.or_else(|| {
+                        find_layout(&layouts, window_idx).map(|l| (l.x, l.y, l.width, l.height))
+                    });
                     // Start snap animation to desktop preview center
-                    if let (Some(rect), Some(ref bar)) = (last_drag_rect, &desktop_bar) {
-                        if let Some((target_x, target_y)) = bar.get_preview_center(desktop_idx) {
-                            // Target size is small (preview size)
-                            let capture = &captures[window_idx];
-                            let aspect = capture.info.height as f64 / capture.info.width as f64;
-                            let target_width = 60u16;
-                            let target_height = (60.0 * aspect) as u16;
-
-                            drag_animation = Some(DragAnimation {
-                                mode: AnimationMode::SnapToDesktop { desktop_idx: desktop_idx as usize },
-                                window_index: window_idx,
-                                start_x: rect.0,
-                                start_y: rect.1,
-                                start_width: rect.2,
-                                start_height: rect.3,
-                                end_x: target_x - (target_width / 2) as i16,
-                                end_y: target_y - (target_height / 2) as i16,
-                                end_width: target_width,
-                                end_height: target_height,
-                                start_time: Instant::now(),
-                                duration_ms: snap_duration_ms,
-                            });
+                    if let (Some(rect), Some(ref bar)) = (start_rect, &desktop_bar) {
+                        drag_animation = snap_animation(bar, &captures, window_idx, desktop_idx, rect, snap_duration_ms, config.snap_easing, follow);
+                        if drag_animation.is_some() {
+                            dragging_window_index = Some(window_idx);
                         }
                     }
                     last_drag_rect = None;
                     needs_present = true;
                 }
+                InputAction::ReorderWindow(window_idx, target_idx) => {
+                    let minimized = minimized_windows(&captures, &destroyed_captures, &filtered_captures, &desktop_state);
+                    let monitor_of = |idx| {
+                        let layout = find_layout(&layouts, idx)?;
+                        let (cx, cy) = (layout.x as i32 + layout.width as i32 / 2, layout.y as i32 + layout.height as i32 / 2);
+                        monitors.iter().position(|m| m.contains(cx, cy))
+                    };
+                    let monitor = monitor_of(target_idx).filter(|&m| monitor_of(window_idx) == Some(m));
+                    // Only within a monitor's grid; the minimized strip goes by its own order
+                    let (Some(monitor), false) = (monitor, minimized.contains(&window_idx) || minimized.contains(&target_idx)) else {
+                        if let (Some(rect), Some(window_idx)) = (last_drag_rect, dragging_window_index) {
+                            drag_animation = revert_animation(&layouts, window_idx, rect, revert_duration_ms, config.revert_easing);
+                        }
+                        last_drag_rect = None;
+                        if drag_animation.is_none() {
+                            dragging_window_index = None;
+                        }
+                        needs_present = true;
+                        continue;
+                    };
+
+                    let grid: Vec<ThumbnailLayout> = layouts
+                        .iter()
+                        .filter(|l| !minimized.contains(&l.window_index) && monitor_of(l.window_index) == Some(monitor))
+                        .cloned()
+                        .collect();
+                    let mut order = layout::reading_order(&grid);
+                    let from = order.iter().position(|&i| i == window_idx).unwrap_or(0);
+                    let to = order.iter().position(|&i| i == target_idx).unwrap_or(0);
+                    // Like moving an item in a list: it takes the target's place
+                    let moved = order.remove(from);
+                    order.insert(to, moved);
+                    log::info!("Moved window {} to place {} on {}", window_idx, to, monitors[monitor].name);
+                    layout_config.arranged.insert(
+                        monitors[monitor].name.clone(),
+                        order.iter().map(|&i| captures[i].info.frame_window).collect(),
+                    );
+
+                    // Saved per group, like the order it came in with
+                    let mut groups: HashMap<String, Vec<&window_finder::WindowInfo>> = HashMap::new();
+                    for &i in &order {
+                        let info = &captures[i].info;
+                        let desktop = desktop_state.get_window_desktop_assignment(info.frame_window).unwrap_or(current_desktop);
+                        groups.entry(WindowState::order_group(info, &xconn.monitors, desktop)).or_default().push(info);
+                    }
+                    for (group, members) in groups {
+                        if let Err(e) = WindowState::save_arrangement(group, &members) {
+                            xconn.report_error(format!("Cannot save window order: {}", e));
+                        }
+                    }
+
+                    // The dropped window flies in from where it was let go
+                    let mut old_layouts = layouts.clone();
+                    if let (Some((x, y, width, height)), Some(layout)) =
+                        (last_drag_rect.take(), old_layouts.iter_mut().find(|l| l.window_index == window_idx))
+                    {
+                        (layout.x, layout.y, layout.width, layout.height) = (x, y, width, height);
+                    }
+                    layouts = relayout!();
+                    grid_transition_animation = Some(GridTransitionAnimation::new(
+                        &old_layouts,
+                        &layouts,
+                        grid_transition_duration_ms,
+                        config.grid_easing,
+                    ));
+                    input_handler.update_layouts(layouts.clone());
+                    dragging_window_index = None;
+                    needs_present = true;
+                }
+                InputAction::GroupWindows(window_idx, target_idx) => {
+                    let minimized = minimized_windows(&captures, &destroyed_captures, &filtered_captures, &desktop_state);
+                    if minimized.contains(&window_idx) || minimized.contains(&target_idx) {
+                        // Minimized windows stay in the strip, out of groups
+                        if let (Some(rect), Some(window_idx)) = (last_drag_rect, dragging_window_index) {
+                            drag_animation = revert_animation(&layouts, window_idx, rect, revert_duration_ms, config.revert_easing);
+                        }
+                        last_drag_rect = None;
+                        if drag_animation.is_none() {
+                            dragging_window_index = None;
+                        }
+                    } else {
+                        let target = &captures[target_idx].info;
+                        let name = target.wm_class.clone().unwrap_or_else(|| "Group".to_string());
+                        let name = window_state.group_windows(target.frame_window, captures[window_idx].info.frame_window, &name);
+                        log::info!("Grouped window {} with window {} in {}", window_idx, target_idx, name);
+                        if let Err(e) = WindowState::save_groups(&window_state.groups) {
+                            xconn.report_error(format!("Cannot save window groups: {}", e));
+                        }
+                        last_drag_rect = None;
+                        dragging_window_index = None;
+                        regrouped = true;
+                    }
+                    needs_present = true;
+                }
+                InputAction::CopyTitle(index) | InputAction::CopyWindowId(index) => {
+                    let info = &captures[index].info;
+                    // The id as list-windows and --print-windows show it
+                    let (what, text) = match action {
+                        InputAction::CopyTitle(_) => ("title", info.wm_name.clone().unwrap_or_default()),
+                        _ => ("id", format!("0x{:08x}", info.client_window)),
+                    };
+                    match clipboard::copy(&text) {
+                        Ok(()) => xconn.notify(format!("Copied {}: {}", what, text)),
+                        Err(e) => xconn.report_error(format!("Cannot copy the window {}: {}", what, e)),
+                    }
+                    needs_present = true;
+                }
+                InputAction::Ungroup(index) => {
+                    let frame = captures[index].info.frame_window;
+                    if window_state.group_of(frame).is_some() {
+                        log::info!("Taking window {} out of its group", index);
+                        window_state.ungroup(frame);
+                        if let Err(e) = WindowState::save_groups(&window_state.groups) {
+                            xconn.report_error(format!("Cannot save window groups: {}", e));
+                        }
+                        regrouped = true;
+                    }
+                }
                 InputAction::CancelDrag => {
                     log::debug!("Drag cancelled");
-                    // Start revert animation back to grid position
-                    if let Some(rect) = last_drag_rect {
-                        // Find the window's grid layout position
-                        if let Some(drag) = input_handler.drag_state() {
-                            if let Some(layout) = find_layout(&layouts, drag.window_index) {
-                                drag_animation = Some(DragAnimation {
-                                    mode: AnimationMode::RevertToGrid,
-                                    window_index: drag.window_index,
-                                    start_x: rect.0,
-                                    start_y: rect.1,
-                                    start_width: rect.2,
-                                    start_height: rect.3,
-                                    end_x: layout.x,
-                                    end_y: layout.y,
-                                    end_width: layout.width,
-                                    end_height: layout.height,
-                                    start_time: Instant::now(),
-                                duration_ms: revert_duration_ms,
-                                });
-                            }
-                        }
+                    // Start revert animation back to grid position. The input
+                    // handler has already dropped its drag state, so go by the
+                    // window hidden from the grid
+                    if let (Some(rect), Some(window_idx)) = (last_drag_rect, dragging_window_index) {
+                        drag_animation = revert_animation(&layouts, window_idx, rect, revert_duration_ms, config.revert_easing);
                     }
                     last_drag_rect = None;
                     // Keep dragging_window_index set until animation completes
+                    if drag_animation.is_none() {
+                        dragging_window_index = None;
+                    }
                     needs_present = true;
                 }
                 InputAction::HoverDesktop(desktop_idx) => {
                     log::debug!("Hover desktop: {:?}", desktop_idx);
                     // Redraw desktop bar with hover highlight
                     if let Some(ref bar) = desktop_bar {
-                        render_desktop_bar(&xconn, &overview, bar, 0, desktop_idx, None, &captures)?;
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), desktop_idx, None, &captures)?;
+                        needs_present = true;
+                    }
+                }
+                InputAction::EditDesktopName(desktop_idx, text) => {
+                    if let Some(ref mut bar) = desktop_bar {
+                        if let Some(preview) = bar.preview_layouts.iter_mut().find(|p| p.desktop_index == desktop_idx) {
+                            // Trailing underscore stands in for a text cursor
+                            preview.name = Some(format!("{}_", text));
+                        }
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                        needs_present = true;
+                    }
+                }
+                InputAction::RenameDesktop(_, _) | InputAction::CancelRename(_) => {
+                    if let InputAction::RenameDesktop(desktop_idx, ref name) = action {
+                        // The window manager's name goes first, as it may refuse
+                        let renamed = match (backend.as_deref_mut(), workspaces.get_mut(desktop_idx as usize)) {
+                            (Some(backend), Some(workspace)) => backend
+                                .rename(&workspace.name, name)
+                                .map(|()| workspace.name = name.clone()),
+                            _ => Ok(()),
+                        };
+                        if let Err(e) =
+                            renamed.and_then(|()| desktop::rename_desktop(&xconn, &mut desktop_state, desktop_idx, name))
+                        {
+                            xconn.report_error(format!("Failed to rename desktop: {}", e));
+                        }
+                    }
+                    if all_desktops {
+                        // The group labels go by the new name too
+                        overview.desktop_labels = desktop_labels(&captures, &desktop_state, all_desktops);
+                        xconn.clear_overview(&overview)?;
+                        render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+                    }
+                    // Back to the names in the desktop state
+                    if let Some(ref mut bar) = desktop_bar {
+                        bar.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                         needs_present = true;
                     }
+                    input_handler.update_desktop_bar(desktop_bar.clone());
                 }
                 InputAction::None => {}
             }
@@ -1422,45 +2852,189 @@ fn run() -> Result<()> {
             break;
         }
 
-        // Process damaged windows - refresh and re-render
-        if !damaged_windows.is_empty() {
-            for &idx in &damaged_windows {
-                if idx < captures.len() {
-                    // Refresh the capture (get new pixmap with updated contents)
-                    if let Err(e) = xconn.refresh_capture(&mut captures[idx]) {
-                        log::warn!("Failed to refresh capture {}: {}", idx, e);
-                        continue;
-                    }
+        if regrouped {
+            (piles, overview.piles) = collapsed_groups(&captures, &window_state.groups, &expanded_groups);
+            overview.desktop_labels = desktop_labels(&captures, &desktop_state, all_desktops);
+            let old_layouts = layouts.clone();
+            layouts = relayout!();
+            let (duration_ms, easing) = (grid_transition_duration_ms, config.grid_easing);
+            grid_transition_animation = Some(match grid_slide {
+                Some(direction) => {
+                    let width = xconn.screen_width.min(i16::MAX as u16) as i16;
+                    let offset = if direction == SlideDirection::Left { width } else { -width };
+                    GridTransitionAnimation::slide(&old_layouts, &layouts, offset, duration_ms, easing)
+                }
+                None => GridTransitionAnimation::new(&old_layouts, &layouts, duration_ms, easing),
+            });
+            input_handler.update_layouts(layouts.clone());
+            needs_present = true;
+        }
 
-                    // Re-render this thumbnail
-                    let highlighted = last_hovered == Some(idx);
-                    redraw_thumbnail(&xconn, &captures, &layouts, &overview, idx, highlighted)?;
+        // Windows destroyed under us - drop them and close the gap in the grid
+        vanished.retain(|&idx| destroyed_captures.insert(idx));
+        if !vanished.is_empty() {
+            let mut left_desktops: Vec<u32> = Vec::new();
+            for &idx in &vanished {
+                captures[idx].window_destroyed();
+                let info = &captures[idx].info;
+                log::info!("Window {:?} (0x{:x}) was destroyed, removing it",
+                    info.wm_name, info.frame_window);
+                left_desktops.extend(desktop_state.get_window_desktop_assignment(info.frame_window));
+                desktop_state.forget_window(info.frame_window);
+                removed_windows.insert(idx);
+                damaged_windows.remove(idx);
+                placeholder_indices.remove(&idx);
+                live_captures.remove(&idx);
+                if last_hovered == Some(idx) {
+                    last_hovered = None;
                 }
-            }
-            // Also re-render the desktop bar so mini-thumbnails update
-            if let Some(ref bar) = desktop_bar {
-                if let Some(dragged) = desktop_dragging {
-                    // During desktop drag, use animated positions
-                    let animated_positions = drag_gap_animation.as_ref().map(|a| a.get_positions());
-                    render_desktop_bar_with_drag(
-                        &xconn, &overview, bar, dragged,
-                        desktop_insert_position, desktop_drag_cursor_x,
-                        &captures, animated_positions,
-                    )?;
-                } else {
-                    render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                if kill_pending == Some(idx) {
+                    kill_pending = None;
+                }
+                if spotlight.as_ref().is_some_and(|layout| layout.window_index == idx) {
+                    spotlight = None;
+                    input_handler.set_spotlight(None);
+                }
+                if quick_look.as_ref().is_some_and(|layout| layout.window_index == idx) {
+                    quick_look = None;
+                    input_handler.set_quick_look(None);
                 }
             }
-            damaged_windows.clear();
-            needs_present = true;
-        }
 
-        // Try to upgrade placeholder captures to real ones
-        if !placeholder_indices.is_empty() {
-            let mut upgraded: Vec<usize> = Vec::new();
+            if config.remove_empty_desktops && backend.is_none() {
+                queue_empty_desktop_removal(&desktop_state, &left_desktops, &mut pending_actions);
+            }
+
+            if let Some(ref mut bar) = desktop_bar {
+                bar.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+            }
+            input_handler.update_desktop_bar(desktop_bar.clone());
+            if let Some(ref mut grid) = desktop_grid {
+                grid.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+                input_handler.update_desktop_grid(desktop_grid.clone());
+            }
+
+            let old_layouts = layouts.clone();
+            layouts = relayout!();
+            grid_transition_animation = Some(
+                GridTransitionAnimation::new(&old_layouts, &layouts, grid_transition_duration_ms, config.grid_easing)
+                    .with_departures(&old_layouts, &vanished),
+            );
+            input_handler.update_layouts(layouts.clone());
+        }
+
+        // Screen geometry changed - resize the overview and lay everything out again
+        if screen_changed && xconn.refresh_screen_size()? {
+            xconn.resize_overview(&mut overview)?;
+            monitors = layout_monitors(&xconn.monitors, options.monitor)?;
+            input_handler.set_monitors(monitors.clone());
+            grid_transition_animation = None;
+            bar_area = show_bar
+                .then(|| desktop_bar::bar_area(&monitors, &layout_config, xconn.screen_width, xconn.screen_height))
+                .flatten();
+
+            desktop_bar = bar_area.map(|area| DesktopBar::new(
+                desktop_state.desktops,
+                desktop_state.current,
+                area,
+                bar_height,
+                bar_position,
+            ));
+            reserved = desktop_bar
+                .as_ref()
+                .map_or(Insets::default(), |bar| bar.reserved(xconn.screen_width, xconn.screen_height));
+            if let Some(ref mut bar) = desktop_bar {
+                bar.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+            }
+            input_handler.update_desktop_bar(desktop_bar.clone());
+
+            layouts = relayout!();
+            input_handler.update_layouts(layouts.clone());
+
+            if desktop_grid.is_some() {
+                desktop_grid = Some(build_desktop_grid(&xconn, &captures, &desktop_state));
+                input_handler.update_desktop_grid(desktop_grid.clone());
+                grid_drag = None;
+            }
+
+            xconn.clear_overview(&overview)?;
+            if let Some(ref bar) = desktop_bar {
+                render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+            }
+            render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+            needs_present = true;
+        }
+
+        // Process damaged windows - refresh and re-render those in view:
+        // in the grid and not under the window being dragged, or in a
+        // desktop preview
+        let drag_rect = match drag_animation {
+            Some(ref anim) => Some(anim.current_position()),
+            None => last_drag_rect.filter(|_| dragging_window_index.is_some()),
+        };
+        let dragged = drag_animation.as_ref().map(|anim| anim.window_index).or(dragging_window_index);
+        let previews = desktop_bar
+            .iter()
+            .flat_map(|bar| &bar.preview_layouts)
+            .chain(desktop_grid.iter().flat_map(|grid| &grid.tiles));
+        let in_previews: HashSet<Window> = previews.flat_map(|p| &p.mini_windows).map(|m| m.window_id).collect();
+        let shown = |idx: usize| {
+            idx >= captures.len()
+                || dragged == Some(idx)
+                || find_layout(&layouts, idx).is_some_and(|layout| !drag_rect.is_some_and(|rect| layout.covered_by(rect)))
+                || in_previews.contains(&captures[idx].info.frame_window)
+        };
+        let damaged = damaged_windows.take_due(Instant::now(), shown);
+        if !damaged.is_empty() {
+            // Labels over the changed contents pick their colors again
+            overview.forget_label_colors();
+            for &idx in &damaged {
+                if idx < captures.len() {
+                    // Refresh the capture (get new pixmap with updated contents)
+                    if let Err(e) = xconn.refresh_capture(&mut captures[idx]) {
+                        if e.is_window_gone() {
+                            log::debug!("Capture {} refresh failed, window is gone: {}", idx, e);
+                            continue;
+                        }
+                        xconn.report_error(format!(
+                            "Cannot refresh {}: {}",
+                            captures[idx].info.wm_name.as_deref().unwrap_or("window"),
+                            e
+                        ));
+                        continue;
+                    }
+                    live_captures.insert(idx);
+
+                    // Re-render this thumbnail
+                    let highlighted = last_hovered == Some(idx);
+                    redraw_thumbnail(&xconn, &captures, &layouts, &overview, idx, highlighted)?;
+                }
+            }
+            // Also re-render the desktop bar so mini-thumbnails update
+            if let Some(ref bar) = desktop_bar {
+                if let Some(dragged) = desktop_dragging {
+                    // During desktop drag, use animated positions
+                    let animated_positions = drag_gap_animation.as_ref().map(|a| a.get_positions());
+                    render_desktop_bar_with_drag(
+                        &xconn, &overview, bar, dragged,
+                        desktop_insert_position, desktop_drag_cursor,
+                        &captures, animated_positions,
+                    )?;
+                } else {
+                    render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                }
+            }
+            needs_present = true;
+        }
+
+        // Try to upgrade placeholder captures to real ones
+        if !placeholder_indices.is_empty() {
+            let mut upgraded: Vec<usize> = Vec::new();
             for &idx in &placeholder_indices {
                 if idx < captures.len() && xconn.try_upgrade_placeholder(&mut captures[idx]) {
+                    overview.forget_label_colors();
                     upgraded.push(idx);
+                    live_captures.insert(idx);
                     // Re-render this thumbnail with real content
                     let highlighted = last_hovered == Some(idx);
                     redraw_thumbnail(&xconn, &captures, &layouts, &overview, idx, highlighted)?;
@@ -1476,11 +3050,11 @@ fn run() -> Result<()> {
                         let animated_positions = drag_gap_animation.as_ref().map(|a| a.get_positions());
                         render_desktop_bar_with_drag(
                             &xconn, &overview, bar, dragged,
-                            desktop_insert_position, desktop_drag_cursor_x,
+                            desktop_insert_position, desktop_drag_cursor,
                             &captures, animated_positions,
                         )?;
                     } else {
-                        render_desktop_bar(&xconn, &overview, bar, 0, input_handler.hovered_desktop(), None, &captures)?;
+                        render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
                     }
                 }
                 needs_present = true;
@@ -1498,36 +3072,50 @@ fn run() -> Result<()> {
                     let animated_positions = drag_gap_animation.as_ref().map(|a| a.get_positions());
                     render_desktop_bar_with_drag(
                         &xconn, &overview, bar, dragged,
-                        desktop_insert_position, desktop_drag_cursor_x,
+                        desktop_insert_position, desktop_drag_cursor,
                         &captures, animated_positions,
                     )?;
                 } else {
-                    render_desktop_bar(&xconn, &overview, bar, 0, None, None, &captures)?;
+                    render_desktop_bar_now(&xconn, &overview, bar, desktop_bar_animation.as_ref(), None, &captures)?;
                 }
             }
             // Hide the animating window from the grid during animation
             render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
-            xconn.render_dragged_window(
-                capture.picture, overview.picture,
-                capture.info.width, capture.info.height,
+            xconn.render_dragged_capture(
+                capture, overview.picture.id(),
                 ax, ay, aw, ah,
             )?;
             needs_present = true;
 
             if anim.is_complete() {
                 match anim.mode {
-                    AnimationMode::SnapToDesktop { desktop_idx } => {
+                    AnimationMode::SnapToDesktop { desktop_idx, follow } => {
                         // Get window ID (use frame window for state tracking)
                         let window_id = captures[anim.window_index].info.frame_window;
 
                         // Move window using integrated desktop manager (0-indexed)
+                        let old_desktop = desktop_state.get_window_desktop_assignment(window_id);
                         match desktop::move_window(&xconn, &mut desktop_state, window_id, desktop_idx as u32) {
                             Ok(()) => {
                                 log::info!("Moved window 0x{:x} to desktop {}", window_id, desktop_idx);
                             }
                             Err(e) => {
-                                log::warn!("Failed to move window: {}", e);
+                                xconn.report_error(format!("Failed to move window: {}", e));
+                            }
+                        }
+                        if follow {
+                            // The window's new desktop becomes current, so its
+                            // old one can be removed if that left it empty
+                            desktop_state.current = desktop_idx as u32;
+                            desktop_state.sync_to_x(&xconn)?;
+                            if let Err(e) = desktop_state.save() {
+                                xconn.report_error(format!("Cannot save desktop state: {}", e));
                             }
+                            selected_window = Some(anim.window_index);
+                            follow_pending = true;
+                        }
+                        if config.remove_empty_desktops && backend.is_none() {
+                            queue_empty_desktop_removal(&desktop_state, old_desktop.as_slice(), &mut pending_actions);
                         }
 
                         // Recalculate mini-layouts for desktop previews
@@ -1540,27 +3128,25 @@ fn run() -> Result<()> {
                             );
                         }
 
-                        // Window was dropped on desktop - remove it from grid
-                        removed_windows.insert(anim.window_index);
+                        // Window was dropped on desktop - remove it from grid,
+                        // or move it to that desktop's group
+                        if !all_desktops {
+                            removed_windows.insert(anim.window_index);
+                        }
+                        overview.desktop_labels = desktop_labels(&captures, &desktop_state, all_desktops);
 
                         // Store old layouts before recalculation
                         let old_layouts = layouts.clone();
 
                         // Recalculate layout for remaining windows
-                        let new_layouts = recalculate_filtered_layout(
-                            &captures,
-                            &removed_windows,
-                            xconn.screen_width,
-                            xconn.screen_height,
-                            &config,
-                            bar_height,
-                        );
+                        let new_layouts = relayout!();
 
                         // Start grid transition animation
                         grid_transition_animation = Some(GridTransitionAnimation::new(
                             &old_layouts,
                             &new_layouts,
                             grid_transition_duration_ms,
+                            config.grid_easing,
                         ));
 
                         // Update layouts to new positions (animation will interpolate)
@@ -1576,6 +3162,15 @@ fn run() -> Result<()> {
                         // Window was dropped outside desktop - just return to grid
                         // (No removal, window already in layouts)
                     }
+                    AnimationMode::QuickLook => {
+                        quick_look = Some(ThumbnailLayout {
+                            x: anim.end_x,
+                            y: anim.end_y,
+                            width: anim.end_width,
+                            height: anim.end_height,
+                            window_index: anim.window_index,
+                        });
+                    }
                 }
 
                 drag_animation = None;
@@ -1589,19 +3184,13 @@ fn run() -> Result<()> {
 
             xconn.clear_overview(&overview)?;
             if let Some(ref bar) = desktop_bar {
-                render_desktop_bar(&xconn, &overview, bar, 0, None, None, &captures)?;
+                render_desktop_bar_now(&xconn, &overview, bar, desktop_bar_animation.as_ref(), None, &captures)?;
             }
 
             // Render thumbnails at interpolated positions
             for layout in &current_layouts {
-                let capture = &captures[layout.window_index];
-                xconn.render_thumbnail(
-                    capture.picture,
-                    overview.picture,
-                    capture.info.width,
-                    capture.info.height,
-                    layout,
-                )?;
+                let (picture, width, height) = xconn.thumbnail_source(&captures[layout.window_index], layout.width, layout.height);
+                xconn.render_thumbnail(picture, overview.picture.id(), width, height, layout)?;
             }
             needs_present = true;
 
@@ -1610,106 +3199,384 @@ fn run() -> Result<()> {
                 // Final render with exact final positions
                 xconn.clear_overview(&overview)?;
                 if let Some(ref bar) = desktop_bar {
-                    render_desktop_bar(&xconn, &overview, bar, 0, None, None, &captures)?;
+                    render_desktop_bar_now(&xconn, &overview, bar, desktop_bar_animation.as_ref(), None, &captures)?;
                 }
                 render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, None)?;
                 needs_present = true;
             }
         }
 
+        // Slide the desktop previews and fly the miniatures along the bar;
+        // a drag or grid transition going on draws the bar as it slides
+        if let Some(ref anim) = desktop_bar_animation {
+            let done = anim.is_complete();
+            if drag_animation.is_none() && grid_transition_animation.is_none() {
+                if let Some(ref bar) = desktop_bar {
+                    xconn.clear_overview(&overview)?;
+                    render_desktop_bar_now(
+                        &xconn,
+                        &overview,
+                        bar,
+                        (!done).then_some(anim),
+                        input_handler.hovered_desktop(),
+                        &captures,
+                    )?;
+                    render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+                    needs_present = true;
+                }
+            }
+            if done {
+                desktop_bar_animation = None;
+            }
+        }
+
+        if last_hovered != announced {
+            announced = last_hovered;
+            let description = last_hovered.map(|index| {
+                let place = layouts
+                    .iter()
+                    .position(|layout| layout.window_index == index)
+                    .map(|position| (position + 1, layouts.len()));
+                announce::describe(&captures[index].info, place, &desktop_state.display_name(desktop_state.current))
+            });
+            if let Err(e) = xconn.announce(description.as_deref()) {
+                log::debug!("Cannot announce the highlighted window: {}", e);
+            }
+        }
+
+        // Hover peek: show the window at rest under the pointer full-size,
+        // and put the grid back once the pointer leaves it or anything else
+        // goes on
+        let busy = dragging_window_index.is_some()
+            || drag_animation.is_some()
+            || grid_transition_animation.is_some()
+            || desktop_grid.is_some()
+            || grid_zoom.is_some()
+            || kill_pending.is_some()
+            || spotlight.is_some()
+            || quick_look.is_some();
+        if busy {
+            // Not to peek as soon as it is over
+            hovered_since = None;
+        }
+        if let Some(index) = peeking.filter(|&index| busy || last_hovered != Some(index)) {
+            log::debug!("Peek at window {} over", index);
+            peeking = None;
+            // Animations draw every frame whole anyway
+            if drag_animation.is_none() && grid_transition_animation.is_none() {
+                xconn.clear_overview(&overview)?;
+                if let Some(ref bar) = desktop_bar {
+                    render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                }
+                render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+            }
+            needs_present = true;
+        }
+        let peek_due = hovered_since.is_some_and(|since| since.elapsed() >= std::time::Duration::from_millis(config.peek_ms));
+        if let Some(index) = last_hovered.filter(|_| config.peek_ms > 0 && peeking.is_none() && peek_due) {
+            log::debug!("Peeking at window {}", index);
+            peeking = Some(index);
+            needs_present = true;
+        }
+        if let Some(index) = peeking.filter(|_| needs_present) {
+            // Whatever was redrawn since goes under the dimming again
+            xconn.clear_overview(&overview)?;
+            if let Some(ref bar) = desktop_bar {
+                render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+            }
+            render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, None)?;
+            xconn.draw_peek(&overview, captures[index].picture.id(), &window_rect(&captures, index))?;
+        }
+
+        // Tooltip: the details of the window at rest under the pointer, taken
+        // down like a peek, and not shown over one
+        if tooltip.as_ref().is_some_and(|&(index, _)| busy || peeking.is_some() || last_hovered != Some(index)) {
+            tooltip = None;
+            if peeking.is_none() && drag_animation.is_none() && grid_transition_animation.is_none() {
+                xconn.clear_overview(&overview)?;
+                if let Some(ref bar) = desktop_bar {
+                    render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                }
+                render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+            }
+            needs_present = true;
+        }
+        let tooltip_due =
+            hovered_since.is_some_and(|since| since.elapsed() >= std::time::Duration::from_millis(config.tooltip_ms));
+        let tooltip_wanted = config.tooltip_ms > 0 && tooltip.is_none() && peeking.is_none() && tooltip_due;
+        if let Some(index) = last_hovered.filter(|_| tooltip_wanted) {
+            let info = &captures[index].info;
+            let pid = xconn.window_pid(info.client_window).unwrap_or_else(|e| {
+                log::debug!("Cannot read the PID of 0x{:x}: {}", info.client_window, e);
+                None
+            });
+            let desktop = if desktop_state.is_sticky(info.frame_window) {
+                "all (pinned)".to_string()
+            } else {
+                desktop_state.display_name(grid_desktop(&desktop_state, info.frame_window))
+            };
+            log::debug!("Tooltip for window {}", index);
+            tooltip = Some((index, tooltip::tooltip_lines(info, &desktop, pid)));
+            needs_present = true;
+        }
+
+        // Whatever was redrawn since goes under the spotlight's dimming again
+        if let Some(layout) = spotlight.as_ref().filter(|_| needs_present) {
+            xconn.clear_overview(&overview)?;
+            if let Some(ref bar) = desktop_bar {
+                render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+            }
+            render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, None)?;
+            xconn.draw_spotlight(&overview, captures[layout.window_index].picture.id(), layout)?;
+        }
+
+        // The window in quick look over whatever was redrawn since, out of
+        // its place in the grid; kept live by the damage refreshes above
+        if let Some(layout) = quick_look.as_ref().filter(|_| needs_present) {
+            if grid_transition_animation.is_none() {
+                xconn.clear_overview(&overview)?;
+                if let Some(ref bar) = desktop_bar {
+                    render_desktop_bar(&xconn, &overview, bar, (0, 0), input_handler.hovered_desktop(), None, &captures)?;
+                }
+                render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, Some(layout.window_index))?;
+            }
+            let capture = &captures[layout.window_index];
+            let (x, y, width, height) = (layout.x, layout.y, layout.width, layout.height);
+            xconn.render_dragged_capture(capture, overview.picture.id(), x, y, width, height)?;
+        }
+
+        // Keep the window marked to be killed red through other redraws
+        if let Some(index) = kill_pending.filter(|_| needs_present && grid_transition_animation.is_none()) {
+            if let Some(layout) = find_layout(&layouts, index) {
+                redraw_thumbnail(&xconn, &captures, &layouts, &overview, index, false)?;
+                xconn.draw_kill_confirm(&overview, layout)?;
+            }
+        }
+
+        // Pulse the borders of windows asking for attention, and of the
+        // desktop previews holding them, on their own steadier beat
+        let pulse_due = next_pulse.is_some_and(|at| at <= Instant::now());
+        next_pulse = None;
+        let covered = desktop_grid.is_some()
+            || grid_zoom.is_some()
+            || peeking.is_some()
+            || spotlight.is_some()
+            || quick_look.is_some();
+        if !overview.urgent.is_empty() && !covered && grid_transition_animation.is_none() {
+            if needs_present || pulse_due {
+                let pulse = animation::urgency_pulse(pulse_start.elapsed());
+                draw_urgent_borders(
+                    &xconn,
+                    &overview,
+                    &captures,
+                    &layouts,
+                    desktop_bar.as_ref().filter(|_| desktop_dragging.is_none()),
+                    [last_hovered, dragging_window_index],
+                    input_handler.hovered_desktop(),
+                    pulse,
+                )?;
+                needs_present = true;
+            }
+            next_pulse = Some(Instant::now() + URGENCY_PULSE_STEP);
+        }
+
+        // Over whatever was redrawn under the tooltip
+        if let Some((index, lines)) = tooltip.as_ref().filter(|_| needs_present) {
+            if let Some(layout) = find_layout(&layouts, *index) {
+                xconn.draw_tooltip(&overview, layout, lines)?;
+            }
+        }
+
+        // Keep the gap opening between moves of a dragged desktop; moved
+        // from the keyboard, no pointer motion steps it
+        if let (Some(dragged), Some(ref bar), Some(ref mut anim)) = (desktop_dragging, &desktop_bar, &mut drag_gap_animation) {
+            if !anim.is_settled() && next_frame.is_none_or(|at| at <= Instant::now()) {
+                anim.step();
+                xconn.clear_overview(&overview)?;
+                render_desktop_bar_with_drag(
+                    &xconn,
+                    &overview,
+                    bar,
+                    dragged,
+                    desktop_insert_position,
+                    desktop_drag_cursor,
+                    &captures,
+                    Some(anim.get_positions()),
+                )?;
+                render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+                needs_present = true;
+            }
+        }
+
+        // The desktop grid, or the zoom into or out of it, covers whatever
+        // was drawn above
+        if let (Some(zoom), Some(ref bar)) = (&grid_zoom, &desktop_bar) {
+            let duration_ms = grid_transition_duration_ms;
+            if render_desktop_grid_zoom(&xconn, &overview, bar, zoom, &captures, duration_ms, config.grid_easing)? {
+                if !zoom.zoom_out {
+                    xconn.clear_overview(&overview)?;
+                    render_desktop_bar(&xconn, &overview, bar, (0, 0), None, None, &captures)?;
+                    render_all_thumbnails(&xconn, &captures, &layouts, &overview, last_hovered, dragging_window_index)?;
+                }
+                grid_zoom = None;
+            }
+            needs_present = true;
+        }
+        if let Some(grid) = desktop_grid.as_ref().filter(|_| grid_zoom.is_none()) {
+            if needs_present {
+                render_desktop_grid(&xconn, &overview, grid, input_handler.hovered_desktop(), grid_drag, &captures)?;
+            }
+        }
+
+        let toasts_active = xconn.toasts_active();
         if needs_present {
             xconn.present_overview(&overview)?;
+        } else if toasts_active {
+            xconn.present_toasts(&overview)?;
         }
 
-        // Continue animation loop if animation is active
-        if drag_animation.is_some() || grid_transition_animation.is_some() {
-            thread::sleep(std::time::Duration::from_millis(16)); // ~60fps
-            continue;
+        // Keep animations and toast fade-outs stepping; events arriving in
+        // between are still handled at once
+        let gap_opening = desktop_dragging.is_some() && drag_gap_animation.as_ref().is_some_and(|a| !a.is_settled());
+        let animating = drag_animation.is_some()
+            || grid_transition_animation.is_some()
+            || desktop_bar_animation.is_some()
+            || grid_zoom.is_some()
+            || gap_opening
+            || toasts_active;
+        let now = Instant::now();
+        next_frame = match next_frame {
+            _ if !animating => None,
+            Some(at) if at > now => Some(at),
+            due => {
+                if due.is_none() {
+                    frame_pacer = FramePacer::new(FRAME_INTERVAL);
+                }
+                // Time the next frame from when the server has drawn this one
+                xconn.sync()?;
+                Some(Instant::now() + frame_pacer.frame_wait())
+            }
+        };
+    }
+
+    // A window picked from another desktop's group takes its desktop along,
+    // and the windows left on other desktops go no further than the grid
+    if all_desktops {
+        if let Some(index) = selected_window.filter(|&i| i < captures.len()) {
+            let frame = captures[index].info.frame_window;
+            match desktop_state.get_window_desktop_assignment(frame) {
+                Some(desktop) if !desktop_state.is_visible_on(frame, desktop_state.current) => {
+                    log::info!("Switching to desktop {} with the picked window", desktop);
+                    desktop_state.current = desktop;
+                    desktop_state.sync_to_x(&xconn)?;
+                }
+                _ => {}
+            }
         }
+        refresh_removed_windows(
+            &xconn,
+            &captures,
+            &destroyed_captures,
+            &filtered_captures,
+            &desktop_state,
+            false,
+            &mut removed_windows,
+        )?;
     }
 
     // Run exit animation
     if let Some(desktop_idx) = selected_desktop {
-        // Desktop zoom animation - scale the selected desktop preview to full screen
-        if let Some(ref bar) = desktop_bar {
-            if let Some(preview) = bar.preview_layouts.iter().find(|p| p.desktop_index == desktop_idx) {
-                // Log what mini_windows we're about to animate
-                log::info!("Desktop zoom animation for desktop {}", desktop_idx);
-                log::info!("Preview mini_windows ({}):", preview.mini_windows.len());
-                for (i, mini) in preview.mini_windows.iter().enumerate() {
+        // Desktop zoom animation - scale the selected desktop preview (or its
+        // tile, when picked from the desktop grid) to full screen
+        let preview = match desktop_grid {
+            Some(ref grid) => grid.tile(desktop_idx),
+            None => desktop_bar
+                .as_ref()
+                .and_then(|bar| bar.preview_layouts.iter().find(|p| p.desktop_index == desktop_idx)),
+        };
+        if let Some(preview) = preview {
+            // Log what mini_windows we're about to animate
+            log::info!("Desktop zoom animation for desktop {}", desktop_idx);
+            log::info!("Preview mini_windows ({}):", preview.mini_windows.len());
+            for (i, mini) in preview.mini_windows.iter().enumerate() {
+                let name = captures.iter()
+                    .find(|c| c.info.frame_window == mini.window_id)
+                    .and_then(|c| c.info.wm_name.as_deref())
+                    .unwrap_or("?");
+                log::info!("  [{}] {:?} (0x{:x})", i, name, mini.window_id);
+            }
+
+            // Log the original stacking order (filtered to this desktop)
+            log::info!("Original stacking order (windows on desktop {}):", desktop_idx);
+            for (i, &frame) in original_stacking_order.iter().enumerate() {
+                if desktop_state.is_visible_on(frame, desktop_idx) {
                     let name = captures.iter()
-                        .find(|c| c.info.frame_window == mini.window_id)
+                        .find(|c| c.info.frame_window == frame)
                         .and_then(|c| c.info.wm_name.as_deref())
                         .unwrap_or("?");
-                    log::info!("  [{}] {:?} (0x{:x})", i, name, mini.window_id);
-                }
-
-                // Log the original stacking order (filtered to this desktop)
-                log::info!("Original stacking order (windows on desktop {}):", desktop_idx);
-                for (i, &frame) in original_stacking_order.iter().enumerate() {
-                    if desktop_state.is_visible_on(frame, desktop_idx) {
-                        let name = captures.iter()
-                            .find(|c| c.info.frame_window == frame)
-                            .and_then(|c| c.info.wm_name.as_deref())
-                            .unwrap_or("?");
-                        log::info!("  [{}] {:?} (0x{:x})", i, name, frame);
-                    }
+                    log::info!("  [{}] {:?} (0x{:x})", i, name, frame);
                 }
+            }
 
-                let exit_animator = Animator::new(vec![], vec![], &exit_anim);
+            let mut exit_animator = Animator::new(vec![], vec![], &exit_anim);
 
-                // Start position: preview in the bar
-                let start_x = preview.x as f64;
-                let start_y = preview.y as f64;
-                let start_w = preview.width as f64;
-                let start_h = preview.height as f64;
+            // Start position: preview in the bar
+            let start_x = preview.x as f64;
+            let start_y = preview.y as f64;
+            let start_w = preview.width as f64;
+            let start_h = preview.height as f64;
 
-                // End position: full screen
-                let end_x = 0.0_f64;
-                let end_y = 0.0_f64;
-                let end_w = xconn.screen_width as f64;
-                let end_h = xconn.screen_height as f64;
+            // End position: full screen
+            let end_x = 0.0_f64;
+            let end_y = 0.0_f64;
+            let end_w = xconn.screen_width as f64;
+            let end_h = xconn.screen_height as f64;
 
-                while !exit_animator.is_complete() {
-                    let progress = exit_animator.progress();
+            while !exit_animator.is_complete() {
+                let progress = exit_animator.progress();
 
-                    // Interpolate position and size
-                    let cur_x = (start_x + (end_x - start_x) * progress) as i16;
-                    let cur_y = (start_y + (end_y - start_y) * progress) as i16;
-                    let cur_w = (start_w + (end_w - start_w) * progress) as u16;
-                    let cur_h = (start_h + (end_h - start_h) * progress) as u16;
+                // Interpolate position and size
+                let cur_x = (start_x + (end_x - start_x) * progress) as i16;
+                let cur_y = (start_y + (end_y - start_y) * progress) as i16;
+                let cur_w = (start_w + (end_w - start_w) * progress) as u16;
+                let cur_h = (start_h + (end_h - start_h) * progress) as u16;
 
-                    xconn.clear_overview(&overview)?;
-                    xconn.render_desktop_preview_animated(
-                        &overview,
-                        preview,
-                        &captures,
-                        cur_x,
-                        cur_y,
-                        cur_w,
-                        cur_h,
-                    )?;
-                    xconn.present_overview(&overview)?;
-                    thread::sleep(exit_animator.frame_duration());
-                }
+                xconn.clear_overview(&overview)?;
+                xconn.render_desktop_preview_animated(
+                    &overview,
+                    preview,
+                    &captures,
+                    cur_x,
+                    cur_y,
+                    cur_w,
+                    cur_h,
+                )?;
+                xconn.present_overview(&overview)?;
+                xconn.sync()?;
+                thread::sleep(exit_animator.frame_wait());
+            }
+            if exit_animator.skipped_frames() > 0 {
+                log::info!("Desktop zoom fell behind, skipped {} frames", exit_animator.skipped_frames());
             }
         }
     } else {
         // Normal window exit animation - fade in skipped windows while animating managed windows back
         let exit_start: Vec<AnimatedLayout> =
             layouts.iter().map(AnimatedLayout::from).collect();
-        let exit_end: Vec<ThumbnailLayout> = grid_infos
+        let exit_end: Vec<ThumbnailLayout> = layouts
             .iter()
-            .zip(grid_indices.iter())
-            .map(|(info, &capture_idx)| ThumbnailLayout {
-                x: info.x,
-                y: info.y,
-                width: info.width,
-                height: info.height,
-                window_index: capture_idx,
+            .map(|layout| match tile_to.filter(|_| selected_window == Some(layout.window_index)) {
+                Some(geometry) => ThumbnailLayout {
+                    x: geometry.x,
+                    y: geometry.y,
+                    width: geometry.width,
+                    height: geometry.height,
+                    window_index: layout.window_index,
+                },
+                None => window_rect(&captures, layout.window_index),
             })
             .collect();
-        let exit_animator = Animator::new(exit_start, exit_end, &exit_anim);
+        let mut exit_animator = Animator::new(exit_start, exit_end, &exit_anim);
 
         // Build render order: original Z-order (bottom to top), with selected window last
         // Map from original_stacking_order (frame IDs) to indices in captures array
@@ -1738,8 +3605,8 @@ fn run() -> Result<()> {
             // Render skipped windows with fading in opacity (0.0 → 1.0)
             for capture in &skipped_captures {
                 xconn.render_window_with_opacity(
-                    capture.picture,
-                    overview.picture,
+                    capture.picture.id(),
+                    overview.picture.id(),
                     capture.info.x,
                     capture.info.y,
                     capture.info.width,
@@ -1752,23 +3619,33 @@ fn run() -> Result<()> {
             for &idx in &render_order {
                 // Find the layout for this window index
                 if let Some(layout) = current.iter().find(|l| l.window_index == idx) {
-                    xconn.render_thumbnail_animated(
-                        captures[idx].picture,
-                        overview.picture,
-                        captures[idx].info.width,
-                        captures[idx].info.height,
-                        layout,
-                    )?;
+                    let (picture, width, height) = xconn.thumbnail_source(&captures[idx], layout.width, layout.height);
+                    xconn.render_thumbnail_animated(picture, overview.picture.id(), width, height, layout)?;
                 }
             }
 
             xconn.present_overview(&overview)?;
-            thread::sleep(exit_animator.frame_duration());
+            xconn.sync()?;
+            thread::sleep(exit_animator.frame_wait());
+        }
+        if exit_animator.skipped_frames() > 0 {
+            log::info!("Exit animation fell behind, skipped {} frames", exit_animator.skipped_frames());
         }
     }
 
     // Cleanup
     log::debug!("Cleaning up");
+    if let Err(e) = xconn.announce(None) {
+        log::debug!("Cannot clear the highlighted window: {}", e);
+    }
+
+    // Windows destroyed during the session need no restoring
+    let destroyed_frames: HashSet<Window> = destroyed_captures
+        .iter()
+        .map(|&idx| captures[idx].info.frame_window)
+        .collect();
+    windows.retain(|info| !destroyed_frames.contains(&info.frame_window));
+    original_stacking_order.retain(|frame| !destroyed_frames.contains(frame));
 
     // Restore original X positions for windows that were moved off-screen
     // Use the original `windows` array which has positions from before we moved them
@@ -1780,9 +3657,57 @@ fn run() -> Result<()> {
     }
     xconn.flush()?;
 
-    // Restore window visibility based on current desktop (unmap windows on other desktops)
-    desktop::restore_window_visibility(&xconn, &desktop_state, &windows)?;
-    log::info!("Restored window visibility for desktop {}", desktop_state.current);
+    if show_desktop {
+        if let Err(e) = desktop::toggle_show_desktop(&xconn, &mut desktop_state, &windows) {
+            log::warn!("Cannot show the desktop: {}", e);
+        }
+    }
+    match backend.as_deref_mut() {
+        // The window manager moves the windows and shows the workspace
+        Some(backend) => {
+            if let Err(e) = desktop::backend::apply_workspaces(backend, &workspaces, &desktop_state, &windows) {
+                log::warn!("Cannot update {}'s workspaces: {}", backend.name(), e);
+            }
+        }
+        None => {
+            // Restore window visibility based on current desktop (unmap windows on other desktops)
+            desktop::restore_window_visibility(&xconn, &desktop_state, &windows)?;
+            log::info!("Restored window visibility for desktop {}", desktop_state.current);
+        }
+    }
+    if config.restore_geometry {
+        desktop::restore_geometry(&xconn, &desktop_state, &windows, desktop_state.current)?;
+    }
+
+    // Switched desktops: show the new desktop's wallpaper, if it has one
+    if desktop_state.current != current_desktop {
+        if let Some(path) = config.wallpapers.get(&desktop_state.current) {
+            if let Err(e) = wallpaper::set_root_wallpaper(path) {
+                log::warn!("Cannot set wallpaper for desktop {}: {}", desktop_state.current, e);
+            }
+        }
+    }
+    desktop_state.displaced.clear();
+    desktop::remember_identities(&mut desktop_state, &windows, &config.identity_titles);
+    if let Some(index) = selected_window.filter(|&i| i < captures.len()) {
+        let frame = captures[index].info.frame_window;
+        if let Some(desktop) = desktop_state.get_window_desktop_assignment(frame) {
+            desktop_state.set_last_selected(desktop, frame);
+        }
+        if toggle_maximized {
+            if let Err(e) = placement::toggle_maximized(&xconn, &mut desktop_state, &captures[index].info) {
+                log::warn!("Cannot maximize window: {}", e);
+            }
+        }
+        if let Some(geometry) = tile_to {
+            if let Err(e) = placement::tile(&xconn, &mut desktop_state, &captures[index].info, geometry) {
+                log::warn!("Cannot tile window: {}", e);
+            }
+        }
+    }
+    if let Err(e) = desktop_state.save() {
+        log::warn!("Cannot save desktop state: {}", e);
+    }
 
     // Restore original window stacking order before raising selected window
     xconn.restore_stacking_order(&original_stacking_order)?;
@@ -1795,32 +3720,53 @@ fn run() -> Result<()> {
                 "Raising window: {:?}",
                 window_info.wm_name.as_deref().unwrap_or("(unnamed)")
             );
-            xconn.raise_and_focus(window_info)?;
-            xconn.sync()?; // Round-trip to ensure raise is fully processed
+            match xconn.raise_and_focus(window_info) {
+                Ok(()) => {
+                    if let Err(e) = WindowState::save_focus(window_info.frame_window) {
+                        log::warn!("Cannot save focus history: {}", e);
+                    }
+                }
+                Err(e) if e.is_window_gone() => {
+                    log::info!("Selected window was destroyed, nothing to raise");
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
-    // Log final Z-order for comparison
-    xconn.log_current_zorder(&original_stacking_order)?;
+    // Log final Z-order for comparison; a round trip per window, so only
+    // when someone is reading
+    if log::log_enabled!(log::Level::Debug) {
+        if let Err(e) = xconn.log_current_zorder(&original_stacking_order) {
+            log::debug!("Cannot log final Z-order: {}", e);
+        }
+    }
 
     xconn.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
     xconn.conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
-    xconn.sync()?; // Ensure ungrabs are processed
-    xconn.destroy_overview(&overview)?;
+    xconn.sync()?; // Ensure the raise and ungrabs are processed
+    xconn.destroy_overview(overview)?;
 
-    for capture in &captures {
-        if let Err(e) = xconn.release_capture(capture) {
-            log::warn!("Failed to release capture: {}", e);
-        }
-    }
+    // Snapshot live windows for the next launch
+    xconn.save_thumbnail_cache(&captures, &live_captures);
+
+    // Freeing the captures puts the windows back as they were
+    drop(captures);
+    drop(skipped_captures);
+    xconn.flush()?;
 
-    for capture in &skipped_captures {
-        if let Err(e) = xconn.release_capture(capture) {
-            log::warn!("Failed to release skipped capture: {}", e);
+    // Drain errors for requests sent during teardown before summarizing
+    xconn.sync()?;
+    while let Some(event) = xconn.conn.poll_for_event()? {
+        if let Event::Error(ref e) = event {
+            xconn.record_async_error(e);
         }
     }
+    log::info!("{}", xconn.errors.summary());
 
-    xconn.flush()?;
+    if let Some((x, y, button)) = replay_click {
+        xconn.replay_click(x, y, button)?;
+    }
 
     log::info!("Done");
     Ok(())
@@ -1835,6 +3781,18 @@ fn render_all_thumbnails(
     highlighted: Option<usize>,
     excluded: Option<usize>,
 ) -> Result<()> {
+    // Each desktop's name over its group, while the grid shows every desktop
+    let mut groups: HashMap<&str, (i16, i16)> = HashMap::new();
+    for layout in layouts.iter().filter(|l| Some(l.window_index) != excluded) {
+        if let Some((_, name)) = overview.desktop_labels.get(&captures[layout.window_index].info.frame_window) {
+            let corner = groups.entry(name).or_insert((layout.x, layout.y));
+            *corner = (corner.0.min(layout.x), corner.1.min(layout.y));
+        }
+    }
+    for (name, (x, y)) in groups {
+        xconn.draw_desktop_label(overview, x, y, name)?;
+    }
+
     // Iterate over layouts and use window_index to find the correct capture.
     // This is necessary because after windows are removed, layouts are filtered
     // but captures remain unchanged - layout.window_index maps back to captures.
@@ -1845,18 +3803,70 @@ fn render_all_thumbnails(
             continue;
         }
         let capture = &captures[idx];
-        xconn.render_thumbnail(
-            capture.picture,
-            overview.picture,
-            capture.info.width,
-            capture.info.height,
-            layout,
-        )?;
-        xconn.draw_thumbnail_border(overview, layout, Some(idx) == highlighted)?;
+        let piled = overview.piles.contains_key(&capture.info.frame_window);
+        let top = if piled {
+            xconn.draw_pile_behind(overview, layout)?;
+            layout.pile_top()
+        } else {
+            layout.clone()
+        };
+        xconn.render_capture_thumbnail(capture, overview.picture.id(), &top)?;
+        draw_frame(xconn, overview, capture, &top, Some(idx) == highlighted)?;
+        if let Some((x, y, size)) = top.pin_badge().filter(|_| overview.pinned.contains(&capture.info.frame_window)) {
+            xconn.render_pin(overview, x, y, size)?;
+        }
+        if let (Some((x, y, size)), Some(icon)) = (top.icon_badge(), overview.icons.get(&capture.info.frame_window)) {
+            xconn.render_icon(overview, icon, x, y, size)?;
+        }
+        let matched = overview.search.as_ref().and_then(|search| search.matched.get(&capture.info.frame_window));
+        if let Some(matched) = matched.filter(|_| !piled) {
+            let title = capture.info.wm_name.as_deref().unwrap_or("(untitled)");
+            xconn.draw_caption(overview, layout, title, matched)?;
+        }
+        if Some(idx) == highlighted && !piled {
+            let allowed = overview.allowed(capture.info.frame_window);
+            if let Some((x, y, size)) = layout.close_button().filter(|_| allowed.close) {
+                xconn.render_delete_button(overview, x, y, size, false)?;
+            }
+            if let Some((x, y, size)) = layout.minimize_button().filter(|_| allowed.minimize) {
+                xconn.render_minimize_button(overview, x, y, size)?;
+            }
+        }
+    }
+    if let Some(search) = &overview.search {
+        xconn.draw_search_query(overview, &search.query)?;
     }
     Ok(())
 }
 
+/// The monitors to lay windows out on: all of them, or only `only`.
+fn layout_monitors(monitors: &[Monitor], only: Option<usize>) -> Result<Vec<Monitor>> {
+    let Some(index) = only else {
+        return Ok(monitors.to_vec());
+    };
+    match monitors.get(index) {
+        // Windows on no monitor of the list go to the primary one
+        Some(monitor) => Ok(vec![Monitor { primary: true, ..monitor.clone() }]),
+        None => Err(error::XposeError::Other(format!(
+            "No monitor {} (there are {})",
+            index + 1,
+            monitors.len()
+        ))),
+    }
+}
+
+/// Durations of the exit animation and of the snap, revert and grid
+/// transition animations (in ms), scaled by the configured speed.
+fn session_durations(config: &Config) -> (AnimationConfig, u64, u64, u64) {
+    let speed = config.animation_speed;
+    (
+        AnimationConfig::new(scale_duration(config.exit_duration(), speed), config.exit_easing),
+        scale_duration_ms(SNAP_DURATION_MS, speed),
+        scale_duration_ms(REVERT_DURATION_MS, speed),
+        scale_duration_ms(GRID_TRANSITION_DURATION_MS, speed),
+    )
+}
+
 fn scale_duration(duration: std::time::Duration, speed: f64) -> std::time::Duration {
     let speed = if speed > 0.0 { speed } else { 1.0 };
     let scaled = duration.as_secs_f64() / speed;
@@ -1873,6 +3883,301 @@ fn find_layout(layouts: &[ThumbnailLayout], window_index: usize) -> Option<&Thum
     layouts.iter().find(|l| l.window_index == window_index)
 }
 
+/// The monitor a window's thumbnail is on, or else the primary one.
+fn thumbnail_monitor<'a>(
+    layouts: &[ThumbnailLayout],
+    monitors: &'a [Monitor],
+    window_index: usize,
+) -> Option<&'a Monitor> {
+    let centre = find_layout(layouts, window_index)
+        .map(|l| (l.x as i32 + l.width as i32 / 2, l.y as i32 + l.height as i32 / 2));
+    monitors
+        .iter()
+        .find(|m| centre.is_some_and(|(x, y)| m.contains(x, y)))
+        .or_else(|| monitors.iter().find(|m| m.primary))
+        .or(monitors.first())
+}
+
+/// Lay out the desktop grid for the current desktops and windows.
+fn build_desktop_grid(
+    xconn: &XConnection,
+    captures: &[CapturedWindow],
+    desktop_state: &desktop::DesktopState,
+) -> DesktopGrid {
+    let mut grid = DesktopGrid::new(
+        desktop_state.desktops,
+        desktop_state.current,
+        xconn.screen_width,
+        xconn.screen_height,
+    );
+    grid.calculate_mini_layouts(captures, desktop_state, xconn.screen_width, xconn.screen_height);
+    grid
+}
+
+/// Draw the pulsing borders of urgent windows in the grid, except `skip`
+/// (hovered or dragged), and of the desktop previews holding them.
+#[allow(clippy::too_many_arguments)]
+fn draw_urgent_borders(
+    xconn: &XConnection,
+    overview: &OverviewWindow,
+    captures: &[CapturedWindow],
+    layouts: &[ThumbnailLayout],
+    desktop_bar: Option<&DesktopBar>,
+    skip: [Option<usize>; 2],
+    hovered_desktop: Option<u32>,
+    pulse: f64,
+) -> Result<()> {
+    for layout in layouts {
+        let frame = captures[layout.window_index].info.frame_window;
+        if !overview.urgent.contains(&frame) || skip.contains(&Some(layout.window_index)) {
+            continue;
+        }
+        let top = if overview.piles.contains_key(&frame) { layout.pile_top() } else { layout.clone() };
+        xconn.draw_urgent_border(overview, &top, pulse)?;
+    }
+    for preview in desktop_bar.iter().flat_map(|bar| &bar.preview_layouts) {
+        if preview.mini_windows.iter().any(|mini| overview.urgent.contains(&mini.window_id)) {
+            let highlighted = preview.is_current || hovered_desktop == Some(preview.desktop_index);
+            xconn.draw_urgent_preview_border(overview, preview, highlighted, pulse)?;
+        }
+    }
+    Ok(())
+}
+
+/// Say that the window manager keeping the workspaces is where to add,
+/// remove and reorder them.
+fn report_workspaces_kept(xconn: &XConnection, backend: Option<&dyn DesktopBackend>) {
+    if let Some(backend) = backend {
+        xconn.report_error(format!("Workspaces are kept by {}; add, remove and reorder them there", backend.name()));
+    }
+}
+
+/// Queue the removal of desktops their last window just left, highest first
+/// so the queued indices stay valid as desktops go. The current desktop stays.
+fn queue_empty_desktop_removal(
+    desktop_state: &desktop::DesktopState,
+    left: &[u32],
+    pending_actions: &mut Vec<InputAction>,
+) {
+    let mut empty: Vec<u32> = left
+        .iter()
+        .copied()
+        .filter(|&d| d != desktop_state.current && d < desktop_state.desktops && desktop_state.is_desktop_empty(d))
+        .collect();
+    empty.sort_unstable_by(|a, b| b.cmp(a));
+    empty.dedup();
+    for desktop in empty {
+        log::info!("Desktop {} is empty, removing it", desktop);
+        pending_actions.push(InputAction::DeleteDesktop(desktop));
+    }
+}
+
+/// Render the desktop grid, with a window miniature being dragged on top.
+fn render_desktop_grid(
+    xconn: &XConnection,
+    overview: &OverviewWindow,
+    grid: &DesktopGrid,
+    hovered_desktop: Option<u32>,
+    drag: Option<(Window, i16, i16)>,
+    captures: &[CapturedWindow],
+) -> Result<()> {
+    xconn.clear_overview(overview)?;
+
+    let dragged = drag.and_then(|(window_id, x, y)| {
+        grid.tiles
+            .iter()
+            .flat_map(|t| &t.mini_windows)
+            .find(|m| m.window_id == window_id)
+            .map(|m| (m.clone(), x, y))
+    });
+
+    for tile in &grid.tiles {
+        let is_hovered = hovered_desktop == Some(tile.desktop_index);
+        match dragged {
+            // The dragged miniature leaves a hole where it came from
+            Some((ref mini, _, _)) if tile.mini_windows.iter().any(|m| m.window_id == mini.window_id) => {
+                let mut tile = tile.clone();
+                tile.mini_windows.retain(|m| m.window_id != mini.window_id);
+                xconn.render_desktop_preview_full(overview, &tile, captures, is_hovered, (0, 0))?;
+            }
+            _ => xconn.render_desktop_preview_full(overview, tile, captures, is_hovered, (0, 0))?,
+        }
+    }
+
+    if let Some((mini, x, y)) = dragged {
+        if let Some(capture) = captures.iter().find(|c| c.info.frame_window == mini.window_id) {
+            xconn.render_dragged_capture(
+                capture, overview.picture.id(),
+                x - (mini.width / 2) as i16, y - (mini.height / 2) as i16, mini.width, mini.height,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Draw a frame of `zoom`: the desktop bar previews growing into the
+/// desktop grid tiles, or the tiles shrinking back into the bar. Returns
+/// whether it is over.
+fn render_desktop_grid_zoom(
+    xconn: &XConnection,
+    overview: &OverviewWindow,
+    bar: &DesktopBar,
+    zoom: &GridZoom,
+    captures: &[CapturedWindow],
+    duration_ms: u64,
+    easing: Easing,
+) -> Result<bool> {
+    let t = (zoom.start_time.elapsed().as_millis() as f64 / duration_ms.max(1) as f64).min(1.0);
+    let eased = easing.apply(t);
+    let progress = if zoom.zoom_out { eased } else { 1.0 - eased };
+    let lerp = |a: i32, b: i32| (a as f64 + (b - a) as f64 * progress) as i32;
+
+    xconn.clear_overview(overview)?;
+    for tile in &zoom.grid.tiles {
+        let Some(preview) = bar.preview_layouts.iter().find(|p| p.desktop_index == tile.desktop_index) else {
+            continue;
+        };
+        xconn.render_desktop_preview_animated(
+            overview,
+            tile,
+            captures,
+            lerp(preview.x as i32, tile.x as i32) as i16,
+            lerp(preview.y as i32, tile.y as i32) as i16,
+            lerp(preview.width as i32, tile.width as i32) as u16,
+            lerp(preview.height as i32, tile.height as i32) as u16,
+        )?;
+    }
+    Ok(t >= 1.0)
+}
+
+/// Recompute which captures are left out of the grid after desktop
+/// assignments changed: those off the current desktop, or with
+/// `all_desktops` only destroyed, filtered out and minimized ones. Windows that joined the
+/// current desktop are moved back on-screen (they were moved off-screen at
+/// startup because they were on other desktops).
+fn refresh_removed_windows(
+    xconn: &XConnection,
+    captures: &[CapturedWindow],
+    destroyed_captures: &HashSet<usize>,
+    filtered_captures: &HashSet<usize>,
+    desktop_state: &desktop::DesktopState,
+    all_desktops: bool,
+    removed_windows: &mut HashSet<usize>,
+) -> Result<()> {
+    let on_current =
+        |capture: &CapturedWindow| desktop_state.is_visible_on(capture.info.frame_window, desktop_state.current);
+    let old_removed = std::mem::take(removed_windows);
+    *removed_windows = captures
+        .iter()
+        .enumerate()
+        .filter(|(i, capture)| {
+            destroyed_captures.contains(i)
+                || filtered_captures.contains(i)
+                || if all_desktops {
+                    desktop_state.is_app_hidden(capture.info.frame_window)
+                } else {
+                    !on_current(capture)
+                }
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    for (i, capture) in captures.iter().enumerate() {
+        // Every desktop is in the grid already; go by what was moved away
+        let joined = if all_desktops {
+            on_current(capture) && desktop_state.displaced.contains_key(&capture.info.frame_window.to_string())
+        } else {
+            old_removed.contains(&i) && !removed_windows.contains(&i)
+        };
+        if joined {
+            log::info!("Moving window {:?} back on-screen", capture.info.wm_name);
+            xconn.conn.configure_window(
+                capture.info.frame_window,
+                &ConfigureWindowAux::new().x(capture.info.x as i32),
+            )?;
+        }
+    }
+    xconn.flush()?;
+    Ok(())
+}
+
+/// Animation shrinking a dropped window from `rect` into a desktop preview.
+#[allow(clippy::too_many_arguments)]
+fn snap_animation(
+    bar: &DesktopBar,
+    captures: &[CapturedWindow],
+    window_index: usize,
+    desktop_idx: u32,
+    rect: (i16, i16, u16, u16),
+    duration_ms: u64,
+    easing: Easing,
+    follow: bool,
+) -> Option<DragAnimation> {
+    let (target_x, target_y) = bar.get_preview_center(desktop_idx)?;
+    // Target size is small (preview size)
+    let capture = &captures[window_index];
+    let aspect = capture.info.height as f64 / capture.info.width as f64;
+    let target_width = 60u16;
+    let target_height = (60.0 * aspect) as u16;
+
+    Some(DragAnimation {
+        mode: AnimationMode::SnapToDesktop { desktop_idx: desktop_idx as usize, follow },
+        window_index,
+        start_x: rect.0,
+        start_y: rect.1,
+        start_width: rect.2,
+        start_height: rect.3,
+        end_x: target_x - (target_width / 2) as i16,
+        end_y: target_y - (target_height / 2) as i16,
+        end_width: target_width,
+        end_height: target_height,
+        start_time: Instant::now(),
+        duration_ms,
+        easing,
+    })
+}
+
+/// Animation returning a dragged window from `rect` to its grid slot.
+fn revert_animation(
+    layouts: &[ThumbnailLayout],
+    window_index: usize,
+    rect: (i16, i16, u16, u16),
+    duration_ms: u64,
+    easing: Easing,
+) -> Option<DragAnimation> {
+    let layout = find_layout(layouts, window_index)?;
+    Some(DragAnimation {
+        mode: AnimationMode::RevertToGrid,
+        window_index,
+        start_x: rect.0,
+        start_y: rect.1,
+        start_width: rect.2,
+        start_height: rect.3,
+        end_x: layout.x,
+        end_y: layout.y,
+        end_width: layout.width,
+        end_height: layout.height,
+        start_time: Instant::now(),
+        duration_ms,
+        easing,
+    })
+}
+
+/// A thumbnail's border; in the all-desktops grid, in its desktop's accent
+/// color with the desktop's number.
+fn draw_frame(
+    xconn: &XConnection,
+    overview: &OverviewWindow,
+    capture: &CapturedWindow,
+    layout: &ThumbnailLayout,
+    highlighted: bool,
+) -> Result<()> {
+    match overview.desktop_labels.get(&capture.info.frame_window) {
+        Some(&(desktop, _)) => xconn.draw_desktop_frame(overview, layout, desktop, highlighted),
+        None => xconn.draw_thumbnail_border(overview, layout, highlighted),
+    }
+}
+
 /// Redraw a single thumbnail (used for hover updates).
 /// `window_index` is the index into captures array (the window_index from layouts).
 fn redraw_thumbnail(
@@ -1898,22 +4203,44 @@ fn redraw_thumbnail(
     // Clear the area first
     xconn.clear_thumbnail_area(overview, layout)?;
 
+    // A group's top window, with the others piled up behind it
+    let pile = overview.piles.get(&capture.info.frame_window);
+    let top = if pile.is_some() {
+        xconn.draw_pile_behind(overview, layout)?;
+        layout.pile_top()
+    } else {
+        layout.clone()
+    };
+
     // Redraw thumbnail
-    xconn.render_thumbnail(
-        capture.picture,
-        overview.picture,
-        capture.info.width,
-        capture.info.height,
-        layout,
-    )?;
+    xconn.render_capture_thumbnail(capture, overview.picture.id(), &top)?;
 
     // Draw border with highlight state
-    xconn.draw_thumbnail_border(overview, layout, highlighted)?;
+    draw_frame(xconn, overview, capture, &top, highlighted)?;
+    if let Some((x, y, size)) = top.pin_badge().filter(|_| overview.pinned.contains(&capture.info.frame_window)) {
+        xconn.render_pin(overview, x, y, size)?;
+    }
+    if let (Some((x, y, size)), Some(icon)) = (top.icon_badge(), overview.icons.get(&capture.info.frame_window)) {
+        xconn.render_icon(overview, icon, x, y, size)?;
+    }
 
-    // Draw title label when highlighted
-    if highlighted {
-        let title = capture.info.wm_name.as_deref().unwrap_or("(untitled)");
-        xconn.draw_title_label(overview, layout, title)?;
+    // Draw title label when highlighted, or on every match while searching;
+    // a pile goes by its group
+    let title = capture.info.wm_name.as_deref().unwrap_or("(untitled)");
+    let matched = overview.search.as_ref().and_then(|search| search.matched.get(&capture.info.frame_window));
+    if let Some((name, count)) = pile.filter(|_| highlighted) {
+        xconn.draw_title_label(overview, &top, &format!("{} ({})", name, count))?;
+    } else if highlighted {
+        xconn.draw_caption(overview, layout, title, matched.map_or(&[], Vec::as_slice))?;
+        let allowed = overview.allowed(capture.info.frame_window);
+        if let Some((x, y, size)) = layout.close_button().filter(|_| allowed.close) {
+            xconn.render_delete_button(overview, x, y, size, false)?;
+        }
+        if let Some((x, y, size)) = layout.minimize_button().filter(|_| allowed.minimize) {
+            xconn.render_minimize_button(overview, x, y, size)?;
+        }
+    } else if let Some(matched) = matched.filter(|_| pile.is_none()) {
+        xconn.draw_caption(overview, layout, title, matched)?;
     }
 
     Ok(())
@@ -1924,13 +4251,16 @@ fn render_desktop_bar(
     xconn: &XConnection,
     overview: &OverviewWindow,
     desktop_bar: &DesktopBar,
-    bar_y_offset: i16,
+    slide: (i16, i16),
     hovered_desktop: Option<u32>,
     hovered_delete_button: Option<u32>,
     captures: &[CapturedWindow],
 ) -> Result<()> {
+    let (dx, dy) = slide;
+
     // Render bar background
-    xconn.render_desktop_bar_background(overview, desktop_bar.bar_height, bar_y_offset)?;
+    let (x, y, width, height) = desktop_bar.rect;
+    xconn.render_desktop_bar_background(overview, (x + dx, y + dy, width, height))?;
 
     // Render desktop previews with wallpaper and mini-windows
     for preview in &desktop_bar.preview_layouts {
@@ -1940,13 +4270,13 @@ fn render_desktop_bar(
             preview,
             captures,
             is_hovered,
-            bar_y_offset,
+            slide,
         )?;
 
         // Render delete button if more than 1 desktop
         if desktop_bar.num_desktops > 1 {
-            let del_x = preview.x + preview.delete_button_x;
-            let del_y = preview.y + preview.delete_button_y + bar_y_offset;
+            let del_x = preview.x + preview.delete_button_x + dx;
+            let del_y = preview.y + preview.delete_button_y + dy;
             let is_del_hovered = hovered_delete_button == Some(preview.desktop_index);
             xconn.render_delete_button(
                 overview,
@@ -1960,7 +4290,11 @@ fn render_desktop_bar(
 
     // Render plus button
     let pb = &desktop_bar.plus_button;
-    xconn.render_plus_button(overview, pb.x, pb.y + bar_y_offset, pb.size, false)?;
+    xconn.render_plus_button(overview, pb.x + dx, pb.y + dy, pb.size, false)?;
+
+    if let Some(ref confirm) = desktop_bar.delete_confirm {
+        xconn.render_delete_confirm(overview, desktop_bar, confirm, slide)?;
+    }
 
     Ok(())
 }
@@ -1974,26 +4308,25 @@ fn render_desktop_bar_animated(
     captures: &[CapturedWindow],
 ) -> Result<()> {
     // Render bar background
-    xconn.render_desktop_bar_background(overview, desktop_bar.bar_height, 0)?;
+    xconn.render_desktop_bar_background(overview, desktop_bar.rect)?;
 
-    // Render desktop previews with animated X positions
+    // Render desktop previews at their animated positions along the bar
     for preview in &desktop_bar.preview_layouts {
-        let animated_x = animation.current_x(preview.desktop_index, preview.x);
-        let mut animated_preview = preview.clone();
-        animated_preview.x = animated_x;
+        let offset = animation.current_offset(preview.desktop_index, desktop_bar.preview_offset(preview));
+        let animated_preview = desktop_bar.moved_preview(preview, offset);
 
         xconn.render_desktop_preview_full(
             overview,
             &animated_preview,
             captures,
             preview.is_current,
-            0,
+            (0, 0),
         )?;
 
         // Render delete button if more than 1 desktop
         if desktop_bar.num_desktops > 1 {
-            let del_x = animated_x + preview.delete_button_x;
-            let del_y = preview.y + preview.delete_button_y;
+            let del_x = animated_preview.x + preview.delete_button_x;
+            let del_y = animated_preview.y + preview.delete_button_y;
             xconn.render_delete_button(
                 overview,
                 del_x,
@@ -2008,22 +4341,45 @@ fn render_desktop_bar_animated(
     let pb = &desktop_bar.plus_button;
     xconn.render_plus_button(overview, pb.x, pb.y, pb.size, false)?;
 
+    // Miniatures flying between previews go over them
+    for &(index, from, to) in &animation.flights {
+        let (x, y, width, height) = animation.flight_rect(from, to);
+        xconn.render_dragged_capture(&captures[index], overview.picture.id(), x, y, width, height)?;
+    }
+
     Ok(())
 }
 
+/// Render the desktop bar as it is now: sliding with `sliding` while that
+/// runs, else as `render_desktop_bar` does.
+fn render_desktop_bar_now(
+    xconn: &XConnection,
+    overview: &OverviewWindow,
+    desktop_bar: &DesktopBar,
+    sliding: Option<&DesktopBarAnimation>,
+    hovered_desktop: Option<u32>,
+    captures: &[CapturedWindow],
+) -> Result<()> {
+    match sliding {
+        Some(animation) => render_desktop_bar_animated(xconn, overview, desktop_bar, animation, captures),
+        None => render_desktop_bar(xconn, overview, desktop_bar, (0, 0), hovered_desktop, None, captures),
+    }
+}
+
 /// Render the desktop bar with a desktop being dragged (shows gap for insertion).
+#[allow(clippy::too_many_arguments)]
 fn render_desktop_bar_with_drag(
     xconn: &XConnection,
     overview: &OverviewWindow,
     desktop_bar: &DesktopBar,
     dragged_desktop: u32,
     insert_position: Option<u32>,
-    cursor_x: i16,
+    cursor: i16,
     captures: &[CapturedWindow],
     animated_positions: Option<Vec<(u32, i16)>>,
 ) -> Result<()> {
     // Render bar background
-    xconn.render_desktop_bar_background(overview, desktop_bar.bar_height, 0)?;
+    xconn.render_desktop_bar_background(overview, desktop_bar.rect)?;
 
     // Use animated positions if provided, otherwise calculate from insert_position
     let gap_layouts = if let Some(positions) = animated_positions {
@@ -2035,31 +4391,30 @@ fn render_desktop_bar_with_drag(
             .preview_layouts
             .iter()
             .filter(|p| p.desktop_index != dragged_desktop)
-            .map(|p| (p.desktop_index, p.x))
+            .map(|p| (p.desktop_index, desktop_bar.preview_offset(p)))
             .collect()
     };
 
     // Render non-dragged previews at their gap-adjusted positions
-    for (desktop_idx, x) in gap_layouts {
+    for (desktop_idx, offset) in gap_layouts {
         if let Some(preview) = desktop_bar
             .preview_layouts
             .iter()
             .find(|p| p.desktop_index == desktop_idx)
         {
-            let mut adjusted_preview = preview.clone();
-            adjusted_preview.x = x;
+            let adjusted_preview = desktop_bar.moved_preview(preview, offset);
             xconn.render_desktop_preview_full(
                 overview,
                 &adjusted_preview,
                 captures,
                 false,
-                0,
+                (0, 0),
             )?;
 
             // Render delete button if more than 1 desktop
             if desktop_bar.num_desktops > 1 {
-                let del_x = x + preview.delete_button_x;
-                let del_y = preview.y + preview.delete_button_y;
+                let del_x = adjusted_preview.x + preview.delete_button_x;
+                let del_y = adjusted_preview.y + preview.delete_button_y;
                 xconn.render_delete_button(
                     overview,
                     del_x,
@@ -2071,26 +4426,23 @@ fn render_desktop_bar_with_drag(
         }
     }
 
-    // Render dragged preview following cursor (constrained to bar Y)
+    // Render dragged preview following cursor (constrained to the bar)
     if let Some(preview) = desktop_bar
         .preview_layouts
         .iter()
         .find(|p| p.desktop_index == dragged_desktop)
     {
-        // Center the preview on cursor X
-        let drag_x = cursor_x - (desktop_bar.preview_width / 2) as i16;
-        let drag_y = preview.y;
+        // Center the preview on the cursor
+        let offset = cursor - (desktop_bar.preview_length() / 2) as i16;
 
         // Render with slight visual difference (could add shadow later)
-        let mut dragged_preview = preview.clone();
-        dragged_preview.x = drag_x;
-        dragged_preview.y = drag_y;
+        let dragged_preview = desktop_bar.moved_preview(preview, offset);
         xconn.render_desktop_preview_full(
             overview,
             &dragged_preview,
             captures,
             true, // Highlight as hovered
-            0,
+            (0, 0),
         )?;
     }
 
@@ -0,0 +1,165 @@
+//! Screen and monitor geometry.
+//!
+//! Monitors come from RandR 1.5 (GetMonitors) when the server has it, from
+//! Xinerama on older multi-head setups, and otherwise the whole screen counts
+//! as one monitor. RandR screen change notifications trigger a re-read.
+
+use x11rb::protocol::randr;
+use x11rb::protocol::xinerama;
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+/// A monitor's area of the root window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    /// Output name from RandR (e.g. "DP-1"), or "xinerama-N"/"screen"
+    pub name: String,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// The whole screen as a single monitor.
+    pub fn whole_screen(width: u16, height: u16) -> Self {
+        Monitor {
+            name: "screen".to_string(),
+            x: 0,
+            y: 0,
+            width,
+            height,
+            primary: true,
+        }
+    }
+
+    /// Whether a root window point lies on this monitor.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x as i32
+            && x < self.x as i32 + self.width as i32
+            && y >= self.y as i32
+            && y < self.y as i32 + self.height as i32
+    }
+}
+
+impl XConnection {
+    /// Ask for RandR ScreenChangeNotify events on the root window.
+    pub fn watch_screen_changes(&self) -> Result<()> {
+        if !self.has_randr {
+            return Ok(());
+        }
+        randr::select_input(&self.conn, self.root, randr::NotifyMask::SCREEN_CHANGE)?;
+        Ok(())
+    }
+
+    /// Re-read the root window size and monitor layout after a screen change.
+    /// Returns true if either differs from the one we had.
+    pub fn refresh_screen_size(&mut self) -> Result<bool> {
+        let geom = self.conn.get_geometry(self.root)?.reply()?;
+        let monitors = self.query_monitors(geom.width, geom.height)?;
+        if geom.width == self.screen_width && geom.height == self.screen_height && monitors == self.monitors {
+            return Ok(false);
+        }
+
+        log::info!(
+            "Screen changed: {}x{} -> {}x{}, {} monitor(s)",
+            self.screen_width,
+            self.screen_height,
+            geom.width,
+            geom.height,
+            monitors.len()
+        );
+        self.screen_width = geom.width;
+        self.screen_height = geom.height;
+        self.monitors = monitors;
+        Ok(true)
+    }
+
+    /// Enumerate monitors, falling back from RandR to Xinerama to the whole
+    /// screen. Never returns an empty list.
+    pub fn query_monitors(&self, screen_width: u16, screen_height: u16) -> Result<Vec<Monitor>> {
+        let monitors = if self.has_randr_monitors {
+            self.randr_monitors()?
+        } else if self.has_xinerama {
+            self.xinerama_monitors()?
+        } else {
+            Vec::new()
+        };
+
+        if monitors.is_empty() {
+            return Ok(vec![Monitor::whole_screen(screen_width, screen_height)]);
+        }
+        for monitor in &monitors {
+            log::info!(
+                "Monitor {}: {}x{}+{}+{}{}",
+                monitor.name,
+                monitor.width,
+                monitor.height,
+                monitor.x,
+                monitor.y,
+                if monitor.primary { " (primary)" } else { "" }
+            );
+        }
+        Ok(monitors)
+    }
+
+    /// Active monitors from RandR 1.5.
+    fn randr_monitors(&self) -> Result<Vec<Monitor>> {
+        let reply = randr::get_monitors(&self.conn, self.root, true)?.reply()?;
+
+        let name_cookies = reply
+            .monitors
+            .iter()
+            .map(|m| self.conn.get_atom_name(m.name))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut monitors = Vec::with_capacity(reply.monitors.len());
+        for (info, cookie) in reply.monitors.iter().zip(name_cookies) {
+            let name = match cookie.reply() {
+                Ok(name) => String::from_utf8_lossy(&name.name).into_owned(),
+                Err(_) => format!("monitor-{}", monitors.len()),
+            };
+            monitors.push(Monitor {
+                name,
+                x: info.x,
+                y: info.y,
+                width: info.width,
+                height: info.height,
+                primary: info.primary,
+            });
+        }
+
+        // Without an explicit primary, the first monitor stands in for it
+        if !monitors.iter().any(|m| m.primary) {
+            if let Some(first) = monitors.first_mut() {
+                first.primary = true;
+            }
+        }
+        Ok(monitors)
+    }
+
+    /// Heads from Xinerama, for servers without RandR 1.5.
+    /// The first head is treated as the primary monitor.
+    fn xinerama_monitors(&self) -> Result<Vec<Monitor>> {
+        if xinerama::is_active(&self.conn)?.reply()?.state == 0 {
+            return Ok(Vec::new());
+        }
+        let reply = xinerama::query_screens(&self.conn)?.reply()?;
+        Ok(reply
+            .screen_info
+            .iter()
+            .enumerate()
+            .map(|(i, head)| Monitor {
+                name: format!("xinerama-{}", i),
+                x: head.x_org,
+                y: head.y_org,
+                width: head.width,
+                height: head.height,
+                primary: i == 0,
+            })
+            .collect())
+    }
+}
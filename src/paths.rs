@@ -0,0 +1,178 @@
+//! Where xpose keeps its files.
+//!
+//! Desktop assignments, window order and the log live under
+//! `$XDG_STATE_HOME/xpose` (~/.local/state/xpose), and the thumbnail cache
+//! under `$XDG_CACHE_HOME/xpose` (~/.cache/xpose). `XPOSE_STATE_DIR` and
+//! `XPOSE_CACHE_DIR` override either. Both directories are created readable
+//! by the user alone.
+//!
+//! Older versions kept everything in /tmp/xpose, which any user can write to
+//! and which is emptied on reboot. State files found there are moved over
+//! the first time they are looked up.
+//!
+//! Several xpose processes may share the state files (the overview, desktop
+//! commands from key bindings, `track-focus`). Each file has a lock file
+//! beside it, held across load-modify-save, and saves replace the file in
+//! one rename so readers never see half of one.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Where state files were kept before.
+const LEGACY_STATE_DIR: &str = "/tmp/xpose";
+/// How long to wait for another xpose to release a state file.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(3);
+/// How often to retry a held lock meanwhile.
+const LOCK_RETRY: Duration = Duration::from_millis(50);
+
+/// An exclusive lock on a state file, released when dropped.
+pub struct StateLock {
+    _file: File,
+}
+
+/// Directory for state that should survive a reboot.
+pub fn state_dir() -> PathBuf {
+    dir_from("XPOSE_STATE_DIR", dirs::state_dir())
+}
+
+/// Directory for files that can be rebuilt at any time.
+pub fn cache_dir() -> PathBuf {
+    dir_from("XPOSE_CACHE_DIR", dirs::cache_dir())
+}
+
+/// The log file.
+pub fn log_file() -> PathBuf {
+    state_dir().join("xpose.log")
+}
+
+/// `$XDG_RUNTIME_DIR`, for files that only matter while logged in; the
+/// state directory without one.
+pub fn runtime_dir() -> PathBuf {
+    dirs::runtime_dir().unwrap_or_else(state_dir)
+}
+
+/// The control socket of `xpose daemon`.
+pub fn socket_path() -> PathBuf {
+    runtime_dir().join("xpose.sock")
+}
+
+/// A file in the state directory, moved there from /tmp/xpose if an older
+/// version left it behind.
+pub fn state_file(name: &str) -> PathBuf {
+    let path = state_dir().join(name);
+    if !path.exists() {
+        migrate(&Path::new(LEGACY_STATE_DIR).join(name), &path);
+    }
+    path
+}
+
+/// Create `dir` (and any missing parents) with mode 0700.
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+/// Lock the state file `name` against other xpose processes, waiting a
+/// little for one that holds it. The error names the holder's pid.
+pub fn lock_state_file(name: &str) -> io::Result<StateLock> {
+    let dir = state_dir();
+    create_private_dir(&dir)?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(dir.join(format!("{}.lock", name)))?;
+
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    while unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::WouldBlock {
+            return Err(error);
+        }
+        if Instant::now() >= deadline {
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("{} is in use by another xpose (pid {})", name, holder.trim()),
+            ));
+        }
+        std::thread::sleep(LOCK_RETRY);
+    }
+
+    // Leave our pid for whoever has to wait
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    Ok(StateLock { _file: file })
+}
+
+/// Replace `path` with `contents` atomically: write a temporary file beside
+/// it, then rename it over the old one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.tmp", file_name));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+fn dir_from(var: &str, base: Option<PathBuf>) -> PathBuf {
+    match std::env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        // Without a home directory, at least keep other users out
+        _ => base
+            .map(|base| base.join("xpose"))
+            .unwrap_or_else(|| PathBuf::from(format!("/tmp/xpose-{}", unsafe { libc::getuid() }))),
+    }
+}
+
+/// Move `from` to `to` if `from` is a file owned by this user; anyone could
+/// have put a file in /tmp.
+fn migrate(from: &Path, to: &Path) {
+    let Ok(metadata) = fs::symlink_metadata(from) else {
+        return;
+    };
+    if !metadata.is_file() || metadata.uid() != unsafe { libc::getuid() } {
+        log::warn!("Not moving {}: not a file of this user", from.display());
+        return;
+    }
+    let moved = to
+        .parent()
+        .map_or(Ok(()), create_private_dir)
+        // /tmp is often another filesystem, where rename can't go
+        .and_then(|()| fs::rename(from, to).or_else(|_| fs::copy(from, to).and_then(|_| fs::remove_file(from))));
+    match moved {
+        Ok(()) => log::info!("Moved {} to {}", from.display(), to.display()),
+        Err(e) => log::warn!("Cannot move {} to {}: {}", from.display(), to.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir().join(format!("xpose-test-{}", std::process::id()));
+        create_private_dir(&dir).unwrap();
+        let path = dir.join("state.json");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        // Nothing left behind but the file itself
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+//! Putting a window in place as the overview closes: maximized, or restored
+//! from it, or tiled to the half (or quarter) of a monitor it was dragged to
+//! the side of.
+//!
+//! Maximizing goes through the window manager when it supports
+//! `_NET_WM_STATE_MAXIMIZED_*`. TWM doesn't, so then xpose resizes the frame
+//! to the window's monitor itself, and remembers the old geometry to restore.
+
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::desktop::state::Geometry;
+use crate::desktop::DesktopState;
+use crate::error::Result;
+use crate::monitor::Monitor;
+use crate::window_finder::WindowInfo;
+
+/// `_NET_WM_STATE` action flipping a state.
+const NET_WM_STATE_TOGGLE: u32 = 2;
+/// Source indication for requests made for the user, as pagers do.
+const SOURCE_PAGER: u32 = 2;
+/// How close to a monitor's left or right side a drop tiles the window.
+const EDGE_ZONE: i32 = 24;
+/// How close to the top or bottom as well a drop takes a quarter instead.
+const CORNER_ZONE: i32 = 96;
+
+/// Maximize a window on its monitor, or restore it if maximized.
+pub fn toggle_maximized(xconn: &XConnection, state: &mut DesktopState, info: &WindowInfo) -> Result<()> {
+    let atoms = &xconn.atoms;
+    if xconn.wm_supports(atoms._NET_WM_STATE_MAXIMIZED_VERT)? {
+        log::info!("Asking the window manager to toggle maximizing 0x{:x}", info.client_window);
+        return xconn.send_window_message(
+            info.client_window,
+            atoms._NET_WM_STATE,
+            &[NET_WM_STATE_TOGGLE, atoms._NET_WM_STATE_MAXIMIZED_VERT, atoms._NET_WM_STATE_MAXIMIZED_HORZ, SOURCE_PAGER],
+        );
+    }
+
+    let key = info.frame_window.to_string();
+    let geometry = match state.maximized.remove(&key) {
+        Some(geometry) => geometry,
+        None => {
+            state.maximized.insert(key, Geometry { x: info.x, y: info.y, width: info.width, height: info.height });
+            let monitor = monitor_of(xconn, info);
+            Geometry { x: monitor.x, y: monitor.y, width: monitor.width, height: monitor.height }
+        }
+    };
+    log::info!("Putting 0x{:x} at {:?}", info.frame_window, geometry);
+    configure_frame(xconn, info.frame_window, geometry)
+}
+
+/// Where a window dropped at (x, y) goes: the left or right half of the
+/// monitor there when at its side, or a quarter in its corners.
+pub fn tile_at(monitors: &[Monitor], x: i16, y: i16) -> Option<Geometry> {
+    let (x, y) = (x as i32, y as i32);
+    let monitor = monitors.iter().find(|m| m.contains(x, y))?;
+    let (left, top) = (monitor.x as i32, monitor.y as i32);
+    let (right, bottom) = (left + monitor.width as i32, top + monitor.height as i32);
+    let (half_width, half_height) = (monitor.width / 2, monitor.height / 2);
+
+    let column = if x < left + EDGE_ZONE {
+        monitor.x
+    } else if x >= right - EDGE_ZONE {
+        monitor.x + (monitor.width - half_width) as i16
+    } else {
+        return None;
+    };
+    let (row, height) = if y < top + CORNER_ZONE {
+        (monitor.y, half_height)
+    } else if y >= bottom - CORNER_ZONE {
+        (monitor.y + (monitor.height - half_height) as i16, half_height)
+    } else {
+        (monitor.y, monitor.height)
+    };
+    Some(Geometry { x: column, y: row, width: half_width, height })
+}
+
+/// Put a window where a drop tiled it.
+pub fn tile(xconn: &XConnection, state: &mut DesktopState, info: &WindowInfo, geometry: Geometry) -> Result<()> {
+    // Tiling ends a maximize xpose did
+    state.maximized.remove(&info.frame_window.to_string());
+    log::info!("Tiling 0x{:x} to {:?}", info.frame_window, geometry);
+    configure_frame(xconn, info.frame_window, geometry)
+}
+
+/// Move and resize a frame.
+fn configure_frame(xconn: &XConnection, frame: Window, geometry: Geometry) -> Result<()> {
+    xconn.conn.configure_window(
+        frame,
+        &ConfigureWindowAux::new()
+            .x(geometry.x as i32)
+            .y(geometry.y as i32)
+            .width(geometry.width as u32)
+            .height(geometry.height as u32),
+    )?;
+    xconn.flush()
+}
+
+/// The monitor holding most of a window: the one its center is on, else
+/// the primary one.
+fn monitor_of(xconn: &XConnection, info: &WindowInfo) -> Monitor {
+    let monitors = &xconn.monitors;
+    let (cx, cy) = (info.x as i32 + info.width as i32 / 2, info.y as i32 + info.height as i32 / 2);
+    monitors
+        .iter()
+        .find(|m| m.contains(cx, cy))
+        .or_else(|| monitors.iter().find(|m| m.primary))
+        .or(monitors.first())
+        .cloned()
+        .unwrap_or_else(|| Monitor::whole_screen(xconn.screen_width, xconn.screen_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_at() {
+        let monitor = |x| Monitor { name: String::new(), x, y: 0, width: 1920, height: 1080, primary: x == 0 };
+        let monitors = [monitor(0), monitor(1920)];
+        let geometry = |x, y, width, height| Some(Geometry { x, y, width, height });
+
+        assert_eq!(tile_at(&monitors, 5, 500), geometry(0, 0, 960, 1080));
+        assert_eq!(tile_at(&monitors, 1910, 500), geometry(960, 0, 960, 1080));
+        // The left side of the second monitor
+        assert_eq!(tile_at(&monitors, 1925, 500), geometry(1920, 0, 960, 1080));
+        // Corners take a quarter
+        assert_eq!(tile_at(&monitors, 5, 10), geometry(0, 0, 960, 540));
+        assert_eq!(tile_at(&monitors, 3830, 1070), geometry(2880, 540, 960, 540));
+        assert_eq!(tile_at(&monitors, 500, 500), None);
+        assert_eq!(tile_at(&monitors, 5, 5000), None);
+    }
+}
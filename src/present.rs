@@ -0,0 +1,82 @@
+//! Showing overview frames through the Present extension (`Present true`).
+//!
+//! By default each frame is copied from the overview's pixmap to its window
+//! as soon as it is drawn, which can tear, and at 4K costs a full-screen
+//! copy for every frame even when the display can't show them all. With
+//! Present, the server copies at the next vertical blank, and of frames
+//! queued for the same blank only the last is copied.
+//!
+//! The copy happens later, from the same pixmap the next frame is drawn
+//! into, so every present comes with an idle fence and the connection waits
+//! on it: the server holds back our later requests until the copy is done.
+//! Letting the server flip to the pixmap instead would save the copy too,
+//! but needs a second pixmap to draw into meanwhile.
+
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::present::{self, Option as PresentOption};
+use x11rb::protocol::sync::{self, Fence};
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::renderer::OverviewWindow;
+
+impl XConnection {
+    /// Present the overview's frames from now on, if the server can. Returns
+    /// whether it does.
+    pub fn start_presenting(&self, overview: &mut OverviewWindow) -> Result<bool> {
+        let conn = &self.conn;
+        if conn.extension_information(present::X11_EXTENSION_NAME)?.is_none()
+            || conn.extension_information(sync::X11_EXTENSION_NAME)?.is_none()
+        {
+            log::warn!("Present or SYNC extension not available, copying frames instead");
+            return Ok(false);
+        }
+        // Both want to know which version we speak before anything else
+        let present_version = present::query_version(conn, 1, 0)?;
+        let sync_version = sync::initialize(conn, 3, 1)?;
+        if let Err(e) = present_version.reply().map(drop).and(sync_version.reply().map(drop)) {
+            log::warn!("Cannot use Present, copying frames instead: {}", e);
+            return Ok(false);
+        }
+
+        let fence = self.generate_id()?;
+        // Triggered, as after a finished present
+        sync::create_fence(conn, overview.window, fence, true)?;
+        overview.present_fence = Some(fence);
+        log::info!("Presenting frames with the Present extension");
+        Ok(true)
+    }
+
+    /// Queue the overview's pixmap for its window at the next vertical
+    /// blank, holding back later drawing until it has been copied.
+    pub(crate) fn present_pixmap(&self, overview: &OverviewWindow, fence: Fence) -> Result<()> {
+        sync::reset_fence(&self.conn, fence)?;
+        present::pixmap(
+            &self.conn,
+            overview.window,
+            overview.pixmap.id(),
+            0,
+            x11rb::NONE,
+            x11rb::NONE,
+            0,
+            0,
+            x11rb::NONE,
+            x11rb::NONE,
+            fence,
+            PresentOption::COPY.into(),
+            0,
+            0,
+            0,
+            &[],
+        )?;
+        sync::await_fence(&self.conn, &[fence])?;
+        Ok(())
+    }
+
+    pub(crate) fn stop_presenting(&self, overview: &OverviewWindow) -> Result<()> {
+        if let Some(fence) = overview.present_fence {
+            sync::destroy_fence(&self.conn, fence)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+//! Cleanup after an overview session that didn't finish.
+//!
+//! While the overview is up, windows on other desktops are mapped and parked
+//! off-screen so they can be captured. Their original positions are recorded
+//! in the desktop state file before they move, so if the X connection drops
+//! mid-session (or xpose is killed) they can be put back: right away over a
+//! fresh connection when the server is still there, or at the next launch.
+
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::desktop::DesktopState;
+use crate::error::Result;
+
+impl XConnection {
+    /// Move windows parked off-screen back to their recorded position, hide
+    /// the ones that don't belong to the current desktop again, and clear
+    /// the record. Windows that no longer exist are skipped by the server.
+    pub fn restore_displaced_windows(&self, state: &mut DesktopState) -> Result<()> {
+        for (key, &x) in &state.displaced {
+            let Ok(window) = key.parse::<Window>() else {
+                continue;
+            };
+            self.conn
+                .configure_window(window, &ConfigureWindowAux::new().x(x as i32))?;
+            if !state.is_visible_on(window, state.current) {
+                self.unmap_window(window)?;
+            }
+        }
+        log::info!("Restored {} off-screen windows", state.displaced.len());
+        state.displaced.clear();
+        self.flush()
+    }
+}
+
+/// Reconnect after the session's connection was lost and undo what the
+/// overview left behind. Fails if the X server itself is gone.
+pub fn recover_after_connection_loss() -> Result<()> {
+    let xconn = XConnection::new()?;
+    let _lock = DesktopState::lock()?;
+    let mut state = DesktopState::load()?;
+    if !state.displaced.is_empty() {
+        xconn.restore_displaced_windows(&mut state)?;
+    }
+    state.sync_to_x(&xconn)?;
+    state.save()?;
+    xconn.sync()
+}
@@ -1,12 +1,54 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
 use x11rb::connection::Connection;
-use x11rb::protocol::render::{self, Picture, PictOp, Transform};
+use x11rb::protocol::render::{self, Color, Picture, PictOp, Transform};
+use x11rb::protocol::sync::Fence;
 use x11rb::protocol::xproto::*;
 use crate::animation::AnimatedLayout;
 use crate::capture::CapturedWindow;
 use crate::connection::XConnection;
-use crate::desktop_bar::DesktopPreviewLayout;
+use crate::desktop::state::Geometry;
+use crate::desktop_bar::{desktop_accent, DeleteConfirm, DesktopBar, DesktopPreviewLayout, Rect};
+use crate::diagnostics::Subsystem;
 use crate::error::Result;
+use crate::ewmh::AllowedActions;
+use crate::icons::Icon;
 use crate::layout::ThumbnailLayout;
+use crate::resources::{OwnedGC, OwnedPicture, OwnedPixmap};
+use crate::scaled::Scaled;
+use crate::search::SearchView;
+use crate::text::{colors_for_background, encode_label, luminance, LabelColors};
+use crate::theme::{Theme, FIXED_FONT};
+use crate::toast::MAX_TOAST_CHARS;
+use crate::wallpaper::Wallpaper;
+
+/// Border of a window that asks for attention, at the height of its pulse.
+const URGENT_COLOR: u32 = 0xFF_8C_1A;
+
+/// Distance of the search query from the bottom of the screen.
+const SEARCH_QUERY_MARGIN: i16 = 24;
+
+/// Distance of the toast stack from the bottom-right screen corner.
+const TOAST_MARGIN: i32 = 16;
+/// Vertical gap between stacked toasts.
+const TOAST_SPACING: i32 = 6;
+const TOAST_PADDING_H: u16 = 10;
+const TOAST_PADDING_V: u16 = 5;
+
+/// Inset of the desktop name label from a preview's bottom-left corner.
+const PREVIEW_NAME_MARGIN: u16 = 4;
+const PREVIEW_NAME_PADDING_H: u16 = 5;
+const PREVIEW_NAME_PADDING_V: u16 = 1;
+
+/// Gap between a delete confirmation's message and its buttons.
+const CONFIRM_MESSAGE_GAP: i16 = 6;
+const CONFIRM_PADDING_H: u16 = 6;
+const CONFIRM_PADDING_V: u16 = 3;
+
+/// Label colors kept at most, before the cache starts over.
+const MAX_CACHED_LABEL_COLORS: usize = 256;
 
 // Fixed-point conversion for XRender transforms (16.16 format)
 const FIXED_SHIFT: i32 = 16;
@@ -15,15 +57,108 @@ fn double_to_fixed(d: f64) -> i32 {
     (d * (1 << FIXED_SHIFT) as f64) as i32
 }
 
+/// Back buffer contents saved from under the toasts, put back after present.
+struct SavedArea {
+    pixmap: OwnedPixmap,
+    picture: OwnedPicture,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+}
+
+/// A collapsed group's name and size, as its pile shows them.
+pub type PileLabel = (String, usize);
+
 pub struct OverviewWindow {
     pub window: Window,
-    pub pixmap: Pixmap,
-    pub picture: Picture,
-    pub gc: Gcontext,
+    pub pixmap: OwnedPixmap,
+    pub picture: OwnedPicture,
+    pub gc: OwnedGC,
     pub width: u16,
     pub height: u16,
-    pub bg_picture: Option<Picture>,
+    pub bg_picture: Option<OwnedPicture>,
     pub font: Font,
+    /// Configured per-desktop wallpapers (see wallpaper.rs)
+    pub wallpapers: HashMap<u32, Wallpaper>,
+    /// Frames of sticky windows, whose thumbnails carry a pin
+    pub pinned: HashSet<Window>,
+    /// Frames of windows asking for attention, whose borders pulse
+    pub urgent: HashSet<Window>,
+    /// Frames of windows the window manager restricts, with what it allows
+    pub restricted: HashMap<Window, AllowedActions>,
+    /// Frames of the windows in collapsed groups -> the group's name and
+    /// size; whichever of them is in the grid is drawn as a pile
+    pub piles: HashMap<Window, PileLabel>,
+    /// Frames in the grid -> the desktop they are grouped under and its
+    /// name, while it shows every desktop; empty otherwise
+    pub desktop_labels: HashMap<Window, (u32, String)>,
+    /// The query and its matches, while searching (see search.rs)
+    pub search: Option<SearchView>,
+    /// Application icons, by frame (see icons.rs)
+    pub icons: HashMap<Window, Icon>,
+    /// Set while frames go through the Present extension (see present.rs)
+    pub present_fence: Option<Fence>,
+    /// Colors, frame widths and label font (see theme.rs)
+    pub theme: Theme,
+    /// Colors picked for the toasts where they sit, kept while only the
+    /// toasts change (see `present_toasts`)
+    toast_colors: RefCell<HashMap<Rect, LabelColors>>,
+    /// Colors picked for the other labels where they sit, kept until what
+    /// is under them may change (see `forget_label_colors`)
+    label_colors: RefCell<HashMap<Rect, LabelColors>>,
+}
+
+impl OverviewWindow {
+    /// What may be done to the window in `frame`.
+    pub fn allowed(&self, frame: Window) -> AllowedActions {
+        self.restricted.get(&frame).copied().unwrap_or_default()
+    }
+
+    /// Background for a desktop's preview with its size: the desktop's own
+    /// wallpaper, else the root background.
+    fn preview_background(&self, desktop: u32) -> Option<(Picture, u16, u16)> {
+        if !self.theme.wallpapers {
+            return None;
+        }
+        match self.wallpapers.get(&desktop) {
+            Some(wallpaper) => Some((wallpaper.picture.id(), wallpaper.width, wallpaper.height)),
+            None => self.bg_picture.as_ref().map(|pic| (pic.id(), self.width, self.height)),
+        }
+    }
+
+    /// The colors picked for a label at `rect`, or those `pick` gives, kept
+    /// for the next time it is drawn there. Reading the back buffer back
+    /// for them is a round trip, which every label would otherwise pay on
+    /// every frame.
+    pub fn cached_label_colors(&self, rect: Rect, pick: impl FnOnce() -> Result<LabelColors>) -> Result<LabelColors> {
+        cached_colors(&self.label_colors, rect, pick)
+    }
+
+    /// Pick the labels' colors afresh, for what is under them changed:
+    /// window contents, the layout or the background.
+    pub fn forget_label_colors(&self) {
+        self.label_colors.borrow_mut().clear();
+    }
+}
+
+/// The colors in `cache` for `rect`, else those `pick` gives, added to it.
+/// Labels that moved leave theirs behind, so a full cache starts over.
+fn cached_colors(
+    cache: &RefCell<HashMap<Rect, LabelColors>>,
+    rect: Rect,
+    pick: impl FnOnce() -> Result<LabelColors>,
+) -> Result<LabelColors> {
+    if let Some(&colors) = cache.borrow().get(&rect) {
+        return Ok(colors);
+    }
+    let colors = pick()?;
+    let mut cache = cache.borrow_mut();
+    if cache.len() >= MAX_CACHED_LABEL_COLORS {
+        cache.clear();
+    }
+    cache.insert(rect, colors);
+    Ok(colors)
 }
 
 impl XConnection {
@@ -77,16 +212,8 @@ impl XConnection {
         Ok(None)
     }
 
-    /// Create the fullscreen overview window.
-    pub fn create_overview_window(&self) -> Result<OverviewWindow> {
-        let window = self.generate_id()?;
-        let pixmap = self.generate_id()?;
-        let gc = self.generate_id()?;
-
-        // Dark background color (fallback)
-        let bg_color = 0x1a1a1a;
-
-        // Try to get root background pixmap and create a picture from it
+    /// Create a picture for the root wallpaper, if one is set.
+    fn create_background_picture(&self) -> Result<Option<OwnedPicture>> {
         let bg_picture = match self.get_root_background_pixmap() {
             Ok(Some(root_pixmap)) => {
                 let pic = self.generate_id()?;
@@ -99,7 +226,7 @@ impl XConnection {
                 ) {
                     Ok(_) => {
                         log::info!("Created background picture from root wallpaper");
-                        Some(pic)
+                        Some(OwnedPicture::new(&self.conn, pic))
                     }
                     Err(e) => {
                         log::warn!("Failed to create picture from root background: {}", e);
@@ -116,6 +243,18 @@ impl XConnection {
                 None
             }
         };
+        Ok(bg_picture)
+    }
+
+    /// Create the fullscreen overview window, drawn with `theme`.
+    pub fn create_overview_window(&self, mut theme: Theme) -> Result<OverviewWindow> {
+        let window = self.generate_id()?;
+
+        // Background color where the wallpaper doesn't show
+        let bg_color = theme.background;
+
+        // Try to get root background pixmap and create a picture from it
+        let bg_picture = if theme.wallpapers { self.create_background_picture()? } else { None };
 
         // Create fullscreen window
         self.conn.create_window(
@@ -140,44 +279,28 @@ impl XConnection {
                 )
                 .override_redirect(1), // Don't let WM manage us
         )?;
+        self.set_overview_hints(window)?;
+        self.set_compositor_hints(window)?;
 
         // Create backing pixmap
-        self.conn.create_pixmap(
-            self.root_depth,
-            pixmap,
-            window,
-            self.screen_width,
-            self.screen_height,
-        )?;
+        let pixmap = self.create_owned_pixmap(window, self.screen_width, self.screen_height)?;
 
-        // Open a font for text rendering
-        let font = self.generate_id()?;
-        // Use "fixed" which is always available
-        self.conn.open_font(font, b"fixed")?;
-        log::info!("Opened font: fixed");
+        let font = self.open_theme_font(&mut theme)?;
 
         // Create graphics context
-        self.conn
-            .create_gc(gc, window, &CreateGCAux::new().foreground(bg_color).font(font))?;
+        let gc = self.create_owned_gc(window, &CreateGCAux::new().foreground(bg_color).font(font))?;
 
         // Create picture for the pixmap
-        let picture = self.generate_id()?;
-        render::create_picture(
-            &self.conn,
-            picture,
-            pixmap,
-            self.pict_format_rgb,
-            &render::CreatePictureAux::new(),
-        )?;
+        let picture = self.create_owned_picture(pixmap.id())?;
 
         // Fill pixmap with background (wallpaper or solid color)
-        if let Some(bg_pic) = bg_picture {
+        if let Some(ref bg_pic) = bg_picture {
             render::composite(
                 &self.conn,
                 PictOp::SRC,
-                bg_pic,
+                bg_pic.id(),
                 x11rb::NONE,
-                picture,
+                picture.id(),
                 0, 0,
                 0, 0,
                 0, 0,
@@ -186,8 +309,8 @@ impl XConnection {
             )?;
         } else {
             self.conn.poly_fill_rectangle(
-                pixmap,
-                gc,
+                pixmap.id(),
+                gc.id(),
                 &[Rectangle {
                     x: 0,
                     y: 0,
@@ -201,7 +324,7 @@ impl XConnection {
         // (instead of flashing the background_pixel color first)
         self.conn.change_window_attributes(
             window,
-            &ChangeWindowAttributesAux::new().background_pixmap(pixmap),
+            &ChangeWindowAttributesAux::new().background_pixmap(pixmap.id()),
         )?;
 
         self.conn.flush()?;
@@ -215,6 +338,18 @@ impl XConnection {
             height: self.screen_height,
             bg_picture,
             font,
+            wallpapers: HashMap::new(),
+            pinned: HashSet::new(),
+            urgent: HashSet::new(),
+            restricted: HashMap::new(),
+            piles: HashMap::new(),
+            desktop_labels: HashMap::new(),
+            search: None,
+            icons: HashMap::new(),
+            present_fence: None,
+            theme,
+            toast_colors: RefCell::new(HashMap::new()),
+            label_colors: RefCell::new(HashMap::new()),
         })
     }
 
@@ -249,14 +384,15 @@ impl XConnection {
         };
 
         // Apply transform to source picture
-        render::set_picture_transform(&self.conn, src_picture, transform)?;
+        let cookie = render::set_picture_transform(&self.conn, src_picture, transform)?;
+        self.check_request(Subsystem::Renderer, "render_thumbnail", cookie)?;
 
         // Set filter for smooth scaling
         // TODO: Add option for "nearest" for faster but pixelated scaling
         render::set_picture_filter(&self.conn, src_picture, b"bilinear", &[])?;
 
         // Composite source to destination
-        render::composite(
+        let cookie = render::composite(
             &self.conn,
             PictOp::SRC,
             src_picture,
@@ -271,10 +407,49 @@ impl XConnection {
             layout.width,
             layout.height,
         )?;
+        self.check_request(Subsystem::Renderer, "render_thumbnail", cookie)?;
 
         Ok(())
     }
 
+    /// A capture's contents at exactly `width` x `height` (see scaled.rs),
+    /// scaling them now unless there is a copy at that size already.
+    fn scaled_thumbnail(&self, capture: &CapturedWindow, width: u16, height: u16) -> Result<Picture> {
+        if let Some(copy) = capture.scaled.borrow().as_ref() {
+            if (copy.width, copy.height) == (width, height) {
+                return Ok(copy.picture.id());
+            }
+        }
+
+        let pixmap = self.create_owned_pixmap(self.root, width, height)?;
+        let picture = self.create_owned_picture(pixmap.id())?;
+        let id = picture.id();
+        // Kept even if scaling fails, to be freed with the capture
+        *capture.scaled.borrow_mut() = Some(Scaled { _pixmap: pixmap, picture, width, height });
+        let layout = ThumbnailLayout { x: 0, y: 0, width, height, window_index: 0 };
+        self.render_thumbnail(capture.picture.id(), id, capture.info.width, capture.info.height, &layout)?;
+        Ok(id)
+    }
+
+    /// What to draw a capture from at `width` x `height`: its scaled copy
+    /// if that is at least as big, else the full contents. Returns the
+    /// picture and its size.
+    pub fn thumbnail_source(&self, capture: &CapturedWindow, width: u16, height: u16) -> (Picture, u16, u16) {
+        match capture.scaled.borrow().as_ref() {
+            Some(copy) if copy.width >= width && copy.height >= height => (copy.picture.id(), copy.width, copy.height),
+            _ => (capture.picture.id(), capture.info.width, capture.info.height),
+        }
+    }
+
+    /// Draw a capture into its grid slot, from its copy at that size.
+    pub fn render_capture_thumbnail(&self, capture: &CapturedWindow, dst_picture: Picture, layout: &ThumbnailLayout) -> Result<()> {
+        if layout.width == 0 || layout.height == 0 {
+            return Ok(());
+        }
+        let picture = self.scaled_thumbnail(capture, layout.width, layout.height)?;
+        self.render_thumbnail(picture, dst_picture, layout.width, layout.height, layout)
+    }
+
     /// Draw border around thumbnail.
     pub fn draw_thumbnail_border(
         &self,
@@ -282,20 +457,14 @@ impl XConnection {
         layout: &ThumbnailLayout,
         highlighted: bool,
     ) -> Result<()> {
-        let border_width: i16 = 3;
+        let border_width = overview.theme.border_width;
 
         // Choose border color based on highlight state
-        let color = if highlighted {
-            // Bright cyan for highlighted
-            0x44_88_FF
-        } else {
-            // Dark gray for normal
-            0x44_44_44
-        };
+        let color = if highlighted { overview.theme.highlight } else { overview.theme.border };
 
         // Set foreground color for drawing
         self.conn.change_gc(
-            overview.gc,
+            overview.gc.id(),
             &ChangeGCAux::new().foreground(color).line_width(border_width as u32),
         )?;
 
@@ -306,8 +475,8 @@ impl XConnection {
         let h = layout.height + 2 * border_width as u16;
 
         self.conn.poly_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle {
                 x,
                 y,
@@ -319,6 +488,120 @@ impl XConnection {
         Ok(())
     }
 
+    /// Draw the border of a window asking for attention over its plain one,
+    /// `pulse` (0.0 to 1.0) of the way to the urgent color.
+    pub fn draw_urgent_border(&self, overview: &OverviewWindow, layout: &ThumbnailLayout, pulse: f64) -> Result<()> {
+        let border_width = overview.theme.border_width;
+        self.draw_outline(
+            overview,
+            layout.x - border_width,
+            layout.y - border_width,
+            layout.width + 2 * border_width as u16,
+            layout.height + 2 * border_width as u16,
+            border_width,
+            mix_colors(overview.theme.border, URGENT_COLOR, pulse),
+        )
+    }
+
+    /// The same around a desktop preview holding such a window.
+    pub fn draw_urgent_preview_border(
+        &self,
+        overview: &OverviewWindow,
+        preview: &DesktopPreviewLayout,
+        highlighted: bool,
+        pulse: f64,
+    ) -> Result<()> {
+        let base = if highlighted { overview.theme.highlight } else { overview.theme.border };
+        self.draw_outline(
+            overview,
+            preview.x,
+            preview.y,
+            preview.width,
+            preview.height,
+            overview.theme.preview_border_width,
+            mix_colors(base, URGENT_COLOR, pulse),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_outline(
+        &self,
+        overview: &OverviewWindow,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        line_width: i16,
+        color: u32,
+    ) -> Result<()> {
+        self.conn.change_gc(
+            overview.gc.id(),
+            &ChangeGCAux::new().foreground(color).line_width(line_width as u32),
+        )?;
+        self.conn.poly_rectangle(overview.pixmap.id(), overview.gc.id(), &[Rectangle { x, y, width, height }])?;
+        Ok(())
+    }
+
+    /// Draw a thumbnail frame in the accent color of the desktop the window
+    /// lives on, with a badge showing the desktop number (1-based) in the
+    /// top-left corner. Used by the all-desktops grid; a highlighted
+    /// thumbnail keeps the usual hover color for its frame.
+    pub fn draw_desktop_frame(
+        &self,
+        overview: &OverviewWindow,
+        layout: &ThumbnailLayout,
+        desktop: u32,
+        highlighted: bool,
+    ) -> Result<()> {
+        let accent = desktop_accent(desktop);
+        if highlighted {
+            self.draw_thumbnail_border(overview, layout, true)?;
+        } else {
+            let border_width = overview.theme.border_width;
+            self.conn.change_gc(
+                overview.gc.id(),
+                &ChangeGCAux::new().foreground(accent).line_width(border_width as u32),
+            )?;
+            self.conn.poly_rectangle(
+                overview.pixmap.id(),
+                overview.gc.id(),
+                &[Rectangle {
+                    x: layout.x - border_width,
+                    y: layout.y - border_width,
+                    width: layout.width + 2 * border_width as u16,
+                    height: layout.height + 2 * border_width as u16,
+                }],
+            )?;
+        }
+
+        // Badge: accent square with the desktop number
+        let label = (desktop + 1).to_string();
+        let font = &overview.theme.font;
+        let badge_height: u16 = font.line_height + 6;
+        let badge_width = (font.text_width(label.as_bytes()) + 10).max(badge_height);
+        if layout.width < badge_width || layout.height < badge_height {
+            return Ok(());
+        }
+        self.conn
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(accent))?;
+        self.conn.poly_fill_rectangle(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Rectangle {
+                x: layout.x,
+                y: layout.y,
+                width: badge_width,
+                height: badge_height,
+            }],
+        )?;
+        let text_color = colors_for_background(luminance(accent)).text;
+        let text_x = layout.x + (badge_width as i16 - font.text_width(label.as_bytes()) as i16) / 2;
+        let text_y = layout.y + (badge_height - font.line_height) as i16 / 2;
+        self.draw_text(overview, text_x, text_y, label.as_bytes(), text_color)?;
+
+        Ok(())
+    }
+
     /// Draw window title label centered on thumbnail.
     pub fn draw_title_label(
         &self,
@@ -326,87 +609,173 @@ impl XConnection {
         layout: &ThumbnailLayout,
         title: &str,
     ) -> Result<()> {
-        // Truncate title if too long
-        let max_chars = 50;
-        let display_title = if title.len() > max_chars {
-            format!("{}...", &title[..max_chars - 3])
-        } else {
-            title.to_string()
-        };
+        self.draw_caption(overview, layout, title, &[])
+    }
 
-        // "fixed" font is 6x13 pixels per character
-        let text_bytes = display_title.as_bytes();
-        let char_width: u16 = 6;
-        let text_width = (text_bytes.len() as u16) * char_width;
-        let text_height: u16 = 13;
-        let text_ascent: u16 = 11; // Baseline offset from top
+    /// Draw a title label centered on a thumbnail with the characters at
+    /// `matched` (a search's, in characters) in the highlight color.
+    pub fn draw_caption(
+        &self,
+        overview: &OverviewWindow,
+        layout: &ThumbnailLayout,
+        title: &str,
+        matched: &[usize],
+    ) -> Result<()> {
+        const MAX_CHARS: usize = 50;
+        let text = encode_label(title, MAX_CHARS);
 
         let padding_h: u16 = 16;
         let padding_v: u16 = 8;
 
         // Calculate label dimensions
-        let label_width = text_width + padding_h * 2;
-        let label_height = text_height + padding_v * 2;
+        let label_width = overview.theme.font.text_width(&text) + padding_h * 2;
+        let label_height = overview.theme.font.line_height + padding_v * 2;
 
         // Center label on thumbnail
         let label_x = layout.x + (layout.width as i16 - label_width as i16) / 2;
         let label_y = layout.y + (layout.height as i16 - label_height as i16) / 2;
 
-        // Draw semi-transparent background rectangle
-        let bg_color = 0x22_22_22; // Dark gray
-        self.conn.change_gc(
-            overview.gc,
-            &ChangeGCAux::new().foreground(bg_color),
-        )?;
-        self.conn.poly_fill_rectangle(
-            overview.pixmap,
-            overview.gc,
-            &[Rectangle {
-                x: label_x,
-                y: label_y,
-                width: label_width,
-                height: label_height,
-            }],
+        self.draw_label(overview, label_x, label_y, &text, padding_h, padding_v)?;
+
+        // The font is fixed-width: each matched character goes over itself.
+        // Those cut off by the "..." aren't shown.
+        let shown = if text.len() < title.chars().count() { MAX_CHARS - 3 } else { text.len() };
+        let char_width = overview.theme.font.char_width as i16;
+        for &position in matched.iter().filter(|&&position| position < shown) {
+            let x = label_x + padding_h as i16 + position as i16 * char_width;
+            let y = label_y + padding_v as i16;
+            self.draw_text(overview, x, y, &text[position..position + 1], overview.theme.highlight)?;
+        }
+        Ok(())
+    }
+
+    /// Draw the search query centered along the bottom of the overview.
+    pub fn draw_search_query(&self, overview: &OverviewWindow, query: &str) -> Result<()> {
+        let text = encode_label(&format!("Search: {}_", query), 80);
+        let (padding_h, padding_v) = (16, 8);
+        let width = overview.theme.font.text_width(&text) + 2 * padding_h;
+        let height = overview.theme.font.line_height + 2 * padding_v;
+        let x = (overview.width as i16 - width as i16) / 2;
+        let y = overview.height as i16 - height as i16 - SEARCH_QUERY_MARGIN;
+        self.draw_label(overview, x, y, &text, padding_h, padding_v)
+    }
+
+    /// Draw a desktop's name just above its group of thumbnails, whose
+    /// top-left corner is (x, y).
+    pub fn draw_desktop_label(&self, overview: &OverviewWindow, x: i16, y: i16, name: &str) -> Result<()> {
+        let text = encode_label(name, 40);
+        let (padding_h, padding_v) = (8, 3);
+        let height = overview.theme.font.line_height + 2 * padding_v;
+        // DESKTOP_LABEL_HEIGHT leaves room for this and a gap in either font
+        self.draw_label(overview, x, y - height as i16 - 4, &text, padding_h, padding_v)
+    }
+
+    /// Mark a thumbnail as about to be killed: a red wash and frame, and how
+    /// to confirm.
+    pub fn draw_kill_confirm(&self, overview: &OverviewWindow, layout: &ThumbnailLayout) -> Result<()> {
+        // Premultiplied: 0xCC4444 at 40%
+        let wash = Color { red: 0x51EB, green: 0x1B4E, blue: 0x1B4E, alpha: 0x6666 };
+        render::fill_rectangles(
+            &self.conn,
+            PictOp::OVER,
+            overview.picture.id(),
+            wash,
+            &[Rectangle { x: layout.x, y: layout.y, width: layout.width, height: layout.height }],
         )?;
 
-        // Draw border around label
-        let border_color = 0x88_88_88;
+        let border_width = overview.theme.border_width;
         self.conn.change_gc(
-            overview.gc,
-            &ChangeGCAux::new().foreground(border_color).line_width(1),
+            overview.gc.id(),
+            &ChangeGCAux::new().foreground(0xCC4444).line_width(border_width as u32),
         )?;
         self.conn.poly_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle {
-                x: label_x,
-                y: label_y,
-                width: label_width,
-                height: label_height,
+                x: layout.x - border_width,
+                y: layout.y - border_width,
+                width: layout.width + 2 * border_width as u16,
+                height: layout.height + 2 * border_width as u16,
             }],
         )?;
 
-        // Draw text centered in the label
-        let text_color = 0xFF_FF_FF; // White
-        self.conn.change_gc(
-            overview.gc,
-            &ChangeGCAux::new().foreground(text_color).font(overview.font),
-        )?;
+        if let Some((x, y, size)) = layout.close_button() {
+            self.render_delete_button(overview, x, y, size, true)?;
+        }
+        self.draw_title_label(overview, layout, "Kill? Enter or Ctrl+close again")
+    }
 
-        // Center text horizontally and vertically
-        // X: label_x + padding
-        // Y: baseline = label_y + padding_v + text_ascent
-        let text_x = label_x + padding_h as i16;
-        let text_y = label_y + padding_v as i16 + text_ascent as i16;
+    /// Draw the edges of the windows piled up behind a group's top window.
+    pub fn draw_pile_behind(&self, overview: &OverviewWindow, layout: &ThumbnailLayout) -> Result<()> {
+        for card in layout.pile_behind() {
+            let rect = Rectangle { x: card.x, y: card.y, width: card.width, height: card.height };
+            self.conn
+                .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0x2e2e2e))?;
+            self.conn.poly_fill_rectangle(overview.pixmap.id(), overview.gc.id(), &[rect])?;
+            self.conn
+                .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0x666666).line_width(1))?;
+            self.conn.poly_rectangle(overview.pixmap.id(), overview.gc.id(), &[rect])?;
+        }
+        Ok(())
+    }
 
-        self.conn.image_text8(
-            overview.pixmap,
-            overview.gc,
-            text_x,
-            text_y,
-            text_bytes,
+    /// Show where a window dropped here would be tiled to.
+    pub fn draw_tile_preview(&self, overview: &OverviewWindow, geometry: Geometry) -> Result<()> {
+        let area = Rectangle { x: geometry.x, y: geometry.y, width: geometry.width, height: geometry.height };
+        // Premultiplied: white at 20%
+        let wash = Color { red: 0x3333, green: 0x3333, blue: 0x3333, alpha: 0x3333 };
+        render::fill_rectangles(&self.conn, PictOp::OVER, overview.picture.id(), wash, &[area])?;
+
+        self.conn.change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0xFFFFFF).line_width(2))?;
+        self.conn.poly_rectangle(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Rectangle { x: area.x + 1, y: area.y + 1, width: area.width.saturating_sub(2), height: area.height.saturating_sub(2) }],
         )?;
+        Ok(())
+    }
+
+    /// Show a window full-size where it sits on screen, over the overview
+    /// dimmed around it (hover peek).
+    pub fn draw_peek(&self, overview: &OverviewWindow, picture: Picture, rect: &ThumbnailLayout) -> Result<()> {
+        // Black at 60%
+        self.draw_over_dimmed(overview, picture, rect, 0x9999)
+    }
 
+    /// Show a window enlarged over the overview, dimmed heavily around it
+    /// (spotlight).
+    pub fn draw_spotlight(&self, overview: &OverviewWindow, picture: Picture, rect: &ThumbnailLayout) -> Result<()> {
+        // Black at 85%
+        self.draw_over_dimmed(overview, picture, rect, 0xD999)
+    }
+
+    /// Draw a window at `rect`, framed in the highlight color, over the
+    /// overview dimmed to black by `alpha`.
+    fn draw_over_dimmed(
+        &self,
+        overview: &OverviewWindow,
+        picture: Picture,
+        rect: &ThumbnailLayout,
+        alpha: u16,
+    ) -> Result<()> {
+        // Premultiplied, so black at any alpha is all zeroes
+        let dim = Color { red: 0, green: 0, blue: 0, alpha };
+        let (width, height) = (self.screen_width, self.screen_height);
+        let screen = Rectangle { x: 0, y: 0, width, height };
+        render::fill_rectangles(&self.conn, PictOp::OVER, overview.picture.id(), dim, &[screen])?;
+        self.render_window_with_opacity(picture, overview.picture.id(), rect.x, rect.y, rect.width, rect.height, 1.0)?;
+
+        self.conn.change_gc(
+            overview.gc.id(),
+            &ChangeGCAux::new()
+                .foreground(overview.theme.highlight)
+                .line_width(overview.theme.preview_border_width as u32),
+        )?;
+        self.conn.poly_rectangle(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Rectangle { x: rect.x - 1, y: rect.y - 1, width: rect.width + 2, height: rect.height + 2 }],
+        )?;
         Ok(())
     }
 
@@ -416,20 +785,21 @@ impl XConnection {
         overview: &OverviewWindow,
         layout: &ThumbnailLayout,
     ) -> Result<()> {
-        let border_width: i16 = 5;
+        // The frame and a little beyond
+        let border_width = overview.theme.border_width + 2;
 
         let x = layout.x - border_width;
         let y = layout.y - border_width;
         let w = layout.width + 2 * border_width as u16;
         let h = layout.height + 2 * border_width as u16;
 
-        if let Some(bg_pic) = overview.bg_picture {
+        if let Some(ref bg_pic) = overview.bg_picture {
             render::composite(
                 &self.conn,
                 PictOp::SRC,
-                bg_pic,
+                bg_pic.id(),
                 x11rb::NONE,
-                overview.picture,
+                overview.picture.id(),
                 x, y,
                 0, 0,
                 x, y,
@@ -437,12 +807,12 @@ impl XConnection {
                 h,
             )?;
         } else {
-            let bg_color = 0x1a1a1a;
+            let bg_color = overview.theme.background;
             self.conn
-                .change_gc(overview.gc, &ChangeGCAux::new().foreground(bg_color))?;
+                .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(bg_color))?;
             self.conn.poly_fill_rectangle(
-                overview.pixmap,
-                overview.gc,
+                overview.pixmap.id(),
+                overview.gc.id(),
                 &[Rectangle {
                     x,
                     y,
@@ -483,10 +853,11 @@ impl XConnection {
             matrix33: double_to_fixed(1.0),
         };
 
-        render::set_picture_transform(&self.conn, src_picture, transform)?;
+        let cookie = render::set_picture_transform(&self.conn, src_picture, transform)?;
+        self.check_request(Subsystem::Renderer, "render_thumbnail_animated", cookie)?;
         render::set_picture_filter(&self.conn, src_picture, b"bilinear", &[])?;
 
-        render::composite(
+        let cookie = render::composite(
             &self.conn,
             PictOp::SRC,
             src_picture,
@@ -501,11 +872,13 @@ impl XConnection {
             layout.width,
             layout.height,
         )?;
+        self.check_request(Subsystem::Renderer, "render_thumbnail_animated", cookie)?;
 
         Ok(())
     }
 
     /// Render a window at its original position with opacity (for skipped windows fade effect).
+    #[allow(clippy::too_many_arguments)]
     pub fn render_window_with_opacity(
         &self,
         src_picture: Picture,
@@ -533,29 +906,18 @@ impl XConnection {
             matrix33: double_to_fixed(1.0),
         };
 
-        render::set_picture_transform(&self.conn, src_picture, transform)?;
+        let cookie = render::set_picture_transform(&self.conn, src_picture, transform)?;
+        self.check_request(Subsystem::Renderer, "render_window_with_opacity", cookie)?;
         render::set_picture_filter(&self.conn, src_picture, b"nearest", &[])?;
 
-        // Create solid fill for alpha mask
-        let alpha = (opacity.clamp(0.0, 1.0) * 65535.0) as u16;
-        let mask_picture = self.generate_id()?;
-        render::create_solid_fill(
-            &self.conn,
-            mask_picture,
-            render::Color {
-                red: alpha,
-                green: alpha,
-                blue: alpha,
-                alpha,
-            },
-        )?;
+        let mask = self.alpha_mask(opacity)?;
 
         // Composite with alpha mask using OVER operator
-        render::composite(
+        let cookie = render::composite(
             &self.conn,
             PictOp::OVER,
             src_picture,
-            mask_picture,
+            mask,
             dst_picture,
             0,
             0,
@@ -566,9 +928,7 @@ impl XConnection {
             width,
             height,
         )?;
-
-        // Free the mask picture
-        render::free_picture(&self.conn, mask_picture)?;
+        self.check_request(Subsystem::Renderer, "render_window_with_opacity", cookie)?;
 
         Ok(())
     }
@@ -580,16 +940,12 @@ impl XConnection {
         layout: &AnimatedLayout,
         highlighted: bool,
     ) -> Result<()> {
-        let border_width: i16 = 3;
+        let border_width = overview.theme.border_width;
 
-        let color = if highlighted {
-            0x44_88_FF
-        } else {
-            0x44_44_44
-        };
+        let color = if highlighted { overview.theme.highlight } else { overview.theme.border };
 
         self.conn.change_gc(
-            overview.gc,
+            overview.gc.id(),
             &ChangeGCAux::new().foreground(color).line_width(border_width as u32),
         )?;
 
@@ -599,8 +955,8 @@ impl XConnection {
         let h = layout.height + 2 * border_width as u16;
 
         self.conn.poly_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle {
                 x,
                 y,
@@ -614,13 +970,13 @@ impl XConnection {
 
     /// Clear entire overview pixmap to background (wallpaper or solid color).
     pub fn clear_overview(&self, overview: &OverviewWindow) -> Result<()> {
-        if let Some(bg_pic) = overview.bg_picture {
+        if let Some(ref bg_pic) = overview.bg_picture {
             render::composite(
                 &self.conn,
                 PictOp::SRC,
-                bg_pic,
+                bg_pic.id(),
                 x11rb::NONE,
-                overview.picture,
+                overview.picture.id(),
                 0, 0,
                 0, 0,
                 0, 0,
@@ -628,12 +984,12 @@ impl XConnection {
                 overview.height,
             )?;
         } else {
-            let bg_color = 0x1a1a1a;
+            let bg_color = overview.theme.background;
             self.conn
-                .change_gc(overview.gc, &ChangeGCAux::new().foreground(bg_color))?;
+                .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(bg_color))?;
             self.conn.poly_fill_rectangle(
-                overview.pixmap,
-                overview.gc,
+                overview.pixmap.id(),
+                overview.gc.id(),
                 &[Rectangle {
                     x: 0,
                     y: 0,
@@ -646,68 +1002,259 @@ impl XConnection {
         Ok(())
     }
 
+    /// Draw the protocol error counters in the bottom-left corner (debug mode).
+    fn draw_debug_hud(&self, overview: &OverviewWindow) -> Result<()> {
+        let text = self.errors.summary();
+        let color = if self.errors.total() > 0 { 0xFF_60_60 } else { 0xA0_A0_A0 };
+        self.conn.change_gc(
+            overview.gc.id(),
+            &ChangeGCAux::new()
+                .foreground(color)
+                .background(overview.theme.background)
+                .font(overview.font),
+        )?;
+        self.conn.image_text8(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            8,
+            overview.height as i16 - 8,
+            text.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Draw queued toasts stacked in the bottom-right corner, oldest at the
+    /// bottom. What was underneath is saved first so the toasts only live for
+    /// one present; fading toasts are crossfaded back towards it.
+    fn draw_toasts(&self, overview: &OverviewWindow, toasts: &[(String, f64)]) -> Result<SavedArea> {
+        let labels: Vec<Vec<u8>> = toasts
+            .iter()
+            .map(|(message, _)| encode_label(message, MAX_TOAST_CHARS))
+            .collect();
+        let font = &overview.theme.font;
+        let toast_height = (font.line_height + TOAST_PADDING_V * 2) as i32;
+        let widths: Vec<i32> = labels
+            .iter()
+            .map(|label| (font.text_width(label) + TOAST_PADDING_H * 2) as i32)
+            .collect();
+
+        let right = overview.width as i32 - TOAST_MARGIN;
+        let bottom = overview.height as i32 - TOAST_MARGIN;
+        let stack_width = widths.iter().copied().max().unwrap_or(0);
+        let stack_height = labels.len() as i32 * (toast_height + TOAST_SPACING) - TOAST_SPACING;
+        let area_x = (right - stack_width).max(0);
+        let area_y = (bottom - stack_height).max(0);
+        let area_w = (right - area_x).max(1) as u16;
+        let area_h = (bottom - area_y).max(1) as u16;
+
+        let pixmap = self.create_owned_pixmap(overview.pixmap.id(), area_w, area_h)?;
+        self.conn.copy_area(
+            overview.pixmap.id(),
+            pixmap.id(),
+            overview.gc.id(),
+            area_x as i16,
+            area_y as i16,
+            0,
+            0,
+            area_w,
+            area_h,
+        )?;
+        let saved = SavedArea {
+            picture: self.create_owned_picture(pixmap.id())?,
+            pixmap,
+            x: area_x as i16,
+            y: area_y as i16,
+            width: area_w,
+            height: area_h,
+        };
+
+        for (i, (label, (_, opacity))) in labels.iter().zip(toasts).enumerate() {
+            let x = right - widths[i];
+            let y = bottom - toast_height - i as i32 * (toast_height + TOAST_SPACING);
+            let rect = (x as i16, y as i16, widths[i] as u16, toast_height as u16);
+            let colors = cached_colors(&overview.toast_colors, rect, || {
+                self.label_colors(overview, rect.0, rect.1, rect.2, rect.3)
+            })?;
+            self.draw_label_in(overview, rect.0, rect.1, label, TOAST_PADDING_H, TOAST_PADDING_V, colors)?;
+
+            if *opacity < 1.0 {
+                // Blend the saved background back in over the toast
+                let mask = self.alpha_mask(1.0 - opacity.max(0.0))?;
+                render::composite(
+                    &self.conn,
+                    PictOp::OVER,
+                    saved.picture.id(),
+                    mask,
+                    overview.picture.id(),
+                    (x - area_x) as i16,
+                    (y - area_y) as i16,
+                    0,
+                    0,
+                    x as i16,
+                    y as i16,
+                    widths[i] as u16,
+                    toast_height as u16,
+                )?;
+            }
+        }
+
+        Ok(saved)
+    }
+
+    /// Put back the back buffer contents covered by `draw_toasts`.
+    fn restore_under_toasts(&self, overview: &OverviewWindow, saved: SavedArea) -> Result<()> {
+        self.conn.copy_area(
+            saved.pixmap.id(),
+            overview.pixmap.id(),
+            overview.gc.id(),
+            0,
+            0,
+            saved.x,
+            saved.y,
+            saved.width,
+            saved.height,
+        )?;
+        Ok(())
+    }
+
     /// Copy rendered content to window.
     pub fn present_overview(&self, overview: &OverviewWindow) -> Result<()> {
+        // Anything may have been drawn under the toasts
+        overview.toast_colors.borrow_mut().clear();
+        self.present(overview)
+    }
+
+    /// Present again with nothing drawn since the last time, for toasts
+    /// fading out: the colors picked for them still hold, and reading the
+    /// back buffer back for each one on every frame is a round trip.
+    pub fn present_toasts(&self, overview: &OverviewWindow) -> Result<()> {
+        self.present(overview)
+    }
+
+    fn present(&self, overview: &OverviewWindow) -> Result<()> {
+        if self.checked_requests {
+            self.draw_debug_hud(overview)?;
+        }
+        let toasts = self.toasts.borrow_mut().visible(Instant::now());
+        let saved = if toasts.is_empty() {
+            None
+        } else {
+            Some(self.draw_toasts(overview, &toasts)?)
+        };
         // Keep overview above all other windows while visible.
         self.conn.configure_window(
             overview.window,
             &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
         )?;
-        self.conn.copy_area(
-            overview.pixmap,
+        match overview.present_fence {
+            Some(fence) => self.present_pixmap(overview, fence)?,
+            None => {
+                self.conn.copy_area(
+                    overview.pixmap.id(),
+                    overview.window,
+                    overview.gc.id(),
+                    0,
+                    0,
+                    0,
+                    0,
+                    overview.width,
+                    overview.height,
+                )?;
+            }
+        }
+        if let Some(saved) = saved {
+            self.restore_under_toasts(overview, saved)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Resize the overview window and its back buffer to the current screen
+    /// size (after a RandR change). The wallpaper picture is recreated too,
+    /// since wallpaper setters usually redraw it for the new size.
+    pub fn resize_overview(&self, overview: &mut OverviewWindow) -> Result<()> {
+        self.conn.configure_window(
             overview.window,
-            overview.gc,
-            0,
-            0,
-            0,
-            0,
-            overview.width,
-            overview.height,
+            &ConfigureWindowAux::new()
+                .x(0)
+                .y(0)
+                .width(self.screen_width as u32)
+                .height(self.screen_height as u32),
         )?;
-        self.conn.flush()?;
+
+        let pixmap = self.create_owned_pixmap(overview.window, self.screen_width, self.screen_height)?;
+        let picture = self.create_owned_picture(pixmap.id())?;
+        self.conn.change_window_attributes(
+            overview.window,
+            &ChangeWindowAttributesAux::new().background_pixmap(pixmap.id()),
+        )?;
+
+        // The old back buffer goes once the new one is in place
+        overview.bg_picture = None;
+        overview.picture = picture;
+        overview.pixmap = pixmap;
+        overview.width = self.screen_width;
+        overview.height = self.screen_height;
+        overview.bg_picture = self.create_background_picture()?;
+
+        log::info!("Resized overview to {}x{}", overview.width, overview.height);
         Ok(())
     }
 
-    /// Destroy overview window and free resources.
-    pub fn destroy_overview(&self, overview: &OverviewWindow) -> Result<()> {
-        // Free the background picture if we created one
-        if let Some(bg_pic) = overview.bg_picture {
-            render::free_picture(&self.conn, bg_pic)?;
+    /// Open the theme's font for text rendering, else "fixed", which is
+    /// always available (and then in the theme in its place).
+    fn open_theme_font(&self, theme: &mut Theme) -> Result<Font> {
+        let font = self.generate_id()?;
+        if let Err(e) = self.conn.open_font(font, theme.font.name.as_bytes())?.check() {
+            log::warn!("Cannot open font {}, using fixed: {}", theme.font.name, e);
+            theme.font = FIXED_FONT;
+            self.conn.open_font(font, theme.font.name.as_bytes())?;
+        }
+        log::info!("Opened font: {}", theme.font.name);
+        Ok(font)
+    }
+
+    /// Draw the overview with `theme` from now on, as after a config reload.
+    /// The caller redraws.
+    pub fn set_overview_theme(&self, overview: &mut OverviewWindow, mut theme: Theme) -> Result<()> {
+        let font = self.open_theme_font(&mut theme)?;
+        self.conn.change_gc(overview.gc.id(), &ChangeGCAux::new().font(font))?;
+        self.conn.close_font(std::mem::replace(&mut overview.font, font))?;
+        if theme.wallpapers != overview.theme.wallpapers {
+            overview.bg_picture = if theme.wallpapers { self.create_background_picture()? } else { None };
         }
-        render::free_picture(&self.conn, overview.picture)?;
-        self.conn.free_gc(overview.gc)?;
-        self.conn.free_pixmap(overview.pixmap)?;
+        overview.theme = theme;
+        overview.forget_label_colors();
+        Ok(())
+    }
+
+    /// Destroy overview window and free resources.
+    pub fn destroy_overview(&self, overview: OverviewWindow) -> Result<()> {
+        self.stop_presenting(&overview)?;
         self.conn.destroy_window(overview.window)?;
+        // Its back buffer, GC, background, wallpapers and icons go with it
+        drop(overview);
         self.conn.flush()?;
         Ok(())
     }
 
-    /// Render the desktop bar background.
-    pub fn render_desktop_bar_background(
-        &self,
-        overview: &OverviewWindow,
-        bar_height: u16,
-        bar_y_offset: i16,
-    ) -> Result<()> {
-        // Dark semi-transparent background
-        let bg_color = 0x1a1a1a;
+    /// Render the desktop bar background over `rect`.
+    pub fn render_desktop_bar_background(&self, overview: &OverviewWindow, rect: Rect) -> Result<()> {
+        let (x, y, width, height) = rect;
+        let bg_color = overview.theme.background;
         self.conn
-            .change_gc(overview.gc, &ChangeGCAux::new().foreground(bg_color))?;
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(bg_color))?;
         self.conn.poly_fill_rectangle(
-            overview.pixmap,
-            overview.gc,
-            &[Rectangle {
-                x: 0,
-                y: bar_y_offset,
-                width: overview.width,
-                height: bar_height,
-            }],
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Rectangle { x, y, width, height }],
         )?;
         Ok(())
     }
 
     /// Render a desktop preview rectangle (simple version, no window content).
     #[allow(dead_code)]
+    #[allow(clippy::too_many_arguments)]
     pub fn render_desktop_preview(
         &self,
         overview: &OverviewWindow,
@@ -719,31 +1266,27 @@ impl XConnection {
         is_hovered: bool,
     ) -> Result<()> {
         // Background color
-        let bg_color = if is_current { 0x3a3a3a } else { 0x2a2a2a };
+        let bg_color = if is_current { overview.theme.current_preview } else { overview.theme.preview };
         self.conn
-            .change_gc(overview.gc, &ChangeGCAux::new().foreground(bg_color))?;
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(bg_color))?;
         self.conn.poly_fill_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle { x, y, width, height }],
         )?;
 
         // Border
-        let border_color = if is_current || is_hovered {
-            0x4488FF // Highlight
-        } else {
-            0x444444 // Normal
-        };
-        let border_width: i16 = 2;
+        let border_color = if is_current || is_hovered { overview.theme.highlight } else { overview.theme.border };
+        let border_width = overview.theme.preview_border_width;
         self.conn.change_gc(
-            overview.gc,
+            overview.gc.id(),
             &ChangeGCAux::new()
                 .foreground(border_color)
                 .line_width(border_width as u32),
         )?;
         self.conn.poly_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle { x, y, width, height }],
         )?;
 
@@ -757,18 +1300,20 @@ impl XConnection {
         preview: &DesktopPreviewLayout,
         captures: &[CapturedWindow],
         is_hovered: bool,
-        y_offset: i16,
+        offset: (i16, i16),
     ) -> Result<()> {
-        let preview_x = preview.x;
-        let preview_y = preview.y + y_offset;
+        let preview_x = preview.x + offset.0;
+        let preview_y = preview.y + offset.1;
         let preview_w = preview.width;
         let preview_h = preview.height;
 
         // 1. Render scaled wallpaper as background
-        if let Some(bg_pic) = overview.bg_picture {
+        if let Some((bg_pic, bg_width, bg_height)) = overview.preview_background(preview.desktop_index) {
             self.render_wallpaper_scaled(
                 bg_pic,
-                overview.picture,
+                bg_width,
+                bg_height,
+                overview.picture.id(),
                 preview_x,
                 preview_y,
                 preview_w,
@@ -776,12 +1321,12 @@ impl XConnection {
             )?;
         } else {
             // Fallback: solid color background
-            let bg_color = if preview.is_current { 0x3a3a3a } else { 0x2a2a2a };
+            let bg_color = if preview.is_current { overview.theme.current_preview } else { overview.theme.preview };
             self.conn
-                .change_gc(overview.gc, &ChangeGCAux::new().foreground(bg_color))?;
+                .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(bg_color))?;
             self.conn.poly_fill_rectangle(
-                overview.pixmap,
-                overview.gc,
+                overview.pixmap.id(),
+                overview.gc.id(),
                 &[Rectangle {
                     x: preview_x,
                     y: preview_y,
@@ -796,8 +1341,8 @@ impl XConnection {
             // Find the capture by frame window ID
             if let Some(capture) = captures.iter().find(|c| c.info.frame_window == mini.window_id) {
                 self.render_mini_thumbnail(
-                    capture.picture,
-                    overview.picture,
+                    capture.picture.id(),
+                    overview.picture.id(),
                     capture.info.width,
                     capture.info.height,
                     preview_x + mini.x,
@@ -809,21 +1354,17 @@ impl XConnection {
         }
 
         // 3. Draw border
-        let border_color = if preview.is_current || is_hovered {
-            0x4488FF
-        } else {
-            0x444444
-        };
-        let border_width: i16 = 2;
+        let border_color = if preview.is_current || is_hovered { overview.theme.highlight } else { overview.theme.border };
+        let border_width = overview.theme.preview_border_width;
         self.conn.change_gc(
-            overview.gc,
+            overview.gc.id(),
             &ChangeGCAux::new()
                 .foreground(border_color)
                 .line_width(border_width as u32),
         )?;
         self.conn.poly_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle {
                 x: preview_x,
                 y: preview_y,
@@ -832,13 +1373,49 @@ impl XConnection {
             }],
         )?;
 
+        // 4. Desktop name in the bottom-left corner
+        if let Some(ref name) = preview.name {
+            self.draw_preview_name(overview, name, preview_x, preview_y, preview_w, preview_h)?;
+        }
+
         Ok(())
     }
 
+    /// Draw a desktop name label inside the bottom-left corner of a preview,
+    /// cut to fit its width.
+    fn draw_preview_name(
+        &self,
+        overview: &OverviewWindow,
+        name: &str,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<()> {
+        let font = &overview.theme.font;
+        let max_chars = (width.saturating_sub(2 * (PREVIEW_NAME_MARGIN + PREVIEW_NAME_PADDING_H)) / font.char_width) as usize;
+        let label_height = font.line_height + 2 * PREVIEW_NAME_PADDING_V + PREVIEW_NAME_MARGIN;
+        if max_chars < 4 || height < label_height {
+            return Ok(());
+        }
+        let text = encode_label(name, max_chars);
+        self.draw_label(
+            overview,
+            x + PREVIEW_NAME_MARGIN as i16,
+            y + (height - label_height) as i16,
+            &text,
+            PREVIEW_NAME_PADDING_H,
+            PREVIEW_NAME_PADDING_V,
+        )
+    }
+
     /// Render wallpaper scaled to fit within a preview rectangle.
+    #[allow(clippy::too_many_arguments)]
     fn render_wallpaper_scaled(
         &self,
         src_picture: Picture,
+        src_width: u16,
+        src_height: u16,
         dst_picture: Picture,
         dst_x: i16,
         dst_y: i16,
@@ -848,8 +1425,8 @@ impl XConnection {
         // XRender transforms work in reverse: we specify how to map
         // destination coords back to source coords
         // scale = src_size / dst_size
-        let scale_x = self.screen_width as f64 / dst_width as f64;
-        let scale_y = self.screen_height as f64 / dst_height as f64;
+        let scale_x = src_width as f64 / dst_width as f64;
+        let scale_y = src_height as f64 / dst_height as f64;
 
         let transform = Transform {
             matrix11: double_to_fixed(scale_x),
@@ -863,10 +1440,11 @@ impl XConnection {
             matrix33: double_to_fixed(1.0),
         };
 
-        render::set_picture_transform(&self.conn, src_picture, transform)?;
+        let cookie = render::set_picture_transform(&self.conn, src_picture, transform)?;
+        self.check_request(Subsystem::DesktopBar, "render_wallpaper_scaled", cookie)?;
         render::set_picture_filter(&self.conn, src_picture, b"bilinear", &[])?;
 
-        render::composite(
+        let cookie = render::composite(
             &self.conn,
             PictOp::SRC,
             src_picture,
@@ -881,6 +1459,7 @@ impl XConnection {
             dst_width,
             dst_height,
         )?;
+        self.check_request(Subsystem::DesktopBar, "render_wallpaper_scaled", cookie)?;
 
         // Reset transform to identity for other operations
         let identity = Transform {
@@ -900,6 +1479,7 @@ impl XConnection {
     }
 
     /// Render a mini window thumbnail at the specified position.
+    #[allow(clippy::too_many_arguments)]
     fn render_mini_thumbnail(
         &self,
         src_picture: Picture,
@@ -930,11 +1510,12 @@ impl XConnection {
             matrix33: double_to_fixed(1.0),
         };
 
-        render::set_picture_transform(&self.conn, src_picture, transform)?;
+        let cookie = render::set_picture_transform(&self.conn, src_picture, transform)?;
+        self.check_request(Subsystem::DesktopBar, "render_mini_thumbnail", cookie)?;
         // Use bilinear filtering for smooth scaling
         render::set_picture_filter(&self.conn, src_picture, b"bilinear", &[])?;
 
-        render::composite(
+        let cookie = render::composite(
             &self.conn,
             PictOp::OVER, // OVER to handle window transparency
             src_picture,
@@ -949,12 +1530,14 @@ impl XConnection {
             dst_width,
             dst_height,
         )?;
+        self.check_request(Subsystem::DesktopBar, "render_mini_thumbnail", cookie)?;
 
         Ok(())
     }
 
     /// Render a window being dragged at a specific position and size.
     /// Used for drag feedback and snap/revert animations.
+    #[allow(clippy::too_many_arguments)]
     pub fn render_dragged_window(
         &self,
         src_picture: Picture,
@@ -986,10 +1569,11 @@ impl XConnection {
             matrix33: double_to_fixed(1.0),
         };
 
-        render::set_picture_transform(&self.conn, src_picture, transform)?;
+        let cookie = render::set_picture_transform(&self.conn, src_picture, transform)?;
+        self.check_request(Subsystem::Renderer, "render_dragged_window", cookie)?;
         render::set_picture_filter(&self.conn, src_picture, b"bilinear", &[])?;
 
-        render::composite(
+        let cookie = render::composite(
             &self.conn,
             PictOp::OVER,
             src_picture,
@@ -1004,10 +1588,26 @@ impl XConnection {
             width,
             height,
         )?;
+        self.check_request(Subsystem::Renderer, "render_dragged_window", cookie)?;
 
         Ok(())
     }
 
+    /// `render_dragged_window` for a capture, from its scaled copy where
+    /// that will do.
+    pub fn render_dragged_capture(
+        &self,
+        capture: &CapturedWindow,
+        dst_picture: Picture,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<()> {
+        let (picture, src_width, src_height) = self.thumbnail_source(capture, width, height);
+        self.render_dragged_window(picture, dst_picture, src_width, src_height, x, y, width, height)
+    }
+
     /// Render the plus button.
     pub fn render_plus_button(
         &self,
@@ -1020,10 +1620,10 @@ impl XConnection {
         // Background circle (approximated with filled rectangle for now)
         let bg_color = if is_hovered { 0x555555 } else { 0x444444 };
         self.conn
-            .change_gc(overview.gc, &ChangeGCAux::new().foreground(bg_color))?;
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(bg_color))?;
         self.conn.poly_fill_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle {
                 x,
                 y,
@@ -1037,7 +1637,7 @@ impl XConnection {
         let line_width = 3u16;
         let margin = size / 4;
         self.conn.change_gc(
-            overview.gc,
+            overview.gc.id(),
             &ChangeGCAux::new()
                 .foreground(plus_color)
                 .line_width(line_width as u32),
@@ -1046,8 +1646,8 @@ impl XConnection {
         // Horizontal line
         let h_y = y + (size / 2) as i16;
         self.conn.poly_segment(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Segment {
                 x1: x + margin as i16,
                 y1: h_y,
@@ -1059,8 +1659,8 @@ impl XConnection {
         // Vertical line
         let v_x = x + (size / 2) as i16;
         self.conn.poly_segment(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Segment {
                 x1: v_x,
                 y1: y + margin as i16,
@@ -1084,10 +1684,10 @@ impl XConnection {
         // Background (dark red, brighter when hovered)
         let bg_color = if is_hovered { 0xCC4444 } else { 0x884444 };
         self.conn
-            .change_gc(overview.gc, &ChangeGCAux::new().foreground(bg_color))?;
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(bg_color))?;
         self.conn.poly_fill_rectangle(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[Rectangle {
                 x,
                 y,
@@ -1101,7 +1701,7 @@ impl XConnection {
         let line_width = 2u16;
         let margin = size / 4;
         self.conn.change_gc(
-            overview.gc,
+            overview.gc.id(),
             &ChangeGCAux::new()
                 .foreground(x_color)
                 .line_width(line_width as u32),
@@ -1109,8 +1709,8 @@ impl XConnection {
 
         // Diagonal lines forming X
         self.conn.poly_segment(
-            overview.pixmap,
-            overview.gc,
+            overview.pixmap.id(),
+            overview.gc.id(),
             &[
                 Segment {
                     x1: x + margin as i16,
@@ -1130,8 +1730,130 @@ impl XConnection {
         Ok(())
     }
 
+    /// Render a thumbnail's minimize button: a bar on a gray square.
+    pub fn render_minimize_button(&self, overview: &OverviewWindow, x: i16, y: i16, size: u16) -> Result<()> {
+        self.conn
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0x555555))?;
+        self.conn.poly_fill_rectangle(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Rectangle { x, y, width: size, height: size }],
+        )?;
+        let margin = size / 4;
+        self.conn
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0xFFFFFF))?;
+        self.conn.poly_fill_rectangle(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Rectangle {
+                x: x + margin as i16,
+                y: y + (size - margin) as i16 - 2,
+                width: size - 2 * margin,
+                height: 2,
+            }],
+        )?;
+        Ok(())
+    }
+
+    /// Render a window's application icon scaled into a badge.
+    pub fn render_icon(&self, overview: &OverviewWindow, icon: &Icon, x: i16, y: i16, size: u16) -> Result<()> {
+        let badge = ThumbnailLayout {
+            x,
+            y,
+            width: size,
+            height: size,
+            window_index: 0,
+        };
+        self.render_thumbnail(icon.picture.id(), overview.picture.id(), icon.width, icon.height, &badge)
+    }
+
+    /// Render the pin of a sticky window's thumbnail: a round head on a
+    /// needle.
+    pub fn render_pin(&self, overview: &OverviewWindow, x: i16, y: i16, size: u16) -> Result<()> {
+        self.conn
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0x555555))?;
+        self.conn.poly_fill_rectangle(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Rectangle { x, y, width: size, height: size }],
+        )?;
+        let head = size / 2;
+        let center = x + (size / 2) as i16;
+        self.conn
+            .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0xFFFFFF).line_width(2))?;
+        self.conn.poly_segment(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Segment { x1: center, y1: y + (size / 4 + head / 2) as i16, x2: center, y2: y + (size - size / 8) as i16 }],
+        )?;
+        self.conn.poly_fill_arc(
+            overview.pixmap.id(),
+            overview.gc.id(),
+            &[Arc {
+                x: center - (head / 2) as i16,
+                y: y + (size / 4) as i16 - (head / 4) as i16,
+                width: head,
+                height: head,
+                angle1: 0,
+                angle2: 360 * 64,
+            }],
+        )?;
+        Ok(())
+    }
+
+    /// Render a delete confirmation over its desktop preview: the message,
+    /// with "Yes" and "No" buttons below it.
+    pub fn render_delete_confirm(
+        &self,
+        overview: &OverviewWindow,
+        bar: &DesktopBar,
+        confirm: &DeleteConfirm,
+        offset: (i16, i16),
+    ) -> Result<()> {
+        let Some(preview) = bar.preview_layouts.iter().find(|p| p.desktop_index == confirm.desktop_index) else {
+            return Ok(());
+        };
+        let Some((yes, no)) = bar.confirm_buttons(confirm.desktop_index) else {
+            return Ok(());
+        };
+
+        let font = &overview.theme.font;
+        let max_chars = (preview.width.saturating_sub(4 * CONFIRM_PADDING_H) / font.char_width) as usize;
+        let text = encode_label(&confirm.message, max_chars);
+        let label_width = font.text_width(&text) + 2 * CONFIRM_PADDING_H;
+        let label_height = font.line_height + 2 * CONFIRM_PADDING_V;
+        self.draw_label(
+            overview,
+            preview.x + offset.0 + (preview.width.saturating_sub(label_width) / 2) as i16,
+            yes.1 + offset.1 - CONFIRM_MESSAGE_GAP - label_height as i16,
+            &text,
+            CONFIRM_PADDING_H,
+            CONFIRM_PADDING_V,
+        )?;
+
+        for ((x, y, width, height), caption, color) in [(yes, &b"Yes"[..], 0xCC4444), (no, &b"No"[..], 0x555555)] {
+            let (x, y) = (x + offset.0, y + offset.1);
+            self.conn
+                .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(color))?;
+            self.conn.poly_fill_rectangle(
+                overview.pixmap.id(),
+                overview.gc.id(),
+                &[Rectangle { x, y, width, height }],
+            )?;
+            self.draw_text(
+                overview,
+                x + (width.saturating_sub(font.text_width(caption)) / 2) as i16,
+                y + (height.saturating_sub(font.line_height) / 2) as i16,
+                caption,
+                0xFFFFFF,
+            )?;
+        }
+        Ok(())
+    }
+
     /// Render a desktop preview at an animated position/size (for zoom animation).
     /// This renders the wallpaper and mini-windows scaled to the given rectangle.
+    #[allow(clippy::too_many_arguments)]
     pub fn render_desktop_preview_animated(
         &self,
         overview: &OverviewWindow,
@@ -1147,10 +1869,12 @@ impl XConnection {
         }
 
         // 1. Render scaled wallpaper as background
-        if let Some(bg_pic) = overview.bg_picture {
+        if let Some((bg_pic, bg_width, bg_height)) = overview.preview_background(preview.desktop_index) {
             self.render_wallpaper_scaled(
                 bg_pic,
-                overview.picture,
+                bg_width,
+                bg_height,
+                overview.picture.id(),
                 dst_x,
                 dst_y,
                 dst_width,
@@ -1159,10 +1883,10 @@ impl XConnection {
         } else {
             // Fallback: solid color background
             self.conn
-                .change_gc(overview.gc, &ChangeGCAux::new().foreground(0x2a2a2a))?;
+                .change_gc(overview.gc.id(), &ChangeGCAux::new().foreground(0x2a2a2a))?;
             self.conn.poly_fill_rectangle(
-                overview.pixmap,
-                overview.gc,
+                overview.pixmap.id(),
+                overview.gc.id(),
                 &[Rectangle {
                     x: dst_x,
                     y: dst_y,
@@ -1187,8 +1911,8 @@ impl XConnection {
                 let mini_h = (mini.height as f64 * scale_y) as u16;
 
                 self.render_mini_thumbnail(
-                    capture.picture,
-                    overview.picture,
+                    capture.picture.id(),
+                    overview.picture.id(),
                     capture.info.width,
                     capture.info.height,
                     mini_x,
@@ -1203,8 +1927,19 @@ impl XConnection {
     }
 }
 
+/// The RGB color `t` (0.0 to 1.0) of the way from `from` to `to`.
+fn mix_colors(from: u32, to: u32, t: f64) -> u32 {
+    let t = t.clamp(0.0, 1.0);
+    [16, 8, 0].iter().fold(0, |color, shift| {
+        let a = f64::from((from >> shift) & 0xFF);
+        let b = f64::from((to >> shift) & 0xFF);
+        color | (((a + (b - a) * t).round() as u32) << shift)
+    })
+}
+
 // TODO: Future enhancements
 // - Animation support (fade-in/out)
 // - Rounded corners using clip masks
 // - Window title labels
 // - Drop shadows
+
@@ -0,0 +1,141 @@
+//! Owned X resources that are freed when dropped.
+//!
+//! Pixmaps, pictures, damage objects and GCs created while building something
+//! up are held in these wrappers, so every early return (a failed request,
+//! a window that vanished halfway) frees what was already created, and
+//! longer-lived owners such as a `CapturedWindow` keep them, so dropping the
+//! owner frees everything in it. `into_raw` releases a resource from its
+//! wrapper for good, e.g. when the server takes it over.
+
+use std::fmt;
+use std::rc::Rc;
+
+use x11rb::protocol::composite;
+use x11rb::protocol::damage::{self, Damage, ReportLevel};
+use x11rb::protocol::render::{self, Color, Picture};
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+macro_rules! owned_resource {
+    ($(#[$meta:meta])* $name:ident, $id:ty, |$conn:ident, $res:ident| $free:expr) => {
+        $(#[$meta])*
+        #[must_use]
+        pub struct $name {
+            conn: Rc<RustConnection>,
+            id: $id,
+        }
+
+        impl $name {
+            /// Take ownership of a resource that was created on `conn`.
+            pub fn new(conn: &Rc<RustConnection>, id: $id) -> Self {
+                Self { conn: conn.clone(), id }
+            }
+
+            // A redirection is known by its window instead
+            #[allow(dead_code)]
+            pub fn id(&self) -> $id {
+                self.id
+            }
+
+            /// Give up ownership without freeing the resource.
+            // Not every kind is ever handed over
+            #[allow(dead_code)]
+            pub fn into_raw(self) -> $id {
+                let id = self.id;
+                std::mem::forget(self);
+                id
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}(0x{:x})", stringify!($name), self.id)
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                let ($conn, $res) = (&*self.conn, self.id);
+                // Nothing to do about a failure here; the connection is
+                // most likely gone, taking the resource with it
+                let _ = $free;
+            }
+        }
+    };
+}
+
+owned_resource!(
+    /// A pixmap freed with FreePixmap on drop.
+    OwnedPixmap, Pixmap, |conn, id| conn.free_pixmap(id)
+);
+owned_resource!(
+    /// A Render picture freed with FreePicture on drop.
+    OwnedPicture, Picture, |conn, id| render::free_picture(conn, id)
+);
+owned_resource!(
+    /// A damage object destroyed on drop.
+    OwnedDamage, Damage, |conn, id| damage::destroy(conn, id)
+);
+owned_resource!(
+    /// A graphics context freed with FreeGC on drop.
+    #[allow(clippy::upper_case_acronyms)]
+    OwnedGC, Gcontext, |conn, id| conn.free_gc(id)
+);
+owned_resource!(
+    /// A window redirected off-screen with XComposite, put back on drop.
+    OwnedRedirection, Window, |conn, id| composite::unredirect_window(conn, id, composite::Redirect::AUTOMATIC)
+);
+
+impl XConnection {
+    /// Create a root-depth pixmap.
+    pub fn create_owned_pixmap(&self, drawable: Drawable, width: u16, height: u16) -> Result<OwnedPixmap> {
+        let pixmap = self.generate_id()?;
+        self.conn
+            .create_pixmap(self.root_depth, pixmap, drawable, width, height)?;
+        Ok(OwnedPixmap::new(&self.conn, pixmap))
+    }
+
+    /// Create a picture for a root-depth drawable.
+    pub fn create_owned_picture(&self, drawable: Drawable) -> Result<OwnedPicture> {
+        let picture = self.generate_id()?;
+        render::create_picture(
+            &self.conn,
+            picture,
+            drawable,
+            self.pict_format_rgb,
+            &render::CreatePictureAux::new(),
+        )?;
+        Ok(OwnedPicture::new(&self.conn, picture))
+    }
+
+    /// Create a single-color fill picture, e.g. an alpha mask.
+    pub fn create_solid_fill(&self, color: Color) -> Result<OwnedPicture> {
+        let picture = self.generate_id()?;
+        render::create_solid_fill(&self.conn, picture, color)?;
+        Ok(OwnedPicture::new(&self.conn, picture))
+    }
+
+    /// Start damage tracking for a window.
+    pub fn create_owned_damage(&self, window: Window) -> Result<OwnedDamage> {
+        let damage_id = self.generate_id()?;
+        damage::create(&self.conn, damage_id, window, ReportLevel::NON_EMPTY)?;
+        Ok(OwnedDamage::new(&self.conn, damage_id))
+    }
+
+    /// Redirect a window to off-screen storage, so its contents can be
+    /// captured.
+    pub fn redirect_window(&self, window: Window) -> Result<OwnedRedirection> {
+        composite::redirect_window(&self.conn, window, composite::Redirect::AUTOMATIC)?;
+        Ok(OwnedRedirection::new(&self.conn, window))
+    }
+
+    /// Create a graphics context for a drawable.
+    pub fn create_owned_gc(&self, drawable: Drawable, aux: &CreateGCAux) -> Result<OwnedGC> {
+        let gc = self.generate_id()?;
+        self.conn.create_gc(gc, drawable, aux)?;
+        Ok(OwnedGC::new(&self.conn, gc))
+    }
+}
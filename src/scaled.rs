@@ -0,0 +1,22 @@
+//! Thumbnails scaled down once, rather than on every frame.
+//!
+//! Drawing a capture straight into its grid slot makes the server rescale
+//! the full-size window contents each time, which is most of the cost of a
+//! frame with big windows. Instead each capture keeps a copy at its grid
+//! size, made when it is first drawn there and again after damage (which
+//! gives the capture new contents, see capture.rs). Grid redraws copy it as
+//! it is, and animations and drags scale it rather than the full contents
+//! whenever they draw no bigger than it (see renderer.rs).
+
+use crate::resources::{OwnedPicture, OwnedPixmap};
+
+/// Capture contents at thumbnail size, freed with the capture or when its
+/// contents change.
+#[derive(Debug)]
+pub struct Scaled {
+    /// Drawn through `picture`; only held to be freed with it
+    pub _pixmap: OwnedPixmap,
+    pub picture: OwnedPicture,
+    pub width: u16,
+    pub height: u16,
+}
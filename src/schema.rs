@@ -0,0 +1,94 @@
+//! Versioned state files.
+//!
+//! Each state file records the `version` of its format. Loading runs the
+//! JSON through the migrations from that version up to the current one
+//! before deserializing, so a format change rewrites old files instead of
+//! failing to read them and starting over. Files written before versioning
+//! count as version 0.
+//!
+//! A file from a newer xpose is read as far as this version understands it,
+//! after copying it aside: the next save would drop whatever it added.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+/// Rewrites a state file's JSON object from one version into the next.
+pub type Migration = fn(&mut Map<String, Value>);
+
+/// Bring `value` from the version it records up to `migrations.len()`,
+/// which it then records. Returns the version it had, which may be newer.
+pub fn upgrade(value: &mut Value, migrations: &[Migration]) -> Result<u32, String> {
+    let current = migrations.len() as u32;
+    let Value::Object(object) = value else {
+        return Err("state is not a JSON object".to_string());
+    };
+    let version = match object.get("version") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("invalid state version {}", v))?,
+    };
+    for (from, migrate) in migrations.iter().enumerate().skip(version as usize) {
+        log::info!("Migrating state from version {} to {}", from, from + 1);
+        migrate(object);
+    }
+    // Saved again, a newer file is in this version's format
+    object.insert("version".to_string(), Value::from(current));
+    Ok(version)
+}
+
+/// Copy a state file about to be lost or cut down to `NAME.SUFFIX`, unless
+/// an earlier run already did.
+pub fn keep_copy(path: &Path, suffix: &str) {
+    let mut copy = path.as_os_str().to_owned();
+    copy.push(format!(".{}", suffix));
+    let copy = Path::new(&copy);
+    if copy.exists() {
+        return;
+    }
+    match fs::copy(path, copy) {
+        Ok(_) => log::warn!("Kept a copy of {} as {}", path.display(), copy.display()),
+        Err(e) => log::warn!("Cannot keep a copy of {}: {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_count(object: &mut Map<String, Value>) {
+        if let Some(count) = object.remove("count") {
+            object.insert("desktops".to_string(), count);
+        }
+    }
+
+    fn double(object: &mut Map<String, Value>) {
+        if let Some(n) = object.get("desktops").and_then(Value::as_u64) {
+            object.insert("desktops".to_string(), Value::from(n * 2));
+        }
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let migrations: [Migration; 2] = [rename_count, double];
+
+        let mut unversioned = serde_json::json!({"count": 2});
+        assert_eq!(upgrade(&mut unversioned, &migrations), Ok(0));
+        assert_eq!(unversioned, serde_json::json!({"desktops": 4, "version": 2}));
+
+        let mut partial = serde_json::json!({"desktops": 2, "version": 1});
+        assert_eq!(upgrade(&mut partial, &migrations), Ok(1));
+        assert_eq!(partial["desktops"], 4);
+
+        // Newer files are read as they are
+        let mut newer = serde_json::json!({"desktops": 2, "version": 5});
+        assert_eq!(upgrade(&mut newer, &migrations), Ok(5));
+        assert_eq!(newer, serde_json::json!({"desktops": 2, "version": 2}));
+
+        assert!(upgrade(&mut serde_json::json!([]), &migrations).is_err());
+        assert!(upgrade(&mut serde_json::json!({"version": "x"}), &migrations).is_err());
+    }
+}
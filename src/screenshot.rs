@@ -0,0 +1,169 @@
+//! Non-interactive screenshot export (`xpose --screenshot`).
+//!
+//! Captures go through the same Composite/Render path as the interactive
+//! overview; the overview frame is rendered into the back buffer of an
+//! overview window that is never mapped, then read back with `get_image_rgba`.
+
+use std::path::Path;
+
+use x11rb::protocol::xproto::*;
+
+use crate::capture::CapturedWindow;
+use crate::cli::ScreenshotTarget;
+use crate::config::Config;
+use crate::connection::XConnection;
+use crate::desktop::DesktopState;
+use crate::desktop_bar::{bar_area, DesktopBar};
+use crate::error::{Result, XposeError};
+use crate::image::RgbaImage;
+use crate::layout::{calculate_monitor_layout, LayoutConfig};
+use crate::state::WindowState;
+use crate::window_finder::WindowInfo;
+
+/// Capture `target` and write it to `path` as PNG.
+pub fn export(target: ScreenshotTarget, path: &Path) -> Result<()> {
+    let config = Config::load();
+    let xconn = XConnection::new()?;
+
+    let image = match target {
+        ScreenshotTarget::Window => grab_focused_window(&xconn, &config)?,
+        ScreenshotTarget::Overview => grab_overview(&xconn, &config)?,
+    };
+
+    image.save_png(path)?;
+    log::info!(
+        "Wrote {}x{} {:?} screenshot to {}",
+        image.width,
+        image.height,
+        target,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Capture the focused window, falling back to the topmost one.
+fn grab_focused_window(xconn: &XConnection, config: &Config) -> Result<RgbaImage> {
+    let (windows, _, stacking_order) = xconn.find_windows(config.window_filter())?;
+    let focus = xconn.conn.get_input_focus()?.reply()?.focus;
+
+    let selected = windows
+        .iter()
+        .find(|w| w.client_window == focus || w.frame_window == focus)
+        .or_else(|| {
+            stacking_order
+                .last()
+                .and_then(|&frame| windows.iter().find(|w| w.frame_window == frame))
+        })
+        .ok_or(XposeError::NoWindows)?;
+
+    log::info!(
+        "Screenshot of {:?} (frame 0x{:x})",
+        selected.wm_name,
+        selected.frame_window
+    );
+
+    let capture = xconn.capture_window(selected)?;
+    xconn.sync()?;
+    let image = xconn.get_image_rgba(
+        capture.pixmap.id(),
+        0,
+        0,
+        capture.info.width,
+        capture.info.height,
+    );
+    drop(capture);
+    xconn.flush()?;
+    image
+}
+
+/// Render one overview frame (desktop bar plus thumbnail grid) offscreen.
+/// Only windows on the current desktop are mapped, so the other desktop
+/// previews show just their background.
+fn grab_overview(xconn: &XConnection, config: &Config) -> Result<RgbaImage> {
+    let (mut windows, _, _) = xconn.find_windows(config.window_filter())?;
+    if windows.is_empty() {
+        return Err(XposeError::NoWindows);
+    }
+
+    let mut desktop_state = DesktopState::load()?;
+    desktop_state.sync_from_x(xconn)?;
+    for info in &windows {
+        desktop_state.get_window_desktop(info.frame_window, desktop_state.current);
+    }
+
+    // Same ordering as the interactive overview, without touching saved state
+    let mut window_state = WindowState::load();
+    window_state.restore_order(&mut windows, |info| {
+        WindowState::order_group(info, &xconn.monitors, desktop_state.current)
+    });
+
+    let captures = capture_all(xconn, &windows);
+    xconn.sync()?;
+
+    let layout_config = LayoutConfig {
+        monitors: config.monitors.clone(),
+        arranged: window_state.arranged_grids(&windows, &xconn.monitors, |_| desktop_state.current),
+        recent: crate::recent_order(config, &window_state, &windows),
+        ..Default::default()
+    };
+    let bar_height = if config.show_desktop_bar { config.desktop_bar_height } else { 0 };
+    let area = config
+        .show_desktop_bar
+        .then(|| bar_area(&xconn.monitors, &layout_config, xconn.screen_width, xconn.screen_height))
+        .flatten();
+    let bar = area.map(|area| {
+        let mut bar = DesktopBar::new(
+            desktop_state.desktops,
+            desktop_state.current,
+            area,
+            bar_height,
+            config.bar_position,
+        );
+        bar.calculate_mini_layouts(&captures, &desktop_state, xconn.screen_width, xconn.screen_height);
+        bar
+    });
+
+    let infos: Vec<WindowInfo> = captures.iter().map(|c| c.info.clone()).collect();
+    let layouts = calculate_monitor_layout(
+        &infos,
+        &xconn.monitors,
+        &layout_config,
+        bar.as_ref().map_or(Default::default(), |bar| bar.reserved(xconn.screen_width, xconn.screen_height)),
+        xconn.screen_width,
+        xconn.screen_height,
+    );
+
+    let mut overview = xconn.create_overview_window(config.theme.theme())?;
+    overview.wallpapers = xconn.load_wallpapers(&config.wallpapers);
+    let rendered = (|| {
+        xconn.clear_overview(&overview)?;
+        if let Some(ref bar) = bar {
+            crate::render_desktop_bar(xconn, &overview, bar, (0, 0), None, None, &captures)?;
+        }
+        crate::render_all_thumbnails(xconn, &captures, &layouts, &overview, None, None)?;
+        xconn.get_image_rgba(overview.pixmap.id(), 0, 0, overview.width, overview.height)
+    })();
+
+    xconn.destroy_overview(overview)?;
+    drop(captures);
+    xconn.flush()?;
+    rendered
+}
+
+/// Capture every window, using placeholders for the ones that fail.
+fn capture_all(xconn: &XConnection, windows: &[WindowInfo]) -> Vec<CapturedWindow> {
+    let mut captures = Vec::new();
+    for (window, captured) in windows.iter().zip(xconn.capture_windows(windows)) {
+        match captured {
+            Ok(capture) => captures.push(capture),
+            Err(e) => {
+                log::debug!("Capture failed for {:?}, using placeholder: {}", window.wm_name, e);
+                match xconn.create_placeholder_capture(window) {
+                    Ok(placeholder) => captures.push(placeholder),
+                    Err(e2) => log::warn!("Failed to create placeholder for {:?}: {}", window.wm_name, e2),
+                }
+            }
+        }
+    }
+    captures
+}
@@ -0,0 +1,154 @@
+//! Finding windows by typing (`/` in the overview).
+//!
+//! The query matches a window when its characters appear in order, not
+//! necessarily together, in the title or the WM_CLASS, ignoring case.
+//! Matches score higher the more of them run together or start words, and
+//! the grid shows the matching windows best first, with the matched
+//! characters of each title highlighted in its caption.
+
+use std::collections::HashMap;
+
+use x11rb::protocol::xproto::Window;
+
+use crate::window_finder::WindowInfo;
+
+/// Score of each matched character.
+const MATCH_SCORE: i32 = 16;
+/// Extra for a character right after the one matched before it.
+const CONSECUTIVE_BONUS: i32 = 12;
+/// Extra for a character starting a word ("fox" in "Firefox" does not,
+/// "In" in "Inbox - Mail" does).
+const WORD_START_BONUS: i32 = 8;
+/// Cost of each character skipped between two matched ones, up to
+/// `MAX_GAP_PENALTY` per gap.
+const GAP_PENALTY: i32 = 1;
+const MAX_GAP_PENALTY: i32 = 8;
+
+/// The query typed so far and where it matched, for the renderer.
+#[derive(Debug, Clone, Default)]
+pub struct SearchView {
+    pub query: String,
+    /// Frames of the matching windows -> the positions (in characters) of
+    /// the matched characters in their titles; empty if only the class
+    /// matched
+    pub matched: HashMap<Window, Vec<usize>>,
+}
+
+/// A query found in a text: how well, and the positions (in characters)
+/// of the characters matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i32,
+    pub positions: Vec<usize>,
+}
+
+/// The best way to find `query`'s characters in order in `text`, ignoring
+/// case; None if they aren't all there. An empty query matches anything.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Match> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let original: Vec<char> = text.chars().collect();
+    let text: Vec<char> = original.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    if query.len() > text.len() {
+        return None;
+    }
+    let word_start = |j: usize| {
+        j == 0
+            || (!original[j - 1].is_alphanumeric() && original[j].is_alphanumeric())
+            || (original[j - 1].is_lowercase() && original[j].is_uppercase())
+    };
+
+    // best[i][j]: the best score with query[..=i] matched and query[i] at
+    // text[j], and where query[i - 1] was for it
+    let mut best: Vec<Vec<Option<(i32, usize)>>> = vec![vec![None; text.len()]; query.len()];
+    for (i, &wanted) in query.iter().enumerate() {
+        for j in (0..text.len()).filter(|&j| text[j] == wanted) {
+            let own = MATCH_SCORE + if word_start(j) { WORD_START_BONUS } else { 0 };
+            if i == 0 {
+                best[i][j] = Some((own, 0));
+                continue;
+            }
+            best[i][j] = (0..j)
+                .filter_map(|k| {
+                    let (score, _) = best[i - 1][k]?;
+                    let link = if k + 1 == j {
+                        CONSECUTIVE_BONUS
+                    } else {
+                        -(GAP_PENALTY * (j - k - 1) as i32).min(MAX_GAP_PENALTY)
+                    };
+                    Some((score + own + link, k))
+                })
+                .max_by_key(|&(score, k)| (score, std::cmp::Reverse(k)));
+        }
+    }
+
+    let Some(last) = query.len().checked_sub(1) else {
+        return Some(Match { score: 0, positions: Vec::new() });
+    };
+    // The earliest of the best ends, for the tightest highlight
+    let (mut j, score) = (0..text.len())
+        .filter_map(|j| Some((j, best[last][j]?.0)))
+        .max_by_key(|&(j, score)| (score, std::cmp::Reverse(j)))?;
+    let mut positions = vec![0; query.len()];
+    for i in (0..query.len()).rev() {
+        positions[i] = j;
+        j = best[i][j].map_or(0, |(_, previous)| previous);
+    }
+    Some(Match { score, positions })
+}
+
+/// The windows of `windows` that match `query` in their title or class,
+/// best first (in `windows` order among equals), with where their titles
+/// matched.
+pub fn rank<'a>(query: &str, windows: impl IntoIterator<Item = &'a WindowInfo>) -> Vec<(Window, Vec<usize>)> {
+    let mut found: Vec<(i32, Window, Vec<usize>)> = windows
+        .into_iter()
+        .filter_map(|info| {
+            let title = info.wm_name.as_deref().and_then(|title| fuzzy_match(query, title));
+            let class = info.wm_class.as_deref().and_then(|class| fuzzy_match(query, class));
+            match (title, class) {
+                (Some(title), Some(class)) if class.score > title.score => {
+                    Some((class.score, info.frame_window, Vec::new()))
+                }
+                (Some(title), _) => Some((title.score, info.frame_window, title.positions)),
+                (None, Some(class)) => Some((class.score, info.frame_window, Vec::new())),
+                (None, None) => None,
+            }
+        })
+        .collect();
+    found.sort_by_key(|&(score, _, _)| std::cmp::Reverse(score));
+    found.into_iter().map(|(_, frame, positions)| (frame, positions)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("inb", "Inbox - Mail").unwrap().positions, [0, 1, 2]);
+        // A word start beats the nearest letter
+        assert_eq!(fuzzy_match("tr", "xterm: results").unwrap().positions, [1, 7]);
+        assert!(fuzzy_match("mx", "Inbox - Mail").is_none());
+        assert!(fuzzy_match("", "anything").is_some());
+        // Together scores higher than spread out
+        let together = fuzzy_match("vim", "vim notes").unwrap();
+        let spread = fuzzy_match("vim", "video import mode").unwrap();
+        assert!(together.score > spread.score);
+    }
+
+    #[test]
+    fn test_rank() {
+        let window = |frame: Window, class: &str, title: &str| WindowInfo::for_test(frame, Some(class), Some(title));
+        let windows = [
+            window(1, "xterm XTerm", "make test"),
+            window(2, "Navigator firefox", "Firefox test results"),
+            window(3, "emacs Emacs", "notes.org"),
+        ];
+        let ranked = rank("fire", &windows);
+        assert_eq!(ranked, [(2, vec![0, 1, 2, 3])]);
+        // Only the class matched: nothing to highlight in the title
+        assert_eq!(rank("emacs", &windows), [(3, Vec::new())]);
+        // "tes" starts a word in the first two titles, not in "notes"
+        assert_eq!(rank("tes", &windows).iter().map(|(frame, _)| *frame).collect::<Vec<_>>(), [1, 2, 3]);
+    }
+}
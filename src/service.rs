@@ -0,0 +1,220 @@
+//! What the long-running control services share: the socket server
+//! (`xpose daemon`, see ipc.rs) and D-Bus (`xpose dbus`, see dbus.rs).
+//!
+//! The overview runs in the service's process, on a thread of its own. While
+//! it is up it holds the desktop state, so requests for it go through X
+//! instead: `_NET_CURRENT_DESKTOP` to switch, `_XPOSE_DISMISS` to close it,
+//! `_XPOSE_RELOAD_CONFIG` to read the config file again.
+//! Otherwise desktop requests run like `xpose switch` and `move-window`.
+//! Services also take SIGUSR1 and SIGUSR2 (see signals.rs), and on SIGTERM
+//! close the overview before exiting.
+//!
+//! Both pass on desktop changes as they happen, whoever made them (the
+//! overview, key bindings, pagers): they follow the desktop properties on the
+//! root and the `_XPOSE_WINDOW_DESKTOP` messages sent there on window moves.
+//! The overview's highlight comes the same way (see announce.rs).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::Event;
+
+use crate::cli::DesktopCommand;
+use crate::connection::XConnection;
+use crate::control;
+use crate::error::{Result, XposeError};
+use crate::signals::{self, Request};
+use crate::systemd;
+
+/// How long to wait at exit for the overview to close.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the overview on request, one at a time.
+#[derive(Clone)]
+pub struct Overview {
+    /// Wakes the overview thread
+    requests: Sender<()>,
+    /// Whether the overview is up, or about to be
+    showing: Arc<AtomicBool>,
+}
+
+impl Overview {
+    /// Start the thread that runs `show_overview` on request.
+    pub fn start(show_overview: fn() -> Result<()>) -> Self {
+        let (requests, received) = mpsc::channel();
+        let showing = Arc::new(AtomicBool::new(false));
+        let done = showing.clone();
+        std::thread::spawn(move || {
+            for () in received {
+                if let Err(e) = show_overview() {
+                    log::error!("Overview failed: {}", e);
+                }
+                done.store(false, Ordering::SeqCst);
+            }
+        });
+        let overview = Overview { requests, showing };
+
+        if let Some(signals) = signals::listen() {
+            let overview = overview.clone();
+            std::thread::spawn(move || loop {
+                for request in signals.wait() {
+                    log::info!("Signal: {:?}", request);
+                    let done = match request {
+                        Request::Toggle => overview.toggle(),
+                        Request::NextDesktop => overview.next_desktop(),
+                        Request::Quit => {
+                            systemd::notify("STOPPING=1");
+                            overview.close();
+                            std::process::exit(0);
+                        }
+                    };
+                    if let Err(e) = done {
+                        log::warn!("Cannot handle {:?}: {}", request, e);
+                    }
+                }
+            });
+        }
+        overview
+    }
+
+    pub fn is_showing(&self) -> bool {
+        self.showing.load(Ordering::SeqCst)
+    }
+
+    /// Open the overview unless it is up. Returns whether it wasn't.
+    pub fn show(&self) -> bool {
+        if self.showing.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        let _ = self.requests.send(());
+        true
+    }
+
+    /// Open the overview, or close it if it is up.
+    pub fn toggle(&self) -> Result<()> {
+        if !self.show() {
+            let xconn = XConnection::new()?;
+            xconn.send_root_message(xconn.atoms._XPOSE_DISMISS, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Close the overview if it is up, and wait for it to put the windows
+    /// back.
+    fn close(&self) {
+        if !self.is_showing() {
+            return;
+        }
+        let sent = XConnection::new().and_then(|xconn| xconn.send_root_message(xconn.atoms._XPOSE_DISMISS, &[]));
+        if let Err(e) = sent {
+            log::warn!("Cannot close the overview: {}", e);
+            return;
+        }
+        let deadline = Instant::now() + CLOSE_TIMEOUT;
+        while self.is_showing() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Have the overview read the config file again if it is up; otherwise
+    /// the next one reads it anyway.
+    pub fn reload_config(&self) -> Result<()> {
+        if self.is_showing() {
+            let xconn = XConnection::new()?;
+            xconn.send_root_message(xconn.atoms._XPOSE_RELOAD_CONFIG, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Switch to `desktop` (0-indexed), through the overview if it is up.
+    pub fn switch_desktop(&self, desktop: u32) -> Result<()> {
+        if self.is_showing() {
+            let xconn = XConnection::new()?;
+            return xconn.send_root_message(xconn.atoms._NET_CURRENT_DESKTOP, &[desktop]);
+        }
+        control::run(DesktopCommand::Switch(desktop))
+    }
+
+    /// Switch to the next desktop, wrapping around.
+    pub fn next_desktop(&self) -> Result<()> {
+        if self.is_showing() {
+            let xconn = XConnection::new()?;
+            let count = xconn.get_num_desktops()?.unwrap_or(1).max(1);
+            let current = xconn.get_current_desktop()?.unwrap_or(0);
+            return xconn.send_root_message(xconn.atoms._NET_CURRENT_DESKTOP, &[(current + 1) % count]);
+        }
+        control::run(DesktopCommand::Next)
+    }
+
+    /// Move a window (client or frame id) to `desktop` (0-indexed). Fails
+    /// while the overview is up.
+    pub fn move_window(&self, window: u32, desktop: u32) -> Result<()> {
+        if self.is_showing() {
+            return Err(XposeError::Other("Cannot move windows while the overview is up".to_string()));
+        }
+        control::run(DesktopCommand::MoveWindow { window, desktop })
+    }
+}
+
+/// A change for pagers and status bars.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum DesktopEvent {
+    /// Switched to `desktop` (0-indexed)
+    DesktopChanged { desktop: u32 },
+    /// Desktops were added, removed, reordered or renamed
+    DesktopsChanged,
+    /// A window (client id) moved to `desktop` (0-indexed)
+    WindowMoved { window: u32, desktop: u32 },
+    /// The overview highlighted a window, described for screen readers
+    /// (see announce.rs)
+    Highlighted { description: String },
+}
+
+/// Pass each desktop change to `on_event` until the X connection goes away.
+pub fn watch_desktops(mut on_event: impl FnMut(DesktopEvent)) -> Result<()> {
+    let xconn = XConnection::new()?;
+    xconn.conn.change_window_attributes(
+        xconn.root,
+        &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE | EventMask::SUBSTRUCTURE_NOTIFY),
+    )?;
+    xconn.flush()?;
+    let mut current = xconn.get_current_desktop()?;
+    loop {
+        match xconn.conn.wait_for_event()? {
+            Event::PropertyNotify(event) if event.atom == xconn.atoms._NET_CURRENT_DESKTOP => {
+                let desktop = xconn.get_current_desktop()?;
+                if desktop == current {
+                    continue;
+                }
+                current = desktop;
+                if let Some(desktop) = desktop {
+                    on_event(DesktopEvent::DesktopChanged { desktop });
+                }
+            }
+            Event::PropertyNotify(event)
+                if event.atom == xconn.atoms._NET_NUMBER_OF_DESKTOPS || event.atom == xconn.atoms._NET_DESKTOP_NAMES =>
+            {
+                on_event(DesktopEvent::DesktopsChanged);
+            }
+            Event::PropertyNotify(event)
+                if event.atom == xconn.atoms._XPOSE_HIGHLIGHTED && event.state == Property::NEW_VALUE =>
+            {
+                if let Some(description) = xconn.highlighted_description()? {
+                    on_event(DesktopEvent::Highlighted { description });
+                }
+            }
+            Event::ClientMessage(event) => {
+                if let Some((window, desktop)) = xconn.window_desktop_announcement(&event) {
+                    on_event(DesktopEvent::WindowMoved { window, desktop });
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! SIGUSR1 and SIGUSR2, for hotkey daemons: `pkill -USR1 xpose` toggles the
+//! overview and `pkill -USR2 xpose` switches to the next desktop.
+//!
+//! Every xpose process ignores both, so a `pkill` can't kill `track-focus`
+//! or a desktop command. The one that acts on them (an open overview, or
+//! `xpose daemon`/`dbus`, which can also open it) takes them through a
+//! self-pipe it polls alongside the X connection. It also takes SIGTERM, to
+//! close the overview properly before exiting.
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+/// Write end of the pipe, for the handler
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+/// Whether something in this process already listens
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// What a signal asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// SIGUSR1
+    Toggle,
+    /// SIGUSR2
+    NextDesktop,
+    /// SIGTERM
+    Quit,
+}
+
+/// Signals received since the last look.
+pub struct SignalPipe {
+    read: OwnedFd,
+}
+
+/// Ignore SIGUSR1 and SIGUSR2 until something listens for them.
+pub fn ignore() {
+    unsafe {
+        libc::signal(libc::SIGUSR1, libc::SIG_IGN);
+        libc::signal(libc::SIGUSR2, libc::SIG_IGN);
+    }
+}
+
+/// Start taking SIGUSR1, SIGUSR2 and SIGTERM. None if another part of this process
+/// already does, which then handles them.
+pub fn listen() -> Option<SignalPipe> {
+    if LISTENING.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    match open_pipe() {
+        Ok(pipe) => Some(pipe),
+        Err(e) => {
+            log::warn!("Cannot listen for signals: {}", e);
+            None
+        }
+    }
+}
+
+fn open_pipe() -> io::Result<SignalPipe> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let read = unsafe { OwnedFd::from_raw_fd(fds[0]) };
+    WRITE_FD.store(fds[1], Ordering::SeqCst);
+    for signal in [libc::SIGUSR1, libc::SIGUSR2, libc::SIGTERM] {
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(SignalPipe { read })
+}
+
+/// Only writes to the pipe, which is all a signal handler may safely do.
+extern "C" fn on_signal(signal: libc::c_int) {
+    let errno = unsafe { *libc::__errno_location() };
+    let byte = signal as u8;
+    unsafe {
+        libc::write(WRITE_FD.load(Ordering::Relaxed), (&byte as *const u8).cast(), 1);
+        *libc::__errno_location() = errno;
+    }
+}
+
+impl SignalPipe {
+    /// The requests that came in, without waiting.
+    pub fn take(&self) -> Vec<Request> {
+        let mut requests = Vec::new();
+        let mut buf = [0u8; 16];
+        loop {
+            let n = unsafe { libc::read(self.read.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 {
+                break;
+            }
+            requests.extend(buf[..n as usize].iter().filter_map(|&byte| match byte as libc::c_int {
+                libc::SIGUSR1 => Some(Request::Toggle),
+                libc::SIGUSR2 => Some(Request::NextDesktop),
+                libc::SIGTERM => Some(Request::Quit),
+                _ => None,
+            }));
+        }
+        requests
+    }
+
+    /// Wait for requests.
+    pub fn wait(&self) -> Vec<Request> {
+        loop {
+            poll(&[self.read.as_raw_fd()], None);
+            let requests = self.take();
+            if !requests.is_empty() {
+                return requests;
+            }
+        }
+    }
+}
+
+/// Wait until one of `fds` can be read, or `timeout` is up. Returns which
+/// can be; none if a signal broke the wait or it timed out.
+fn poll(fds: &[RawFd], timeout: Option<Duration>) -> Vec<bool> {
+    let mut polled: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&fd| libc::pollfd { fd, events: libc::POLLIN, revents: 0 })
+        .collect();
+    let timeout = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as libc::c_int);
+    if unsafe { libc::poll(polled.as_mut_ptr(), polled.len() as libc::nfds_t, timeout) } < 0 {
+        return vec![false; fds.len()];
+    }
+    polled.iter().map(|p| p.revents != 0).collect()
+}
+
+impl XConnection {
+    /// Like `wait_for_event`, but give up waiting when a signal comes in or
+    /// `timeout` is up (None).
+    pub fn wait_for_event_or_signal(&self, signals: Option<&SignalPipe>, timeout: Option<Duration>) -> Result<Option<Event>> {
+        if signals.is_none() && timeout.is_none() {
+            return Ok(Some(self.conn.wait_for_event()?));
+        }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut fds = vec![self.conn.stream().as_raw_fd()];
+        fds.extend(signals.map(|s| s.read.as_raw_fd()));
+        loop {
+            self.conn.flush()?;
+            if let Some(event) = self.conn.poll_for_event()? {
+                return Ok(Some(event));
+            }
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if left.is_some_and(|left| left.is_zero()) {
+                return Ok(None);
+            }
+            let ready = poll(&fds, left);
+            if ready.get(1) == Some(&true) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_pipe() {
+        let signals = listen().unwrap();
+        assert!(listen().is_none());
+        assert!(signals.take().is_empty());
+        unsafe {
+            libc::raise(libc::SIGUSR2);
+            libc::raise(libc::SIGUSR1);
+        }
+        assert_eq!(signals.take(), [Request::NextDesktop, Request::Toggle]);
+    }
+}
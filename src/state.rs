@@ -1,19 +1,86 @@
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
+use x11rb::protocol::xproto::Window;
 
+use crate::error::{Result, XposeError};
+use crate::monitor::Monitor;
+use crate::paths;
+use crate::schema::{self, Migration};
 use crate::window_finder::WindowInfo;
 
 /// Persistent state for window ordering.
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct WindowState {
+    /// Format version (see schema.rs)
+    #[serde(default)]
+    pub version: u32,
+    /// Saved order per monitor and desktop (see `order_group`), so
+    /// rearranging one screen leaves the others' order alone
+    #[serde(default)]
+    pub orders: HashMap<String, SavedOrder>,
+    /// Frame window ID (as string) -> when it last had the focus, in
+    /// milliseconds since the epoch. Kept by `xpose track-focus` and by
+    /// selections in the overview.
+    #[serde(default)]
+    pub focus_times: HashMap<String, u64>,
+    /// Groups of windows made in the overview, piled up in the grid
+    #[serde(default)]
+    pub groups: Vec<WindowGroup>,
+}
+
+/// Windows dropped on each other in the overview, shown as one pile.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WindowGroup {
+    pub name: String,
+    /// Frame window IDs (as strings), the pile's top first
+    pub members: Vec<String>,
+}
+
+/// The saved order of the windows in one group.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SavedOrder {
     /// WM_CLASS strings in display order
     pub window_order: Vec<String>,
     /// Hash of the sorted window set (to detect changes)
     pub window_set_hash: String,
+    /// Set once the user rearranged the group by dragging thumbnails; its
+    /// windows then fill the grid in this order rather than by where they
+    /// are on screen, and keep it as windows come and go
+    #[serde(default)]
+    pub arranged: bool,
+}
+
+const STATE_FILE: &str = "state.json";
+/// Format changes, oldest first (see schema.rs).
+const MIGRATIONS: &[Migration] = &[
+    // 1: window order kept per monitor and desktop; the single global
+    // order that came before can't be split up, so it goes
+    |state| {
+        state.remove("window_order");
+        state.remove("window_set_hash");
+    },
+];
+const STATE_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Focus times kept at most; the oldest are dropped beyond this, so windows
+/// long gone don't pile up.
+const MAX_FOCUS_TIMES: usize = 256;
+
+impl Default for WindowState {
+    fn default() -> Self {
+        WindowState {
+            version: STATE_VERSION,
+            orders: HashMap::new(),
+            focus_times: HashMap::new(),
+            groups: Vec::new(),
+        }
+    }
 }
 
 impl WindowState {
@@ -28,37 +95,52 @@ impl WindowState {
             return Self::default();
         }
 
-        match fs::read_to_string(&path) {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-            Err(_) => Self::default(),
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        match Self::from_json(&content) {
+            Ok((state, version)) => {
+                if version > STATE_VERSION {
+                    log::warn!("{} is from a newer xpose (format {})", path.display(), version);
+                    schema::keep_copy(&path, &format!("v{}", version));
+                }
+                state
+            }
+            Err(e) => {
+                // Starting over is fine for ordering, but keep the old file
+                log::warn!("Cannot read {}: {}", path.display(), e);
+                schema::keep_copy(&path, "bad");
+                Self::default()
+            }
         }
     }
 
+    /// Parse state JSON of any version, returning the version it had.
+    fn from_json(content: &str) -> std::result::Result<(Self, u32), String> {
+        let mut value: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        let version = schema::upgrade(&mut value, MIGRATIONS)?;
+        let state = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        Ok((state, version))
+    }
+
     /// Save state to file.
-    pub fn save(&self) {
-        let path = match Self::state_path() {
-            Some(p) => p,
-            None => {
-                log::warn!("Cannot determine config directory for state file");
-                return;
-            }
-        };
+    pub fn save(&self) -> Result<()> {
+        let path = Self::state_path()
+            .ok_or_else(|| XposeError::Other("Cannot determine config directory for state file".to_string()))?;
 
         if let Some(parent) = path.parent() {
-            if let Err(e) = fs::create_dir_all(parent) {
-                log::warn!("Cannot create config directory: {}", e);
-                return;
-            }
+            paths::create_private_dir(parent)?;
         }
 
-        match serde_json::to_string_pretty(self) {
-            Ok(content) => {
-                if let Err(e) = fs::write(&path, content) {
-                    log::warn!("Cannot save state: {}", e);
-                }
-            }
-            Err(e) => log::warn!("Cannot serialize state: {}", e),
-        }
+        paths::write_atomic(&path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Keep other xpose processes from changing the state until the lock
+    /// is dropped. Take it before `load` when the state will be saved.
+    pub fn lock() -> Result<paths::StateLock> {
+        paths::lock_state_file(STATE_FILE)
+            .map_err(|e| XposeError::Other(format!("Cannot lock window state: {}", e)))
     }
 
     /// Get a unique key for a window (WM_CLASS + WM_NAME).
@@ -69,8 +151,8 @@ impl WindowState {
     }
 
     /// Compute a hash of the window set (sorted window keys).
-    pub fn compute_hash(windows: &[WindowInfo]) -> String {
-        let mut keys: Vec<String> = windows.iter().map(Self::window_key).collect();
+    fn compute_hash<'a>(windows: impl Iterator<Item = &'a WindowInfo>) -> String {
+        let mut keys: Vec<String> = windows.map(Self::window_key).collect();
         keys.sort();
 
         let mut hasher = DefaultHasher::new();
@@ -78,25 +160,288 @@ impl WindowState {
         format!("{:x}", hasher.finish())
     }
 
-    /// Sort windows according to saved order.
-    /// Windows not in saved order go to the end, preserving their relative order.
-    pub fn sort_windows(&self, windows: &mut [WindowInfo]) {
-        windows.sort_by_key(|w| {
-            let key = Self::window_key(w);
-            self.window_order
-                .iter()
-                .position(|k| k == &key)
-                .unwrap_or(usize::MAX)
-        });
+    /// The group a window's order is kept in: the monitor its center is on
+    /// (see `order_monitor`) and its desktop.
+    pub fn order_group(window: &WindowInfo, monitors: &[Monitor], desktop: u32) -> String {
+        format!("{}/{}", Self::order_monitor(window, monitors), desktop)
+    }
+
+    /// Name of the monitor a window's center is on, the first one if it's
+    /// on none.
+    pub fn order_monitor<'a>(window: &WindowInfo, monitors: &'a [Monitor]) -> &'a str {
+        let cx = window.x as i32 + window.width as i32 / 2;
+        let cy = window.y as i32 + window.height as i32 / 2;
+        let monitor = monitors.iter().find(|m| m.contains(cx, cy)).or(monitors.first());
+        monitor.map_or("", |m| m.name.as_str())
+    }
+
+    /// The grids the user arranged, for `LayoutConfig::arranged`: monitor
+    /// name -> frames of `windows` (already in their saved order) in
+    /// arranged groups there. `desktop` gives each window's desktop.
+    pub fn arranged_grids(
+        &self,
+        windows: &[WindowInfo],
+        monitors: &[Monitor],
+        desktop: impl Fn(&WindowInfo) -> u32,
+    ) -> HashMap<String, Vec<Window>> {
+        let mut grids: HashMap<String, Vec<Window>> = HashMap::new();
+        for window in windows {
+            let group = Self::order_group(window, monitors, desktop(window));
+            if self.orders.get(&group).is_some_and(|saved| saved.arranged) {
+                grids
+                    .entry(Self::order_monitor(window, monitors).to_string())
+                    .or_default()
+                    .push(window.frame_window);
+            }
+        }
+        grids
+    }
+
+    /// Keep `windows`, a group's members, in the order given from now on.
+    pub fn arrange(&mut self, group: String, windows: &[&WindowInfo]) {
+        let window_order = windows.iter().map(|w| Self::window_key(w)).collect();
+        let window_set_hash = Self::compute_hash(windows.iter().copied());
+        self.orders.insert(group, SavedOrder { window_order, window_set_hash, arranged: true });
+    }
+
+    /// Save the order the user arranged a group in, on top of whatever is
+    /// saved.
+    pub fn save_arrangement(group: String, windows: &[&WindowInfo]) -> Result<()> {
+        let _lock = Self::lock()?;
+        let mut state = Self::load();
+        state.arrange(group, windows);
+        state.save()
+    }
+
+    /// Sort `windows` into the order saved for each group, as given by
+    /// `group`. Groups whose window set changed keep the order they're in,
+    /// which becomes their saved order. Returns whether any did, so the
+    /// state needs saving.
+    pub fn restore_order(&mut self, windows: &mut [WindowInfo], group: impl Fn(&WindowInfo) -> String) -> bool {
+        let mut members: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, window) in windows.iter().enumerate() {
+            members.entry(group(window)).or_default().push(i);
+        }
+
+        // Position of each window within its group; groups are laid out
+        // separately, so how they interleave doesn't matter
+        let mut rank = vec![usize::MAX; windows.len()];
+        let mut changed = false;
+        for (key, indices) in members {
+            let hash = Self::compute_hash(indices.iter().map(|&i| &windows[i]));
+            match self.orders.get(&key) {
+                Some(saved) if saved.window_set_hash == hash => {
+                    log::debug!("Restoring saved window order for {}", key);
+                    for &i in &indices {
+                        let window_key = Self::window_key(&windows[i]);
+                        rank[i] = saved.window_order.iter().position(|k| k == &window_key).unwrap_or(usize::MAX);
+                    }
+                }
+                Some(saved) if saved.arranged => {
+                    // The user's order stands; new windows go last
+                    log::debug!("Window set of {} changed, keeping its arrangement", key);
+                    let mut arranged = indices.clone();
+                    arranged.sort_by_key(|&i| {
+                        let window_key = Self::window_key(&windows[i]);
+                        saved.window_order.iter().position(|k| k == &window_key).unwrap_or(usize::MAX)
+                    });
+                    for (n, &i) in arranged.iter().enumerate() {
+                        rank[i] = n;
+                    }
+                    let members: Vec<&WindowInfo> = arranged.iter().map(|&i| &windows[i]).collect();
+                    self.arrange(key, &members);
+                    changed = true;
+                }
+                _ => {
+                    log::debug!("Window set of {} changed, using fresh layout", key);
+                    for (n, &i) in indices.iter().enumerate() {
+                        rank[i] = n;
+                    }
+                    let window_order = indices.iter().map(|&i| Self::window_key(&windows[i])).collect();
+                    self.orders.insert(key, SavedOrder { window_order, window_set_hash: hash, arranged: false });
+                    changed = true;
+                }
+            }
+        }
+
+        let mut order: Vec<usize> = (0..windows.len()).collect();
+        order.sort_by_key(|&i| rank[i]);
+        let sorted: Vec<WindowInfo> = order.iter().map(|&i| windows[i].clone()).collect();
+        windows.clone_from_slice(&sorted);
+        changed
+    }
+
+    /// Note that `frame` got the focus at `time` (ms since the epoch).
+    pub fn record_focus(&mut self, frame: Window, time: u64) {
+        self.focus_times.insert(frame.to_string(), time);
+        if self.focus_times.len() > MAX_FOCUS_TIMES {
+            let mut times: Vec<u64> = self.focus_times.values().copied().collect();
+            times.sort_unstable_by(|a, b| b.cmp(a));
+            let cutoff = times[MAX_FOCUS_TIMES - 1];
+            self.focus_times.retain(|_, t| *t >= cutoff);
+        }
+    }
+
+    /// Record that `frame` has the focus now, on top of whatever is saved
+    /// (the tracker and the overview both write the file).
+    pub fn save_focus(frame: Window) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let _lock = Self::lock()?;
+        let mut state = Self::load();
+        state.record_focus(frame, now);
+        state.save()
+    }
+
+    /// The group `frame` is in, if any.
+    pub fn group_of(&self, frame: Window) -> Option<&WindowGroup> {
+        let key = frame.to_string();
+        self.groups.iter().find(|g| g.members.contains(&key))
+    }
+
+    /// Put `dragged` in the group of `onto`, or in a new group with it named
+    /// `name` (numbered if taken). Returns the group's name.
+    pub fn group_windows(&mut self, onto: Window, dragged: Window, name: &str) -> String {
+        self.ungroup(dragged);
+        let (onto, dragged) = (onto.to_string(), dragged.to_string());
+        if let Some(group) = self.groups.iter_mut().find(|g| g.members.contains(&onto)) {
+            group.members.push(dragged);
+            return group.name.clone();
+        }
+        let taken = |name: &str| self.groups.iter().any(|g| g.name == name);
+        let name = (1..)
+            .map(|n| if n == 1 { name.to_string() } else { format!("{} {}", name, n) })
+            .find(|name| !taken(name))
+            .unwrap_or_default();
+        self.groups.push(WindowGroup { name: name.clone(), members: vec![onto, dragged] });
+        name
+    }
+
+    /// Take `frame` out of its group; a group left with one window goes.
+    pub fn ungroup(&mut self, frame: Window) {
+        let key = frame.to_string();
+        for group in &mut self.groups {
+            group.members.retain(|k| k != &key);
+        }
+        self.groups.retain(|g| g.members.len() > 1);
+    }
+
+    /// Forget windows that are gone from their groups. Returns whether any
+    /// were.
+    pub fn prune_groups(&mut self, live: &[Window]) -> bool {
+        let live: Vec<String> = live.iter().map(|frame| frame.to_string()).collect();
+        let before = self.groups.clone();
+        for group in &mut self.groups {
+            group.members.retain(|k| live.contains(k));
+        }
+        self.groups.retain(|g| g.members.len() > 1);
+        self.groups != before
+    }
+
+    /// Save the groups, on top of whatever else is saved.
+    pub fn save_groups(groups: &[WindowGroup]) -> Result<()> {
+        let _lock = Self::lock()?;
+        let mut state = Self::load();
+        state.groups = groups.to_vec();
+        state.save()
+    }
+
+    /// When `window` last had the focus, if it ever did.
+    pub fn last_focus(&self, window: &WindowInfo) -> Option<u64> {
+        self.focus_times.get(&window.frame_window.to_string()).copied()
     }
 
-    /// Update state from current windows.
-    pub fn update_from_windows(&mut self, windows: &[WindowInfo]) {
-        self.window_order = windows.iter().map(Self::window_key).collect();
-        self.window_set_hash = Self::compute_hash(windows);
+    /// Indices of `windows`, most recently focused first. Windows never
+    /// focused keep their relative order at the end.
+    pub fn recent_first(&self, windows: &[WindowInfo]) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..windows.len()).collect();
+        indices.sort_by_key(|&i| std::cmp::Reverse(self.last_focus(&windows[i])));
+        indices
     }
 
     fn state_path() -> Option<PathBuf> {
-        Some(PathBuf::from("/tmp/xpose/state.json"))
+        Some(paths::state_file(STATE_FILE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(frame: Window) -> WindowInfo {
+        WindowInfo { client_window: frame + 1, ..WindowInfo::for_test(frame, None, Some(&frame.to_string())) }
+    }
+
+    #[test]
+    fn test_order_per_group() {
+        let group = |w: &WindowInfo| if w.frame_window < 100 { "left/0" } else { "right/0" }.to_string();
+        let frames = |windows: &[WindowInfo]| windows.iter().map(|w| w.frame_window).collect::<Vec<_>>();
+        let mut state = WindowState::default();
+        let mut windows = vec![window(1), window(101), window(2), window(102)];
+        assert!(state.restore_order(&mut windows, group));
+
+        // The right monitor's windows came in another order and one was added
+        let mut windows = vec![window(2), window(103), window(102), window(1), window(101)];
+        assert!(state.restore_order(&mut windows, group));
+        let left: Vec<Window> = frames(&windows).into_iter().filter(|&f| f < 100).collect();
+        assert_eq!(left, vec![1, 2]);
+        let right: Vec<Window> = frames(&windows).into_iter().filter(|&f| f >= 100).collect();
+        assert_eq!(right, vec![103, 102, 101]);
+        assert!(!state.restore_order(&mut windows, group));
+    }
+
+    #[test]
+    fn test_arrangement_survives_changes() {
+        let group = |_: &WindowInfo| "screen/0".to_string();
+        let frames = |windows: &[WindowInfo]| windows.iter().map(|w| w.frame_window).collect::<Vec<_>>();
+        let mut state = WindowState::default();
+        state.arrange("screen/0".to_string(), &[&window(3), &window(1), &window(2)]);
+
+        // A window closed and another opened; the rest keep their places
+        let mut windows = vec![window(1), window(4), window(3)];
+        assert!(state.restore_order(&mut windows, group));
+        assert_eq!(frames(&windows), vec![3, 1, 4]);
+        assert!(state.orders["screen/0"].arranged);
+
+        let monitors = [Monitor::whole_screen(1920, 1080)];
+        let grids = state.arranged_grids(&windows, &monitors, |_| 0);
+        assert_eq!(grids["screen"], vec![3, 1, 4]);
+        assert!(state.arranged_grids(&windows, &monitors, |_| 1).is_empty());
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut state = WindowState::default();
+        assert_eq!(state.group_windows(1, 2, "XTerm"), "XTerm");
+        assert_eq!(state.group_windows(2, 3, "XTerm"), "XTerm");
+        assert_eq!(state.group_windows(4, 5, "XTerm"), "XTerm 2");
+        assert_eq!(state.group_of(3).unwrap().members, ["1", "2", "3"]);
+
+        // Moving a window to another group, and a group running out
+        state.group_windows(4, 1, "XTerm");
+        state.ungroup(5);
+        assert_eq!(state.group_of(1).unwrap().members, ["4", "1"]);
+        assert!(state.group_of(5).is_none());
+
+        assert!(state.prune_groups(&[1, 2, 4]));
+        assert_eq!(state.groups.len(), 1);
+        assert!(!state.prune_groups(&[1, 2, 4]));
+    }
+
+    #[test]
+    fn test_recent_first() {
+        let windows = [window(10), window(20), window(30), window(40)];
+        let mut state = WindowState::default();
+        state.record_focus(30, 1000);
+        state.record_focus(10, 2000);
+        assert_eq!(state.recent_first(&windows), vec![0, 2, 1, 3]);
+        assert_eq!(state.last_focus(&windows[1]), None);
+
+        for frame in 0..MAX_FOCUS_TIMES as Window + 10 {
+            state.record_focus(frame + 100, 3000 + frame as u64);
+        }
+        assert_eq!(state.focus_times.len(), MAX_FOCUS_TIMES);
+        assert_eq!(state.last_focus(&windows[0]), None);
     }
 }
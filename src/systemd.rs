@@ -0,0 +1,73 @@
+//! Running `xpose daemon --systemd` as a systemd user unit (see
+//! contrib/systemd): the control socket may come from socket activation, and
+//! readiness and shutdown are reported over `$NOTIFY_SOCKET`.
+
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram, UnixListener};
+
+/// The first file descriptor systemd passes.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// The socket systemd opened for us, if it started us for one.
+pub fn activated_listener() -> Option<UnixListener> {
+    let fd = activated_fd(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )?;
+    // Not for the overview or anything else started from here
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    Some(unsafe { UnixListener::from_raw_fd(fd) })
+}
+
+/// The passed socket, if the variables are meant for process `pid` and
+/// pass one. Only the first is used.
+fn activated_fd(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<RawFd> {
+    if listen_pid?.parse::<u32>().ok()? != pid {
+        return None;
+    }
+    match listen_fds?.parse::<u32>().ok()? {
+        0 => None,
+        1 => Some(LISTEN_FDS_START),
+        n => {
+            log::warn!("Given {} sockets, using the first", n);
+            Some(LISTEN_FDS_START)
+        }
+    }
+}
+
+/// Tell systemd `state` (e.g. "READY=1"), if it is listening.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy();
+    let address = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(path.as_ref()),
+    };
+    let sent = address.and_then(|address| UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address));
+    if let Err(e) = sent {
+        log::warn!("Cannot notify systemd ({}): {}", state, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activated_fd() {
+        assert_eq!(activated_fd(Some("42"), Some("1"), 42), Some(3));
+        assert_eq!(activated_fd(Some("42"), Some("2"), 42), Some(3));
+        // Meant for another process, e.g. inherited from our parent
+        assert_eq!(activated_fd(Some("41"), Some("1"), 42), None);
+        assert_eq!(activated_fd(Some("42"), Some("0"), 42), None);
+        assert_eq!(activated_fd(None, Some("1"), 42), None);
+        assert_eq!(activated_fd(Some("42"), Some("x"), 42), None);
+    }
+}
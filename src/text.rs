@@ -0,0 +1,281 @@
+//! Text rendering with a core font: "fixed", or the theme's larger one (see
+//! theme.rs).
+//!
+//! Labels sit on a semi-transparent pill whose colors are picked from the
+//! brightness of whatever is underneath (window contents or wallpaper), so
+//! they stay readable on both dark and bright backgrounds. A theme can fix
+//! the colors instead, on an opaque pill.
+
+use x11rb::protocol::render::{self, Color, PictOp};
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::renderer::OverviewWindow;
+use crate::theme::LabelFont;
+
+/// Corner radius of label pills.
+const PILL_RADIUS: u16 = 8;
+
+/// Text and backing colors chosen for a background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LabelColors {
+    /// Text color (0xRRGGBB)
+    pub text: u32,
+    /// Pill color (0xRRGGBB), drawn with `pill_alpha`
+    pub pill: u32,
+    pub pill_alpha: f64,
+}
+
+/// Approximate luminance (0.0-1.0) of an 0xRRGGBB color (Rec. 709 weights).
+pub fn luminance(rgb: u32) -> f64 {
+    let channel = |shift: u32| ((rgb >> shift) & 0xFF) as f64 / 255.0;
+    0.2126 * channel(16) + 0.7152 * channel(8) + 0.0722 * channel(0)
+}
+
+/// Dark text on a light pill for bright backgrounds, light text on a dark
+/// pill otherwise.
+pub fn colors_for_background(luminance: f64) -> LabelColors {
+    if luminance > 0.55 {
+        LabelColors {
+            text: 0x11_11_11,
+            pill: 0xF5_F5_F5,
+            pill_alpha: 0.75,
+        }
+    } else {
+        LabelColors {
+            text: 0xFF_FF_FF,
+            pill: 0x10_10_10,
+            pill_alpha: 0.65,
+        }
+    }
+}
+
+/// Convert text to the font's Latin-1 encoding, cut to `max_chars`
+/// (with "..." when truncated). Characters outside Latin-1 become '?'.
+pub fn encode_label(text: &str, max_chars: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = text
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+        .collect();
+    if bytes.len() > max_chars {
+        bytes.truncate(max_chars.saturating_sub(3));
+        bytes.extend_from_slice(b"...");
+    }
+    bytes
+}
+
+/// Size of the pill `draw_text_box` draws for `lines`.
+pub fn text_box_size(font: &LabelFont, lines: &[Vec<u8>], padding_h: u16, padding_v: u16) -> (u16, u16) {
+    let widest = lines.iter().map(|line| font.text_width(line)).max().unwrap_or(0);
+    (widest + padding_h * 2, (lines.len() as u16).saturating_mul(font.line_height) + padding_v * 2)
+}
+
+impl XConnection {
+    /// Mean luminance of a region of the overview back buffer.
+    /// The region is clipped to the buffer; an empty region counts as dark.
+    pub fn sample_luminance(
+        &self,
+        overview: &OverviewWindow,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<f64> {
+        let x0 = (x as i32).clamp(0, overview.width as i32);
+        let y0 = (y as i32).clamp(0, overview.height as i32);
+        let x1 = (x as i32 + width as i32).clamp(0, overview.width as i32);
+        let y1 = (y as i32 + height as i32).clamp(0, overview.height as i32);
+        if x1 <= x0 || y1 <= y0 {
+            return Ok(0.0);
+        }
+
+        let image = self.get_image_rgba(
+            overview.pixmap.id(),
+            x0 as i16,
+            y0 as i16,
+            (x1 - x0) as u16,
+            (y1 - y0) as u16,
+        )?;
+        let pixels = image.data.len() / 4;
+        let total: f64 = image
+            .data
+            .chunks_exact(4)
+            .map(|px| luminance((px[0] as u32) << 16 | (px[1] as u32) << 8 | px[2] as u32))
+            .sum();
+        Ok(total / pixels.max(1) as f64)
+    }
+
+    /// Colors for a label over a region of the overview back buffer: the
+    /// theme's, else picked to contrast with what is there.
+    pub fn label_colors(
+        &self,
+        overview: &OverviewWindow,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<LabelColors> {
+        Ok(match overview.theme.labels {
+            Some((text, pill)) => LabelColors { text, pill, pill_alpha: 1.0 },
+            None => colors_for_background(self.sample_luminance(overview, x, y, width, height)?),
+        })
+    }
+
+    /// Draw a label with its top-left corner at (x, y): text on a rounded,
+    /// semi-transparent pill in colors that contrast with the background
+    /// (picked once for where it is, see `OverviewWindow::forget_label_colors`).
+    pub fn draw_label(
+        &self,
+        overview: &OverviewWindow,
+        x: i16,
+        y: i16,
+        text: &[u8],
+        padding_h: u16,
+        padding_v: u16,
+    ) -> Result<()> {
+        let font = &overview.theme.font;
+        let width = font.text_width(text) + padding_h * 2;
+        let height = font.line_height + padding_v * 2;
+        let colors = overview.cached_label_colors((x, y, width, height), || {
+            self.label_colors(overview, x, y, width, height)
+        })?;
+        self.draw_label_in(overview, x, y, text, padding_h, padding_v, colors)
+    }
+
+    /// Draw a label as `draw_label` does, in `colors`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_label_in(
+        &self,
+        overview: &OverviewWindow,
+        x: i16,
+        y: i16,
+        text: &[u8],
+        padding_h: u16,
+        padding_v: u16,
+        colors: LabelColors,
+    ) -> Result<()> {
+        let font = &overview.theme.font;
+        let width = font.text_width(text) + padding_h * 2;
+        let height = font.line_height + padding_v * 2;
+        self.fill_pill(overview, x, y, width, height, colors.pill, colors.pill_alpha)?;
+        self.draw_text(overview, x + padding_h as i16, y + padding_v as i16, text, colors.text)
+    }
+
+    /// Draw lines of text one under another on a single pill with its
+    /// top-left corner at (x, y), in the colors `draw_label` would pick.
+    pub fn draw_text_box(
+        &self,
+        overview: &OverviewWindow,
+        x: i16,
+        y: i16,
+        lines: &[Vec<u8>],
+        padding_h: u16,
+        padding_v: u16,
+    ) -> Result<()> {
+        let font = &overview.theme.font;
+        let (width, height) = text_box_size(font, lines, padding_h, padding_v);
+        let colors = overview.cached_label_colors((x, y, width, height), || {
+            self.label_colors(overview, x, y, width, height)
+        })?;
+        self.fill_pill(overview, x, y, width, height, colors.pill, colors.pill_alpha)?;
+        for (row, line) in lines.iter().enumerate() {
+            let top = y + padding_v as i16 + (row as u16 * font.line_height) as i16;
+            self.draw_text(overview, x + padding_h as i16, top, line, colors.text)?;
+        }
+        Ok(())
+    }
+
+    /// Draw text without a background, top of the line at (x, y).
+    pub fn draw_text(&self, overview: &OverviewWindow, x: i16, y: i16, text: &[u8], color: u32) -> Result<()> {
+        self.conn.change_gc(
+            overview.gc.id(),
+            &ChangeGCAux::new().foreground(color).font(overview.font),
+        )?;
+
+        // PolyText8 items: length byte, delta byte, then up to 254 characters
+        let mut items = Vec::with_capacity(text.len() + 2 * (text.len() / 254 + 1));
+        for chunk in text.chunks(254) {
+            items.push(chunk.len() as u8);
+            items.push(0);
+            items.extend_from_slice(chunk);
+        }
+        self.conn
+            .poly_text8(overview.pixmap.id(), overview.gc.id(), x, y + overview.theme.font.ascent, &items)?;
+        Ok(())
+    }
+
+    /// Fill a rounded rectangle with a translucent color. Rows are emitted
+    /// as non-overlapping rectangles so the alpha stays uniform.
+    #[allow(clippy::too_many_arguments)]
+    fn fill_pill(
+        &self,
+        overview: &OverviewWindow,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        rgb: u32,
+        alpha: f64,
+    ) -> Result<()> {
+        let radius = PILL_RADIUS.min(width / 2).min(height / 2);
+        let mut rects = Vec::with_capacity(radius as usize * 2 + 1);
+
+        for row in 0..radius {
+            let dy = radius as f64 - row as f64 - 0.5;
+            let inset = (radius as f64 - (radius as f64 * radius as f64 - dy * dy).max(0.0).sqrt()).round() as u16;
+            let row_width = width.saturating_sub(inset * 2);
+            rects.push(Rectangle {
+                x: x + inset as i16,
+                y: y + row as i16,
+                width: row_width,
+                height: 1,
+            });
+            rects.push(Rectangle {
+                x: x + inset as i16,
+                y: y + (height - 1 - row) as i16,
+                width: row_width,
+                height: 1,
+            });
+        }
+        rects.push(Rectangle {
+            x,
+            y: y + radius as i16,
+            width,
+            height: height - radius * 2,
+        });
+
+        // Render colors are premultiplied 16-bit
+        let a = (alpha.clamp(0.0, 1.0) * 65535.0) as u32;
+        let premultiply = |shift: u32| ((((rgb >> shift) & 0xFF) * 257 * a) / 65535) as u16;
+        let color = Color {
+            red: premultiply(16),
+            green: premultiply(8),
+            blue: premultiply(0),
+            alpha: a as u16,
+        };
+        render::fill_rectangles(&self.conn, PictOp::OVER, overview.picture.id(), color, &rects)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contrast_choice() {
+        assert_eq!(colors_for_background(luminance(0xFF_FF_FF)).text, 0x11_11_11);
+        assert_eq!(colors_for_background(luminance(0x00_00_00)).text, 0xFF_FF_FF);
+        // Saturated yellow is bright, saturated blue is dark
+        assert_eq!(colors_for_background(luminance(0xFF_FF_00)).text, 0x11_11_11);
+        assert_eq!(colors_for_background(luminance(0x00_00_FF)).text, 0xFF_FF_FF);
+    }
+
+    #[test]
+    fn test_encode_label() {
+        assert_eq!(encode_label("caf\u{e9}", 50), b"caf\xe9".to_vec());
+        assert_eq!(encode_label("\u{2014}", 50), b"?".to_vec());
+        assert_eq!(encode_label("abcdefgh", 6), b"abc...".to_vec());
+    }
+}
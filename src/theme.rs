@@ -0,0 +1,123 @@
+//! Built-in looks for the overview (`Theme default` or `Theme
+//! high-contrast`).
+//!
+//! A theme gives the colors frames, previews and labels are drawn in, the
+//! width of the frames and the core font of the labels. The high-contrast
+//! theme draws thick white frames (yellow when highlighted) on black, with
+//! no wallpaper behind, and white labels on solid black in the 10x20 font.
+//! Frames and font grow together, so a label still fits the frame it sits
+//! on; label boxes follow the font's size (see text.rs).
+
+/// A core font and its cell size. Both fonts used are fixed-width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LabelFont {
+    pub name: &'static str,
+    pub char_width: u16,
+    pub line_height: u16,
+    /// Baseline offset from the top of a line
+    pub ascent: i16,
+}
+
+/// "fixed", which every server has.
+pub const FIXED_FONT: LabelFont = LabelFont { name: "fixed", char_width: 6, line_height: 13, ascent: 11 };
+/// "10x20", from the same misc-fixed family at about twice the size.
+pub const LARGE_FONT: LabelFont = LabelFont { name: "10x20", char_width: 10, line_height: 20, ascent: 16 };
+
+impl LabelFont {
+    /// Width in pixels of encoded text.
+    pub fn text_width(&self, bytes: &[u8]) -> u16 {
+        (bytes.len() as u16).saturating_mul(self.char_width)
+    }
+}
+
+/// Which built-in theme to draw with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThemeKind {
+    #[default]
+    Default,
+    HighContrast,
+}
+
+impl ThemeKind {
+    /// Parse a config value: default or high-contrast.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "default" => Some(Self::Default),
+            "high-contrast" => Some(Self::HighContrast),
+            _ => None,
+        }
+    }
+
+    pub fn theme(self) -> Theme {
+        match self {
+            Self::Default => Theme {
+                background: 0x1a_1a_1a,
+                preview: 0x2a_2a_2a,
+                current_preview: 0x3a_3a_3a,
+                border: 0x44_44_44,
+                highlight: 0x44_88_FF,
+                border_width: 3,
+                preview_border_width: 2,
+                wallpapers: true,
+                font: FIXED_FONT,
+                labels: None,
+            },
+            Self::HighContrast => Theme {
+                background: 0x00_00_00,
+                preview: 0x00_00_00,
+                current_preview: 0x33_33_33,
+                border: 0xFF_FF_FF,
+                highlight: 0xFF_FF_00,
+                border_width: 6,
+                preview_border_width: 4,
+                wallpapers: false,
+                font: LARGE_FONT,
+                labels: Some((0xFF_FF_FF, 0x00_00_00)),
+            },
+        }
+    }
+}
+
+/// Colors (0xRRGGBB) and sizes the renderer draws with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Behind the grid where no wallpaper shows, and behind the desktop bar
+    pub background: u32,
+    /// Desktop previews without a wallpaper, and the current one
+    pub preview: u32,
+    pub current_preview: u32,
+    /// Frames of thumbnails and previews, and of the highlighted one
+    pub border: u32,
+    pub highlight: u32,
+    pub border_width: i16,
+    pub preview_border_width: i16,
+    /// Whether the root background and desktop wallpapers show behind the
+    /// grid and in the previews
+    pub wallpapers: bool,
+    pub font: LabelFont,
+    /// Text and backing color of labels, opaque; None picks them from
+    /// what is underneath
+    pub labels: Option<(u32, u32)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        ThemeKind::Default.theme()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_high_contrast_scales_together() {
+        assert_eq!(ThemeKind::parse("High-Contrast"), Some(ThemeKind::HighContrast));
+        assert_eq!(ThemeKind::parse("dark"), None);
+        let (normal, high) = (ThemeKind::Default.theme(), ThemeKind::HighContrast.theme());
+        // Twice the frame for a font about twice the size
+        assert_eq!(high.border_width, 2 * normal.border_width);
+        assert!(high.font.line_height >= normal.font.line_height * 3 / 2);
+        assert_eq!(high.font.text_width(b"abc"), 30);
+    }
+}
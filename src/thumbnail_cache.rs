@@ -0,0 +1,131 @@
+//! Persistent thumbnail cache in the `thumbs` directory of the cache dir.
+//!
+//! On exit a downscaled PNG of every live capture is written, keyed by frame
+//! id and WM_CLASS. On the next launch, windows that were unmapped (other
+//! desktops) show their cached image until the first damage event, instead of
+//! the black contents a freshly mapped window has before it repaints.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::capture::CapturedWindow;
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::image::RgbaImage;
+use crate::layout::ThumbnailLayout;
+use crate::paths;
+use crate::window_finder::WindowInfo;
+
+/// Longest side of a cached snapshot in pixels.
+const CACHE_MAX_SIZE: u16 = 480;
+
+/// Cache file for a window: `<frame id>-<WM_CLASS>.png`.
+fn cache_path(info: &WindowInfo) -> PathBuf {
+    let class: String = info
+        .wm_class
+        .as_deref()
+        .unwrap_or("")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    cache_dir().join(format!("{:08x}-{}.png", info.frame_window, class))
+}
+
+fn cache_dir() -> PathBuf {
+    paths::cache_dir().join("thumbs")
+}
+
+/// Fit `width`x`height` inside CACHE_MAX_SIZE, keeping the aspect ratio.
+fn cached_size(width: u16, height: u16) -> (u16, u16) {
+    let longest = width.max(height).max(1);
+    if longest <= CACHE_MAX_SIZE {
+        return (width.max(1), height.max(1));
+    }
+    let scale = CACHE_MAX_SIZE as f64 / longest as f64;
+    (
+        ((width as f64 * scale) as u16).max(1),
+        ((height as f64 * scale) as u16).max(1),
+    )
+}
+
+impl XConnection {
+    /// Write snapshots for the captures in `live` and drop cache files for
+    /// windows that no longer exist. Errors are logged, not returned.
+    pub fn save_thumbnail_cache(&self, captures: &[CapturedWindow], live: &HashSet<usize>) {
+        if let Err(e) = paths::create_private_dir(&cache_dir()) {
+            log::warn!("Cannot create thumbnail cache directory: {}", e);
+            return;
+        }
+
+        for &idx in live {
+            if let Some(capture) = captures.get(idx) {
+                if let Err(e) = self.save_cached_thumbnail(capture) {
+                    log::debug!("Failed to cache thumbnail for {:?}: {}", capture.info.wm_name, e);
+                }
+            }
+        }
+
+        // Prune snapshots of windows that are gone
+        let known: HashSet<PathBuf> = captures.iter().map(|c| cache_path(&c.info)).collect();
+        if let Ok(entries) = fs::read_dir(cache_dir()) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if !known.contains(&path) {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+    }
+
+    /// Downscale a capture on the server and write it as PNG.
+    fn save_cached_thumbnail(&self, capture: &CapturedWindow) -> Result<()> {
+        let (width, height) = cached_size(capture.info.width, capture.info.height);
+        let pixmap = self.create_owned_pixmap(self.root, width, height)?;
+        let picture = self.create_owned_picture(pixmap.id())?;
+
+        let layout = ThumbnailLayout {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            window_index: 0,
+        };
+        self.render_thumbnail(capture.picture.id(), picture.id(), capture.info.width, capture.info.height, &layout)?;
+        let image = self.get_image_rgba(pixmap.id(), 0, 0, width, height)?;
+        image.save_png(&cache_path(&capture.info))
+    }
+
+    /// Replace a capture's contents with its cached snapshot, scaled up to
+    /// the window size. Returns false if there is no snapshot for the window.
+    /// The next refresh_capture swaps the live contents back in.
+    pub fn apply_cached_thumbnail(&self, capture: &mut CapturedWindow) -> Result<bool> {
+        let cached = match RgbaImage::load(&cache_path(&capture.info)) {
+            Ok(image) => image,
+            Err(_) => return Ok(false),
+        };
+
+        // Upload the snapshot
+        let (small_pixmap, small_picture) = self.upload_image(&cached)?;
+
+        // Scale it to the window size so it renders like a live capture
+        let (width, height) = (capture.info.width.max(1), capture.info.height.max(1));
+        let pixmap = self.create_owned_pixmap(self.root, width, height)?;
+        let picture = self.create_owned_picture(pixmap.id())?;
+        let layout = ThumbnailLayout {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            window_index: 0,
+        };
+        self.render_thumbnail(small_picture.id(), picture.id(), cached.width, cached.height, &layout)?;
+        drop(small_picture);
+        drop(small_pixmap);
+
+        self.replace_capture_contents(capture, pixmap, picture);
+
+        log::debug!("Using cached thumbnail for {:?}", capture.info.wm_name);
+        Ok(true)
+    }
+}
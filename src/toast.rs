@@ -0,0 +1,133 @@
+//! Transient error notifications shown in a corner of the overview.
+//!
+//! Non-fatal failures (a window that can't be captured, a desktop move or
+//! state save that didn't go through) are reported with
+//! `XConnection::report_error`, and the odd confirmation (a copied title)
+//! with `XConnection::notify`. The message is logged and queued here; the
+//! renderer draws the visible toasts on every present, and each one fades out
+//! once its time is up.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::connection::XConnection;
+
+/// How long a toast stays on screen, including the fade-out.
+pub const TOAST_DURATION: Duration = Duration::from_millis(4000);
+/// Length of the fade-out at the end of `TOAST_DURATION`.
+pub const TOAST_FADE: Duration = Duration::from_millis(500);
+/// Toasts shown at once; the rest wait their turn.
+pub const MAX_VISIBLE_TOASTS: usize = 3;
+/// Longest message shown, in characters.
+pub const MAX_TOAST_CHARS: usize = 80;
+
+#[derive(Debug)]
+struct Toast {
+    message: String,
+    /// When the toast was first drawn (None while still queued)
+    shown_at: Option<Instant>,
+}
+
+/// FIFO of pending and visible toasts.
+#[derive(Debug, Default)]
+pub struct ToastQueue {
+    toasts: VecDeque<Toast>,
+}
+
+impl ToastQueue {
+    /// Queue a message. A message that is already queued or showing is not
+    /// repeated, so a failure hit on every frame shows up once.
+    pub fn push(&mut self, message: String) {
+        if self.toasts.iter().any(|t| t.message == message) {
+            return;
+        }
+        self.toasts.push_back(Toast {
+            message,
+            shown_at: None,
+        });
+    }
+
+    /// Whether anything is showing or waiting to be shown.
+    pub fn is_active(&self) -> bool {
+        !self.toasts.is_empty()
+    }
+
+    /// Drop expired toasts and return the ones to draw at `now`, oldest first,
+    /// with their opacity (1.0 until the fade-out starts).
+    pub fn visible(&mut self, now: Instant) -> Vec<(String, f64)> {
+        self.toasts.retain(|t| match t.shown_at {
+            Some(shown) => now.duration_since(shown) < TOAST_DURATION,
+            None => true,
+        });
+
+        self.toasts
+            .iter_mut()
+            .take(MAX_VISIBLE_TOASTS)
+            .map(|t| {
+                let shown = *t.shown_at.get_or_insert(now);
+                let remaining = TOAST_DURATION.saturating_sub(now.duration_since(shown));
+                let opacity = (remaining.as_secs_f64() / TOAST_FADE.as_secs_f64()).min(1.0);
+                (t.message.clone(), opacity)
+            })
+            .collect()
+    }
+}
+
+impl XConnection {
+    /// Report a non-fatal error: log it and show it as a toast.
+    pub fn report_error(&self, message: impl Into<String>) {
+        let message = message.into();
+        log::warn!("{}", message);
+        self.toasts.borrow_mut().push(message);
+    }
+
+    /// Tell the user something went through, the same way.
+    pub fn notify(&self, message: impl Into<String>) {
+        let message = message.into();
+        log::info!("{}", message);
+        self.toasts.borrow_mut().push(message);
+    }
+
+    /// Whether toasts are on screen or queued, so the event loop keeps
+    /// presenting frames for the fade-out.
+    pub fn toasts_active(&self) -> bool {
+        self.toasts.borrow().is_active()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toast_lifecycle() {
+        let start = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push("a".to_string());
+        queue.push("a".to_string());
+        queue.push("b".to_string());
+        assert_eq!(queue.visible(start).len(), 2);
+
+        // Fading during the last TOAST_FADE, gone afterwards
+        let fading = queue.visible(start + TOAST_DURATION - TOAST_FADE / 2);
+        assert!((fading[0].1 - 0.5).abs() < 1e-6);
+        assert!(queue.visible(start + TOAST_DURATION).is_empty());
+        assert!(!queue.is_active());
+    }
+
+    #[test]
+    fn test_toast_overflow_waits() {
+        let start = Instant::now();
+        let mut queue = ToastQueue::default();
+        for i in 0..MAX_VISIBLE_TOASTS + 1 {
+            queue.push(format!("error {}", i));
+        }
+        assert_eq!(queue.visible(start).len(), MAX_VISIBLE_TOASTS);
+
+        // The waiting toast gets a full duration from when it appears
+        let later = start + TOAST_DURATION;
+        let shown = queue.visible(later);
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0], (format!("error {}", MAX_VISIBLE_TOASTS), 1.0));
+    }
+}
@@ -0,0 +1,104 @@
+//! Details of the window under the pointer (`TooltipMs`).
+//!
+//! Once the pointer has rested on a thumbnail for a moment, a tooltip under
+//! it gives what the caption can't: the full title, WM_CLASS, the window's
+//! geometry, its desktop and the PID of its process, from `_NET_WM_PID` when
+//! the client sets it. Windows whose titles are cut to the same caption can
+//! be told apart by it.
+
+use x11rb::protocol::xproto::*;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::layout::ThumbnailLayout;
+use crate::renderer::OverviewWindow;
+use crate::text::{encode_label, text_box_size};
+use crate::window_finder::WindowInfo;
+
+/// Longest line shown; titles longer than this are cut.
+const MAX_TOOLTIP_CHARS: usize = 100;
+const TOOLTIP_PADDING_H: u16 = 8;
+const TOOLTIP_PADDING_V: u16 = 6;
+/// Space between a thumbnail and its tooltip.
+const TOOLTIP_GAP: i16 = 8;
+
+impl XConnection {
+    /// The process id a client gives in `_NET_WM_PID`, if any.
+    pub fn window_pid(&self, client: Window) -> Result<Option<u32>> {
+        let reply = self
+            .conn
+            .get_property(false, client, self.atoms._NET_WM_PID, AtomEnum::CARDINAL, 0, 1)?
+            .reply()?;
+        Ok(reply.value32().and_then(|mut v| v.next()))
+    }
+
+    /// Draw a tooltip of `lines` below a thumbnail, or above it where the
+    /// screen ends first.
+    pub fn draw_tooltip(&self, overview: &OverviewWindow, layout: &ThumbnailLayout, lines: &[String]) -> Result<()> {
+        let lines: Vec<Vec<u8>> = lines.iter().map(|line| encode_label(line, MAX_TOOLTIP_CHARS)).collect();
+        let size = text_box_size(&overview.theme.font, &lines, TOOLTIP_PADDING_H, TOOLTIP_PADDING_V);
+        let (x, y) = tooltip_position(layout, size, (overview.width, overview.height));
+        self.draw_text_box(overview, x, y, &lines, TOOLTIP_PADDING_H, TOOLTIP_PADDING_V)
+    }
+}
+
+/// What a tooltip says about a window on `desktop` (its name, or how it is
+/// shown on every desktop).
+pub fn tooltip_lines(info: &WindowInfo, desktop: &str, pid: Option<u32>) -> Vec<String> {
+    // WM_CLASS is "instance Class"
+    let class = match info.wm_class.as_deref().map(|class| class.split_once(' ').unwrap_or((class, class))) {
+        Some((instance, class)) if instance != class => format!("{} ({})", class, instance),
+        Some((_, class)) => class.to_string(),
+        None => "none".to_string(),
+    };
+    vec![
+        info.wm_name.clone().unwrap_or_else(|| "Untitled window".to_string()),
+        format!("Class: {}", class),
+        format!("Geometry: {}x{}+{}+{}", info.width, info.height, info.x, info.y),
+        format!("Desktop: {}", desktop),
+        format!("PID: {}", pid.map_or_else(|| "unknown".to_string(), |pid| pid.to_string())),
+    ]
+}
+
+/// Top-left corner of a tooltip of `size` for a thumbnail: centred under
+/// it, above it if there is no room below, and kept on the screen.
+fn tooltip_position(layout: &ThumbnailLayout, size: (u16, u16), screen: (u16, u16)) -> (i16, i16) {
+    let (width, height) = (size.0 as i32, size.1 as i32);
+    let (screen_width, screen_height) = (screen.0 as i32, screen.1 as i32);
+    let x = layout.x as i32 + (layout.width as i32 - width) / 2;
+    let below = layout.y as i32 + layout.height as i32 + TOOLTIP_GAP as i32;
+    let above = layout.y as i32 - TOOLTIP_GAP as i32 - height;
+    let y = if below + height <= screen_height || above < 0 { below } else { above };
+    (
+        x.min(screen_width - width).max(0) as i16,
+        y.min(screen_height - height).max(0) as i16,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tooltip() {
+        let info = WindowInfo {
+            x: 10,
+            y: 20,
+            width: 800,
+            height: 600,
+            ..WindowInfo::for_test(1, Some("Navigator firefox"), Some("Inbox"))
+        };
+        assert_eq!(
+            tooltip_lines(&info, "Web", Some(4242)),
+            ["Inbox", "Class: firefox (Navigator)", "Geometry: 800x600+10+20", "Desktop: Web", "PID: 4242"]
+        );
+        let bare = WindowInfo { wm_class: None, wm_name: None, ..info };
+        assert_eq!(tooltip_lines(&bare, "1", None)[1], "Class: none");
+
+        let layout = |x, y| ThumbnailLayout { x, y, width: 200, height: 100, window_index: 0 };
+        // Centred under the thumbnail
+        assert_eq!(tooltip_position(&layout(100, 100), (100, 50), (1000, 800)), (150, 208));
+        // Above it at the bottom of the screen, and kept off the left edge
+        assert_eq!(tooltip_position(&layout(-60, 680), (100, 50), (1000, 800)), (0, 622));
+    }
+}
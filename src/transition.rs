@@ -0,0 +1,86 @@
+//! Slide transition for desktop switches made outside the overview.
+//!
+//! `xpose switch`, `next` and `prev` cover the screen with a snapshot of the
+//! old desktop before switching, then slide the snapshot off-screen to reveal
+//! the new desktop underneath. Only a root-window copy and one
+//! override-redirect window are involved, so it needs neither a compositor
+//! nor the overview's captures.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::*;
+
+use crate::animation::FramePacer;
+use crate::connection::XConnection;
+use crate::error::Result;
+
+/// Which way the old desktop leaves the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlideDirection {
+    Left,
+    Right,
+}
+
+impl XConnection {
+    /// Cover the screen with a window showing what it shows right now.
+    pub fn cover_screen(&self) -> Result<Window> {
+        let (width, height) = (self.screen_width, self.screen_height);
+        let snapshot = self.create_owned_pixmap(self.root, width, height)?;
+        let gc = self.create_owned_gc(
+            self.root,
+            &CreateGCAux::new().subwindow_mode(SubwindowMode::INCLUDE_INFERIORS),
+        )?;
+        self.conn
+            .copy_area(self.root, snapshot.id(), gc.id(), 0, 0, 0, 0, width, height)?;
+
+        let window = self.generate_id()?;
+        self.conn.create_window(
+            self.root_depth,
+            window,
+            self.root,
+            0,
+            0,
+            width,
+            height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            self.root_visual,
+            &CreateWindowAux::new()
+                .background_pixmap(snapshot.id())
+                .override_redirect(1),
+        )?;
+        self.conn.map_window(window)?;
+        // The window keeps its background; the pixmap can go
+        drop(snapshot);
+        self.sync()?;
+        Ok(window)
+    }
+
+    /// Slide a cover window off-screen over `duration`, then destroy it.
+    pub fn slide_away(&self, cover: Window, direction: SlideDirection, duration: Duration) -> Result<()> {
+        let distance = self.screen_width as f64;
+        let sign = match direction {
+            SlideDirection::Left => -1.0,
+            SlideDirection::Right => 1.0,
+        };
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Duration::from_millis(16));
+        loop {
+            let t = (start.elapsed().as_secs_f64() / duration.as_secs_f64().max(0.001)).min(1.0);
+            // Ease-in cubic: the old desktop starts slowly and speeds away
+            let eased = t * t * t;
+            self.conn.configure_window(
+                cover,
+                &ConfigureWindowAux::new().x((sign * distance * eased) as i32),
+            )?;
+            self.sync()?;
+            if t >= 1.0 {
+                break;
+            }
+            thread::sleep(pacer.frame_wait());
+        }
+        self.conn.destroy_window(cover)?;
+        self.flush()
+    }
+}
@@ -0,0 +1,125 @@
+//! Per-desktop wallpapers.
+//!
+//! `Wallpaper N PATH` lines in the config give desktops their own image. The
+//! images are uploaded once per overview session and drawn into the desktop
+//! bar previews; when the overview exits on a different desktop than it
+//! started on, that desktop's image becomes the root window background.
+//! Desktops without a wallpaper keep whatever background the root has.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+use crate::image::RgbaImage;
+use crate::layout::ThumbnailLayout;
+use crate::resources::{OwnedPicture, OwnedPixmap};
+
+/// A wallpaper image uploaded to the server at its native size, freed when
+/// dropped.
+#[derive(Debug)]
+pub struct Wallpaper {
+    /// Drawn through `picture`; only held to be freed with it
+    pub _pixmap: OwnedPixmap,
+    pub picture: OwnedPicture,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl XConnection {
+    /// Decode and upload the configured wallpapers. Images that can't be
+    /// loaded are reported and left out.
+    pub fn load_wallpapers(&self, paths: &HashMap<u32, PathBuf>) -> HashMap<u32, Wallpaper> {
+        let mut wallpapers = HashMap::new();
+        for (&desktop, path) in paths {
+            let uploaded = RgbaImage::load(path).and_then(|image| {
+                let (pixmap, picture) = self.upload_image(&image)?;
+                Ok(Wallpaper {
+                    _pixmap: pixmap,
+                    picture,
+                    width: image.width,
+                    height: image.height,
+                })
+            });
+            match uploaded {
+                Ok(wallpaper) => {
+                    log::info!("Loaded wallpaper for desktop {}: {}", desktop, path.display());
+                    wallpapers.insert(desktop, wallpaper);
+                }
+                Err(e) => self.report_error(format!("Cannot load wallpaper {}: {}", path.display(), e)),
+            }
+        }
+        wallpapers
+    }
+
+    /// Make this connection's pixmap the root background the way wallpaper
+    /// setters do: publish it in `_XROOTPMAP_ID`/`ESETROOT_PMAP_ID` so
+    /// compositors and pseudo-transparent terminals find it, and free the
+    /// pixmap a previous setter left behind.
+    fn install_root_pixmap(&self, pixmap: Pixmap) -> Result<()> {
+        if let (Some(old), Some(current)) = (
+            self.get_root_pixmap_property(self.atoms.ESETROOT_PMAP_ID)?,
+            self.get_root_pixmap_property(self.atoms._XROOTPMAP_ID)?,
+        ) {
+            // The old pixmap was kept alive with RetainPermanent by its
+            // setter; killing that client's resources frees it
+            if old == current {
+                let _ = self.conn.kill_client(old);
+            }
+        }
+
+        for atom in [self.atoms._XROOTPMAP_ID, self.atoms.ESETROOT_PMAP_ID] {
+            self.conn
+                .change_property32(PropMode::REPLACE, self.root, atom, AtomEnum::PIXMAP, &[pixmap])?;
+        }
+        self.conn.change_window_attributes(
+            self.root,
+            &ChangeWindowAttributesAux::new().background_pixmap(pixmap),
+        )?;
+        self.conn.clear_area(false, self.root, 0, 0, 0, 0)?;
+        Ok(())
+    }
+
+    fn get_root_pixmap_property(&self, atom: Atom) -> Result<Option<Pixmap>> {
+        let reply = self
+            .conn
+            .get_property(false, self.root, atom, AtomEnum::PIXMAP, 0, 1)?
+            .reply()?;
+        Ok(reply.value32().and_then(|mut v| v.next()).filter(|&id| id != 0))
+    }
+}
+
+/// Set an image, stretched to the screen, as the root window background.
+///
+/// Uses a connection of its own that is closed with RetainPermanent, so the
+/// pixmap outlives xpose without keeping the overview's resources around.
+pub fn set_root_wallpaper(path: &Path) -> Result<()> {
+    let image = RgbaImage::load(path)?;
+    let xconn = XConnection::new()?;
+    let (width, height) = (xconn.screen_width, xconn.screen_height);
+
+    let pixmap = xconn.create_owned_pixmap(xconn.root, width, height)?;
+    {
+        // Everything but the final pixmap is freed before the close-down
+        // mode changes, or it would be retained too
+        let (_source_pixmap, source) = xconn.upload_image(&image)?;
+        let picture = xconn.create_owned_picture(pixmap.id())?;
+        let layout = ThumbnailLayout {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            window_index: 0,
+        };
+        xconn.render_thumbnail(source.id(), picture.id(), image.width, image.height, &layout)?;
+    }
+
+    xconn.install_root_pixmap(pixmap.into_raw())?;
+    xconn.conn.set_close_down_mode(CloseDown::RETAIN_PERMANENT)?;
+    xconn.sync()?;
+    log::info!("Root background set to {}", path.display());
+    Ok(())
+}
@@ -1,8 +1,13 @@
+use std::rc::Rc;
+
+use regex::Regex;
 use x11rb::connection::Connection;
+use x11rb::cookie::Cookie;
 use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
 
 use crate::connection::XConnection;
-use crate::error::Result;
+use crate::error::{Result, XposeError};
 
 #[derive(Debug, Clone)]
 pub struct WindowInfo {
@@ -16,6 +21,87 @@ pub struct WindowInfo {
     pub wm_name: Option<String>,
     /// Whether the window was mapped (visible) when discovered
     pub is_mapped: bool,
+    /// Whether the window asks for attention (ICCCM urgency hint or
+    /// `_NET_WM_STATE_DEMANDS_ATTENTION`) when discovered
+    pub urgent: bool,
+}
+
+impl WindowInfo {
+    /// Whether the window manager reparented the client into a frame.
+    /// Non-reparenting WMs (dwm, i3) leave clients as direct children of
+    /// root, so the client is its own frame and must only be configured once.
+    pub fn is_reparented(&self) -> bool {
+        self.client_window != self.frame_window
+    }
+
+    /// A mapped 100x100 window at the origin, its own frame, for tests to
+    /// adjust.
+    #[cfg(test)]
+    pub fn for_test(frame: Window, class: Option<&str>, title: Option<&str>) -> Self {
+        WindowInfo {
+            client_window: frame,
+            frame_window: frame,
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+            wm_class: class.map(str::to_string),
+            wm_name: title.map(str::to_string),
+            is_mapped: true,
+            urgent: false,
+        }
+    }
+}
+
+/// Managed windows the config keeps out of the grid. They are treated like
+/// skipped windows: left in place and faded back in on exit.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WindowFilter<'a> {
+    /// WM_CLASS instance or class names, compared case-insensitively
+    pub exclude_classes: &'a [String],
+    /// Patterns searched for in WM_NAME
+    pub exclude_titles: &'a [Regex],
+    /// When this or `include_titles` is non-empty, only windows matching one
+    /// of the include lists are shown
+    pub include_classes: &'a [String],
+    pub include_titles: &'a [Regex],
+}
+
+impl WindowFilter<'_> {
+    /// Whether a managed window should be left out of the grid.
+    pub fn excludes(&self, info: &WindowInfo) -> bool {
+        let include_only = !self.include_classes.is_empty() || !self.include_titles.is_empty();
+        if include_only && !class_matches(info, self.include_classes) && !title_matches(info, self.include_titles) {
+            log::debug!("Excluding window not in include list: {:?} ({:?})", info.wm_name, info.wm_class);
+            return true;
+        }
+        if class_matches(info, self.exclude_classes) {
+            log::debug!("Excluding window by class: {:?} ({:?})", info.wm_name, info.wm_class);
+            return true;
+        }
+        if title_matches(info, self.exclude_titles) {
+            log::debug!("Excluding window by title: {:?}", info.wm_name);
+            return true;
+        }
+        false
+    }
+}
+
+/// Whether any part of WM_CLASS is one of `classes`.
+fn class_matches(info: &WindowInfo, classes: &[String]) -> bool {
+    // WM_CLASS contains "instance class" (e.g. "xpad xpad" or "org.gnome.Boxes Org.gnome.Boxes")
+    info.wm_class.as_ref().is_some_and(|class| {
+        classes
+            .iter()
+            .any(|name| class.split_whitespace().any(|part| part.eq_ignore_ascii_case(name)))
+    })
+}
+
+/// Whether WM_NAME matches any of `patterns`.
+fn title_matches(info: &WindowInfo, patterns: &[Regex]) -> bool {
+    info.wm_name
+        .as_deref()
+        .is_some_and(|title| patterns.iter().any(|re| re.is_match(title)))
 }
 
 /// Result of examining a frame window.
@@ -28,44 +114,107 @@ enum ExamineResult {
     Ignored,
 }
 
+/// In-flight requests for the client properties discovery looks at.
+struct ClientPropertyCookies<'c> {
+    wm_state: Cookie<'c, Rc<RustConnection>, GetPropertyReply>,
+    wm_class: Cookie<'c, Rc<RustConnection>, GetPropertyReply>,
+    wm_name: Cookie<'c, Rc<RustConnection>, GetPropertyReply>,
+    window_type: Cookie<'c, Rc<RustConnection>, GetPropertyReply>,
+    wm_state_hints: Cookie<'c, Rc<RustConnection>, GetPropertyReply>,
+    transient_for: Cookie<'c, Rc<RustConnection>, GetPropertyReply>,
+    wm_hints: Cookie<'c, Rc<RustConnection>, GetPropertyReply>,
+}
+
+/// Client properties used to identify and filter a window.
+struct ClientProperties {
+    /// ICCCM WM_STATE
+    wm_state: GetPropertyReply,
+    wm_class: GetPropertyReply,
+    wm_name: GetPropertyReply,
+    /// _NET_WM_WINDOW_TYPE
+    window_type: GetPropertyReply,
+    /// _NET_WM_STATE
+    wm_state_hints: GetPropertyReply,
+    transient_for: GetPropertyReply,
+    /// ICCCM WM_HINTS (only the flags)
+    wm_hints: GetPropertyReply,
+}
+
+impl ClientPropertyCookies<'_> {
+    fn reply(self) -> Result<ClientProperties> {
+        Ok(ClientProperties {
+            wm_state: self.wm_state.reply()?,
+            wm_class: self.wm_class.reply()?,
+            wm_name: self.wm_name.reply()?,
+            window_type: self.window_type.reply()?,
+            wm_state_hints: self.wm_state_hints.reply()?,
+            transient_for: self.transient_for.reply()?,
+            wm_hints: self.wm_hints.reply()?,
+        })
+    }
+}
+
+/// Check if WM_STATE says Withdrawn (should be ignored).
+/// WM_STATE values: WithdrawnState=0, NormalState=1, IconicState=3
+fn is_withdrawn(reply: &GetPropertyReply) -> bool {
+    if reply.value_len == 0 {
+        return false;
+    }
+    // First value is the state
+    reply.value32().and_then(|mut v| v.next()) == Some(0)
+}
+
+/// WM_CLASS (instance and class names) as "instance class".
+fn wm_class_string(reply: &GetPropertyReply) -> Option<String> {
+    if reply.type_ == u32::from(AtomEnum::NONE) || reply.value.is_empty() {
+        return None;
+    }
+
+    // WM_CLASS is null-separated: "instance\0class\0"
+    let value = String::from_utf8_lossy(&reply.value);
+    Some(value.replace('\0', " ").trim().to_string())
+}
+
+/// WM_NAME (window title).
+fn wm_name_string(reply: &GetPropertyReply) -> Option<String> {
+    if reply.type_ == u32::from(AtomEnum::NONE) || reply.value.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&reply.value).to_string())
+}
+
+/// Check if WM_HINTS has the urgency flag set.
+fn has_urgency_hint(reply: &GetPropertyReply) -> bool {
+    // UrgencyHint is bit 8 of the flags, the first value
+    const URGENCY_HINT: u32 = 1 << 8;
+    reply.value32().and_then(|mut v| v.next()).is_some_and(|flags| flags & URGENCY_HINT != 0)
+}
+
+/// Check if WM_TRANSIENT_FOR is set (dialog).
+fn is_transient(reply: &GetPropertyReply) -> bool {
+    reply.type_ != u32::from(AtomEnum::NONE) && !reply.value.is_empty()
+}
+
 impl XConnection {
     /// Enumerate all visible application windows.
     /// Returns (managed_windows, skipped_windows, original_stacking_order).
     /// Skipped windows are visible but filtered out (docks, panels, etc.) - used for fade effect.
     /// original_stacking_order contains the frame window IDs of managed windows in their
     /// original X11 stacking order (bottom-to-top), used to restore Z-order on exit.
-    /// `exclude_classes` is a list of WM_CLASS values to exclude from the exposé view.
-    #[allow(dead_code)]
+    /// Windows matching `filter` are returned as skipped.
     pub fn find_windows(
         &self,
-        exclude_classes: &[String],
+        filter: WindowFilter,
     ) -> Result<(Vec<WindowInfo>, Vec<WindowInfo>, Vec<Window>)> {
         let mut windows = Vec::new();
         let mut skipped = Vec::new();
         let mut original_stacking_order = Vec::new();
 
-        // Get all children of root (these are TWM frame windows)
-        // tree.children is in X11 stacking order (bottom-to-top)
-        let tree = self.conn.query_tree(self.root)?.reply()?;
-
-        for frame_window in tree.children {
-            match self.examine_frame(frame_window) {
-                Ok(ExamineResult::Managed(info)) => {
-                    // Check if this window's class is in the exclude list
-                    // WM_CLASS contains "instance class" (e.g. "xpad xpad" or "org.gnome.Boxes Org.gnome.Boxes")
-                    let is_excluded = info.wm_class.as_ref().map_or(false, |class| {
-                        exclude_classes.iter().any(|exc| {
-                            // Check if any part of WM_CLASS matches the exclude pattern
-                            class.split_whitespace().any(|part| part.eq_ignore_ascii_case(exc))
-                        })
-                    });
-
-                    if is_excluded {
-                        log::debug!(
-                            "Excluding window by class: {:?} ({:?})",
-                            info.wm_name,
-                            info.wm_class
-                        );
+        // Frame windows in X11 stacking order (bottom-to-top)
+        for result in self.examine_toplevels(false)? {
+            match result {
+                ExamineResult::Managed(info) => {
+                    if filter.excludes(&info) {
                         skipped.push(info);
                     } else {
                         log::debug!(
@@ -83,7 +232,7 @@ impl XConnection {
                         windows.push(info);
                     }
                 }
-                Ok(ExamineResult::Skipped(info)) => {
+                ExamineResult::Skipped(info) => {
                     log::debug!(
                         "Skipped visible window: {:?} at {}x{}+{}+{}",
                         info.wm_name,
@@ -94,11 +243,7 @@ impl XConnection {
                     );
                     skipped.push(info);
                 }
-                Ok(ExamineResult::Ignored) => {}
-                Err(e) => {
-                    // Window may have been destroyed, skip it
-                    log::debug!("Error examining frame 0x{:x}: {}", frame_window, e);
-                }
+                ExamineResult::Ignored => {}
             }
         }
 
@@ -130,30 +275,16 @@ impl XConnection {
     /// Windows have is_mapped set to indicate their visibility state.
     pub fn find_all_windows(
         &self,
-        exclude_classes: &[String],
+        filter: WindowFilter,
     ) -> Result<(Vec<WindowInfo>, Vec<WindowInfo>, Vec<Window>)> {
         let mut windows = Vec::new();
         let mut skipped = Vec::new();
         let mut original_stacking_order = Vec::new();
 
-        // Get all children of root (these are TWM frame windows)
-        let tree = self.conn.query_tree(self.root)?.reply()?;
-
-        for frame_window in tree.children {
-            match self.examine_frame_including_unmapped(frame_window) {
-                Ok(ExamineResult::Managed(info)) => {
-                    let is_excluded = info.wm_class.as_ref().map_or(false, |class| {
-                        exclude_classes.iter().any(|exc| {
-                            class.split_whitespace().any(|part| part.eq_ignore_ascii_case(exc))
-                        })
-                    });
-
-                    if is_excluded {
-                        log::debug!(
-                            "Excluding window by class: {:?} ({:?})",
-                            info.wm_name,
-                            info.wm_class
-                        );
+        for result in self.examine_toplevels(true)? {
+            match result {
+                ExamineResult::Managed(info) => {
+                    if filter.excludes(&info) {
                         skipped.push(info);
                     } else {
                         log::debug!(
@@ -171,7 +302,7 @@ impl XConnection {
                         windows.push(info);
                     }
                 }
-                Ok(ExamineResult::Skipped(info)) => {
+                ExamineResult::Skipped(info) => {
                     log::debug!(
                         "Skipped visible window: {:?} at {}x{}+{}+{}",
                         info.wm_name,
@@ -182,10 +313,7 @@ impl XConnection {
                     );
                     skipped.push(info);
                 }
-                Ok(ExamineResult::Ignored) => {}
-                Err(e) => {
-                    log::debug!("Error examining frame 0x{:x}: {}", frame_window, e);
-                }
+                ExamineResult::Ignored => {}
             }
         }
 
@@ -194,92 +322,179 @@ impl XConnection {
             windows.len(),
             skipped.len()
         );
+        if !windows.is_empty() && !windows.iter().any(WindowInfo::is_reparented) {
+            log::info!("Window manager does not reparent, using client windows as frames");
+        }
 
         Ok((windows, skipped, original_stacking_order))
     }
 
-    /// Examine a potential frame window to find the client window inside.
-    /// Applies EWMH-based filtering to exclude non-application windows.
-    #[allow(dead_code)]
-    fn examine_frame(&self, frame: Window) -> Result<ExamineResult> {
-        // Get frame attributes
-        let attrs = self.conn.get_window_attributes(frame)?.reply()?;
-
-        // Skip override-redirect windows (menus, tooltips, popups)
-        if attrs.override_redirect {
-            return Ok(ExamineResult::Ignored);
+    /// Top-level windows to examine, bottom-to-top, with their client window
+    /// when it is already known.
+    /// On EWMH window managers this comes from _NET_CLIENT_LIST_STACKING,
+    /// which lists only managed clients in the WM's own stacking order;
+    /// otherwise all children of root are examined (TWM frame windows) and
+    /// their clients are found by searching for WM_STATE.
+    fn toplevel_windows(&self) -> Result<Vec<(Window, Option<Window>)>> {
+        if let Some(clients) = self.client_list_stacking()? {
+            log::debug!("Using _NET_CLIENT_LIST_STACKING ({} clients)", clients.len());
+            let frames = self.frames_of(&clients)?;
+            return Ok(clients
+                .into_iter()
+                .zip(frames)
+                .filter_map(|(client, frame)| frame.map(|frame| (frame, Some(client))))
+                .collect());
         }
 
-        // Skip unmapped windows
-        if attrs.map_state != MapState::VIEWABLE {
-            return Ok(ExamineResult::Ignored);
+        let tree = self.conn.query_tree(self.root)?.reply()?;
+        Ok(tree.children.into_iter().map(|frame| (frame, None)).collect())
+    }
+
+    /// Walk up from each client window to its top-level ancestor (the WM
+    /// frame, or the client itself on non-reparenting window managers).
+    /// All clients climb one level per round trip. None for clients that
+    /// were destroyed since the WM updated its list.
+    fn frames_of(&self, clients: &[Window]) -> Result<Vec<Option<Window>>> {
+        let mut frames = vec![None; clients.len()];
+        // (client index, window to look up the parent of)
+        let mut pending: Vec<(usize, Window)> = clients.iter().copied().enumerate().collect();
+
+        while !pending.is_empty() {
+            let cookies = pending
+                .iter()
+                .map(|&(_, window)| self.conn.query_tree(window))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            let mut next = Vec::new();
+            for ((i, window), cookie) in pending.into_iter().zip(cookies) {
+                match cookie.reply() {
+                    Ok(tree) if tree.parent == self.root || tree.parent == x11rb::NONE => {
+                        frames[i] = Some(window);
+                    }
+                    Ok(tree) => next.push((i, tree.parent)),
+                    Err(e) => log::debug!("Error finding frame of 0x{:x}: {}", clients[i], e),
+                }
+            }
+            pending = next;
         }
 
-        // Get frame geometry
-        let geom = self.conn.get_geometry(frame)?.reply()?;
+        Ok(frames)
+    }
 
-        // Skip tiny windows (1x1 placeholders used by some apps)
-        if geom.width <= 1 || geom.height <= 1 {
-            return Ok(ExamineResult::Ignored);
+    /// Whether a window still exists on the server.
+    pub fn window_exists(&self, window: Window) -> Result<bool> {
+        match self.conn.get_window_attributes(window)?.reply() {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let e = XposeError::from(e);
+                if e.is_window_gone() {
+                    Ok(false)
+                } else {
+                    Err(e)
+                }
+            }
         }
+    }
 
-        // Find client window with WM_STATE property
-        if let Some(client) = self.find_client_window(frame)? {
-            let wm_class = self.get_wm_class(client).ok().flatten();
-            let wm_name = self.get_wm_name(client).ok().flatten();
-
-            let info = WindowInfo {
-                client_window: client,
-                frame_window: frame,
-                x: geom.x,
-                y: geom.y,
-                width: geom.width,
-                height: geom.height,
-                wm_class,
-                wm_name,
-                is_mapped: attrs.map_state == MapState::VIEWABLE,
+    /// Examine every top-level window, in stacking order (bottom-to-top).
+    /// With `include_unmapped`, unmapped windows are kept (for virtual desktop
+    /// support) and withdrawn clients dropped instead.
+    ///
+    /// Requests go out in batches - attributes and geometry for all frames,
+    /// then the client search, then all client properties - and replies are
+    /// collected afterwards, so discovery costs a handful of round trips
+    /// instead of several per window. A window that vanishes part way
+    /// through is skipped.
+    fn examine_toplevels(&self, include_unmapped: bool) -> Result<Vec<ExamineResult>> {
+        let toplevels = self.toplevel_windows()?;
+
+        let frame_cookies = toplevels
+            .iter()
+            .map(|&(frame, _)| {
+                Ok((
+                    self.conn.get_window_attributes(frame)?,
+                    self.conn.get_geometry(frame)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Frames that are worth looking into, with their attributes and geometry
+        let mut candidates = Vec::new();
+        for (&(frame, known_client), (attrs_cookie, geom_cookie)) in toplevels.iter().zip(frame_cookies) {
+            let (attrs, geom) = match (attrs_cookie.reply(), geom_cookie.reply()) {
+                (Ok(attrs), Ok(geom)) => (attrs, geom),
+                (Err(e), _) | (_, Err(e)) => {
+                    // Window may have been destroyed, skip it
+                    log::debug!("Error examining frame 0x{:x}: {}", frame, e);
+                    continue;
+                }
             };
 
-            // Apply EWMH-based filtering on the client window
-            if self.should_skip_window(client)? {
-                // This is a visible window but filtered by EWMH - track it for fade effect
-                return Ok(ExamineResult::Skipped(info));
+            // Skip override-redirect windows (menus, tooltips, popups)
+            if attrs.override_redirect {
+                continue;
             }
 
-            return Ok(ExamineResult::Managed(info));
-        }
-
-        Ok(ExamineResult::Ignored)
-    }
+            // Skip unmapped windows
+            if !include_unmapped && attrs.map_state != MapState::VIEWABLE {
+                continue;
+            }
 
-    /// Examine a potential frame window including unmapped ones.
-    /// Similar to examine_frame but doesn't skip unmapped windows.
-    fn examine_frame_including_unmapped(&self, frame: Window) -> Result<ExamineResult> {
-        // Get frame attributes
-        let attrs = self.conn.get_window_attributes(frame)?.reply()?;
+            // Skip tiny windows (1x1 placeholders used by some apps)
+            if geom.width <= 1 || geom.height <= 1 {
+                continue;
+            }
 
-        // Skip override-redirect windows (menus, tooltips, popups)
-        if attrs.override_redirect {
-            return Ok(ExamineResult::Ignored);
+            candidates.push((frame, known_client, attrs, geom));
         }
 
-        // Get frame geometry
-        let geom = self.conn.get_geometry(frame)?.reply()?;
-
-        // Skip tiny windows (1x1 placeholders used by some apps)
-        if geom.width <= 1 || geom.height <= 1 {
-            return Ok(ExamineResult::Ignored);
+        // Find client windows with the WM_STATE property where the WM didn't tell us
+        let unknown: Vec<Window> = candidates
+            .iter()
+            .filter(|(_, known_client, _, _)| known_client.is_none())
+            .map(|&(frame, _, _, _)| frame)
+            .collect();
+        let mut found = self.find_client_windows(&unknown)?.into_iter();
+        let clients: Vec<Option<Window>> = candidates
+            .iter()
+            .map(|(_, known_client, _, _)| known_client.or_else(|| found.next().flatten()))
+            .collect();
+        // The ones the WM told us about are worth remembering too
+        let mut cache = self.clients.borrow_mut();
+        for &(frame, known_client, _, _) in &candidates {
+            if let Some(client) = known_client {
+                cache.insert(frame, client);
+            }
         }
+        drop(cache);
+
+        let property_cookies = clients
+            .iter()
+            .map(|client| client.map(|client| self.request_client_properties(client)).transpose())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut results = Vec::with_capacity(candidates.len());
+        for ((frame, _, attrs, geom), (client, cookies)) in
+            candidates.into_iter().zip(clients.into_iter().zip(property_cookies))
+        {
+            let (Some(client), Some(cookies)) = (client, cookies) else {
+                results.push(ExamineResult::Ignored);
+                continue;
+            };
+            let props = match cookies.reply() {
+                Ok(props) => props,
+                Err(e) => {
+                    log::debug!("Error examining frame 0x{:x}: {}", frame, e);
+                    continue;
+                }
+            };
 
-        // Find client window with WM_STATE property
-        if let Some(client) = self.find_client_window(frame)? {
             // Skip windows in Withdrawn state (dead/closed windows not yet destroyed)
-            if self.is_withdrawn(client) {
+            if include_unmapped && is_withdrawn(&props.wm_state) {
                 log::debug!("Skipping withdrawn window: frame=0x{:x}", frame);
-                return Ok(ExamineResult::Ignored);
+                results.push(ExamineResult::Ignored);
+                continue;
             }
-            let wm_class = self.get_wm_class(client).ok().flatten();
-            let wm_name = self.get_wm_name(client).ok().flatten();
 
             let info = WindowInfo {
                 client_window: client,
@@ -288,25 +503,129 @@ impl XConnection {
                 y: geom.y,
                 width: geom.width,
                 height: geom.height,
-                wm_class,
-                wm_name,
+                wm_class: wm_class_string(&props.wm_class),
+                wm_name: wm_name_string(&props.wm_name),
                 is_mapped: attrs.map_state == MapState::VIEWABLE,
+                urgent: has_urgency_hint(&props.wm_hints)
+                    || self.has_state(&props.wm_state_hints, self.atoms._NET_WM_STATE_DEMANDS_ATTENTION),
             };
 
             // Apply EWMH-based filtering on the client window
-            if self.should_skip_window(client)? {
-                return Ok(ExamineResult::Skipped(info));
+            if self.should_skip_window(client, &props) {
+                // This is a visible window but filtered by EWMH - track it for fade effect
+                results.push(ExamineResult::Skipped(info));
+            } else {
+                results.push(ExamineResult::Managed(info));
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Breadth-first search below each frame for a window with WM_STATE,
+    /// one tree level of all frames per round trip. Frames searched before
+    /// are answered from the cache (see clients.rs).
+    fn find_client_windows(&self, frames: &[Window]) -> Result<Vec<Option<Window>>> {
+        let mut clients: Vec<Option<Window>> = {
+            let cache = self.clients.borrow();
+            frames.iter().map(|&frame| cache.get(frame)).collect()
+        };
+        // (frame index, window to check)
+        let mut pending: Vec<(usize, Window)> = frames
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(i, _)| clients[i].is_none())
+            .collect();
+
+        while !pending.is_empty() {
+            let cookies = pending
+                .iter()
+                .map(|&(_, window)| {
+                    self.conn
+                        .get_property(false, window, self.atoms.WM_STATE, AtomEnum::ANY, 0, 0)
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            let mut unresolved = Vec::new();
+            for ((i, window), cookie) in pending.into_iter().zip(cookies) {
+                if clients[i].is_some() {
+                    continue;
+                }
+                match cookie.reply() {
+                    Ok(reply) if reply.type_ != u32::from(AtomEnum::NONE) => clients[i] = Some(window),
+                    Ok(_) => unresolved.push((i, window)),
+                    Err(e) => log::debug!("Error searching 0x{:x} for a client: {}", window, e),
+                }
             }
 
-            return Ok(ExamineResult::Managed(info));
+            // Search the children of windows without WM_STATE
+            unresolved.retain(|&(i, _)| clients[i].is_none());
+            let tree_cookies = unresolved
+                .iter()
+                .map(|&(_, window)| self.conn.query_tree(window))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            pending = Vec::new();
+            for ((i, window), cookie) in unresolved.into_iter().zip(tree_cookies) {
+                match cookie.reply() {
+                    Ok(tree) => pending.extend(tree.children.into_iter().map(|child| (i, child))),
+                    Err(e) => log::debug!("Error searching 0x{:x} for a client: {}", window, e),
+                }
+            }
         }
 
-        Ok(ExamineResult::Ignored)
+        let mut cache = self.clients.borrow_mut();
+        for (&frame, client) in frames.iter().zip(&clients) {
+            if let Some(client) = *client {
+                cache.insert(frame, client);
+            }
+        }
+
+        Ok(clients)
+    }
+
+    /// Send the property requests discovery needs for a client window.
+    fn request_client_properties(&self, client: Window) -> Result<ClientPropertyCookies<'_>> {
+        let get = |property: Atom, type_: AtomEnum, long_length: u32| {
+            self.conn
+                .get_property(false, client, property, type_, 0, long_length)
+        };
+        Ok(ClientPropertyCookies {
+            // WM_STATE is 2 32-bit values: state, icon_window
+            wm_state: self.conn.get_property(
+                false,
+                client,
+                self.atoms.WM_STATE,
+                self.atoms.WM_STATE,
+                0,
+                2,
+            )?,
+            wm_class: get(AtomEnum::WM_CLASS.into(), AtomEnum::STRING, 256)?,
+            wm_name: get(AtomEnum::WM_NAME.into(), AtomEnum::ANY, 256)?,
+            window_type: get(self.atoms._NET_WM_WINDOW_TYPE, AtomEnum::ATOM, 32)?,
+            wm_state_hints: get(self.atoms._NET_WM_STATE, AtomEnum::ATOM, 32)?,
+            transient_for: get(self.atoms.WM_TRANSIENT_FOR, AtomEnum::WINDOW, 1)?,
+            wm_hints: get(AtomEnum::WM_HINTS.into(), AtomEnum::WM_HINTS, 1)?,
+        })
+    }
+
+    /// The client window in a frame: the window with WM_STATE at or below
+    /// it. Remembered once found (see clients.rs).
+    pub fn find_client_window(&self, window: Window) -> Result<Option<Window>> {
+        if let Some(client) = self.clients.borrow().get(window) {
+            return Ok(Some(client));
+        }
+        let client = self.search_client_window(window)?;
+        if let Some(client) = client {
+            self.clients.borrow_mut().insert(window, client);
+        }
+        Ok(client)
     }
 
     /// Depth-first search for a window with WM_STATE property.
     /// The WM_STATE property indicates a real client window managed by the WM.
-    fn find_client_window(&self, window: Window) -> Result<Option<Window>> {
+    fn search_client_window(&self, window: Window) -> Result<Option<Window>> {
         // Check if this window has WM_STATE
         if self.has_wm_state(window)? {
             return Ok(Some(window));
@@ -315,7 +634,7 @@ impl XConnection {
         // Search children
         let tree = self.conn.query_tree(window)?.reply()?;
         for child in tree.children {
-            if let Some(client) = self.find_client_window(child)? {
+            if let Some(client) = self.search_client_window(child)? {
                 return Ok(Some(client));
             }
         }
@@ -340,124 +659,52 @@ impl XConnection {
         Ok(reply.type_ != u32::from(AtomEnum::NONE))
     }
 
-    /// Check if window is in Withdrawn state (should be ignored).
-    /// WM_STATE values: WithdrawnState=0, NormalState=1, IconicState=3
-    fn is_withdrawn(&self, window: Window) -> bool {
-        let reply = match self.conn.get_property(
-            false,
-            window,
-            self.atoms.WM_STATE,
-            self.atoms.WM_STATE,
-            0,
-            2, // WM_STATE is 2 32-bit values: state, icon_window
-        ) {
-            Ok(cookie) => match cookie.reply() {
-                Ok(r) => r,
-                Err(_) => return false,
-            },
-            Err(_) => return false,
-        };
-
-        if reply.value_len == 0 {
-            return false;
-        }
-
-        // First value is the state
-        if let Some(state) = reply.value32().and_then(|mut v| v.next()) {
-            // WithdrawnState = 0
-            return state == 0;
-        }
-        false
-    }
-
-    /// Get WM_CLASS property (instance and class names).
-    fn get_wm_class(&self, window: Window) -> Result<Option<String>> {
-        let reply = self
-            .conn
-            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
-            .reply()?;
-
-        if reply.type_ == u32::from(AtomEnum::NONE) || reply.value.is_empty() {
-            return Ok(None);
-        }
-
-        // WM_CLASS is null-separated: "instance\0class\0"
-        let value = String::from_utf8_lossy(&reply.value);
-        Ok(Some(value.replace('\0', " ").trim().to_string()))
-    }
-
     /// Get WM_NAME property (window title).
     fn get_wm_name(&self, window: Window) -> Result<Option<String>> {
         let reply = self
             .conn
             .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::ANY, 0, 256)?
             .reply()?;
-
-        if reply.type_ == u32::from(AtomEnum::NONE) || reply.value.is_empty() {
-            return Ok(None);
-        }
-
-        Ok(Some(String::from_utf8_lossy(&reply.value).to_string()))
+        Ok(wm_name_string(&reply))
     }
 
     /// Check if window should be skipped based on EWMH hints.
     /// Returns true if the window should NOT be shown in exposé.
-    fn should_skip_window(&self, window: Window) -> Result<bool> {
+    fn should_skip_window(&self, window: Window, props: &ClientProperties) -> bool {
         // Skip windows without WM_CLASS (minimal/broken apps like xdeskie)
-        if !self.has_wm_class(window)? {
+        if props.wm_class.type_ == u32::from(AtomEnum::NONE) {
             log::debug!("Skipping window 0x{:x}: no WM_CLASS", window);
-            return Ok(true);
+            return true;
         }
 
         // Skip non-normal window types (docks, toolbars, menus, etc.)
-        if !self.is_normal_window_type(window)? {
+        if !self.is_normal_window_type(&props.window_type) {
             log::debug!("Skipping window 0x{:x}: non-normal window type", window);
-            return Ok(true);
+            return true;
         }
 
         // Skip windows that want to be hidden from taskbar/pager
-        if self.should_skip_by_state(window)? {
+        if self.should_skip_by_state(&props.wm_state_hints) {
             log::debug!("Skipping window 0x{:x}: skip_taskbar/skip_pager state", window);
-            return Ok(true);
+            return true;
         }
 
         // Skip transient windows only if they have DIALOG type
         // (GTK apps use WM_TRANSIENT_FOR for legitimate windows like settings sheets)
-        if self.is_transient(window)? && self.has_dialog_type(window)? {
+        if is_transient(&props.transient_for) && self.has_dialog_type(&props.window_type) {
             log::debug!("Skipping window 0x{:x}: transient dialog", window);
-            return Ok(true);
+            return true;
         }
 
-        Ok(false)
-    }
-
-    /// Check if window has WM_CLASS property set.
-    fn has_wm_class(&self, window: Window) -> Result<bool> {
-        let reply = self
-            .conn
-            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 0)?
-            .reply()?;
-        Ok(reply.type_ != u32::from(AtomEnum::NONE))
+        false
     }
 
     /// Check if window type is NORMAL (or unset, which defaults to NORMAL).
     /// Returns false for DOCK, DESKTOP, TOOLBAR, MENU, UTILITY, SPLASH, DIALOG, etc.
-    fn is_normal_window_type(&self, window: Window) -> Result<bool> {
-        let reply = self
-            .conn
-            .get_property(
-                false,
-                window,
-                self.atoms._NET_WM_WINDOW_TYPE,
-                AtomEnum::ATOM,
-                0,
-                32,
-            )?
-            .reply()?;
-
+    fn is_normal_window_type(&self, reply: &GetPropertyReply) -> bool {
         // No _NET_WM_WINDOW_TYPE set = assume NORMAL
         if reply.type_ == u32::from(AtomEnum::NONE) || reply.value.is_empty() {
-            return Ok(true);
+            return true;
         }
 
         // Check if any of the types is NORMAL
@@ -465,7 +712,7 @@ impl XConnection {
             for window_type in types {
                 // If NORMAL is in the list, show it
                 if window_type == self.atoms._NET_WM_WINDOW_TYPE_NORMAL {
-                    return Ok(true);
+                    return true;
                 }
                 // Skip these types entirely
                 if window_type == self.atoms._NET_WM_WINDOW_TYPE_DOCK
@@ -482,31 +729,19 @@ impl XConnection {
                     || window_type == self.atoms._NET_WM_WINDOW_TYPE_COMBO
                     || window_type == self.atoms._NET_WM_WINDOW_TYPE_DND
                 {
-                    return Ok(false);
+                    return false;
                 }
             }
         }
 
         // Unknown type, assume normal
-        Ok(true)
+        true
     }
 
     /// Check if window has _NET_WM_STATE_SKIP_TASKBAR or _NET_WM_STATE_SKIP_PAGER.
-    fn should_skip_by_state(&self, window: Window) -> Result<bool> {
-        let reply = self
-            .conn
-            .get_property(
-                false,
-                window,
-                self.atoms._NET_WM_STATE,
-                AtomEnum::ATOM,
-                0,
-                32,
-            )?
-            .reply()?;
-
+    fn should_skip_by_state(&self, reply: &GetPropertyReply) -> bool {
         if reply.type_ == u32::from(AtomEnum::NONE) || reply.value.is_empty() {
-            return Ok(false);
+            return false;
         }
 
         if let Some(states) = reply.value32() {
@@ -514,58 +749,28 @@ impl XConnection {
                 if state == self.atoms._NET_WM_STATE_SKIP_TASKBAR
                     || state == self.atoms._NET_WM_STATE_SKIP_PAGER
                 {
-                    return Ok(true);
+                    return true;
                 }
             }
         }
 
-        Ok(false)
+        false
     }
 
-    /// Check if window has _NET_WM_WINDOW_TYPE_DIALOG.
-    fn has_dialog_type(&self, window: Window) -> Result<bool> {
-        let reply = self
-            .conn
-            .get_property(
-                false,
-                window,
-                self.atoms._NET_WM_WINDOW_TYPE,
-                AtomEnum::ATOM,
-                0,
-                32,
-            )?
-            .reply()?;
+    /// Check if _NET_WM_STATE includes `state`.
+    fn has_state(&self, reply: &GetPropertyReply, state: Atom) -> bool {
+        reply.value32().is_some_and(|mut states| states.any(|s| s == state))
+    }
 
+    /// Check if window has _NET_WM_WINDOW_TYPE_DIALOG.
+    fn has_dialog_type(&self, reply: &GetPropertyReply) -> bool {
         if reply.type_ == u32::from(AtomEnum::NONE) || reply.value.is_empty() {
-            return Ok(false);
-        }
-
-        if let Some(types) = reply.value32() {
-            for window_type in types {
-                if window_type == self.atoms._NET_WM_WINDOW_TYPE_DIALOG {
-                    return Ok(true);
-                }
-            }
+            return false;
         }
 
-        Ok(false)
-    }
-
-    /// Check if window is transient for another window (dialog).
-    fn is_transient(&self, window: Window) -> Result<bool> {
-        let reply = self
-            .conn
-            .get_property(
-                false,
-                window,
-                self.atoms.WM_TRANSIENT_FOR,
-                AtomEnum::WINDOW,
-                0,
-                1,
-            )?
-            .reply()?;
-
-        Ok(reply.type_ != u32::from(AtomEnum::NONE) && !reply.value.is_empty())
+        reply
+            .value32()
+            .is_some_and(|mut types| types.any(|t| t == self.atoms._NET_WM_WINDOW_TYPE_DIALOG))
     }
 
     /// Query and log the current Z-order of managed windows.
@@ -573,14 +778,14 @@ impl XConnection {
     pub fn log_current_zorder(&self, managed_frames: &[Window]) -> Result<()> {
         let tree = self.conn.query_tree(self.root)?.reply()?;
 
-        log::info!("=== CURRENT Z-ORDER (bottom to top) ===");
+        log::debug!("=== CURRENT Z-ORDER (bottom to top) ===");
         let mut idx = 0;
         for frame in &tree.children {
             if managed_frames.contains(frame) {
                 // Get window name for logging
                 if let Some(client) = self.find_client_window(*frame)? {
                     let name = self.get_wm_name(client).ok().flatten();
-                    log::info!(
+                    log::debug!(
                         "  [{}] frame=0x{:x} {:?}",
                         idx,
                         frame,
@@ -590,7 +795,7 @@ impl XConnection {
                 }
             }
         }
-        log::info!("========================================");
+        log::debug!("========================================");
         Ok(())
     }
 
@@ -632,6 +837,34 @@ impl XConnection {
         Ok(())
     }
 
+    /// Class name (the second part of WM_CLASS) of the focused window, read
+    /// from the window holding the focus or the nearest one above it with
+    /// a WM_CLASS.
+    pub fn focused_class(&self) -> Result<Option<String>> {
+        let mut window = self.conn.get_input_focus()?.reply()?.focus;
+        // No focus, or PointerRoot
+        if window == x11rb::NONE || window == u32::from(InputFocus::POINTER_ROOT) {
+            return Ok(None);
+        }
+        while window != self.root {
+            let Ok(reply) = self
+                .conn
+                .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+                .reply()
+            else {
+                return Ok(None);
+            };
+            if let Some(class) = wm_class_string(&reply) {
+                return Ok(class.split(' ').next_back().map(str::to_string));
+            }
+            let Ok(tree) = self.conn.query_tree(window)?.reply() else {
+                return Ok(None);
+            };
+            window = tree.parent;
+        }
+        Ok(None)
+    }
+
     /// Raise and focus a window.
     pub fn raise_and_focus(&self, window: &WindowInfo) -> Result<()> {
         log::debug!(
@@ -640,23 +873,46 @@ impl XConnection {
             window.client_window
         );
 
+        // Ask for WM_PROTOCOLS up front, so the reply comes back with the
+        // raise rather than costing a round trip of its own
+        let protocols = self.conn.get_property(
+            false,
+            window.client_window,
+            self.atoms.WM_PROTOCOLS,
+            AtomEnum::ATOM,
+            0,
+            32,
+        )?;
+
         // Raise BOTH frame and client windows to top of stack
+        // (just the one window when the WM doesn't reparent)
         self.conn.configure_window(
             window.frame_window,
             &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
         )?;
-        self.conn.configure_window(
-            window.client_window,
-            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
-        )?;
+        if window.is_reparented() {
+            self.conn.configure_window(
+                window.client_window,
+                &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+            )?;
+        }
 
         // Map both windows in case they're iconified
         self.conn.map_window(window.frame_window)?;
-        self.conn.map_window(window.client_window)?;
-        self.conn.flush()?;
+        if window.is_reparented() {
+            self.conn.map_window(window.client_window)?;
+        }
 
         // Send WM_TAKE_FOCUS if supported
-        self.send_take_focus(window.client_window)?;
+        let supports_take_focus = protocols
+            .reply()?
+            .value32()
+            .is_some_and(|mut atoms| atoms.any(|a| a == self.atoms.WM_TAKE_FOCUS));
+        if supports_take_focus {
+            self.send_take_focus(window.client_window)?;
+        } else {
+            log::debug!("Window does not support WM_TAKE_FOCUS");
+        }
 
         // Set input focus
         self.conn.set_input_focus(
@@ -664,53 +920,112 @@ impl XConnection {
             window.client_window,
             x11rb::CURRENT_TIME,
         )?;
+        self.set_active_window(window.client_window)?;
         self.conn.flush()?;
 
         log::debug!("Raise and focus complete");
         Ok(())
     }
 
-    /// Send WM_TAKE_FOCUS client message if the window supports it.
+    /// Send a WM_TAKE_FOCUS client message; the window must list the
+    /// protocol in WM_PROTOCOLS.
     fn send_take_focus(&self, window: Window) -> Result<()> {
-        let wm_protocols = self
-            .conn
-            .intern_atom(false, b"WM_PROTOCOLS")?
-            .reply()?
-            .atom;
-        let wm_take_focus = self
-            .conn
-            .intern_atom(false, b"WM_TAKE_FOCUS")?
-            .reply()?
-            .atom;
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.atoms.WM_PROTOCOLS,
+            [self.atoms.WM_TAKE_FOCUS, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+
+        self.conn
+            .send_event(false, window, EventMask::NO_EVENT, event)?;
+        log::debug!("Sent WM_TAKE_FOCUS");
+
+        Ok(())
+    }
 
-        // Check if window supports WM_TAKE_FOCUS
+    /// Ask a window to close with WM_DELETE_WINDOW. Returns false, without
+    /// sending anything, if the client doesn't support the protocol.
+    pub fn close_window(&self, window: &WindowInfo) -> Result<bool> {
         let prop = self
             .conn
-            .get_property(false, window, wm_protocols, AtomEnum::ATOM, 0, 32)?
+            .get_property(false, window.client_window, self.atoms.WM_PROTOCOLS, AtomEnum::ATOM, 0, 32)?
             .reply()?;
-
-        let supports_take_focus = prop
+        let supports_delete = prop
             .value32()
-            .map(|atoms| atoms.into_iter().any(|a| a == wm_take_focus))
-            .unwrap_or(false);
-
-        if !supports_take_focus {
-            log::debug!("Window does not support WM_TAKE_FOCUS");
-            return Ok(());
+            .is_some_and(|mut atoms| atoms.any(|a| a == self.atoms.WM_DELETE_WINDOW));
+        if !supports_delete {
+            return Ok(false);
         }
 
-        // Send the message
         let event = ClientMessageEvent::new(
             32,
-            window,
-            wm_protocols,
-            [wm_take_focus, x11rb::CURRENT_TIME, 0, 0, 0],
+            window.client_window,
+            self.atoms.WM_PROTOCOLS,
+            [self.atoms.WM_DELETE_WINDOW, x11rb::CURRENT_TIME, 0, 0, 0],
         );
-
         self.conn
-            .send_event(false, window, EventMask::NO_EVENT, event)?;
-        log::debug!("Sent WM_TAKE_FOCUS");
+            .send_event(false, window.client_window, EventMask::NO_EVENT, event)?;
+        self.conn.flush()?;
+        Ok(true)
+    }
 
+    /// Ask the window manager to iconify a window (ICCCM WM_CHANGE_STATE).
+    pub fn iconify_window(&self, window: &WindowInfo) -> Result<()> {
+        const ICONIC_STATE: u32 = 3;
+        let event = ClientMessageEvent::new(
+            32,
+            window.client_window,
+            self.atoms.WM_CHANGE_STATE,
+            [ICONIC_STATE, 0, 0, 0, 0],
+        );
+        self.conn.send_event(
+            false,
+            self.root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )?;
+        self.conn.flush()?;
         Ok(())
     }
+
+    /// Bring an iconified window back; mapping the client asks the window
+    /// manager to.
+    pub fn deiconify_window(&self, window: &WindowInfo) -> Result<()> {
+        self.conn.map_window(window.client_window)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Disconnect the client owning a window (XKillClient), for applications
+    /// that ignore WM_DELETE_WINDOW or hang.
+    pub fn kill_window(&self, window: &WindowInfo) -> Result<()> {
+        self.conn.kill_client(window.client_window)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(class: &str, title: &str) -> WindowInfo {
+        WindowInfo::for_test(2, Some(class), Some(title))
+    }
+
+    #[test]
+    fn test_filter_include_only() {
+        let terminals = ["XTerm".to_string()];
+        let pip = [Regex::new("^Picture-in-Picture$").unwrap()];
+        let filter = WindowFilter {
+            include_classes: &terminals,
+            exclude_titles: &pip,
+            ..Default::default()
+        };
+        assert!(!filter.excludes(&window("xterm XTerm", "~")));
+        assert!(filter.excludes(&window("firefox Firefox", "Mozilla Firefox")));
+        assert!(filter.excludes(&window("xterm XTerm", "Picture-in-Picture")));
+        assert!(!WindowFilter::default().excludes(&window("firefox Firefox", "Picture-in-Picture")));
+    }
 }
@@ -0,0 +1,65 @@
+//! Synthetic input through the XTEST extension.
+
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::xtest;
+
+use crate::connection::XConnection;
+use crate::error::Result;
+
+impl XConnection {
+    /// Whether the server supports XTEST.
+    pub fn has_xtest(&self) -> Result<bool> {
+        Ok(self
+            .conn
+            .extension_information(xtest::X11_EXTENSION_NAME)?
+            .is_some())
+    }
+
+    /// Move the pointer to (x, y) and click `button` there.
+    /// Call only after the overview is gone and grabs are released,
+    /// otherwise the click comes straight back to us.
+    pub fn replay_click(&self, x: i16, y: i16, button: u8) -> Result<()> {
+        if !self.has_xtest()? {
+            log::warn!("XTEST not available, cannot replay click");
+            return Ok(());
+        }
+
+        self.fake_motion(x, y)?;
+        self.fake_button(button, true)?;
+        self.fake_button(button, false)?;
+        self.sync()?;
+        log::info!("Replayed button {} click at ({}, {})", button, x, y);
+        Ok(())
+    }
+
+    /// Warp the pointer to absolute root coordinates.
+    pub fn fake_motion(&self, x: i16, y: i16) -> Result<()> {
+        xtest::fake_input(
+            &self.conn,
+            MOTION_NOTIFY_EVENT,
+            0,
+            x11rb::CURRENT_TIME,
+            self.root,
+            x,
+            y,
+            0,
+        )?;
+        Ok(())
+    }
+
+    /// Press or release a pointer button.
+    pub fn fake_button(&self, button: u8, pressed: bool) -> Result<()> {
+        let event_type = if pressed { BUTTON_PRESS_EVENT } else { BUTTON_RELEASE_EVENT };
+        xtest::fake_input(&self.conn, event_type, button, x11rb::CURRENT_TIME, x11rb::NONE, 0, 0, 0)?;
+        Ok(())
+    }
+
+    /// Press and release a key.
+    pub fn fake_key(&self, keycode: u8) -> Result<()> {
+        for event_type in [KEY_PRESS_EVENT, KEY_RELEASE_EVENT] {
+            xtest::fake_input(&self.conn, event_type, keycode, x11rb::CURRENT_TIME, x11rb::NONE, 0, 0, 0)?;
+        }
+        Ok(())
+    }
+}